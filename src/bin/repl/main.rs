@@ -238,6 +238,9 @@ fn repl(locustdb: &LocustDB) {
 }
 
 fn print_error(fail: locustdb::QueryError) {
+    if let locustdb::QueryError::SyntaxError(ref err) = fail {
+        println!("{}", err.highlight());
+    }
     println!("{}", fail);
     while let Some(cause) = fail.cause() {
         println!("{}", cause);
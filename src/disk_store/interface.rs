@@ -18,5 +18,6 @@ pub struct PartitionMetadata {
     pub tablename: String,
     pub len: usize,
     pub columns: Vec<String>,
+    pub nullable_columns: Vec<String>,
 }
 
@@ -13,6 +13,7 @@ use mem_store::column::{Column, DataSection};
 use scheduler::inner_locustdb::InnerLocustDB;
 use mem_store::codec::CodecOp;
 use engine::types::EncodingType as Type;
+use ingest::ordered_float::OrderedFloat;
 
 
 pub struct RocksDB {
@@ -143,10 +144,19 @@ fn deserialize_column(data: &[u8]) -> Column {
             U64(data) => DataSection::U64(data.unwrap().iter().collect()),
             I64(data) => DataSection::I64(data.unwrap().iter().collect()),
             Null(count) => DataSection::Null(count as usize),
+            F64(data) => DataSection::F64(data.unwrap().iter().map(OrderedFloat).collect()),
         }
     }).collect::<Vec<_>>();
 
-    Column::new(name, len, range, codec, data_sections)
+    let nulls = if column.has_nulls() {
+        Some(column.get_nulls().unwrap().iter().collect())
+    } else {
+        None
+    };
+
+    let mut column = Column::new(name, len, range, codec, data_sections);
+    column.set_nulls(nulls);
+    column
 }
 
 fn deserialize_type(t: EncodingType) -> Type {
@@ -157,6 +167,7 @@ fn deserialize_type(t: EncodingType) -> Type {
         U32 => Type::U32,
         U64 => Type::U64,
         I64 => Type::I64,
+        F64 => Type::F64,
         Null => Type::Null,
     }
 }
@@ -171,6 +182,11 @@ fn deserialize_meta_data(data: &[u8], partition_id: PartitionID) -> PartitionMet
         len: meta_data.get_len() as usize,
         tablename: meta_data.get_tablename().unwrap().to_string(),
         columns: meta_data.get_columns().unwrap().iter().map(|c| c.unwrap().to_string()).collect(),
+        nullable_columns: if meta_data.has_nullable_columns() {
+            meta_data.get_nullable_columns().unwrap().iter().map(|c| c.unwrap().to_string()).collect()
+        } else {
+            Vec::new()
+        },
     }
 }
 
@@ -186,6 +202,13 @@ fn serialize_meta_data(tablename: &str, columns: &[Arc<Column>]) -> Vec<u8> {
                 col_names.set(i as u32, column.name());
             }
         }
+        {
+            let nullable = columns.iter().filter(|c| c.has_nulls()).collect::<Vec<_>>();
+            let mut col_names = meta_data.reborrow().init_nullable_columns(nullable.len() as u32);
+            for (i, column) in nullable.iter().enumerate() {
+                col_names.set(i as u32, column.name());
+            }
+        }
     }
     let mut buffer = Vec::new();
     capnp::serialize::write_message(&mut buffer, &builder).unwrap();
@@ -258,10 +281,20 @@ fn serialize_column(col: &Column) -> Vec<u8> {
                         let mut builder = ds.init_i64(x.len() as u32);
                         populate_primitive_list(&mut builder, x);
                     }
+                    DataSection::F64(x) => {
+                        let mut builder = ds.init_f64(x.len() as u32);
+                        for (i, &x) in x.iter().enumerate() {
+                            builder.set(i as u32, x.0);
+                        }
+                    }
                     DataSection::Null(count) => ds.set_null(*count as u64),
                 }
             }
         }
+        if let Some(nulls) = col.nulls() {
+            let mut builder = column.reborrow().init_nulls(nulls.len() as u32);
+            populate_primitive_list(&mut builder, nulls);
+        }
     }
     let mut buffer = Vec::new();
     capnp::serialize::write_message(&mut buffer, &builder).unwrap();
@@ -275,6 +308,7 @@ fn encoding_type_to_capnp(t: Type) -> EncodingType {
         Type::U32 => EncodingType::U32,
         Type::U64 => EncodingType::U64,
         Type::I64 => EncodingType::I64,
+        Type::F64 => EncodingType::F64,
         Type::Null => EncodingType::Null,
         _ => panic!("Trying to encode unsupported type {:?}", t)
     }
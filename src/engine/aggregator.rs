@@ -1,11 +1,69 @@
+use std::cmp;
+
+use errors::QueryError;
+use ingest::ordered_float::OrderedFloat;
+
+
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Aggregator {
     Sum,
     Count,
+    Min,
+    Max,
+    /// Computed as a sum during query execution, and divided by the count of the aggregated expression once the
+    /// results of all partitions have been combined.
+    Avg,
+    /// Exact number of distinct values. Partial results are sets of values that are merged by taking their union.
+    CountDistinct,
+    /// Estimate of the number of distinct values computed with HyperLogLog sketches of the given precision.
+    ApproxCountDistinct(u8),
+    /// Estimate of the given quantile computed with t-digest sketches.
+    Percentile(OrderedFloat),
+    /// Sample variance, standard deviation, covariance and correlation, computed from moments that are merged across
+    /// partitions. The argument of `Covar` and `Corr` is a `Func2Type::Pair`.
+    Variance,
+    StdDev,
+    Covar,
+    Corr,
 }
 
 impl Aggregator {
-    pub fn combine_i64(&self, accumulator: i64, elem: i64) -> i64 {
-        accumulator + elem
+    /// Whether the results of `self` and `other` over the same expression are derived from the same aggregation state,
+    /// which is then computed only once.
+    pub fn shares_state(&self, other: &Aggregator) -> bool {
+        match (*self, *other) {
+            (Aggregator::Percentile(_), Aggregator::Percentile(_)) => true,
+            (a, b) => a == b || a.is_derived_from_moments() && b.is_derived_from_moments(),
+        }
+    }
+
+    /// Whether the result is estimated from the moments of its arguments, see `moments::Moments`.
+    pub fn is_derived_from_moments(&self) -> bool {
+        match *self {
+            Aggregator::Variance | Aggregator::StdDev | Aggregator::Covar | Aggregator::Corr => true,
+            _ => false,
+        }
+    }
+
+    /// Returns the function that combines integer results of the same group from different partitions.
+    /// Results of aggregators that are computed from aggregation states are combined by merging the states instead.
+    pub fn combine_i64(&self) -> Result<fn(i64, i64) -> i64, QueryError> {
+        Ok(match *self {
+            Aggregator::Sum | Aggregator::Count | Aggregator::Avg => |accumulator, elem| accumulator + elem,
+            Aggregator::Min => cmp::min,
+            Aggregator::Max => cmp::max,
+            _ => bail!(QueryError::FatalError, "{:?} is combined by merging aggregation states", self),
+        })
+    }
+
+    /// Returns the function that combines float results of the same group from different partitions.
+    pub fn combine_f64(&self) -> Result<fn(OrderedFloat, OrderedFloat) -> OrderedFloat, QueryError> {
+        Ok(match *self {
+            Aggregator::Sum | Aggregator::Count | Aggregator::Avg =>
+                |accumulator, elem| OrderedFloat(accumulator.0 + elem.0),
+            Aggregator::Min => cmp::min,
+            Aggregator::Max => cmp::max,
+            _ => bail!(QueryError::FatalError, "{:?} is combined by merging aggregation states", self),
+        })
     }
 }
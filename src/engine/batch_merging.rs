@@ -1,15 +1,21 @@
-use std::cmp::min;
+use std::cmp::{min, Ordering};
 use std::usize;
 
 use engine::*;
 use engine::aggregator::Aggregator;
+use engine::row_eval;
 use errors::QueryError;
+use ingest::ordered_float::OrderedFloat;
+use ingest::raw_val::RawVal;
 
 
 pub struct BatchResult<'a> {
     pub group_by: Option<Vec<BoxedVec<'a>>>,
     pub sort_by: Option<usize>,
     pub desc: bool,
+    /// Sort keys of queries with multiple sort keys or sort keys containing NULL values, which are applied while
+    /// combining batches instead of `sort_by`.
+    pub sort_keys: Vec<SortKey>,
     pub select: Vec<BoxedVec<'a>>,
     pub aggregators: Vec<Aggregator>,
     pub level: u32,
@@ -19,6 +25,15 @@ pub struct BatchResult<'a> {
     pub unsafe_referenced_buffers: Vec<BoxedVec<'a>>,
 }
 
+#[derive(Debug, Clone, Copy)]
+pub struct SortKey {
+    /// Index of the select column holding the values of the sort key.
+    pub index: usize,
+    /// Index of the select column that is 1 in rows where the sort key is NULL.
+    pub null_indicator: Option<usize>,
+    pub desc: bool,
+}
+
 impl<'a> BatchResult<'a> {
     pub fn len(&self) -> usize {
         match self.group_by {
@@ -93,10 +108,12 @@ pub fn combine<'a>(batch1: BatchResult<'a>, batch2: BatchResult<'a>, limit: usiz
 
             let mut aggregates = Vec::with_capacity(batch1.aggregators.len());
             for ((aggregator, select1), select2) in batch1.aggregators.iter().zip(batch1.select).zip(batch2.select) {
+                let (select1, select2) = unify_numeric(select1, select2);
+                let t = select1.get_type();
                 let left = set("left", select1, &mut executor);
                 let right = set("right", select2, &mut executor);
                 let aggregated = executor.named_buffer("aggregated");
-                executor.push(VecOperator::merge_aggregate(ops, left, right, aggregated, *aggregator));
+                executor.push(VecOperator::merge_aggregate(ops, left, right, aggregated, *aggregator, t)?);
                 aggregates.push(aggregated);
             }
 
@@ -109,6 +126,7 @@ pub fn combine<'a>(batch1: BatchResult<'a>, batch2: BatchResult<'a>, limit: usiz
                 group_by: Some(group_by_cols),
                 sort_by: None,
                 desc: batch1.desc,
+                sort_keys: Vec::new(),
                 select,
                 aggregators: batch1.aggregators,
                 level: batch1.level + 1,
@@ -127,10 +145,13 @@ pub fn combine<'a>(batch1: BatchResult<'a>, batch2: BatchResult<'a>, limit: usiz
                 // Sort query
                 Some(index) => {
                     let mut executor = QueryExecutor::default();
-                    let left_t = batch1.select.iter().map(|vec| { vec.get_type() }).collect::<Vec<_>>();
-                    let right_t = batch2.select.iter().map(|vec| { vec.get_type() }).collect::<Vec<_>>();
-                    let left = batch1.select.into_iter().map(|vec| { set("left", vec, &mut executor) }).collect::<Vec<_>>();
-                    let right = batch2.select.into_iter().map(|vec| { set("right", vec, &mut executor) }).collect::<Vec<_>>();
+                    let (select1, select2): (Vec<_>, Vec<_>) = batch1.select.into_iter().zip(batch2.select)
+                        .map(|(left, right)| unify_numeric(left, right))
+                        .unzip();
+                    let left_t = select1.iter().map(|vec| { vec.get_type() }).collect::<Vec<_>>();
+                    let right_t = select2.iter().map(|vec| { vec.get_type() }).collect::<Vec<_>>();
+                    let left = select1.into_iter().map(|vec| { set("left", vec, &mut executor) }).collect::<Vec<_>>();
+                    let right = select2.into_iter().map(|vec| { set("right", vec, &mut executor) }).collect::<Vec<_>>();
                     let ops = executor.named_buffer("take_left");
                     let merged_sort_cols = executor.named_buffer("merged_sort_cols");
                     executor.push(VecOperator::merge(left[index], right[index], merged_sort_cols, ops, left_t[index], right_t[index], limit, batch1.desc));
@@ -156,6 +177,7 @@ pub fn combine<'a>(batch1: BatchResult<'a>, batch2: BatchResult<'a>, limit: usiz
                         sort_by: Some(index),
                         select,
                         desc: batch1.desc,
+                        sort_keys: Vec::new(),
                         aggregators: Vec::new(),
                         level: batch1.level + 1,
                         batch_count: batch1.batch_count + batch2.batch_count,
@@ -169,9 +191,12 @@ pub fn combine<'a>(batch1: BatchResult<'a>, batch2: BatchResult<'a>, limit: usiz
                 }
                 // Select query
                 None => {
+                    let sorted = !batch1.sort_keys.is_empty();
                     let mut result = Vec::with_capacity(batch1.select.len());
-                    for (mut col1, col2) in batch1.select.into_iter().zip(batch2.select) {
-                        let count = if col1.len() >= limit { 0 } else {
+                    for (col1, col2) in batch1.select.into_iter().zip(batch2.select) {
+                        let (mut col1, col2) = unify_numeric(col1, col2);
+                        // Rows of sorted batches are only dropped once the rows of both batches have been sorted
+                        let count = if sorted { col2.len() } else if col1.len() >= limit { 0 } else {
                             min(col2.len(), limit - col1.len())
                         };
                         if let Some(newcol) = col1.extend(col2, count) {
@@ -180,11 +205,15 @@ pub fn combine<'a>(batch1: BatchResult<'a>, batch2: BatchResult<'a>, limit: usiz
                             result.push(col1)
                         }
                     }
+                    if sorted {
+                        result = sort_and_limit(result, &batch1.sort_keys, limit, batch1.show || batch2.show);
+                    }
                     Ok(BatchResult {
                         group_by: None,
                         sort_by: None,
                         select: result,
                         desc: batch1.desc,
+                        sort_keys: batch1.sort_keys,
                         aggregators: Vec::new(),
                         level: batch1.level + 1,
                         batch_count: batch1.batch_count + batch2.batch_count,
@@ -202,6 +231,44 @@ pub fn combine<'a>(batch1: BatchResult<'a>, batch2: BatchResult<'a>, limit: usiz
     }
 }
 
+/// Sorts the rows of `select` by `sort_keys` and keeps the first `limit` rows.
+fn sort_and_limit<'a>(select: Vec<BoxedVec<'a>>, sort_keys: &[SortKey], limit: usize, show: bool) -> Vec<BoxedVec<'a>> {
+    let len = select.get(0).map_or(0, |col| col.len());
+    if len <= limit {
+        return select;
+    }
+    let keys = (0..len)
+        .map(|i| sort_keys.iter()
+            .map(|key| match key.null_indicator {
+                Some(indicator) if select[indicator].get_raw(i) == RawVal::Int(1) => RawVal::Null,
+                _ => select[key.index].get_raw(i),
+            })
+            .collect::<Vec<_>>())
+        .collect::<Vec<_>>();
+    let mut indices = (0..len).collect::<Vec<usize>>();
+    indices.sort_by(|&i, &j| {
+        sort_keys.iter().enumerate()
+            .map(|(k, key)| row_eval::cmp_sort_key(&keys[i][k], &keys[j][k], key.desc))
+            .find(|&ordering| ordering != Ordering::Equal)
+            .unwrap_or(Ordering::Equal)
+    });
+    indices.truncate(limit);
+
+    let mut executor = QueryExecutor::default();
+    let indices = set("indices", AnyVec::owned(indices), &mut executor);
+    let mut selected = Vec::with_capacity(select.len());
+    for col in select {
+        let t = col.get_type();
+        let input = set("select", col, &mut executor);
+        let output = executor.named_buffer("selected");
+        executor.push(VecOperator::select(t, input, indices, output));
+        selected.push(output);
+    }
+    let mut results = executor.prepare_no_columns();
+    executor.run(1, &mut results, show);
+    selected.into_iter().map(|i| results.collect(i)).collect()
+}
+
 fn set<'a>(name: &'static str, vec: BoxedVec<'a>, executor: &mut QueryExecutor<'a>) -> BufferRef {
     let buffer = executor.named_buffer(name);
     let op = VecOperator::constant_vec(vec, buffer);
@@ -209,3 +276,15 @@ fn set<'a>(name: &'static str, vec: BoxedVec<'a>, executor: &mut QueryExecutor<'
     buffer
 }
 
+/// Rows ingested with `RawVal::Int` and `RawVal::Float` values for the same column result in a mix of integer and
+/// float partitions. Converts integer results to floats if the other side of the merge holds floats.
+fn unify_numeric<'a>(left: BoxedVec<'a>, right: BoxedVec<'a>) -> (BoxedVec<'a>, BoxedVec<'a>) {
+    fn to_float<'a>(vec: BoxedVec<'a>) -> BoxedVec<'a> {
+        AnyVec::owned(vec.cast_ref_i64().iter().map(|&i| OrderedFloat(i as f64)).collect::<Vec<_>>())
+    }
+    match (left.get_type(), right.get_type()) {
+        (EncodingType::I64, EncodingType::F64) => (to_float(left), right),
+        (EncodingType::F64, EncodingType::I64) => (left, to_float(right)),
+        _ => (left, right),
+    }
+}
@@ -0,0 +1,142 @@
+use chrono::{Datelike, NaiveDate, NaiveDateTime};
+
+use syntax::expression::{Func1Type, TimeUnit};
+
+
+const HOUR: i64 = 3600;
+const DAY: i64 = 24 * HOUR;
+const WEEK: i64 = 7 * DAY;
+/// 1970-01-01 was a Thursday, the first Monday after the epoch was 1970-01-05.
+const FIRST_MONDAY: i64 = 4 * DAY;
+
+/// Functions that map timestamps (seconds since the epoch, UTC) to integers.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum DateFunction {
+    Year,
+    Month,
+    Day,
+    Hour,
+    /// Day of the week, 0 (Sunday) to 6 (Saturday).
+    DayOfWeek,
+    Trunc(TimeUnit),
+}
+
+impl DateFunction {
+    /// Returns the `DateFunction` that implements `function`, if any.
+    pub fn from_func1(function: Func1Type) -> Option<DateFunction> {
+        match function {
+            Func1Type::ToYear => Some(DateFunction::Year),
+            Func1Type::ToMonth => Some(DateFunction::Month),
+            Func1Type::ToDay => Some(DateFunction::Day),
+            Func1Type::ToHour => Some(DateFunction::Hour),
+            Func1Type::DayOfWeek => Some(DateFunction::DayOfWeek),
+            Func1Type::DateTrunc(unit) => Some(DateFunction::Trunc(unit)),
+            _ => None,
+        }
+    }
+
+    pub fn apply(&self, ts: i64) -> i64 {
+        match *self {
+            DateFunction::Year => NaiveDateTime::from_timestamp(ts, 0).year() as i64,
+            DateFunction::Month => NaiveDateTime::from_timestamp(ts, 0).month() as i64,
+            DateFunction::Day => NaiveDateTime::from_timestamp(ts, 0).day() as i64,
+            DateFunction::Hour => modulo(ts, DAY) / HOUR,
+            DateFunction::DayOfWeek => modulo((ts - modulo(ts, DAY)) / DAY + 4, 7),
+            DateFunction::Trunc(TimeUnit::Hour) => ts - modulo(ts, HOUR),
+            DateFunction::Trunc(TimeUnit::Day) => ts - modulo(ts, DAY),
+            DateFunction::Trunc(TimeUnit::Week) => ts - modulo(ts - FIRST_MONDAY, WEEK),
+            DateFunction::Trunc(TimeUnit::Month) => {
+                let date = NaiveDateTime::from_timestamp(ts, 0).date();
+                NaiveDate::from_ymd(date.year(), date.month(), 1).and_hms(0, 0, 0).timestamp()
+            }
+        }
+    }
+
+    /// Range of the outputs given that all inputs fall into `input_range`.
+    pub fn range(&self, input_range: Option<(i64, i64)>) -> Option<(i64, i64)> {
+        match *self {
+            DateFunction::Month => Some((1, 12)),
+            DateFunction::Day => Some((1, 31)),
+            DateFunction::Hour => Some((0, 23)),
+            DateFunction::DayOfWeek => Some((0, 6)),
+            // Monotonic functions
+            DateFunction::Year | DateFunction::Trunc(_) =>
+                input_range.map(|(min, max)| (self.apply(min), self.apply(max))),
+        }
+    }
+}
+
+/// Parses date/time literals of the form `YYYY-MM-DD` and `YYYY-MM-DD HH:MM:SS` into seconds since the epoch.
+pub fn parse_timestamp(s: &str) -> Option<i64> {
+    NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S")
+        .or_else(|_| NaiveDate::parse_from_str(s, "%Y-%m-%d").map(|date| date.and_hms(0, 0, 0)))
+        .map(|datetime| datetime.timestamp())
+        .ok()
+}
+
+/// Parses intervals such as `1 hour` or `2 days 30 minutes` into a number of seconds.
+pub fn parse_interval(s: &str) -> Option<i64> {
+    let tokens = s.split_whitespace().collect::<Vec<_>>();
+    if tokens.is_empty() || tokens.len() % 2 != 0 {
+        return None;
+    }
+    let mut seconds = 0i64;
+    for pair in tokens.chunks(2) {
+        let count = pair[0].parse::<i64>().ok()?;
+        let unit = match pair[1].to_lowercase().trim_right_matches('s') {
+            "second" => 1,
+            "minute" => 60,
+            "hour" => HOUR,
+            "day" => DAY,
+            "week" => WEEK,
+            _ => return None,
+        };
+        seconds = seconds.checked_add(count.checked_mul(unit)?)?;
+    }
+    Some(seconds)
+}
+
+fn modulo(x: i64, m: i64) -> i64 {
+    ((x % m) + m) % m
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_date_functions() {
+        // Sunday 2016-03-13 01:59:26
+        let ts = parse_timestamp("2016-03-13 01:59:26").unwrap();
+        assert_eq!(ts, 1457834366);
+        assert_eq!(DateFunction::Year.apply(ts), 2016);
+        assert_eq!(DateFunction::Month.apply(ts), 3);
+        assert_eq!(DateFunction::Day.apply(ts), 13);
+        assert_eq!(DateFunction::Hour.apply(ts), 1);
+        assert_eq!(DateFunction::DayOfWeek.apply(ts), 0);
+        assert_eq!(DateFunction::Trunc(TimeUnit::Hour).apply(ts), parse_timestamp("2016-03-13 01:00:00").unwrap());
+        assert_eq!(DateFunction::Trunc(TimeUnit::Day).apply(ts), parse_timestamp("2016-03-13").unwrap());
+        assert_eq!(DateFunction::Trunc(TimeUnit::Week).apply(ts), parse_timestamp("2016-03-07").unwrap());
+        assert_eq!(DateFunction::Trunc(TimeUnit::Month).apply(ts), parse_timestamp("2016-03-01").unwrap());
+    }
+
+    #[test]
+    fn test_parse_interval() {
+        assert_eq!(parse_interval("1 hour"), Some(HOUR));
+        assert_eq!(parse_interval(" 2 Days 30 minutes "), Some(2 * DAY + 1800));
+        assert_eq!(parse_interval("1 week -1 second"), Some(WEEK - 1));
+        assert_eq!(parse_interval("1 month"), None);
+        assert_eq!(parse_interval("hour"), None);
+    }
+
+    #[test]
+    fn test_date_functions_before_epoch() {
+        // Wednesday 1969-12-31 23:00:00
+        let ts = -3600;
+        assert_eq!(DateFunction::Hour.apply(ts), 23);
+        assert_eq!(DateFunction::DayOfWeek.apply(ts), 3);
+        assert_eq!(DateFunction::Trunc(TimeUnit::Day).apply(ts), -DAY);
+        assert_eq!(DateFunction::Trunc(TimeUnit::Week).apply(ts), parse_timestamp("1969-12-29").unwrap());
+    }
+}
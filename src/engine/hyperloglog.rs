@@ -0,0 +1,117 @@
+use std::cmp;
+
+
+/// Default precision of `APPROX_COUNT_DISTINCT`, which yields a standard error of about 0.8% using 16KiB per group.
+pub const DEFAULT_PRECISION: u8 = 14;
+pub const MIN_PRECISION: u8 = 4;
+pub const MAX_PRECISION: u8 = 18;
+
+/// HyperLogLog sketch that estimates the number of distinct 64 bit hashes inserted into it.
+/// Uses `2^precision` registers which are only allocated once the first hash is inserted, which keeps sketches of
+/// empty groups small.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HyperLogLog {
+    precision: u8,
+    registers: Vec<u8>,
+}
+
+impl HyperLogLog {
+    pub fn new(precision: u8) -> HyperLogLog {
+        assert!(precision >= MIN_PRECISION && precision <= MAX_PRECISION,
+                "HyperLogLog precision {} outside of [{}, {}]", precision, MIN_PRECISION, MAX_PRECISION);
+        HyperLogLog { precision, registers: Vec::new() }
+    }
+
+    pub fn precision(&self) -> u8 { self.precision }
+
+    #[inline]
+    pub fn insert(&mut self, hash: u64) {
+        if self.registers.is_empty() {
+            self.registers = vec![0; 1 << self.precision];
+        }
+        let index = (hash >> (64 - self.precision)) as usize;
+        // Sentinel bit bounds the rank by `65 - precision` if all remaining bits are zero
+        let rest = (hash << self.precision) | (1 << (self.precision - 1));
+        let rank = rest.leading_zeros() as u8 + 1;
+        let register = &mut self.registers[index];
+        *register = cmp::max(*register, rank);
+    }
+
+    /// Combines two sketches into a sketch of the union of the hashes inserted into either.
+    pub fn merge(&mut self, other: &HyperLogLog) {
+        assert_eq!(self.precision, other.precision);
+        if other.registers.is_empty() {
+            return;
+        }
+        if self.registers.is_empty() {
+            self.registers = other.registers.clone();
+            return;
+        }
+        for (register, &other) in self.registers.iter_mut().zip(other.registers.iter()) {
+            *register = cmp::max(*register, other);
+        }
+    }
+
+    pub fn estimate(&self) -> f64 {
+        if self.registers.is_empty() {
+            return 0.0;
+        }
+        let m = self.registers.len() as f64;
+        let alpha = match self.registers.len() {
+            16 => 0.673,
+            32 => 0.697,
+            64 => 0.709,
+            _ => 0.7213 / (1.0 + 1.079 / m),
+        };
+        let mut sum = 0.0;
+        let mut zeros = 0;
+        for &register in &self.registers {
+            sum += 1.0 / (1u64 << register) as f64;
+            if register == 0 {
+                zeros += 1;
+            }
+        }
+        let estimate = alpha * m * m / sum;
+        // Linear counting is more accurate for small cardinalities. With 64 bit hashes, collisions are rare enough
+        // that no correction is required for large cardinalities.
+        if estimate <= 2.5 * m && zeros > 0 {
+            m * (m / zeros as f64).ln()
+        } else {
+            estimate
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use seahash;
+
+    fn sketch(precision: u8, values: ::std::ops::Range<u64>) -> HyperLogLog {
+        let mut hll = HyperLogLog::new(precision);
+        for value in values {
+            hll.insert(seahash::hash(&value.to_string().into_bytes()));
+        }
+        hll
+    }
+
+    #[test]
+    fn test_estimate() {
+        assert_eq!(HyperLogLog::new(14).estimate(), 0.0);
+        assert_eq!(sketch(14, 0..10).estimate().round(), 10.0);
+        for &n in &[1000, 100_000] {
+            let estimate = sketch(14, 0..n).estimate();
+            assert!((estimate - n as f64).abs() < 0.03 * n as f64, "{} {}", n, estimate);
+        }
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut left = sketch(12, 0..60_000);
+        left.merge(&sketch(12, 40_000..100_000));
+        assert_eq!(left, sketch(12, 0..100_000));
+        left.merge(&HyperLogLog::new(12));
+        assert_eq!(left, sketch(12, 0..100_000));
+    }
+}
@@ -0,0 +1,326 @@
+use std::borrow::Borrow;
+use std::collections::HashMap;
+use std::collections::HashSet;
+use std::iter;
+use std::mem;
+use std::str;
+use std::sync::Arc;
+
+use ::QueryError;
+use disk_store::interface::DiskStore;
+use engine::QueryExecutor;
+use engine::query::{Join, JoinType, Query};
+use engine::query_plan::{self, missing_column, QueryPlan};
+use engine::types::{BasicType, EncodingType};
+use ingest::ordered_float::OrderedFloat;
+use ingest::raw_val::RawVal;
+use mem_store::codec::CodecOp;
+use mem_store::column::{Column, DataSection};
+use mem_store::column_builder::*;
+use mem_store::partition::Partition;
+use syntax::expression::*;
+
+
+/// Build side of a hash join, which holds the rows of the joined table indexed by their key.
+/// It is built once per query and probed with each partition of the queried table.
+pub struct HashJoin {
+    /// Column of the queried table that has to match the key of the joined table.
+    key: String,
+    /// Columns of the joined table that are referenced by the query, starting with its key.
+    columns: Vec<JoinedColumn>,
+    /// Row of the joined table for each value of its key.
+    index: HashMap<RawVal, usize>,
+}
+
+/// Values of a column of the joined table, read from all of its partitions.
+struct JoinedColumn {
+    /// Qualified name of the column.
+    name: String,
+    values: Values,
+    /// 1 for each row that is NULL.
+    nulls: Vec<u8>,
+}
+
+/// Decoded values of a column. Rows that are NULL hold an arbitrary value.
+enum Values {
+    Null(usize),
+    Int(Vec<i64>),
+    Float(Vec<f64>),
+    Str(Vec<String>),
+}
+
+/// Qualifies columns that only exist in the joined table and resolves the columns compared by the join condition.
+/// Inner joins are restricted to rows that have a matching row in the joined table.
+/// Returns the rewritten query, the key column of the queried table and the (qualified) key column of the joined table.
+pub fn resolve_join(query: Query,
+                    join: &Join,
+                    cols: &[String],
+                    joined_cols: &[String]) -> Result<(Query, String, String), QueryError> {
+    let prefix = format!("{}.", join.alias);
+    let qualify = |name: &str| -> String {
+        if name != "*" && !cols.iter().any(|col| col == name) && joined_cols.iter().any(|col| col == name) {
+            format!("{}{}", prefix, name)
+        } else {
+            name.to_string()
+        }
+    };
+    let mut query = query.substitute(&|expr| match *expr {
+        Expr::ColName(ref name) => Some(Expr::ColName(qualify(name))),
+        _ => None,
+    });
+
+    let (lhs, rhs) = (qualify(&join.on.0), qualify(&join.on.1));
+    let (key, foreign_key) = match (lhs.starts_with(&prefix), rhs.starts_with(&prefix)) {
+        (false, true) => (lhs, rhs),
+        (true, false) => (rhs, lhs),
+        _ => bail!(QueryError::InvalidQuery, "JOIN condition has to compare a column of {} with a column of {}",
+                   &query.table, &join.table),
+    };
+    if !cols.contains(&key) {
+        return Err(missing_column(&key, cols.iter().map(|s| s.as_str())));
+    }
+    if !joined_cols.iter().any(|col| *col == foreign_key[prefix.len()..]) {
+        return Err(missing_column(&foreign_key[prefix.len()..], joined_cols.iter().map(|s| s.as_str())));
+    }
+
+    if join.kind == JoinType::Inner {
+        let matched = Expr::func1(Func1Type::IsNotNull, Expr::ColName(foreign_key.clone()));
+        query.filter = match query.filter {
+            Expr::Const(RawVal::Int(1)) => matched,
+            filter => Expr::func(Func2Type::And, filter, matched),
+        };
+    }
+    Ok((query, key, foreign_key))
+}
+
+impl HashJoin {
+    /// Reads the key and all `referenced_cols` of the joined table from `source`.
+    /// Fails with `InvalidQuery` if the key of the joined table contains duplicate values, since each row of the
+    /// queried table can match at most one row of the joined table.
+    pub fn build(join: &Join,
+                 key: String,
+                 foreign_key: String,
+                 referenced_cols: &HashSet<String>,
+                 source: &[Arc<Partition>],
+                 db: &DiskStore) -> Result<HashJoin, QueryError> {
+        let prefix = format!("{}.", join.alias);
+        let mut names = referenced_cols.iter()
+            .filter(|col| col.starts_with(&prefix) && **col != foreign_key)
+            .cloned()
+            .collect::<Vec<_>>();
+        names.sort();
+        names.insert(0, foreign_key);
+        let unqualified = names.iter().map(|col| col[prefix.len()..].to_string()).collect::<Vec<_>>();
+        let colnames = unqualified.iter().cloned().collect::<HashSet<_>>();
+
+        let mut columns = names.into_iter()
+            .map(|name| JoinedColumn { name, values: Values::Null(0), nulls: Vec::new() })
+            .collect::<Vec<_>>();
+        for partition in source {
+            let cols = partition.get_cols(&colnames, db);
+            for (column, colname) in columns.iter_mut().zip(&unqualified) {
+                // Columns that don't exist in the partition are NULL
+                let (values, nulls) = match cols.get(colname) {
+                    Some(col) => (Values::read(col)?, null_map(col)),
+                    None => (Values::Null(partition.len()), vec![1; partition.len()]),
+                };
+                column.values = mem::replace(&mut column.values, Values::Null(0)).append(values);
+                column.nulls.extend(nulls);
+            }
+        }
+
+        let mut index = HashMap::new();
+        let key_column = &columns[0];
+        for row in 0..key_column.nulls.len() {
+            if key_column.nulls[row] == 1 {
+                continue;
+            }
+            let value = key_column.values.get_raw(row);
+            if index.contains_key(&value) {
+                bail!(QueryError::InvalidQuery, "JOIN on column {} of {}, which contains duplicate value {}",
+                      &unqualified[0], &join.table, &value);
+            }
+            index.insert(value, row);
+        }
+        Ok(HashJoin { key, columns, index })
+    }
+
+    /// Adds the columns of the joined table to the columns of a partition of the queried table.
+    /// Rows without a matching row in the joined table are NULL in all of these columns.
+    pub fn probe(&self, cols: &mut HashMap<String, Arc<Column>>) -> Result<(), QueryError> {
+        let matches = match cols.get(&self.key) {
+            Some(key) => match self.probe_dictionary(key) {
+                Some(matches) => matches,
+                None => {
+                    let values = Values::read(key)?;
+                    null_map(key).iter().enumerate()
+                        .map(|(row, &null)| if null == 1 {
+                            None
+                        } else {
+                            self.index.get(&values.get_raw(row)).cloned()
+                        })
+                        .collect()
+                }
+            },
+            None => vec![None; cols.values().next().map_or(0, |col| col.len())],
+        };
+        for column in &self.columns {
+            cols.insert(column.name.to_string(), column.gather(&matches));
+        }
+        Ok(())
+    }
+
+    /// Matches each entry of the dictionary of a dictionary encoded key column instead of each row.
+    /// Returns `None` if `key` is not dictionary encoded.
+    fn probe_dictionary(&self, key: &Column) -> Option<Vec<Option<usize>>> {
+        match key.codec().ops() {
+            [CodecOp::PushDataSection(1), CodecOp::PushDataSection(2), CodecOp::DictLookup(_)] => {}
+            _ => return None,
+        }
+        let entries = match (&key.data()[1], &key.data()[2]) {
+            (&DataSection::U64(ref dict_indices), &DataSection::U8(ref dict_data)) =>
+                dict_indices.iter()
+                    .map(|&offset_len| {
+                        let offset = (offset_len >> 24) as usize;
+                        let len = (offset_len & 0xffffff) as usize;
+                        let entry = unsafe { str::from_utf8_unchecked(&dict_data[offset..(offset + len)]) };
+                        self.index.get(&RawVal::Str(entry.to_string())).cloned()
+                    })
+                    .collect::<Vec<_>>(),
+            _ => return None,
+        };
+        let mut matches = match key.data()[0] {
+            DataSection::U8(ref indices) => indices.iter().map(|&i| entries[i as usize]).collect::<Vec<_>>(),
+            DataSection::U16(ref indices) => indices.iter().map(|&i| entries[i as usize]).collect::<Vec<_>>(),
+            _ => return None,
+        };
+        if let Some(nulls) = key.nulls() {
+            for (row, &null) in matches.iter_mut().zip(nulls) {
+                if null == 1 {
+                    *row = None;
+                }
+            }
+        }
+        Some(matches)
+    }
+}
+
+impl JoinedColumn {
+    /// Column that holds the value of row `rows[i]` of the joined table in its `i`th row, or NULL if there is no
+    /// such row.
+    fn gather(&self, rows: &[Option<usize>]) -> Arc<Column> {
+        let rows = rows.iter()
+            .map(|row| row.and_then(|row| if self.nulls[row] == 1 { None } else { Some(row) }))
+            .collect::<Vec<_>>();
+        if rows.iter().all(|row| row.is_none()) {
+            return Arc::new(Column::null(&self.name, rows.len()));
+        }
+        match self.values {
+            Values::Null(_) => Arc::new(Column::null(&self.name, rows.len())),
+            Values::Int(ref values) => gather(IntColBuilder::new(), values, &rows, &self.name),
+            Values::Float(ref values) => gather(FloatColBuilder::new(), values, &rows, &self.name),
+            Values::Str(ref values) => gather(StringColBuilder::new(), values, &rows, &self.name),
+        }
+    }
+}
+
+fn gather<T: ?Sized, V: Borrow<T>, B: ColumnBuilder<T>>(mut builder: B,
+                                                         values: &[V],
+                                                         rows: &[Option<usize>],
+                                                         name: &str) -> Arc<Column> {
+    for row in rows {
+        match *row {
+            Some(row) => builder.push(values[row].borrow()),
+            None => builder.push_null(),
+        }
+    }
+    builder.finalize(name)
+}
+
+impl Values {
+    /// Decodes all values of `column`.
+    fn read(column: &Column) -> Result<Values, QueryError> {
+        let mut executor = QueryExecutor::default();
+        let plan = column.codec().decode(
+            Box::new(QueryPlan::ReadColumnSection(column.name().to_string(), 0, column.range())));
+        let decoded = query_plan::prepare(*plan, &mut executor);
+        let data = iter::once((column.name().to_string(), column.data_sections())).collect();
+        let mut results = executor.prepare(data);
+        executor.run(column.len(), &mut results, false);
+        let values = results.collect(decoded);
+        Ok(match values.get_type() {
+            EncodingType::Null => Values::Null(column.len()),
+            EncodingType::I64 => Values::Int(values.cast_ref_i64().to_vec()),
+            EncodingType::F64 => Values::Float(values.cast_ref_f64().iter().map(|float| float.0).collect()),
+            EncodingType::Str => Values::Str(values.cast_ref_str().iter().map(|s| s.to_string()).collect()),
+            t => bail!(QueryError::NotImplemented, "JOIN with column {} of type {:?}", column.name(), t),
+        })
+    }
+
+    fn get_raw(&self, row: usize) -> RawVal {
+        match *self {
+            Values::Null(_) => RawVal::Null,
+            Values::Int(ref values) => RawVal::Int(values[row]),
+            Values::Float(ref values) => RawVal::Float(OrderedFloat(values[row])),
+            Values::Str(ref values) => RawVal::Str(values[row].clone()),
+        }
+    }
+
+    /// Appends the values of another partition. Integers are converted to floats and numbers to strings if the
+    /// partitions hold values of different types.
+    fn append(self, other: Values) -> Values {
+        match (self, other) {
+            (Values::Null(len), Values::Null(other)) => Values::Null(len + other),
+            (Values::Int(mut values), Values::Int(other)) => {
+                values.extend(other);
+                Values::Int(values)
+            }
+            (Values::Float(mut values), Values::Float(other)) => {
+                values.extend(other);
+                Values::Float(values)
+            }
+            (Values::Str(mut values), Values::Str(other)) => {
+                values.extend(other);
+                Values::Str(values)
+            }
+            (Values::Null(len), other) => other.placeholders(len).append(other),
+            (values, Values::Null(len)) => {
+                let placeholders = values.placeholders(len);
+                values.append(placeholders)
+            }
+            (Values::Int(values), Values::Float(other)) =>
+                Values::Float(values.iter().map(|&int| int as f64).collect()).append(Values::Float(other)),
+            (Values::Float(values), Values::Int(other)) =>
+                Values::Float(values).append(Values::Float(other.iter().map(|&int| int as f64).collect())),
+            (values, other) => Values::Str(values.into_strings()).append(Values::Str(other.into_strings())),
+        }
+    }
+
+    /// `len` arbitrary values of the same type, which stand in for NULL values.
+    fn placeholders(&self, len: usize) -> Values {
+        match *self {
+            Values::Null(_) => Values::Null(len),
+            Values::Int(_) => Values::Int(vec![0; len]),
+            Values::Float(_) => Values::Float(vec![0.0; len]),
+            Values::Str(_) => Values::Str(vec![String::new(); len]),
+        }
+    }
+
+    fn into_strings(self) -> Vec<String> {
+        match self {
+            Values::Null(len) => vec![String::new(); len],
+            Values::Int(values) => values.iter().map(|int| int.to_string()).collect(),
+            Values::Float(values) => values.iter().map(|float| float.to_string()).collect(),
+            Values::Str(values) => values,
+        }
+    }
+}
+
+/// 1 for each row of `column` that is NULL.
+fn null_map(column: &Column) -> Vec<u8> {
+    match column.nulls() {
+        _ if column.basic_type() == BasicType::Null => vec![1; column.len()],
+        Some(nulls) => nulls.to_vec(),
+        None => vec![0; column.len()],
+    }
+}
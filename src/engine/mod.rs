@@ -2,9 +2,16 @@ mod batch_merging;
 pub mod query_plan;
 pub mod vector_op;
 pub mod aggregator;
+pub mod date_function;
 pub mod filter;
+pub mod hyperloglog;
+pub mod join;
+pub mod moments;
 pub mod query;
 pub mod query_task;
+pub mod row_eval;
+pub mod string_function;
+pub mod tdigest;
 pub mod typed_vec;
 pub mod types;
 
@@ -16,6 +23,8 @@ pub use self::typed_vec::{
     GenericIntVec,
     CastUsize,
     ConstType,
+    ValueSet,
+    AggregationState,
 };
 
 pub use self::filter::Filter;
@@ -0,0 +1,122 @@
+/// Statistic estimated from `Moments`. All statistics use the sample (rather than population) estimators.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Statistic {
+    Variance,
+    StdDev,
+    Covariance,
+    Correlation,
+}
+
+/// Count, means and sums of squared deviations of two variables, which are updated one value at a time and merged
+/// using the numerically stable formulas of Welford and Chan et al.
+/// Aggregates over a single variable insert each value as both `x` and `y`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Moments {
+    count: f64,
+    mean_x: f64,
+    mean_y: f64,
+    m2_x: f64,
+    m2_y: f64,
+    c_xy: f64,
+}
+
+impl Moments {
+    pub fn new() -> Moments {
+        Moments {
+            count: 0.0,
+            mean_x: 0.0,
+            mean_y: 0.0,
+            m2_x: 0.0,
+            m2_y: 0.0,
+            c_xy: 0.0,
+        }
+    }
+
+    #[inline]
+    pub fn insert(&mut self, x: f64, y: f64) {
+        self.count += 1.0;
+        let dx = x - self.mean_x;
+        let dy = y - self.mean_y;
+        self.mean_x += dx / self.count;
+        self.mean_y += dy / self.count;
+        self.m2_x += dx * (x - self.mean_x);
+        self.m2_y += dy * (y - self.mean_y);
+        self.c_xy += dx * (y - self.mean_y);
+    }
+
+    /// Combines the moments of two disjoint sets of values.
+    pub fn merge(&mut self, other: &Moments) {
+        if other.count == 0.0 {
+            return;
+        }
+        if self.count == 0.0 {
+            *self = other.clone();
+            return;
+        }
+        let count = self.count + other.count;
+        let dx = other.mean_x - self.mean_x;
+        let dy = other.mean_y - self.mean_y;
+        let weight = self.count * other.count / count;
+        self.mean_x += dx * other.count / count;
+        self.mean_y += dy * other.count / count;
+        self.m2_x += other.m2_x + dx * dx * weight;
+        self.m2_y += other.m2_y + dy * dy * weight;
+        self.c_xy += other.c_xy + dx * dy * weight;
+        self.count = count;
+    }
+
+    /// Value of `statistic`, or `None` if it is undefined because there are fewer than two values or, for
+    /// correlations, one of the variables is constant.
+    pub fn estimate(&self, statistic: Statistic) -> Option<f64> {
+        if self.count < 2.0 {
+            return None;
+        }
+        match statistic {
+            Statistic::Variance => Some(self.m2_x / (self.count - 1.0)),
+            Statistic::StdDev => Some((self.m2_x / (self.count - 1.0)).sqrt()),
+            Statistic::Covariance => Some(self.c_xy / (self.count - 1.0)),
+            Statistic::Correlation => {
+                let denominator = (self.m2_x * self.m2_y).sqrt();
+                if denominator == 0.0 { None } else { Some(self.c_xy / denominator) }
+            }
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn moments(values: &[(f64, f64)]) -> Moments {
+        let mut moments = Moments::new();
+        for &(x, y) in values {
+            moments.insert(x, y);
+        }
+        moments
+    }
+
+    #[test]
+    fn test_estimate() {
+        let values = [(1.0, 2.0), (2.0, 4.5), (4.0, 7.0), (5.0, 9.5)];
+        assert_eq!(moments(&values[..1]).estimate(Statistic::Variance), None);
+        assert_eq!(moments(&[(1.0, 3.0), (2.0, 3.0)]).estimate(Statistic::Correlation), None);
+        for &(statistic, expected) in &[(Statistic::Variance, 10.0 / 3.0),
+                                        (Statistic::StdDev, (10.0f64 / 3.0).sqrt()),
+                                        (Statistic::Covariance, 17.5 / 3.0),
+                                        (Statistic::Correlation, 0.9899494936611665)] {
+            let estimate = moments(&values).estimate(statistic).unwrap();
+            assert!((estimate - expected).abs() < 1e-12, "{:?} {} {}", statistic, estimate, expected);
+        }
+    }
+
+    #[test]
+    fn test_merge_is_stable() {
+        let values = (0..1000).map(|i| (1e9 + i as f64, 1e9 - i as f64)).collect::<Vec<_>>();
+        let mut merged = moments(&values[..300]);
+        merged.merge(&moments(&values[300..]));
+        merged.merge(&Moments::new());
+        let covariance = merged.estimate(Statistic::Covariance).unwrap();
+        assert!((covariance + 83416.66666666667).abs() < 1e-6, "{}", covariance);
+    }
+}
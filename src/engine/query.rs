@@ -1,6 +1,7 @@
 use std::collections::HashMap;
 use std::collections::HashSet;
 use std::iter::Iterator;
+use std::mem;
 use std::sync::Arc;
 
 use ::QueryError;
@@ -8,24 +9,81 @@ use engine::*;
 use engine::aggregator::*;
 use engine::batch_merging::*;
 use engine::query_plan::QueryPlan;
+use engine::row_eval;
 use engine::types::EncodingType;
 use engine::types::Type;
 use ingest::raw_val::RawVal;
 use mem_store::column::Column;
 use syntax::expression::*;
 use syntax::limit::*;
+use time;
 
 
 #[derive(Debug, Clone)]
 pub struct Query {
+    /// Common table expressions and subqueries of the FROM clause, which are evaluated before the query and can be
+    /// referenced like tables by the query and all subsequent subqueries.
+    pub with: Vec<(String, Query)>,
     pub select: Vec<Expr>,
     pub table: String,
+    pub join: Option<Join>,
     pub filter: Expr,
     pub aggregate: Vec<(Aggregator, Expr)>,
-    pub order_by: Option<String>,
-    pub order_desc: bool,
+    pub group_by: Vec<Expr>,
+    /// Indices into `group_by` of the expressions of each grouping set (`ROLLUP`, `CUBE` and `GROUPING SETS`).
+    /// Empty if the query is grouped by all expressions of `group_by`. Expressions that are not part of a grouping set
+    /// are NULL in its results, which makes subtotals indistinguishable from groups of NULL values (there is no
+    /// `GROUPING()` function).
+    pub grouping_sets: Vec<Vec<usize>>,
+    pub having: Option<Expr>,
+    pub aliases: Vec<Option<String>>,
+    pub order_by: Vec<(Expr, bool)>,
     pub limit: LimitClause,
+    pub limit_by: Option<LimitBy>,
     pub order_by_index: Option<usize>,
+    /// Queries whose results are appended to the result of the query (`UNION ALL`). The `order_by` and `limit` of a
+    /// query with UNION ALL apply to the concatenated results, and its sort keys refer to columns of the results.
+    pub union_all: Vec<Query>,
+}
+
+/// Join of the rows of the queried table with the rows of another table that have a matching key.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Join {
+    pub kind: JoinType,
+    pub table: String,
+    /// Prefix that qualifies the names of columns of the joined table, e.g. `alias.column`.
+    pub alias: String,
+    /// The two columns compared by the ON clause, which are resolved to a column of each table once the columns of
+    /// both tables are known.
+    pub on: (String, String),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum JoinType {
+    /// Only rows with a matching row in the joined table.
+    Inner,
+    /// All rows, with NULL values for the columns of the joined table if there is no matching row.
+    Left,
+}
+
+/// Retains only the first rows for each distinct value of the `by` expressions (`LIMIT n [OFFSET m] BY exprs`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct LimitBy {
+    pub limit: LimitClause,
+    pub by: Vec<Expr>,
+}
+
+/// Buffers that hold the results of aggregating a partition by the grouping key of a grouping set.
+#[derive(Debug, Clone)]
+struct Grouping {
+    /// Distinct (encoded) grouping keys of all groups.
+    keys: BufferRef,
+    /// Aggregation results of all groups before decoding, in the same order as `keys`.
+    aggregates: Vec<(BufferRef, Type)>,
+    /// Decoded group by columns.
+    group_by: Vec<(BufferRef, Type)>,
+    /// Decoded aggregation results.
+    select: Vec<(BufferRef, Type)>,
 }
 
 impl Query {
@@ -33,43 +91,70 @@ impl Query {
     pub fn run<'a>(&self, columns: &'a HashMap<String, Arc<Column>>, explain: bool, show: bool)
                    -> Result<(BatchResult<'a>, Option<String>), QueryError> {
         let limit = (self.limit.limit + self.limit.offset) as usize;
+        let desc = self.order_by.get(0).map_or(false, |&(_, desc)| desc);
         let len = columns.iter().next().unwrap().1.len();
         let mut executor = QueryExecutor::default();
 
         let (filter_plan, filter_type) = QueryPlan::create_query_plan(&self.filter, Filter::None, columns)?;
-        let mut filter = match filter_type.encoding_type() {
+        let filter = match filter_type.encoding_type() {
             EncodingType::BitVec => {
-                let mut compiled_filter = query_plan::prepare(filter_plan, &mut executor);
+                let compiled_filter = query_plan::prepare(filter_plan, &mut executor);
                 Filter::BitVec(compiled_filter)
             }
             _ => Filter::None,
         };
 
         let mut select = Vec::new();
+        let mut sort_indices = None;
         if let Some(index) = self.order_by_index {
-            let (plan, plan_t) = query_plan::order_preserving(
-                QueryPlan::create_query_plan(&self.select[index], filter, columns)?);
+            let (plan, plan_t) = query_plan::order_preserving(query_plan::boolean_as_integer(
+                QueryPlan::create_query_plan(&self.select[index], filter, columns)?));
             // TODO(clemens): Reuse sort_column for result
             let sort_column = query_plan::prepare(plan.clone(), &mut executor);
             // TODO(clemens): better criterion
-            let sort_indices = if limit < len / 2 {
+            let indices = if let Some(ref limit_by) = self.limit_by {
+                match self.limit_by_grouping_key(&limit_by.by, filter, columns) {
+                    Some(grouping_key) => query_plan::prepare(
+                        QueryPlan::TopNPerGroup(
+                            Box::new(QueryPlan::ReadBuffer(sort_column)),
+                            plan_t.encoding_type(),
+                            Box::new(grouping_key),
+                            (limit_by.limit.limit + limit_by.limit.offset) as usize,
+                            desc),
+                        &mut executor),
+                    // Groups are only limited once the results of all partitions have been combined
+                    None => query_plan::prepare(
+                        QueryPlan::SortIndices(
+                            Box::new(QueryPlan::ReadBuffer(sort_column)),
+                            desc),
+                        &mut executor),
+                }
+            } else if limit < len / 2 {
                 query_plan::prepare(
                     QueryPlan::TopN(
                         Box::new(QueryPlan::ReadBuffer(sort_column)),
-                        plan_t.encoding_type(), limit, self.order_desc),
+                        plan_t.encoding_type(), limit, desc),
                     &mut executor)
             } else {
                 // TODO(clemens): Optimization: sort directly if only single column selected
                 query_plan::prepare(
                     QueryPlan::SortIndices(
                         Box::new(QueryPlan::ReadBuffer(sort_column)),
-                        self.order_desc),
+                        desc),
                     &mut executor)
             };
-            filter = Filter::Indices(sort_indices);
+            sort_indices = Some(indices);
         }
         for expr in &self.select {
-            let (mut plan, plan_type) = QueryPlan::create_query_plan(expr, filter, columns)?;
+            let (mut plan, plan_type) =
+                query_plan::boolean_as_integer(QueryPlan::create_query_plan(expr, filter, columns)?);
+            // Sort indices refer to positions after applying the filter
+            if let Some(indices) = sort_indices {
+                plan = QueryPlan::Select(
+                    Box::new(plan),
+                    Box::new(QueryPlan::ReadBuffer(indices)),
+                    plan_type.encoding_type());
+            }
             if let Some(codec) = plan_type.codec {
                 plan = *codec.decode(Box::new(plan));
             }
@@ -85,7 +170,8 @@ impl Query {
                 group_by: None,
                 sort_by: self.order_by_index,
                 select,
-                desc: self.order_desc,
+                desc,
+                sort_keys: if self.order_by_index.is_none() { self.sort_keys() } else { Vec::with_capacity(0) },
                 aggregators: Vec::with_capacity(0),
                 level: 0,
                 batch_count: 1,
@@ -95,13 +181,48 @@ impl Query {
              if explain { Some(format!("{}", executor)) } else { None }))
     }
 
+    /// Positions of the (hidden) select columns that hold the sort keys and their null indicators.
+    fn sort_keys(&self) -> Vec<SortKey> {
+        self.order_by.iter()
+            .map(|&(ref expr, desc)| {
+                // Sort keys and null indicators are added to the select columns by `resolve_columns`
+                let index = self.select.iter().position(|e| e == expr).unwrap();
+                let indicator = Expr::func1(Func1Type::IsNull, expr.clone());
+                let null_indicator = self.select.iter().position(|e| *e == indicator);
+                SortKey { index, null_indicator, desc }
+            })
+            .collect()
+    }
+
+    /// Grouping key (of type i64) that distinguishes the groups of the LIMIT BY clause, or `None` if the expressions
+    /// can't be packed into a grouping key.
+    fn limit_by_grouping_key(&self, by: &[Expr], filter: Filter, columns: &HashMap<String, Arc<Column>>)
+                             -> Option<QueryPlan> {
+        // NULL values are distinguished by the null indicators of nullable expressions
+        let mut exprs = by.to_vec();
+        for expr in by {
+            let indicator = Expr::func1(Func1Type::IsNull, expr.clone());
+            if self.select.contains(&indicator) && !exprs.contains(&indicator) {
+                exprs.push(indicator);
+            }
+        }
+        let grouping_set = (0..exprs.len()).collect::<Vec<_>>();
+        let (plan, t, _, _) = QueryPlan::compile_grouping_key(&exprs, &grouping_set, filter, columns).ok()?;
+        match t.encoding_type() {
+            EncodingType::I64 => Some(plan),
+            t @ EncodingType::U8 | t @ EncodingType::U16 | t @ EncodingType::U32 =>
+                Some(QueryPlan::Cast(Box::new(plan), t, EncodingType::I64)),
+            _ => None,
+        }
+    }
+
     #[inline(never)] // produces more useful profiles
     pub fn run_aggregate<'a>(&self,
                              columns: &'a HashMap<String, Arc<Column>>,
                              explain: bool,
                              show: bool,
                              partition: usize)
-                             -> Result<(BatchResult<'a>, Option<String>), QueryError> {
+                             -> Result<(Vec<BatchResult<'a>>, Option<String>), QueryError> {
         trace_start!("run_aggregate");
 
         let mut executor = QueryExecutor::default();
@@ -116,10 +237,93 @@ impl Query {
             _ => Filter::None,
         };
 
-        // Combine all group by columns into a single decodable grouping key
-        let (grouping_key_plan, raw_grouping_key_type, max_grouping_key, decode_plans) =
-            QueryPlan::compile_grouping_key(&self.select, filter, columns)?;
-        let raw_grouping_key = query_plan::prepare(grouping_key_plan, &mut executor);
+        // The finest grouping by all group by expressions is aggregated once, and the results of coarser grouping sets
+        // are derived by aggregating its results over the fields of their expressions in the packed grouping key.
+        // Grouping sets are aggregated separately if the grouping key of all expressions doesn't fit into 64 bits.
+        let grouping_sets = self.grouping_set_indices();
+        let (states, _) = self.aggregation_states();
+        let all = (0..self.group_by.len()).collect::<Vec<_>>();
+        let mut groupings = Vec::with_capacity(grouping_sets.len());
+        match QueryPlan::compile_grouping_key(&self.group_by, &all, filter, columns) {
+            Ok((grouping_key_plan, raw_grouping_key_type, max_grouping_key, decode_plans)) => {
+                let finest = Query::aggregate_rows(
+                    &states,
+                    (grouping_key_plan, raw_grouping_key_type.clone(), max_grouping_key, decode_plans.clone()),
+                    filter, columns, &mut executor)?;
+                let mut finest_results = Some(finest.clone());
+                for grouping_set in &grouping_sets {
+                    if *grouping_set == all {
+                        if let Some(results) = finest_results.take() {
+                            groupings.push(results);
+                            continue;
+                        }
+                    }
+                    executor.set_encoded_group_by(finest.keys);
+                    let grouping_key = if *grouping_set == all {
+                        (QueryPlan::EncodedGroupByPlaceholder, raw_grouping_key_type.clone(), max_grouping_key, decode_plans.clone())
+                    } else {
+                        QueryPlan::compile_grouping_key(&self.group_by, grouping_set, filter, columns)?
+                    };
+                    groupings.push(Query::aggregate_groups(&states, grouping_key, &finest, &mut executor)?);
+                }
+            }
+            Err(_) if grouping_sets.len() > 1 => {
+                for grouping_set in &grouping_sets {
+                    let exprs = grouping_set.iter().map(|&i| self.group_by[i].clone()).collect::<Vec<_>>();
+                    let all = (0..exprs.len()).collect::<Vec<_>>();
+                    let grouping_key = QueryPlan::compile_grouping_key(&exprs, &all, filter, columns)?;
+                    groupings.push(Query::aggregate_rows(&states, grouping_key, filter, columns, &mut executor)?);
+                }
+            }
+            Err(err) => return Err(err),
+        }
+
+        for c in columns {
+            debug!("{}: {:?}", partition, c);
+        }
+        let mut results = executor.prepare(Query::column_data(columns));
+        debug!("{:#}", &executor);
+        executor.run(columns.iter().next().unwrap().1.len(), &mut results, show);
+
+        let mut batches = Vec::with_capacity(groupings.len());
+        for grouping in &groupings {
+            let batch = BatchResult {
+                group_by: Some(grouping.group_by.iter().map(|&(i, _)| results.collect(i)).collect()),
+                sort_by: None,
+                select: grouping.select.iter().map(|&(i, _)| results.collect(i)).collect(),
+                desc: false,
+                sort_keys: Vec::with_capacity(0),
+                aggregators: states.iter().map(|x| x.0).collect(),
+                level: 0,
+                batch_count: 1,
+                show,
+                unsafe_referenced_buffers: Vec::with_capacity(0),
+            };
+            if let Err(err) = batch.validate() {
+                warn!("Query result failed validation (partition {}): {}\n{:#}\nGroup By: {:?}\nSelect: {:?}",
+                      partition, err, &executor, grouping.group_by, grouping.select);
+                return Err(err);
+            }
+            batches.push(batch);
+        }
+        // The results of all grouping sets are kept until they have been combined with the results of all partitions
+        batches[0].unsafe_referenced_buffers = results.collect_pinned();
+        Ok((
+            batches,
+            if explain { Some(format!("{}", executor)) } else { None }
+        ))
+    }
+
+    /// Computes the aggregation `states` (see `aggregation_states`) for the rows of a partition, grouped by the
+    /// grouping key returned by `QueryPlan::compile_grouping_key`.
+    fn aggregate_rows<'a>(states: &[(Aggregator, Expr)],
+                          grouping_key: (QueryPlan, Type, i64, Vec<(QueryPlan, Type)>),
+                          filter: Filter,
+                          columns: &'a HashMap<String, Arc<Column>>,
+                          executor: &mut QueryExecutor<'a>) -> Result<Grouping, QueryError> {
+        // Combine all group by columns of the grouping set into a single decodable grouping key
+        let (grouping_key_plan, raw_grouping_key_type, max_grouping_key, decode_plans) = grouping_key;
+        let raw_grouping_key = query_plan::prepare(grouping_key_plan, executor);
 
         // Reduce cardinality of grouping key if necessary and perform grouping
         // TODO(clemens): also determine and use is_dense. always true for hashmap, depends on group by columns for raw.
@@ -127,7 +331,7 @@ impl Query {
         // TODO(clemens): refine criterion
             if max_grouping_key < 1 << 16 && raw_grouping_key_type.is_positive_integer() {
                 let max_grouping_key_buf = query_plan::prepare(
-                    QueryPlan::Constant(RawVal::Int(max_grouping_key), true), &mut executor);
+                    QueryPlan::Constant(RawVal::Int(max_grouping_key), true), executor);
                 (None,
                  raw_grouping_key,
                  raw_grouping_key_type.clone(),
@@ -137,41 +341,141 @@ impl Query {
                     raw_grouping_key,
                     raw_grouping_key_type.encoding_type(),
                     max_grouping_key as usize,
-                    &mut executor)
+                    executor)
             };
 
         // Aggregators
         let mut aggregation_results = Vec::new();
-        let mut selector = None;
         let mut selector_index = None;
-        for (i, &(aggregator, ref expr)) in self.aggregate.iter().enumerate() {
-            let (plan, plan_type) = QueryPlan::create_query_plan(expr, filter, columns)?;
-            let (aggregate, t) = query_plan::prepare_aggregation(
-                plan,
-                plan_type,
-                grouping_key,
-                grouping_key_type.encoding_type(),
-                aggregation_cardinality,
-                aggregator,
-                &mut executor)?;
+        for (i, &(aggregator, ref expr)) in states.iter().enumerate() {
+            // NULL values are not counted and don't contribute to sums
+            let not_null = QueryPlan::not_null_plan(expr, filter, columns)?;
+            let (aggregator, aggregate, t) = match *expr {
+                // Arguments of two-argument aggregates, rows in which either argument is NULL are skipped
+                Expr::Func2(Func2Type::Pair, ref x, ref y) => {
+                    let (plan_x, type_x) = QueryPlan::create_query_plan(x, filter, columns)?;
+                    let y = QueryPlan::create_query_plan(y, filter, columns)?;
+                    let (aggregate, t) = query_plan::prepare_moments(
+                        plan_x,
+                        type_x,
+                        Some(y),
+                        not_null,
+                        grouping_key,
+                        grouping_key_type.encoding_type(),
+                        aggregation_cardinality,
+                        aggregator,
+                        executor)?;
+                    (aggregator, aggregate, t)
+                }
+                _ => {
+                    let (plan, plan_type) = match aggregator {
+                        // Sums of predicates count the rows in which they are true
+                        Aggregator::Sum | Aggregator::Avg => query_plan::cast_boolean_to_integer(
+                            QueryPlan::create_query_plan(expr, filter, columns)?),
+                        _ => QueryPlan::create_query_plan(expr, filter, columns)?,
+                    };
+                    let (aggregator, plan, plan_type) = match not_null {
+                        Some(ref not_null) => query_plan::ignore_nulls(aggregator, plan, plan_type, not_null.clone()),
+                        None => (aggregator, plan, plan_type),
+                    };
+                    let (aggregate, t) = query_plan::prepare_aggregation(
+                        plan,
+                        plan_type,
+                        not_null,
+                        grouping_key,
+                        grouping_key_type.encoding_type(),
+                        aggregation_cardinality,
+                        aggregator,
+                        executor)?;
+                    (aggregator, aggregate, t)
+                }
+            };
             // TODO(clemens): if summation column is strictly positive, can use sum as well
             if aggregator == Aggregator::Count {
-                selector = Some((aggregate, t.encoding_type()));
                 selector_index = Some(i)
             }
             aggregation_results.push((aggregator, aggregate, t))
         }
 
+        Query::compact_and_decode(
+            aggregation_results,
+            selector_index,
+            encoded_group_by_column,
+            grouping_key,
+            grouping_key_type,
+            raw_grouping_key_type,
+            aggregation_cardinality,
+            decode_plans,
+            executor)
+    }
+
+    /// Derives the results of a coarser grouping set from the results of the `finest` grouping by aggregating the
+    /// partial aggregation results of its groups. The grouping key is computed from the grouping keys of `finest`.
+    fn aggregate_groups(states: &[(Aggregator, Expr)],
+                        grouping_key: (QueryPlan, Type, i64, Vec<(QueryPlan, Type)>),
+                        finest: &Grouping,
+                        executor: &mut QueryExecutor) -> Result<Grouping, QueryError> {
+        let (grouping_key_plan, raw_grouping_key_type, max_grouping_key, decode_plans) = grouping_key;
+        let raw_grouping_key = query_plan::prepare(grouping_key_plan, executor);
+        // Aggregation states can only be merged by grouping keys that number groups in order of their first occurrence
+        let (encoded_group_by_column, grouping_key, grouping_key_type, aggregation_cardinality) =
+            query_plan::prepare_hashmap_grouping(
+                raw_grouping_key,
+                raw_grouping_key_type.encoding_type(),
+                max_grouping_key as usize,
+                executor);
+
+        let mut aggregation_results = Vec::with_capacity(states.len());
+        for (&(aggregator, _), &(aggregate, ref t)) in states.iter().zip(&finest.aggregates) {
+            let (aggregate, t) = query_plan::prepare_reaggregation(
+                aggregate,
+                t.clone(),
+                aggregator,
+                grouping_key,
+                grouping_key_type.encoding_type(),
+                aggregation_cardinality,
+                executor)?;
+            // Counts of the finest grouping are summed
+            let aggregator = if aggregator == Aggregator::Count { Aggregator::Sum } else { aggregator };
+            aggregation_results.push((aggregator, aggregate, t));
+        }
+
+        Query::compact_and_decode(
+            aggregation_results,
+            None,
+            encoded_group_by_column,
+            grouping_key,
+            grouping_key_type,
+            raw_grouping_key_type,
+            aggregation_cardinality,
+            decode_plans,
+            executor)
+    }
+
+    /// Removes empty groups from the aggregation results and decodes them together with the group by columns.
+    /// `selector_index` is the index of a count which is nonzero for all groups.
+    fn compact_and_decode(aggregation_results: Vec<(Aggregator, BufferRef, Type)>,
+                          selector_index: Option<usize>,
+                          encoded_group_by_column: Option<BufferRef>,
+                          grouping_key: BufferRef,
+                          grouping_key_type: Type,
+                          raw_grouping_key_type: Type,
+                          aggregation_cardinality: BufferRef,
+                          decode_plans: Vec<(QueryPlan, Type)>,
+                          executor: &mut QueryExecutor) -> Result<Grouping, QueryError> {
         // Determine selector
-        let (selector, selector_type) = selector.unwrap_or_else(|| {
-            let s = query_plan::prepare(
-                QueryPlan::Exists(
-                    Box::new(QueryPlan::ReadBuffer(grouping_key)),
-                    grouping_key_type.encoding_type(),
-                    Box::new(QueryPlan::ReadBuffer(aggregation_cardinality))),
-                &mut executor);
-            (s, EncodingType::U8)
-        });
+        let (selector, selector_type) = match selector_index {
+            Some(i) => (aggregation_results[i].1, aggregation_results[i].2.encoding_type()),
+            None => {
+                let s = query_plan::prepare(
+                    QueryPlan::Exists(
+                        Box::new(QueryPlan::ReadBuffer(grouping_key)),
+                        grouping_key_type.encoding_type(),
+                        Box::new(QueryPlan::ReadBuffer(aggregation_cardinality))),
+                    executor);
+                (s, EncodingType::U8)
+            }
+        };
 
         // Construct (encoded) group by column
         let encoded_group_by_column = encoded_group_by_column.unwrap_or_else(|| {
@@ -180,29 +484,34 @@ impl Query {
                     Box::new(QueryPlan::ReadBuffer(selector)),
                     selector_type,
                     grouping_key_type.encoding_type()),
-                &mut executor)
+                executor)
         });
         executor.set_encoded_group_by(encoded_group_by_column);
 
         // Compact and decode aggregation results
+        let mut aggregates = Vec::new();
         let mut select = Vec::new();
         {
-            let mut decode_compact = |aggregator: Aggregator, aggregate: BufferRef, t: Type, select: &mut Vec<(BufferRef, Type)>| {
+            let mut decode_compact = |aggregator: Aggregator, aggregate: BufferRef, t: Type,
+                                      aggregates: &mut Vec<(BufferRef, Type)>, select: &mut Vec<(BufferRef, Type)>| {
                 let compacted = match aggregator {
                     // TODO(clemens): if summation column is strictly positive, can use NonzeroCompact
-                    Aggregator::Sum => query_plan::prepare(
+                    Aggregator::Sum | Aggregator::Avg | Aggregator::Min | Aggregator::Max | Aggregator::CountDistinct |
+                    Aggregator::ApproxCountDistinct(_) | Aggregator::Percentile(_) | Aggregator::Variance |
+                    Aggregator::StdDev | Aggregator::Covar | Aggregator::Corr => query_plan::prepare(
                         QueryPlan::Compact(
                             Box::new(QueryPlan::ReadBuffer(aggregate)), t.encoding_type(),
                             Box::new(QueryPlan::ReadBuffer(selector)), selector_type),
-                        &mut executor),
+                        executor),
                     Aggregator::Count => query_plan::prepare(
                         QueryPlan::NonzeroCompact(Box::new(QueryPlan::ReadBuffer(aggregate)), t.encoding_type()),
-                        &mut executor),
+                        executor),
                 };
+                aggregates.push((compacted, t.clone()));
                 if t.is_encoded() {
                     let decoded = query_plan::prepare(
                         *t.codec.clone().unwrap().decode(Box::new(QueryPlan::ReadBuffer(compacted))),
-                        &mut executor);
+                        executor);
                     select.push((decoded, t.decoded()));
                 } else {
                     select.push((compacted, t));
@@ -211,14 +520,16 @@ impl Query {
 
             for (i, &(aggregator, aggregate, ref t)) in aggregation_results.iter().enumerate() {
                 if selector_index != Some(i) {
-                    decode_compact(aggregator, aggregate, t.clone(), &mut select);
+                    decode_compact(aggregator, aggregate, t.clone(), &mut aggregates, &mut select);
                 }
             }
 
             // TODO(clemens): is there a simpler way to do this?
             selector_index.map(|i| {
                 let (aggregator, aggregate, ref t) = aggregation_results[i];
-                decode_compact(aggregator, aggregate, t.clone(), &mut select);
+                decode_compact(aggregator, aggregate, t.clone(), &mut aggregates, &mut select);
+                let last = aggregates.pop().unwrap();
+                aggregates.insert(i, last);
                 let last = select.pop().unwrap();
                 select.insert(i, last);
             });
@@ -227,7 +538,7 @@ impl Query {
         //  Reconstruct all group by columns from grouping
         let mut grouping_columns = Vec::with_capacity(decode_plans.len());
         for (decode_plan, t) in decode_plans {
-            let decoded = query_plan::prepare_no_alias(decode_plan.clone(), &mut executor);
+            let decoded = query_plan::prepare_no_alias(decode_plan.clone(), executor);
             grouping_columns.push((decoded, t));
         }
 
@@ -239,7 +550,7 @@ impl Query {
                     QueryPlan::SortIndices(
                         Box::new(QueryPlan::ReadBuffer(encoded_group_by_column)),
                         false),
-                    &mut executor)
+                    executor)
             } else {
                 if grouping_columns.len() != 1 {
                     bail!(QueryError::NotImplemented,
@@ -251,7 +562,7 @@ impl Query {
                     QueryPlan::SortIndices(
                         Box::new(QueryPlan::ReadBuffer(grouping_columns[0].0)),
                         false),
-                    &mut executor)
+                    executor)
             };
 
             select = select.iter().map(|(s, t)| {
@@ -261,7 +572,7 @@ impl Query {
                         Box::new(QueryPlan::ReadBuffer(sort_indices)),
                         t.encoding_type(),
                     ),
-                    &mut executor), t.clone())
+                    executor), t.clone())
             }).collect();
             grouping_columns = grouping_columns.iter().map(|(s, t)| {
                 (query_plan::prepare_no_alias(
@@ -270,39 +581,98 @@ impl Query {
                         Box::new(QueryPlan::ReadBuffer(sort_indices)),
                         t.encoding_type(),
                     ),
-                    &mut executor), t.clone())
+                    executor), t.clone())
             }).collect();
         }
 
-        for c in columns {
-            debug!("{}: {:?}", partition, c);
+        Ok(Grouping {
+            keys: encoded_group_by_column,
+            aggregates,
+            group_by: grouping_columns,
+            select,
+        })
+    }
+
+    /// Returns a copy of the query in which parameters are replaced by `args` and `now()` by the current time.
+    pub fn bind(&self, args: &[RawVal]) -> Result<Query, QueryError> {
+        let parameters = self.parameters();
+        if parameters.len() != args.len() {
+            bail!(QueryError::InvalidQuery, "Query has {} parameters, but {} arguments were supplied",
+                  parameters.len(), args.len());
         }
-        let mut results = executor.prepare(Query::column_data(columns));
-        debug!("{:#}", &executor);
-        executor.run(columns.iter().next().unwrap().1.len(), &mut results, show);
-        let select_cols = select.iter().map(|&(i, _)| results.collect(i)).collect();
-        let group_by_cols = grouping_columns.iter().map(|&(i, _)| results.collect(i)).collect();
-
-        let batch = BatchResult {
-            group_by: Some(group_by_cols),
-            sort_by: None,
-            select: select_cols,
-            desc: self.order_desc,
-            aggregators: self.aggregate.iter().map(|x| x.0).collect(),
-            level: 0,
-            batch_count: 1,
-            show,
-            unsafe_referenced_buffers: results.collect_pinned(),
-        };
-        if let Err(err) = batch.validate() {
-            warn!("Query result failed validation (partition {}): {}\n{:#}\nGroup By: {:?}\nSelect: {:?}",
-                  partition, err, &executor, grouping_columns, select);
-            Err(err)
-        } else {
-            Ok((
-                batch,
-                if explain { Some(format!("{}", executor)) } else { None }
-            ))
+        let now = time::now().to_timespec().sec;
+        Ok(self.clone().substitute_all(&|expr| match *expr {
+            Expr::Parameter(index, _) => Some(Expr::Const(args[index].clone())),
+            Expr::Now => Some(Expr::Const(RawVal::Int(now))),
+            _ => None,
+        }))
+    }
+
+    /// Returns the name (if any) of each parameter of the query, ordered by index.
+    pub fn parameters(&self) -> Vec<Option<String>> {
+        let mut parameters = Vec::new();
+        self.for_each_expr(&mut |expr| expr.add_parameters(&mut parameters));
+        parameters.sort_by_key(|&(index, _)| index);
+        parameters.dedup();
+        parameters.into_iter().map(|(_, name)| name).collect()
+    }
+
+    /// Applies `Expr::substitute` to all expressions of the query, its subqueries and the queries of its UNION ALL.
+    pub fn substitute_all(mut self, f: &Fn(&Expr) -> Option<Expr>) -> Query {
+        let with = mem::replace(&mut self.with, Vec::new()).into_iter()
+            .map(|(name, subquery)| (name, subquery.substitute_all(f)))
+            .collect();
+        let union_all = mem::replace(&mut self.union_all, Vec::new()).into_iter()
+            .map(|query| query.substitute_all(f))
+            .collect();
+        Query { with, union_all, ..self.substitute(f) }
+    }
+
+    /// Applies `Expr::substitute` to all expressions of the query, but not to those of its subqueries.
+    pub fn substitute(self, f: &Fn(&Expr) -> Option<Expr>) -> Query {
+        Query {
+            select: self.select.into_iter().map(|expr| expr.substitute(f)).collect(),
+            filter: self.filter.substitute(f),
+            aggregate: self.aggregate.into_iter().map(|(aggregator, expr)| (aggregator, expr.substitute(f))).collect(),
+            group_by: self.group_by.into_iter().map(|expr| expr.substitute(f)).collect(),
+            having: self.having.map(|expr| expr.substitute(f)),
+            order_by: self.order_by.into_iter().map(|(expr, desc)| (expr.substitute(f), desc)).collect(),
+            limit_by: self.limit_by.map(|limit_by| LimitBy {
+                by: limit_by.by.into_iter().map(|expr| expr.substitute(f)).collect(),
+                ..limit_by
+            }),
+            ..self
+        }
+    }
+
+    /// Calls `f` on each expression of the query, its subqueries and the queries of its UNION ALL.
+    pub fn for_each_expr(&self, f: &mut FnMut(&Expr)) {
+        for &(_, ref subquery) in &self.with {
+            subquery.for_each_expr(f);
+        }
+        for expr in &self.select {
+            f(expr);
+        }
+        f(&self.filter);
+        for &(_, ref expr) in &self.aggregate {
+            f(expr);
+        }
+        for expr in &self.group_by {
+            f(expr);
+        }
+        if let Some(ref having) = self.having {
+            f(having);
+        }
+        for &(ref expr, _) in &self.order_by {
+            f(expr);
+        }
+        if let Some(ref limit_by) = self.limit_by {
+            for expr in &limit_by.by {
+                f(expr);
+            }
+        }
+        for query in &self.union_all {
+            query.for_each_expr(f);
         }
     }
 
@@ -336,10 +706,236 @@ impl Query {
                 match agg {
                     Aggregator::Count => format!("count_{}", anon_aggregates),
                     Aggregator::Sum => format!("sum_{}", anon_aggregates),
+                    Aggregator::Min => format!("min_{}", anon_aggregates),
+                    Aggregator::Max => format!("max_{}", anon_aggregates),
+                    Aggregator::Avg => format!("avg_{}", anon_aggregates),
+                    Aggregator::CountDistinct => format!("count_distinct_{}", anon_aggregates),
+                    Aggregator::ApproxCountDistinct(_) => format!("approx_count_distinct_{}", anon_aggregates),
+                    Aggregator::Percentile(_) => format!("percentile_{}", anon_aggregates),
+                    Aggregator::Variance => format!("variance_{}", anon_aggregates),
+                    Aggregator::StdDev => format!("stddev_{}", anon_aggregates),
+                    Aggregator::Covar => format!("covar_{}", anon_aggregates),
+                    Aggregator::Corr => format!("corr_{}", anon_aggregates),
                 }
             });
 
-        select_cols.chain(aggregate_cols).collect()
+        select_cols.chain(aggregate_cols)
+            .enumerate()
+            .map(|(i, name)| match self.aliases.get(i) {
+                Some(&Some(ref alias)) => alias.clone(),
+                _ => name,
+            })
+            .collect()
+    }
+
+    /// Adds columns and aggregates that are required to evaluate the query but are not part of the select clause
+    /// (aggregates referenced by HAVING or ORDER BY, sort keys that are not selected, or a count to determine the groups
+    /// of a query without aggregates, or indicators for NULL values of columns in `nullable_cols`) and resolves
+    /// ORDER BY ordinals.
+    /// Returns the index of each output column within the rows of the final result.
+    pub fn resolve_columns(&mut self, nullable_cols: &HashSet<String>) -> Result<Vec<usize>, QueryError> {
+        let visible_selects = self.select.len();
+        let visible_aggregates = self.aggregate.len();
+
+        let output_columns = self.output_columns();
+        for &mut (ref mut expr, _) in &mut self.order_by {
+            let ordinal = match *expr {
+                Expr::Const(RawVal::Int(ordinal)) => ordinal,
+                _ => continue,
+            };
+            if ordinal < 1 || ordinal as usize > output_columns.len() {
+                bail!(QueryError::InvalidQuery, "ORDER BY position {} is not in select list", ordinal)
+            }
+            *expr = output_columns[ordinal as usize - 1].clone();
+        }
+
+        if self.aggregate.is_empty() && self.group_by.is_empty() {
+            if self.having.is_some() {
+                bail!(QueryError::InvalidQuery, "HAVING clause requires GROUP BY or aggregation")
+            }
+            // Sort keys are computed as (hidden) select columns
+            for &(ref expr, _) in &self.order_by {
+                let mut aggregates = Vec::new();
+                expr.add_aggregates(&mut aggregates);
+                if !aggregates.is_empty() {
+                    bail!(QueryError::InvalidQuery, "ORDER BY clause contains aggregate, but query is not grouped")
+                }
+                if !self.select.contains(expr) {
+                    self.select.push(expr.clone());
+                }
+            }
+            // Groups of the LIMIT BY clause are determined from (hidden) select columns
+            if let Some(ref limit_by) = self.limit_by {
+                for expr in &limit_by.by {
+                    if !self.select.contains(expr) {
+                        self.select.push(expr.clone());
+                    }
+                }
+            }
+            let null_indicators = self.select.iter()
+                .filter(|expr| is_nullable(expr, nullable_cols))
+                .map(|expr| Expr::func1(Func1Type::IsNull, expr.clone()))
+                .collect::<Vec<_>>();
+            for indicator in null_indicators {
+                if !self.select.contains(&indicator) {
+                    self.select.push(indicator);
+                }
+            }
+            // Single sort keys are handled during query execution, multiple sort keys and sort keys containing NULL
+            // values are applied to the final result. This way, the sort operators never encounter NULL values, which
+            // always come last (see `row_eval::cmp_sort_key`).
+            if self.order_by.len() == 1 && !is_nullable(&self.order_by[0].0, nullable_cols) {
+                let key = &self.order_by[0].0;
+                self.order_by_index = self.select.iter().position(|expr| expr == key);
+            }
+            return Ok((0..visible_selects).collect());
+        }
+
+        if self.aggregate.is_empty() {
+            self.aggregate.push((Aggregator::Count, Expr::Const(RawVal::Int(1))));
+        }
+        let mut referenced = Vec::new();
+        if let Some(ref having) = self.having {
+            having.add_aggregates(&mut referenced);
+        }
+        for &(ref expr, _) in &self.order_by {
+            expr.add_aggregates(&mut referenced);
+        }
+        for aggregate in referenced {
+            if !self.aggregate.contains(&aggregate) {
+                self.aggregate.push(aggregate);
+            }
+        }
+        for &(aggregator, _) in &self.aggregate {
+            if let Aggregator::Percentile(quantile) = aggregator {
+                if !(quantile.0 >= 0.0 && quantile.0 <= 1.0) {
+                    bail!(QueryError::InvalidQuery, "Percentile must be between 0 and 1, was {}", quantile)
+                }
+            }
+        }
+        // NULL values form their own group, and aggregates other than count over only NULL values are NULL.
+        // Indicators are the leading grouping keys so that groups of NULL values come last.
+        let null_indicators = self.group_by.iter()
+            .filter(|expr| is_nullable(expr, nullable_cols))
+            .map(|expr| Expr::func1(Func1Type::IsNull, expr.clone()))
+            .filter(|indicator| !self.group_by.contains(indicator))
+            .collect::<Vec<_>>();
+        // Grouping sets include the indicators of their nullable expressions
+        for grouping_set in &mut self.grouping_sets {
+            let mut indices = grouping_set.iter().map(|&i| i + null_indicators.len()).collect::<Vec<_>>();
+            for &i in grouping_set.iter() {
+                let indicator = Expr::func1(Func1Type::IsNull, self.group_by[i].clone());
+                if let Some(index) = null_indicators.iter().position(|expr| *expr == indicator) {
+                    indices.push(index);
+                } else if let Some(index) = self.group_by.iter().position(|expr| *expr == indicator) {
+                    indices.push(index + null_indicators.len());
+                }
+            }
+            indices.sort();
+            indices.dedup();
+            *grouping_set = indices;
+        }
+        let group_by = mem::replace(&mut self.group_by, null_indicators);
+        self.group_by.extend(group_by);
+        // Averages are computed by dividing sums by counts once all partitions have been combined.
+        let counts = self.aggregate.iter()
+            .filter(|&&(aggregator, ref expr)| match aggregator {
+                // Empty sketches yield NULL
+                Aggregator::Count | Aggregator::CountDistinct | Aggregator::ApproxCountDistinct(_) |
+                Aggregator::Percentile(_) | Aggregator::Variance | Aggregator::StdDev | Aggregator::Covar |
+                Aggregator::Corr => false,
+                Aggregator::Avg => true,
+                Aggregator::Sum | Aggregator::Min | Aggregator::Max => is_nullable(expr, nullable_cols),
+            })
+            .map(|&(_, ref expr)| (Aggregator::Count, expr.clone()))
+            .collect::<Vec<_>>();
+        for count in counts {
+            if !self.aggregate.contains(&count) {
+                self.aggregate.push(count);
+            }
+        }
+
+        let mut projection = Vec::with_capacity(visible_selects + visible_aggregates);
+        for expr in &self.select[..visible_selects] {
+            match self.group_by.iter().position(|g| g == expr) {
+                Some(index) => projection.push(index),
+                None => bail!(QueryError::InvalidQuery,
+                              "{:?} must appear in the GROUP BY clause or be used in an aggregate function", expr),
+            }
+        }
+        for i in 0..visible_aggregates {
+            projection.push(self.group_by.len() + i);
+        }
+
+        let result_columns = self.result_columns();
+        if let Some(ref having) = self.having {
+            if let Some(colname) = row_eval::unresolved_column(having, &result_columns) {
+                bail!(QueryError::InvalidQuery,
+                      "Column {} in HAVING clause must appear in the GROUP BY clause or be used in an aggregate function", colname)
+            }
+        }
+        for &(ref expr, _) in &self.order_by {
+            if let Some(colname) = row_eval::unresolved_column(expr, &result_columns) {
+                bail!(QueryError::InvalidQuery,
+                      "Column {} in ORDER BY clause must appear in the GROUP BY clause or be used in an aggregate function", colname)
+            }
+        }
+        if let Some(ref limit_by) = self.limit_by {
+            for expr in &limit_by.by {
+                if let Some(colname) = row_eval::unresolved_column(expr, &result_columns) {
+                    bail!(QueryError::InvalidQuery,
+                          "Column {} in LIMIT BY clause must appear in the GROUP BY clause or be used in an aggregate function", colname)
+                }
+            }
+        }
+        Ok(projection)
+    }
+
+    /// Returns the aggregates that are computed during query execution, and for each aggregate the index of the
+    /// computed aggregate that holds its result or the aggregation state it is derived from.
+    /// Aggregates of the same expression that share their aggregation state (see `Aggregator::shares_state`) are
+    /// computed only once.
+    pub fn aggregation_states(&self) -> (Vec<(Aggregator, Expr)>, Vec<usize>) {
+        let mut states: Vec<(Aggregator, Expr)> = Vec::new();
+        let mut indices = Vec::with_capacity(self.aggregate.len());
+        for &(aggregator, ref expr) in &self.aggregate {
+            match states.iter().position(|&(state, ref input)| input == expr && state.shares_state(&aggregator)) {
+                Some(index) => indices.push(index),
+                None => {
+                    indices.push(states.len());
+                    states.push((aggregator, expr.clone()));
+                }
+            }
+        }
+        (states, indices)
+    }
+
+    /// Indices into `group_by` of the expressions of each grouping set. Queries without `ROLLUP`, `CUBE` or
+    /// `GROUPING SETS` have a single grouping set that contains all group by expressions.
+    pub fn grouping_set_indices(&self) -> Vec<Vec<usize>> {
+        if self.grouping_sets.is_empty() {
+            vec![(0..self.group_by.len()).collect()]
+        } else {
+            self.grouping_sets.clone()
+        }
+    }
+
+    /// Expressions for all columns of the combined query result, in the order they appear in its rows.
+    pub fn result_columns(&self) -> Vec<Expr> {
+        if self.aggregate.is_empty() {
+            self.select.clone()
+        } else {
+            self.group_by.iter().cloned()
+                .chain(self.aggregate.iter().map(|&(aggregator, ref expr)| Expr::Aggregate(aggregator, Box::new(expr.clone()))))
+                .collect()
+        }
+    }
+
+    /// Expressions for all columns in the select clause, in the order they are output.
+    fn output_columns(&self) -> Vec<Expr> {
+        self.select.iter().cloned()
+            .chain(self.aggregate.iter().map(|&(aggregator, ref expr)| Expr::Aggregate(aggregator, Box::new(expr.clone()))))
+            .collect()
     }
 
     pub fn find_referenced_cols(&self) -> HashSet<String> {
@@ -347,6 +943,9 @@ impl Query {
         for expr in &self.select {
             expr.add_colnames(&mut colnames);
         }
+        for expr in &self.group_by {
+            expr.add_colnames(&mut colnames);
+        }
         self.filter.add_colnames(&mut colnames);
         for &(_, ref expr) in &self.aggregate {
             expr.add_colnames(&mut colnames);
@@ -362,4 +961,13 @@ impl Query {
     }
 }
 
-
+fn is_nullable(expr: &Expr, nullable_cols: &HashSet<String>) -> bool {
+    let mut colnames = HashSet::new();
+    expr.add_nullable_colnames(&mut colnames);
+    let mut conditionals = Vec::new();
+    expr.add_conditionals(&mut conditionals);
+    !colnames.is_disjoint(nullable_cols) || conditionals.iter().any(|conditional| match *conditional {
+        Expr::If(_, ref lhs, ref rhs) => is_nullable(lhs, nullable_cols) || is_nullable(rhs, nullable_cols),
+        _ => true,
+    })
+}
@@ -1,17 +1,26 @@
+use std::cmp;
+use std::f64;
+use std::i64;
 use std::collections::HashMap;
+use std::collections::HashSet;
 use std::sync::Arc;
 
-use chrono::{Datelike, NaiveDateTime};
 use crypto::digest::Digest;
 use crypto::md5::Md5;
 use itertools::Itertools;
+use regex::Regex;
 
 use ::QueryError;
 use engine::aggregator::Aggregator;
+use engine::date_function::DateFunction;
 use engine::filter::Filter;
+use engine::hyperloglog;
+use engine::row_eval;
+use engine::string_function::{self, StringFunction};
 use engine::types::*;
 use engine::vector_op::*;
 use engine::vector_op::vector_operator::BufferRef;
+use ingest::ordered_float::OrderedFloat;
 use ingest::raw_val::RawVal;
 use mem_store::*;
 use mem_store::column::Column;
@@ -25,6 +34,22 @@ pub enum QueryPlan {
 
     DictLookup(Box<QueryPlan>, EncodingType, Box<QueryPlan>, Box<QueryPlan>),
     InverseDictLookup(Box<QueryPlan>, Box<QueryPlan>, Box<QueryPlan>),
+    InverseDictLookupSet(Box<QueryPlan>, Box<QueryPlan>, Vec<String>),
+    /// Looks up encoded values in a table with one entry per dictionary entry, e.g. the result of `DictRegexMatch`.
+    /// The second type is the type of the table, which is `BitVec` for tables of booleans.
+    TableLookup(Box<QueryPlan>, EncodingType, Box<QueryPlan>, EncodingType),
+    RegexMatch(Box<QueryPlan>, String),
+    DictRegexMatch(Box<QueryPlan>, Box<QueryPlan>, String),
+    DictionaryEntries(Box<QueryPlan>, Box<QueryPlan>),
+    /// Applies a string function and dictionary encodes the results.
+    /// Section 0 holds the dictionary indices, sections 1 and 2 the offset/length pairs and data of the dictionary.
+    MapStrings(Vec<QueryPlan>, StringFunction, usize),
+    /// Dictionary with the given (sorted, distinct) entries.
+    /// Section 0 holds the offset/length pairs and section 1 the data of the dictionary.
+    ConstantDictionary(Vec<String>, usize),
+    StringLength(Box<QueryPlan>),
+    /// Hashes values consistently across partitions, see `HashValue`.
+    HashValues(Box<QueryPlan>, EncodingType),
     Cast(Box<QueryPlan>, EncodingType, EncodingType),
     LZ4Decode(Box<QueryPlan>, usize, EncodingType),
     UnpackStrings(Box<QueryPlan>),
@@ -41,23 +66,52 @@ pub enum QueryPlan {
     BitUnpack(Box<QueryPlan>, u8, u8),
 
     LessThanVS(EncodingType, Box<QueryPlan>, Box<QueryPlan>),
+    LessThanEqualsVS(EncodingType, Box<QueryPlan>, Box<QueryPlan>),
+    GreaterThanVS(EncodingType, Box<QueryPlan>, Box<QueryPlan>),
+    GreaterThanEqualsVS(EncodingType, Box<QueryPlan>, Box<QueryPlan>),
     EqualsVS(EncodingType, Box<QueryPlan>, Box<QueryPlan>),
     NotEqualsVS(EncodingType, Box<QueryPlan>, Box<QueryPlan>),
+    LessThanVV(EncodingType, Box<QueryPlan>, Box<QueryPlan>),
+    LessThanEqualsVV(EncodingType, Box<QueryPlan>, Box<QueryPlan>),
+    EqualsVV(EncodingType, Box<QueryPlan>, Box<QueryPlan>),
+    NotEqualsVV(EncodingType, Box<QueryPlan>, Box<QueryPlan>),
     DivideVS(Box<QueryPlan>, Box<QueryPlan>),
     AddVS(EncodingType, Box<QueryPlan>, Box<QueryPlan>),
+    SubtractVS(Box<QueryPlan>, Box<QueryPlan>),
+    MultiplyVS(Box<QueryPlan>, Box<QueryPlan>),
+    ModuloVS(Box<QueryPlan>, Box<QueryPlan>),
+    DivideSV(Box<QueryPlan>, Box<QueryPlan>),
+    ModuloSV(Box<QueryPlan>, Box<QueryPlan>),
+    AddVV(Box<QueryPlan>, Box<QueryPlan>),
+    SubtractVV(Box<QueryPlan>, Box<QueryPlan>),
+    MultiplyVV(Box<QueryPlan>, Box<QueryPlan>),
+    DivideVV(Box<QueryPlan>, Box<QueryPlan>),
+    ModuloVV(Box<QueryPlan>, Box<QueryPlan>),
+    FloatArithmeticVS(Func2Type, Box<QueryPlan>, Box<QueryPlan>),
+    FloatArithmeticSV(Func2Type, Box<QueryPlan>, Box<QueryPlan>),
+    FloatArithmeticVV(Func2Type, Box<QueryPlan>, Box<QueryPlan>),
     And(Box<QueryPlan>, Box<QueryPlan>),
     Or(Box<QueryPlan>, Box<QueryPlan>),
-    ToYear(Box<QueryPlan>),
+    Not(Box<QueryPlan>),
+    AndNot(Box<QueryPlan>, Box<QueryPlan>),
+    MapTimestamps(Box<QueryPlan>, DateFunction),
 
     SortIndices(Box<QueryPlan>, bool),
     TopN(Box<QueryPlan>, EncodingType, usize, bool),
+    /// Top n of the first plan for each distinct value of the (i64) grouping key given by the second plan.
+    TopNPerGroup(Box<QueryPlan>, EncodingType, Box<QueryPlan>, usize, bool),
 
     Select(Box<QueryPlan>, Box<QueryPlan>, EncodingType),
     Filter(Box<QueryPlan>, EncodingType, Box<QueryPlan>),
+    FillNulls(Box<QueryPlan>, EncodingType, Box<QueryPlan>, Box<QueryPlan>),
+    Blend(Box<QueryPlan>, Box<QueryPlan>, Box<QueryPlan>, EncodingType),
 
     EncodedGroupByPlaceholder,
 
     Constant(RawVal, bool),
+    ConstantExpand(Box<QueryPlan>, u8),
+    /// Repeats the scalar of the first plan once for every element of the second plan.
+    ExpandScalar(Box<QueryPlan>, Box<QueryPlan>, EncodingType),
 }
 
 pub fn prepare<'a>(plan: QueryPlan, result: &mut QueryExecutor<'a>) -> BufferRef {
@@ -85,8 +139,16 @@ fn _prepare<'a>(plan: QueryPlan, no_alias: bool, result: &mut QueryExecutor<'a>)
             VecOperator::read_column_data(colname, section, result.named_buffer("column")),
         QueryPlan::Filter(plan, t, filter) =>
             VecOperator::filter(t, prepare(*plan, result), prepare(*filter, result), result.named_buffer("filtered")),
+        QueryPlan::FillNulls(plan, t, not_null, fill) =>
+            VecOperator::fill_nulls(prepare(*plan, result), prepare(*not_null, result), prepare(*fill, result), result.named_buffer("filled"), t),
+        QueryPlan::Blend(condition, if_true, if_false, t) =>
+            VecOperator::blend(prepare(*condition, result), prepare(*if_true, result), prepare(*if_false, result), result.named_buffer("blended"), t),
         QueryPlan::Constant(ref c, hide_value) =>
             VecOperator::constant(c.clone(), hide_value, result.named_buffer("constant")),
+        QueryPlan::ConstantExpand(plan, value) =>
+            VecOperator::constant_expand(value, prepare(*plan, result), result.named_buffer("expanded")),
+        QueryPlan::ExpandScalar(value, plan, t) =>
+            VecOperator::expand_scalar(prepare(*value, result), prepare(*plan, result), result.named_buffer("expanded"), t),
         QueryPlan::DictLookup(plan, t, dict_indices, dict_data) =>
             VecOperator::dict_lookup(
                 prepare(*plan, result),
@@ -99,6 +161,71 @@ fn _prepare<'a>(plan: QueryPlan, no_alias: bool, result: &mut QueryExecutor<'a>)
                 prepare(*dict_data, result),
                 prepare(*constant, result),
                 result.named_buffer("encoded")),
+        QueryPlan::InverseDictLookupSet(dict_indices, dict_data, constants) =>
+            VecOperator::inverse_dict_lookup_set(
+                prepare(*dict_indices, result),
+                prepare(*dict_data, result),
+                constants.into_iter().collect(),
+                result.named_buffer("encoded_set")),
+        QueryPlan::TableLookup(indices, t, table, table_type) =>
+            VecOperator::table_lookup(prepare(*indices, result), prepare(*table, result), result.named_buffer("table_lookup"), t, table_type),
+        QueryPlan::RegexMatch(plan, regex) =>
+            VecOperator::regex_match(prepare(*plan, result), &regex, result.named_buffer("matches")),
+        QueryPlan::DictionaryEntries(dict_indices, dict_data) =>
+            VecOperator::dictionary_entries(prepare(*dict_indices, result), prepare(*dict_data, result), result.named_buffer("dictionary_entries")),
+        QueryPlan::MapStrings(inputs, function, section) => {
+            let inputs = inputs.into_iter().map(|input| prepare(input, result)).collect::<Vec<_>>();
+            // All sections are produced by a single operator which is identified by its inputs
+            let mut signature = [0u8; 16];
+            let mut hasher = Md5::new();
+            for input in &inputs {
+                hasher.input(&input.0.to_bytes());
+            }
+            hasher.input_str(&format!("{:?}", function));
+            hasher.result(&mut signature);
+            let outputs = match result.get_outputs(&signature) {
+                Some(outputs) => outputs,
+                None => {
+                    let outputs = vec![result.named_buffer("string_indices"),
+                                       result.named_buffer("dict_indices"),
+                                       result.named_buffer("dict_data")];
+                    result.push(VecOperator::map_strings(inputs, function, outputs[0], outputs[1], outputs[2]));
+                    result.cache_outputs(signature, outputs.clone());
+                    outputs
+                }
+            };
+            return outputs[section];
+        }
+        QueryPlan::ConstantDictionary(entries, section) => {
+            let mut signature = [0u8; 16];
+            let mut hasher = Md5::new();
+            hasher.input_str("ConstantDictionary");
+            for entry in &entries {
+                hasher.input(&entry.len().to_bytes());
+                hasher.input_str(entry);
+            }
+            hasher.result(&mut signature);
+            let outputs = match result.get_outputs(&signature) {
+                Some(outputs) => outputs,
+                None => {
+                    let outputs = vec![result.named_buffer("dict_indices"), result.named_buffer("dict_data")];
+                    result.push(VecOperator::constant_dictionary(entries, outputs[0], outputs[1]));
+                    result.cache_outputs(signature, outputs.clone());
+                    outputs
+                }
+            };
+            return outputs[section];
+        }
+        QueryPlan::StringLength(plan) =>
+            VecOperator::string_length(prepare(*plan, result), result.named_buffer("length")),
+        QueryPlan::HashValues(plan, t) =>
+            VecOperator::hash_values(prepare(*plan, result), result.named_buffer("hashes"), t),
+        QueryPlan::DictRegexMatch(dict_indices, dict_data, regex) =>
+            VecOperator::dict_regex_match(
+                prepare(*dict_indices, result),
+                prepare(*dict_data, result),
+                &regex,
+                result.named_buffer("encoded_matches")),
         QueryPlan::Cast(plan, initial_type, target_type) =>
             VecOperator::type_conversion(prepare(*plan, result), result.named_buffer("casted"), initial_type, target_type),
         QueryPlan::DeltaDecode(plan, t) =>
@@ -130,14 +257,54 @@ fn _prepare<'a>(plan: QueryPlan, no_alias: bool, result: &mut QueryExecutor<'a>)
             VecOperator::bit_unpack(prepare(*inner, result), result.named_buffer("unpacked"), shift, width),
         QueryPlan::LessThanVS(left_type, lhs, rhs) =>
             VecOperator::less_than_vs(left_type, prepare(*lhs, result), prepare(*rhs, result), result.named_buffer("less_than")),
+        QueryPlan::LessThanEqualsVS(left_type, lhs, rhs) =>
+            VecOperator::less_than_equals_vs(left_type, prepare(*lhs, result), prepare(*rhs, result), result.named_buffer("less_than_equals")),
+        QueryPlan::GreaterThanVS(left_type, lhs, rhs) =>
+            VecOperator::greater_than_vs(left_type, prepare(*lhs, result), prepare(*rhs, result), result.named_buffer("greater_than")),
+        QueryPlan::GreaterThanEqualsVS(left_type, lhs, rhs) =>
+            VecOperator::greater_than_equals_vs(left_type, prepare(*lhs, result), prepare(*rhs, result), result.named_buffer("greater_than_equals")),
         QueryPlan::EqualsVS(left_type, lhs, rhs) =>
             VecOperator::equals_vs(left_type, prepare(*lhs, result), prepare(*rhs, result), result.named_buffer("equals")),
         QueryPlan::NotEqualsVS(left_type, lhs, rhs) =>
             VecOperator::not_equals_vs(left_type, prepare(*lhs, result), prepare(*rhs, result), result.named_buffer("equals")),
+        QueryPlan::LessThanVV(t, lhs, rhs) =>
+            VecOperator::less_than_vv(t, prepare(*lhs, result), prepare(*rhs, result), result.named_buffer("less_than")),
+        QueryPlan::LessThanEqualsVV(t, lhs, rhs) =>
+            VecOperator::less_than_equals_vv(t, prepare(*lhs, result), prepare(*rhs, result), result.named_buffer("less_than_equals")),
+        QueryPlan::EqualsVV(t, lhs, rhs) =>
+            VecOperator::equals_vv(t, prepare(*lhs, result), prepare(*rhs, result), result.named_buffer("equals")),
+        QueryPlan::NotEqualsVV(t, lhs, rhs) =>
+            VecOperator::not_equals_vv(t, prepare(*lhs, result), prepare(*rhs, result), result.named_buffer("equals")),
         QueryPlan::DivideVS(lhs, rhs) =>
             VecOperator::divide_vs(prepare(*lhs, result), prepare(*rhs, result), result.named_buffer("division")),
         QueryPlan::AddVS(left_type, lhs, rhs) =>
             VecOperator::addition_vs(prepare(*lhs, result), prepare(*rhs, result), result.named_buffer("addition"), left_type),
+        QueryPlan::SubtractVS(lhs, rhs) =>
+            VecOperator::subtraction_vs(prepare(*lhs, result), prepare(*rhs, result), result.named_buffer("subtraction")),
+        QueryPlan::MultiplyVS(lhs, rhs) =>
+            VecOperator::multiplication_vs(prepare(*lhs, result), prepare(*rhs, result), result.named_buffer("multiplication")),
+        QueryPlan::ModuloVS(lhs, rhs) =>
+            VecOperator::modulo_vs(prepare(*lhs, result), prepare(*rhs, result), result.named_buffer("modulo")),
+        QueryPlan::DivideSV(lhs, rhs) =>
+            VecOperator::division_sv(prepare(*lhs, result), prepare(*rhs, result), result.named_buffer("division")),
+        QueryPlan::ModuloSV(lhs, rhs) =>
+            VecOperator::modulo_sv(prepare(*lhs, result), prepare(*rhs, result), result.named_buffer("modulo")),
+        QueryPlan::FloatArithmeticVS(function, lhs, rhs) =>
+            VecOperator::float_arithmetic_vs(function, prepare(*lhs, result), prepare(*rhs, result), result.named_buffer("float_arithmetic")),
+        QueryPlan::FloatArithmeticSV(function, lhs, rhs) =>
+            VecOperator::float_arithmetic_sv(function, prepare(*lhs, result), prepare(*rhs, result), result.named_buffer("float_arithmetic")),
+        QueryPlan::FloatArithmeticVV(function, lhs, rhs) =>
+            VecOperator::float_arithmetic_vv(function, prepare(*lhs, result), prepare(*rhs, result), result.named_buffer("float_arithmetic")),
+        QueryPlan::AddVV(lhs, rhs) =>
+            VecOperator::addition_vv(prepare(*lhs, result), prepare(*rhs, result), result.named_buffer("addition")),
+        QueryPlan::SubtractVV(lhs, rhs) =>
+            VecOperator::subtraction_vv(prepare(*lhs, result), prepare(*rhs, result), result.named_buffer("subtraction")),
+        QueryPlan::MultiplyVV(lhs, rhs) =>
+            VecOperator::multiplication_vv(prepare(*lhs, result), prepare(*rhs, result), result.named_buffer("multiplication")),
+        QueryPlan::DivideVV(lhs, rhs) =>
+            VecOperator::division_vv(prepare(*lhs, result), prepare(*rhs, result), result.named_buffer("division")),
+        QueryPlan::ModuloVV(lhs, rhs) =>
+            VecOperator::modulo_vv(prepare(*lhs, result), prepare(*rhs, result), result.named_buffer("modulo")),
         QueryPlan::Or(lhs, rhs) => {
             let inplace = prepare(*lhs, result);
             // If we don't assign to `operation` and pass expression directly to push, we trigger an infinite loop in the compiler
@@ -154,13 +321,19 @@ fn _prepare<'a>(plan: QueryPlan, no_alias: bool, result: &mut QueryExecutor<'a>)
             result.push(operation);
             return inplace;
         }
-        QueryPlan::ToYear(plan) =>
-            VecOperator::to_year(prepare(*plan, result), result.named_buffer("year")),
+        QueryPlan::Not(plan) =>
+            VecOperator::not(prepare(*plan, result), result.named_buffer("not")),
+        QueryPlan::AndNot(lhs, rhs) =>
+            VecOperator::and_not(prepare(*lhs, result), prepare(*rhs, result), result.named_buffer("and_not")),
+        QueryPlan::MapTimestamps(plan, function) =>
+            VecOperator::map_timestamps(prepare(*plan, result), function, result.named_buffer("timestamp_function")),
         QueryPlan::EncodedGroupByPlaceholder => return result.encoded_group_by().unwrap(),
         QueryPlan::SortIndices(plan, descending) =>
             VecOperator::sort_indices(prepare(*plan, result), result.named_buffer("permutation"), descending),
         QueryPlan::TopN(plan, t, n, desc) =>
             VecOperator::top_n(prepare(*plan, result), result.named_buffer("tmp_keys"), result.named_buffer("top_n"), t, n, desc),
+        QueryPlan::TopNPerGroup(plan, t, grouping, n, desc) =>
+            VecOperator::top_n_per_group(prepare(*plan, result), prepare(*grouping, result), result.named_buffer("top_n"), t, n, desc),
         QueryPlan::ReadBuffer(buffer) => return buffer,
     };
     result.push(operation);
@@ -188,6 +361,7 @@ pub fn prepare_hashmap_grouping<'a>(raw_grouping_key: BufferRef,
 // TODO(clemens): add QueryPlan::Aggregation and merge with prepare function
 pub fn prepare_aggregation<'a, 'b>(plan: QueryPlan,
                                    mut plan_type: Type,
+                                   not_null: Option<QueryPlan>,
                                    grouping_key: BufferRef,
                                    grouping_type: EncodingType,
                                    max_index: BufferRef,
@@ -203,25 +377,476 @@ pub fn prepare_aggregation<'a, 'b>(plan: QueryPlan,
                                 max_index),
              Type::encoded(Codec::integer_cast(EncodingType::U32)))
         }
-        (Aggregator::Sum, mut plan) => {
+        (Aggregator::Sum, mut plan) | (Aggregator::Avg, mut plan) => {
             output_location = result.named_buffer("sum");
             if !plan_type.is_summation_preserving() {
                 plan = *plan_type.codec.clone().unwrap().decode(Box::new(plan));
                 plan_type = plan_type.decoded();
             }
+            let sum_type = match plan_type.decoded {
+                BasicType::Float => BasicType::Float,
+                _ => BasicType::Integer,
+            };
             (VecOperator::summation(prepare(plan, result),
                                     grouping_key,
                                     output_location,
                                     plan_type.encoding_type(),
                                     grouping_type,
                                     max_index), // TODO(clemens): determine dense groupings
-             Type::unencoded(BasicType::Integer))
+             Type::unencoded(sum_type))
+        }
+        (aggregator @ Aggregator::Min, mut plan) | (aggregator @ Aggregator::Max, mut plan) => {
+            if plan_type.decoded != BasicType::Integer && plan_type.decoded != BasicType::Float {
+                bail!(QueryError::TypeError, "Found {:?}({:?}), expected {:?}(integer or float)", aggregator, plan_type, aggregator)
+            }
+            if plan_type.is_scalar {
+                bail!(QueryError::NotImplemented, "{:?} of constant", aggregator)
+            }
+            // Order preserving encodings yield the same minimum and maximum as the decoded values
+            if !plan_type.is_order_preserving() {
+                plan = *plan_type.codec.clone().unwrap().decode(Box::new(plan));
+                plan_type = Type::unencoded(plan_type.decoded);
+            }
+            let input = prepare(plan, result);
+            if aggregator == Aggregator::Min {
+                output_location = result.named_buffer("min");
+                (VecOperator::minimum(input, grouping_key, output_location, plan_type.encoding_type(), grouping_type, max_index),
+                 plan_type)
+            } else {
+                output_location = result.named_buffer("max");
+                (VecOperator::maximum(input, grouping_key, output_location, plan_type.encoding_type(), grouping_type, max_index),
+                 plan_type)
+            }
+        }
+        (Aggregator::CountDistinct, plan) => {
+            if plan_type.is_scalar {
+                bail!(QueryError::NotImplemented, "{:?} of constant", aggregator)
+            }
+            output_location = result.named_buffer("distinct");
+            let not_null = not_null.map(|not_null| prepare(not_null, result));
+            let operation = match dictionary(&plan_type) {
+                // Sets of dictionary indices are translated into sets of strings only once per group
+                Some((dict_indices, dict_data)) => {
+                    let dictionary = prepare(QueryPlan::DictionaryEntries(dict_indices, dict_data), result);
+                    VecOperator::count_distinct_dictionary(prepare(plan, result),
+                                                           grouping_key,
+                                                           not_null,
+                                                           dictionary,
+                                                           output_location,
+                                                           plan_type.encoding_type(),
+                                                           grouping_type,
+                                                           max_index)
+                }
+                None => {
+                    match plan_type.decoded {
+                        BasicType::Integer | BasicType::Float | BasicType::String => {}
+                        t => bail!(QueryError::NotImplemented, "{:?} of {:?}", aggregator, t),
+                    }
+                    VecOperator::count_distinct(prepare(decode(plan, &plan_type), result),
+                                                grouping_key,
+                                                not_null,
+                                                output_location,
+                                                plan_type.decoded.to_encoded(),
+                                                grouping_type,
+                                                max_index)
+                }
+            };
+            (operation, Type::unencoded(BasicType::ValueSet))
         }
+        (Aggregator::ApproxCountDistinct(precision), plan) => {
+            if plan_type.is_scalar {
+                bail!(QueryError::NotImplemented, "{:?} of constant", aggregator)
+            }
+            if precision < hyperloglog::MIN_PRECISION || precision > hyperloglog::MAX_PRECISION {
+                bail!(QueryError::InvalidQuery, "APPROX_COUNT_DISTINCT precision must be between {} and {}, was {}",
+                      hyperloglog::MIN_PRECISION, hyperloglog::MAX_PRECISION, precision)
+            }
+            let hashes = prepare(hash_values(plan, &plan_type)?, result);
+            let not_null = not_null.map(|not_null| prepare(not_null, result));
+            output_location = result.named_buffer("sketches");
+            (VecOperator::approx_count_distinct(hashes, grouping_key, not_null, output_location, grouping_type, max_index, precision),
+             Type::unencoded(BasicType::HyperLogLog))
+        }
+        // The quantile is only estimated once the digests of all partitions have been merged, see `QueryTask`
+        (Aggregator::Percentile(_), plan) => {
+            let input = prepare(decode_to_float(plan, &plan_type, aggregator)?, result);
+            let not_null = not_null.map(|not_null| prepare(not_null, result));
+            output_location = result.named_buffer("digests");
+            (VecOperator::percentile(input, grouping_key, not_null, output_location, grouping_type, max_index),
+             Type::unencoded(BasicType::TDigest))
+        }
+        (Aggregator::Variance, plan) | (Aggregator::StdDev, plan) => return prepare_moments(
+            plan, plan_type, None, not_null, grouping_key, grouping_type, max_index, aggregator, result),
+        (Aggregator::Covar, _) | (Aggregator::Corr, _) =>
+            bail!(QueryError::InvalidQuery, "{:?} requires two arguments", aggregator),
     };
     result.push(operation);
     Ok((output_location, t))
 }
 
+/// Prepares aggregates that are computed from the moments of `x` and the optional second argument `y`.
+pub fn prepare_moments<'a>(x: QueryPlan,
+                           x_type: Type,
+                           y: Option<(QueryPlan, Type)>,
+                           not_null: Option<QueryPlan>,
+                           grouping_key: BufferRef,
+                           grouping_type: EncodingType,
+                           max_index: BufferRef,
+                           aggregator: Aggregator,
+                           result: &mut QueryExecutor<'a>) -> Result<(BufferRef, Type), QueryError> {
+    if !aggregator.is_derived_from_moments() {
+        bail!(QueryError::FatalError, "{:?} is not computed from moments", aggregator)
+    }
+    let x = prepare(decode_to_float(x, &x_type, aggregator)?, result);
+    let y = match y {
+        Some((y, y_type)) => Some(prepare(decode_to_float(y, &y_type, aggregator)?, result)),
+        None => None,
+    };
+    let not_null = not_null.map(|not_null| prepare(not_null, result));
+    let output = result.named_buffer("moments");
+    result.push(VecOperator::moments(x, y, grouping_key, not_null, output, grouping_type, max_index));
+    Ok((output, Type::unencoded(BasicType::Moments)))
+}
+
+/// Aggregates the partial results of `aggregator`, which derives the results of a coarser grouping set from the
+/// results of the finest grouping. The grouping key has to number groups in order of their first occurrence.
+pub fn prepare_reaggregation<'a>(aggregate: BufferRef,
+                                 t: Type,
+                                 aggregator: Aggregator,
+                                 grouping_key: BufferRef,
+                                 grouping_type: EncodingType,
+                                 max_index: BufferRef,
+                                 result: &mut QueryExecutor<'a>) -> Result<(BufferRef, Type), QueryError> {
+    match aggregator {
+        // Counts are summed, averages are sums until all partitions have been combined
+        Aggregator::Count | Aggregator::Sum | Aggregator::Avg => prepare_aggregation(
+            QueryPlan::ReadBuffer(aggregate), t, None, grouping_key, grouping_type, max_index, Aggregator::Sum, result),
+        Aggregator::Min | Aggregator::Max => prepare_aggregation(
+            QueryPlan::ReadBuffer(aggregate), t, None, grouping_key, grouping_type, max_index, aggregator, result),
+        Aggregator::CountDistinct | Aggregator::ApproxCountDistinct(_) | Aggregator::Percentile(_) |
+        Aggregator::Variance | Aggregator::StdDev | Aggregator::Covar | Aggregator::Corr => {
+            let output = result.named_buffer("merged_states");
+            result.push(VecOperator::aggregate_states(
+                aggregate, grouping_key, output, t.encoding_type(), grouping_type, max_index));
+            Ok((output, t))
+        }
+    }
+}
+
+/// Decodes the argument of an aggregate that operates on floats, and converts integers to floats.
+fn decode_to_float(plan: QueryPlan, plan_type: &Type, aggregator: Aggregator) -> Result<QueryPlan, QueryError> {
+    if plan_type.is_scalar {
+        bail!(QueryError::NotImplemented, "{:?} of constant", aggregator)
+    }
+    match plan_type.decoded {
+        BasicType::Integer => Ok(QueryPlan::Cast(Box::new(decode(plan, plan_type)), EncodingType::I64, EncodingType::F64)),
+        BasicType::Float => Ok(decode(plan, plan_type)),
+        t => bail!(QueryError::NotImplemented, "{:?} of {:?}", aggregator, t),
+    }
+}
+
+/// Converts a branch of an `if` into a vector of the unencoded type `t` of its result with one element for each
+/// element of `condition`.
+fn if_branch(plan: QueryPlan, plan_type: &Type, t: &Type, condition: &QueryPlan) -> Result<QueryPlan, QueryError> {
+    let (plan, plan_type) = match plan {
+        QueryPlan::Constant(RawVal::Int(i), _) if t.decoded == BasicType::Float =>
+            expand_constant(RawVal::Float(OrderedFloat(i as f64)), condition.clone())?,
+        QueryPlan::Constant(value, _) => expand_constant(value, condition.clone())?,
+        plan => (plan, plan_type.clone()),
+    };
+    Ok(if t.decoded == BasicType::Float { as_float(plan, &plan_type) } else { decode(plan, &plan_type) })
+}
+
+/// Repeats the constant `value` once for every element of `plan`.
+/// Strings are expanded into indices into a dictionary that holds the constant, which the decoded strings reference.
+fn expand_constant(value: RawVal, plan: QueryPlan) -> Result<(QueryPlan, Type), QueryError> {
+    let t = match value {
+        RawVal::Int(_) => Type::unencoded(BasicType::Integer),
+        RawVal::Float(_) => Type::unencoded(BasicType::Float),
+        RawVal::Str(string) => {
+            let dictionary = |section| QueryPlan::ConstantDictionary(vec![string.clone()], section);
+            let codec = Codec::computed_dictionary(EncodingType::U8, dictionary(0), dictionary(1));
+            return Ok((QueryPlan::ConstantExpand(Box::new(plan), 0), Type::encoded(codec)));
+        }
+        RawVal::Null => bail!(QueryError::NotImplemented, "Expanding NULL constant"),
+    };
+    let plan = QueryPlan::ExpandScalar(Box::new(QueryPlan::Constant(value, false)), Box::new(plan), t.encoding_type());
+    Ok((plan, t))
+}
+
+fn apply_filter(plan: QueryPlan, t: EncodingType, filter: Filter) -> QueryPlan {
+    match filter {
+        Filter::BitVec(filter) => {
+            QueryPlan::Filter(
+                Box::new(plan),
+                t,
+                Box::new(QueryPlan::ReadBuffer(filter)))
+        }
+        Filter::Indices(indices) => {
+            QueryPlan::Select(
+                Box::new(plan),
+                Box::new(QueryPlan::ReadBuffer(indices)),
+                t)
+        }
+        Filter::None => plan,
+    }
+}
+
+/// Changes the input of `aggregator` so that rows for which `not_null` is 0 don't contribute to the aggregate.
+pub fn ignore_nulls(aggregator: Aggregator, plan: QueryPlan, plan_type: Type, not_null: QueryPlan) -> (Aggregator, QueryPlan, Type) {
+    match aggregator {
+        Aggregator::Count => (Aggregator::Sum, not_null, Type::encoded(Codec::integer_cast(EncodingType::U8))),
+        // Columns without any values, e.g. columns missing from some of the tables matched by a wildcard
+        Aggregator::Sum | Aggregator::Avg if plan_type.decoded == BasicType::Null => (
+            aggregator,
+            syntax::cast(not_null, EncodingType::U8, EncodingType::I64),
+            Type::unencoded(BasicType::Integer)),
+        Aggregator::Sum | Aggregator::Avg if plan_type.decoded == BasicType::Float => (
+            aggregator,
+            QueryPlan::FloatArithmeticVV(
+                Func2Type::Multiply,
+                Box::new(decode(plan, &plan_type)),
+                Box::new(syntax::cast(not_null, EncodingType::U8, EncodingType::F64))),
+            Type::unencoded(BasicType::Float)),
+        Aggregator::Sum | Aggregator::Avg => (
+            aggregator,
+            QueryPlan::MultiplyVV(
+                Box::new(decode(plan, &plan_type)),
+                Box::new(syntax::cast(not_null, EncodingType::U8, EncodingType::I64))),
+            Type::unencoded(BasicType::Integer)),
+        // NULL values are replaced by a value that doesn't change the result
+        Aggregator::Min | Aggregator::Max if plan_type.decoded == BasicType::Integer || plan_type.decoded == BasicType::Float => {
+            let fill = match (aggregator, plan_type.decoded) {
+                (Aggregator::Min, BasicType::Integer) => RawVal::Int(i64::MAX),
+                (_, BasicType::Integer) => RawVal::Int(i64::MIN),
+                // NaN is ordered after all other floats
+                (Aggregator::Min, _) => RawVal::Float(OrderedFloat(f64::NAN)),
+                (_, _) => RawVal::Float(OrderedFloat(f64::NEG_INFINITY)),
+            };
+            let decoded_type = Type::unencoded(plan_type.decoded);
+            (aggregator,
+             QueryPlan::FillNulls(
+                 Box::new(decode(plan, &plan_type)),
+                 decoded_type.encoding_type(),
+                 Box::new(not_null),
+                 Box::new(QueryPlan::Constant(fill, true))),
+             decoded_type)
+        }
+        Aggregator::Min | Aggregator::Max => (aggregator, plan, plan_type),
+        // Rows with NULL values are skipped when collecting distinct values, see `prepare_aggregation`
+        Aggregator::CountDistinct | Aggregator::ApproxCountDistinct(_) | Aggregator::Percentile(_) |
+        Aggregator::Variance | Aggregator::StdDev | Aggregator::Covar | Aggregator::Corr =>
+            (aggregator, plan, plan_type),
+    }
+}
+
+/// Returns a plan that hashes the values of `plan`.
+/// Values are hashed without decoding if the hash of the encoded value is the same as that of the decoded value.
+fn hash_values(plan: QueryPlan, plan_type: &Type) -> Result<QueryPlan, QueryError> {
+    match plan_type.decoded {
+        BasicType::Integer | BasicType::Float | BasicType::String => {}
+        t => bail!(QueryError::NotImplemented, "hashing values of type {:?}", t),
+    }
+    if let Some((dict_indices, dict_data)) = dictionary(plan_type) {
+        // Each dictionary entry is hashed only once
+        let hashes = QueryPlan::HashValues(
+            Box::new(QueryPlan::DictionaryEntries(dict_indices, dict_data)),
+            EncodingType::Str);
+        return Ok(QueryPlan::TableLookup(Box::new(plan), plan_type.encoding_type(), Box::new(hashes), EncodingType::U64));
+    }
+    // Integers are hashed as `i64`, so casts don't change the hash
+    let is_integer_cast = plan_type.codec.as_ref().map_or(true, |codec| {
+        codec.ops().iter().all(|op| match op {
+            CodecOp::ToI64(_) => true,
+            _ => false,
+        })
+    });
+    if is_integer_cast {
+        Ok(QueryPlan::HashValues(Box::new(plan), plan_type.encoding_type()))
+    } else {
+        Ok(QueryPlan::HashValues(Box::new(decode(plan, plan_type)), plan_type.decoded.to_encoded()))
+    }
+}
+
+/// Returns plans for the dictionary of `t`, if `t` is dictionary encoded.
+fn dictionary(t: &Type) -> Option<(Box<QueryPlan>, Box<QueryPlan>)> {
+    match t.codec {
+        Some(ref codec) if t.is_encoded() => codec.dictionary(),
+        _ => None,
+    }
+}
+
+/// Applies `function` to the (non-constant) string `inputs`, yielding dictionary encoded strings.
+fn map_strings(mut inputs: Vec<(QueryPlan, Type)>, function: StringFunction) -> (QueryPlan, Type) {
+    let computed_dictionary = |sections: &Fn(usize) -> QueryPlan|
+        Type::encoded(Codec::computed_dictionary(EncodingType::U32, sections(1), sections(2)));
+    // Functions of a single dictionary encoded column are evaluated only once for each dictionary entry
+    if inputs.len() == 1 {
+        if let Some((dict_indices, dict_data)) = dictionary(&inputs[0].1) {
+            let (plan, t) = inputs.pop().unwrap();
+            let entries = QueryPlan::DictionaryEntries(dict_indices, dict_data);
+            let sections = |section| QueryPlan::MapStrings(vec![entries.clone()], function.clone(), section);
+            let plan = QueryPlan::TableLookup(Box::new(plan), t.encoding_type(), Box::new(sections(0)), EncodingType::U32);
+            return (plan, computed_dictionary(&sections));
+        }
+    }
+    let inputs = inputs.into_iter().map(|(plan, t)| decode(plan, &t)).collect::<Vec<_>>();
+    let sections = |section| QueryPlan::MapStrings(inputs.clone(), function.clone(), section);
+    (sections(0), computed_dictionary(&sections))
+}
+
+/// Collects the results of the conditional expression `expr`, returning false if any of them is not a string constant.
+fn constant_strings<'a>(expr: &'a Expr, strings: &mut Vec<&'a str>) -> bool {
+    match *expr {
+        Expr::Const(RawVal::Str(ref s)) => {
+            strings.push(s);
+            true
+        }
+        Expr::If(_, ref lhs, ref rhs) => constant_strings(lhs, strings) && constant_strings(rhs, strings),
+        Expr::Func2(Func2Type::Filtered, ref value, _) => constant_strings(value, strings),
+        _ => false,
+    }
+}
+
+/// Replaces the string constant results of `expr` by their index in `dictionary`.
+fn encode_constant_strings(expr: &Expr, dictionary: &[&str]) -> Expr {
+    match *expr {
+        Expr::Const(RawVal::Str(ref s)) =>
+            Expr::Const(RawVal::Int(dictionary.binary_search(&s.as_str()).unwrap() as i64)),
+        Expr::If(ref cond, ref lhs, ref rhs) => Expr::If(
+            cond.clone(),
+            Box::new(encode_constant_strings(lhs, dictionary)),
+            Box::new(encode_constant_strings(rhs, dictionary))),
+        Expr::Func2(Func2Type::Filtered, ref value, ref predicate) =>
+            Expr::Func2(Func2Type::Filtered, Box::new(encode_constant_strings(value, dictionary)), predicate.clone()),
+        ref other => other.clone(),
+    }
+}
+
+/// Error for a reference to the missing column `name`, which suggests the most similar of the existing `columns`.
+pub fn missing_column<'a, I: Iterator<Item=&'a str>>(name: &str, columns: I) -> QueryError {
+    let suggestions = closest_names(name, columns);
+    if suggestions.is_empty() {
+        QueryError::InvalidQuery(format!("Referencing missing column {}", name))
+    } else {
+        let suggestions = suggestions.iter().map(|s| format!("`{}`", s)).join(", ");
+        QueryError::InvalidQuery(format!("Referencing missing column {}. Did you mean {}?", name, suggestions))
+    }
+}
+
+/// Returns up to three of the `candidates` that are most similar to `name`, ignoring candidates that are neither
+/// plausible misspellings nor extensions of `name`.
+fn closest_names<'a, I: Iterator<Item=&'a str>>(name: &str, candidates: I) -> Vec<&'a str> {
+    let name = name.to_lowercase();
+    let max_distance = cmp::max(1, name.chars().count() / 3);
+    let mut closest = candidates
+        .filter_map(|candidate| {
+            let lowercase = candidate.to_lowercase();
+            let distance = edit_distance(&name, &lowercase);
+            if distance <= max_distance || lowercase.starts_with(&name) {
+                Some((distance, candidate))
+            } else {
+                None
+            }
+        })
+        .collect::<Vec<_>>();
+    closest.sort();
+    closest.into_iter().take(3).map(|(_, candidate)| candidate).collect()
+}
+
+/// Number of insertions, deletions, substitutions and transpositions of adjacent characters required to turn `a`
+/// into `b` (optimal string alignment distance).
+fn edit_distance(a: &str, b: &str) -> usize {
+    let (a, b) = (a.chars().collect::<Vec<_>>(), b.chars().collect::<Vec<_>>());
+    let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for i in 0..a.len() + 1 { d[i][0] = i; }
+    for j in 0..b.len() + 1 { d[0][j] = j; }
+    for i in 1..a.len() + 1 {
+        for j in 1..b.len() + 1 {
+            let cost = if a[i - 1] == b[j - 1] { 0 } else { 1 };
+            d[i][j] = cmp::min(d[i - 1][j - 1] + cost, cmp::min(d[i - 1][j], d[i][j - 1]) + 1);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = cmp::min(d[i][j], d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    d[a.len()][b.len()]
+}
+
+/// Collects the arguments of nested `concat` calls.
+fn concat_args<'a>(expr: &'a Expr, args: &mut Vec<&'a Expr>) {
+    match *expr {
+        Expr::Func2(Func2Type::Concat, ref lhs, ref rhs) => {
+            concat_args(lhs, args);
+            concat_args(rhs, args);
+        }
+        _ => args.push(expr),
+    }
+}
+
+fn decode(plan: QueryPlan, t: &Type) -> QueryPlan {
+    match t.codec {
+        Some(ref codec) => *codec.decode(Box::new(plan)),
+        None => plan,
+    }
+}
+
+/// Decodes the integer or float vector `plan` and converts it to floats.
+fn as_float(plan: QueryPlan, t: &Type) -> QueryPlan {
+    match t.decoded {
+        BasicType::Float => decode(plan, t),
+        _ => syntax::cast(decode(plan, t), EncodingType::I64, EncodingType::F64),
+    }
+}
+
+/// Returns an expression equivalent to `NOT expr` with the negation pushed down one level,
+/// or `None` if `expr` can't be negated without a NOT operator.
+/// Under three-valued logic, De Morgan's laws hold and negated comparisons are NULL exactly when the original is NULL.
+fn negate(expr: &Expr) -> Option<Expr> {
+    use self::Expr::*;
+    use self::Func2Type::*;
+    Some(match *expr {
+        Func1(Func1Type::Not, ref inner) => (**inner).clone(),
+        Func1(Func1Type::IsNull, ref inner) => Func1(Func1Type::IsNotNull, inner.clone()),
+        Func1(Func1Type::IsNotNull, ref inner) => Func1(Func1Type::IsNull, inner.clone()),
+        Func2(And, ref lhs, ref rhs) =>
+            Expr::func(Or, Expr::func1(Func1Type::Not, (**lhs).clone()), Expr::func1(Func1Type::Not, (**rhs).clone())),
+        Func2(Or, ref lhs, ref rhs) =>
+            Expr::func(And, Expr::func1(Func1Type::Not, (**lhs).clone()), Expr::func1(Func1Type::Not, (**rhs).clone())),
+        Func2(function, ref lhs, ref rhs) => {
+            let negated = match function {
+                Equals => NotEquals,
+                NotEquals => Equals,
+                LT => GTE,
+                LTE => GT,
+                GT => LTE,
+                GTE => LT,
+                _ => return None,
+            };
+            Func2(negated, lhs.clone(), rhs.clone())
+        }
+        _ => return None,
+    })
+}
+
+/// Booleans are grouped by (and returned) as 0 and 1.
+pub fn boolean_as_integer((plan, t): (QueryPlan, Type)) -> (QueryPlan, Type) {
+    if t.decoded == BasicType::Boolean {
+        (plan, Type::encoded(Codec::integer_cast(EncodingType::U8)))
+    } else {
+        (plan, t)
+    }
+}
+
+/// Casts (non-constant) booleans to integers, e.g. to count the rows in which a predicate is true by summing it.
+pub fn cast_boolean_to_integer((plan, t): (QueryPlan, Type)) -> (QueryPlan, Type) {
+    if t.decoded == BasicType::Boolean && !t.is_scalar {
+        (syntax::cast(plan, EncodingType::U8, EncodingType::I64), Type::unencoded(BasicType::Integer))
+    } else {
+        (plan, t)
+    }
+}
+
 pub fn order_preserving((plan, t): (QueryPlan, Type)) -> (QueryPlan, Type) {
     if t.is_order_preserving() {
         (plan, t)
@@ -249,111 +874,110 @@ impl QueryPlan {
                         t = Type::encoded(codec);
                         plan = *fixed_width;
                     }
-                    plan = match filter {
-                        Filter::BitVec(filter) => {
-                            QueryPlan::Filter(
-                                Box::new(plan),
-                                t.encoding_type(),
-                                Box::new(QueryPlan::ReadBuffer(filter)))
-                        }
-                        Filter::Indices(indices) => {
-                            QueryPlan::Select(
-                                Box::new(plan),
-                                Box::new(QueryPlan::ReadBuffer(indices)),
-                                t.encoding_type())
-                        }
-                        Filter::None => plan,
-                    };
-                    (plan, t)
+                    (apply_filter(plan, t.encoding_type(), filter), t)
                 }
-                None => bail!(QueryError::NotImplemented, "Referencing missing column {}", name)
+                None => return Err(missing_column(name, columns.keys().map(|s| s.as_str()))),
             }
-            Func2(LT, ref lhs, ref rhs) => {
-                let (plan_lhs, type_lhs) = QueryPlan::create_query_plan(lhs, filter, columns)?;
-                let (plan_rhs, type_rhs) = QueryPlan::create_query_plan(rhs, filter, columns)?;
-                match (type_lhs.decoded, type_rhs.decoded) {
-                    (BasicType::Integer, BasicType::Integer) => {
-                        let plan = if type_rhs.is_scalar {
-                            if type_lhs.is_encoded() {
-                                let encoded = QueryPlan::EncodeIntConstant(Box::new(plan_rhs), type_lhs.codec.clone().unwrap());
-                                QueryPlan::LessThanVS(type_lhs.encoding_type(), Box::new(plan_lhs), Box::new(encoded))
-                            } else {
-                                QueryPlan::LessThanVS(type_lhs.encoding_type(), Box::new(plan_lhs), Box::new(plan_rhs))
-                            }
-                        } else {
-                            bail!(QueryError::NotImplemented, "< operator only implemented for column < constant")
-                        };
-                        (plan, Type::new(BasicType::Boolean, None).mutable())
-                    }
-                    _ => bail!(QueryError::TypeError, "{:?} < {:?}", type_lhs, type_rhs)
+            Func2(function @ LT, ref lhs, ref rhs)
+            | Func2(function @ LTE, ref lhs, ref rhs)
+            | Func2(function @ GT, ref lhs, ref rhs)
+            | Func2(function @ GTE, ref lhs, ref rhs)
+            | Func2(function @ Equals, ref lhs, ref rhs)
+            | Func2(function @ NotEquals, ref lhs, ref rhs) =>
+                QueryPlan::create_comparison_plan(function, expr, lhs, rhs, filter, columns)?,
+            In(ref lhs, ref items) => QueryPlan::create_in_plan(expr, lhs, items, filter, columns)?,
+            // Rows in which the predicate isn't true are NULL, see `null_maps`
+            Func2(Filtered, ref inner, ref predicate) => {
+                match QueryPlan::create_query_plan(inner, filter, columns)? {
+                    // Constants are expanded into a vector with one element for each row of the predicate
+                    (QueryPlan::Constant(value, hide_value), t) => match QueryPlan::is_true_plan(predicate, filter, columns)? {
+                        Some(predicate) => expand_constant(value, predicate)?,
+                        None => (QueryPlan::Constant(value, hide_value), t),
+                    },
+                    (plan, t) => (plan, t),
                 }
             }
-            Func2(Equals, ref lhs, ref rhs) => {
-                let (plan_lhs, type_lhs) = QueryPlan::create_query_plan(lhs, filter, columns)?;
-                let (plan_rhs, type_rhs) = QueryPlan::create_query_plan(rhs, filter, columns)?;
-                match (type_lhs.decoded, type_rhs.decoded) {
-                    (BasicType::String, BasicType::String) => {
-                        let plan = if type_rhs.is_scalar {
-                            if type_lhs.is_encoded() {
-                                let encoded = type_lhs.codec.clone().unwrap().encode_str(Box::new(plan_rhs));
-                                QueryPlan::EqualsVS(type_lhs.encoding_type(), Box::new(plan_lhs), encoded)
-                            } else {
-                                QueryPlan::EqualsVS(type_lhs.encoding_type(), Box::new(plan_lhs), Box::new(plan_rhs))
-                            }
-                        } else {
-                            bail!(QueryError::NotImplemented, "= operator only implemented for column = constant")
-                        };
-                        (plan, Type::new(BasicType::Boolean, None).mutable())
-                    }
-                    (BasicType::Integer, BasicType::Integer) => {
-                        let plan = if type_rhs.is_scalar {
-                            if type_lhs.is_encoded() {
-                                let encoded = QueryPlan::EncodeIntConstant(Box::new(plan_rhs), type_lhs.codec.clone().unwrap());
-                                QueryPlan::EqualsVS(type_lhs.encoding_type(), Box::new(plan_lhs), Box::new(encoded))
-                            } else {
-                                QueryPlan::EqualsVS(type_lhs.encoding_type(), Box::new(plan_lhs), Box::new(plan_rhs))
-                            }
-                        } else {
-                            bail!(QueryError::NotImplemented, "= operator only implemented for column = constant")
-                        };
-                        (plan, Type::new(BasicType::Boolean, None).mutable())
-                    }
-                    _ => bail!(QueryError::TypeError, "{:?} = {:?}", type_lhs, type_rhs)
+            If(ref cond, ref lhs, ref rhs) => QueryPlan::create_if_plan(cond, lhs, rhs, filter, columns)?,
+            Func2(RegexMatch, ref lhs, ref rhs) => {
+                let regex = match **rhs {
+                    Const(RawVal::Str(ref regex)) => regex,
+                    _ => bail!(QueryError::NotImplemented, "regex({:?}, {:?}), expected constant pattern", lhs, rhs),
+                };
+                QueryPlan::create_regex_plan(expr, lhs, regex, filter, columns)?
+            }
+            Func2(function @ Like, ref lhs, ref rhs) | Func2(function @ ILike, ref lhs, ref rhs) => {
+                let regex = match **rhs {
+                    Const(RawVal::Str(ref pattern)) => string_function::like_to_regex(pattern, function == ILike),
+                    _ => bail!(QueryError::NotImplemented, "{:?}({:?}, {:?}), expected constant pattern", function, lhs, rhs),
+                };
+                QueryPlan::create_regex_plan(expr, lhs, &regex, filter, columns)?
+            }
+            Func1(function @ Lower, ref inner)
+            | Func1(function @ Upper, ref inner)
+            | Func1(function @ Substr(_, _), ref inner) => {
+                let (plan, t) = QueryPlan::create_query_plan(inner, filter, columns)?;
+                if t.decoded != BasicType::String {
+                    bail!(QueryError::TypeError, "Found {:?}({:?}), expected {:?}(string)", function, &t, function)
+                }
+                if t.is_scalar {
+                    (QueryPlan::Constant(row_eval::eval(expr, &[], &[])?, false), t)
+                } else {
+                    let function = StringFunction::from_func1(function).unwrap();
+                    map_strings(vec![(plan, t)], function)
                 }
             }
-            Func2(NotEquals, ref lhs, ref rhs) => {
-                let (plan_lhs, type_lhs) = QueryPlan::create_query_plan(lhs, filter, columns)?;
-                let (plan_rhs, type_rhs) = QueryPlan::create_query_plan(rhs, filter, columns)?;
-                match (type_lhs.decoded, type_rhs.decoded) {
-                    (BasicType::String, BasicType::String) => {
-                        let plan = if type_rhs.is_scalar {
-                            if type_lhs.is_encoded() {
-                                let encoded = type_lhs.codec.clone().unwrap().encode_str(Box::new(plan_rhs));
-                                QueryPlan::NotEqualsVS(type_lhs.encoding_type(), Box::new(plan_lhs), encoded)
-                            } else {
-                                QueryPlan::NotEqualsVS(type_lhs.encoding_type(), Box::new(plan_lhs), Box::new(plan_rhs))
-                            }
-                        } else {
-                            bail!(QueryError::NotImplemented, "<> operator only implemented for column <> constant")
-                        };
-                        (plan, Type::new(BasicType::Boolean, None).mutable())
-                    }
-                    (BasicType::Integer, BasicType::Integer) => {
-                        let plan = if type_rhs.is_scalar {
-                            if type_lhs.is_encoded() {
-                                let encoded = QueryPlan::EncodeIntConstant(Box::new(plan_rhs), type_lhs.codec.clone().unwrap());
-                                QueryPlan::NotEqualsVS(type_lhs.encoding_type(), Box::new(plan_lhs), Box::new(encoded))
-                            } else {
-                                QueryPlan::NotEqualsVS(type_lhs.encoding_type(), Box::new(plan_lhs), Box::new(plan_rhs))
-                            }
-                        } else {
-                            bail!(QueryError::NotImplemented, "<> operator only implemented for column <>= constant")
-                        };
-                        (plan, Type::new(BasicType::Boolean, None).mutable())
+            Func2(Concat, _, _) => {
+                let mut args = Vec::new();
+                concat_args(expr, &mut args);
+                let mut parts = Vec::with_capacity(args.len());
+                let mut inputs = Vec::new();
+                for arg in args {
+                    let (plan, t) = QueryPlan::create_query_plan(arg, filter, columns)?;
+                    match plan {
+                        QueryPlan::Constant(RawVal::Str(constant), _) => parts.push(Some(constant)),
+                        _ if t.decoded == BasicType::String && !t.is_scalar => {
+                            parts.push(None);
+                            inputs.push((plan, t));
+                        }
+                        _ => bail!(QueryError::TypeError, "Found concat({:?}), expected concat(string)", &t),
                     }
-                    _ => bail!(QueryError::TypeError, "{:?} <> {:?}", type_lhs, type_rhs)
+                }
+                if inputs.is_empty() {
+                    (QueryPlan::Constant(row_eval::eval(expr, &[], &[])?, false), Type::scalar(BasicType::String))
+                } else {
+                    map_strings(inputs, StringFunction::Concat(parts))
                 }
             }
+            Func1(Length, ref inner) => {
+                let (plan, t) = QueryPlan::create_query_plan(inner, filter, columns)?;
+                if t.decoded != BasicType::String {
+                    bail!(QueryError::TypeError, "Found length({:?}), expected length(string)", &t)
+                }
+                if t.is_scalar {
+                    (QueryPlan::Constant(row_eval::eval(expr, &[], &[])?, false), Type::scalar(BasicType::Integer))
+                } else {
+                    let plan = match dictionary(&t) {
+                        Some((dict_indices, dict_data)) => QueryPlan::TableLookup(
+                            Box::new(plan),
+                            t.encoding_type(),
+                            Box::new(QueryPlan::StringLength(Box::new(QueryPlan::DictionaryEntries(dict_indices, dict_data)))),
+                            EncodingType::I64),
+                        None => QueryPlan::StringLength(Box::new(decode(plan, &t))),
+                    };
+                    (plan, Type::unencoded(BasicType::Integer).mutable())
+                }
+            }
+            Func1(Not, ref inner) => match negate(inner) {
+                Some(negated) => QueryPlan::create_query_plan(&negated, filter, columns)?,
+                None => {
+                    let (plan, t) = QueryPlan::create_query_plan(inner, filter, columns)?;
+                    if t.decoded != BasicType::Boolean || t.is_scalar {
+                        bail!(QueryError::TypeError, "Found NOT {:?}, expected NOT bool", &t)
+                    }
+                    // NOT NULL is NULL
+                    (QueryPlan::exclude_nulls(QueryPlan::Not(Box::new(plan)), inner, filter, columns)?, Type::bit_vec())
+                }
+            },
             Func2(Or, ref lhs, ref rhs) => {
                 let (plan_lhs, type_lhs) = QueryPlan::create_query_plan(lhs, filter, columns)?;
                 let (plan_rhs, type_rhs) = QueryPlan::create_query_plan(rhs, filter, columns)?;
@@ -370,65 +994,561 @@ impl QueryPlan {
                 }
                 (QueryPlan::And(Box::new(plan_lhs), Box::new(plan_rhs)), Type::bit_vec())
             }
-            Func2(Divide, ref lhs, ref rhs) => {
-                let (mut plan_lhs, mut type_lhs) = QueryPlan::create_query_plan(lhs, filter, columns)?;
-                let (plan_rhs, type_rhs) = QueryPlan::create_query_plan(rhs, filter, columns)?;
-                match (type_lhs.decoded, type_rhs.decoded) {
-                    (BasicType::Integer, BasicType::Integer) => {
-                        let plan = if type_rhs.is_scalar {
-                            if let Some(codec) = type_lhs.codec {
-                                plan_lhs = *codec.decode(Box::new(plan_lhs));
-                            }
-                            QueryPlan::DivideVS(Box::new(plan_lhs), Box::new(plan_rhs))
-                        } else {
-                            bail!(QueryError::NotImplemented, "/ operator only implemented for column / constant")
-                        };
-                        (plan, Type::unencoded(BasicType::Integer).mutable())
+            Func2(function @ Add, ref lhs, ref rhs)
+            | Func2(function @ Subtract, ref lhs, ref rhs)
+            | Func2(function @ Multiply, ref lhs, ref rhs)
+            | Func2(function @ Divide, ref lhs, ref rhs)
+            | Func2(function @ Modulo, ref lhs, ref rhs) =>
+                QueryPlan::create_arithmetic_plan(function, expr, lhs, rhs, filter, columns)?,
+            Func1(Negate, ref inner) => {
+                let (plan, t) = QueryPlan::create_query_plan(inner, filter, columns)?;
+                if t.decoded == BasicType::Float {
+                    if t.is_scalar {
+                        (QueryPlan::Constant(row_eval::eval(expr, &[], &[])?, false), t)
+                    } else {
+                        (QueryPlan::FloatArithmeticVS(
+                            Func2Type::Multiply,
+                            Box::new(decode(plan, &t)),
+                            Box::new(QueryPlan::Constant(RawVal::Float(OrderedFloat(-1.0)), true))),
+                         Type::unencoded(BasicType::Float).mutable())
                     }
-                    _ => bail!(QueryError::TypeError, "{:?} / {:?}", type_lhs, type_rhs)
+                } else if t.decoded != BasicType::Integer {
+                    bail!(QueryError::TypeError, "Found -{:?}, expected -integer or -float", &t)
+                } else if t.is_scalar {
+                    (QueryPlan::Constant(row_eval::eval(expr, &[], &[])?, false), t)
+                } else {
+                    (QueryPlan::MultiplyVS(Box::new(decode(plan, &t)), Box::new(QueryPlan::Constant(RawVal::Int(-1), true))),
+                     Type::unencoded(BasicType::Integer).mutable())
                 }
             }
-            Func1(ToYear, ref inner) => {
+            Func1(function @ ToYear, ref inner)
+            | Func1(function @ ToMonth, ref inner)
+            | Func1(function @ ToDay, ref inner)
+            | Func1(function @ ToHour, ref inner)
+            | Func1(function @ DayOfWeek, ref inner)
+            | Func1(function @ DateTrunc(_), ref inner) => {
                 let (plan, t) = QueryPlan::create_query_plan(inner, filter, columns)?;
                 if t.decoded != BasicType::Integer {
-                    bail!(QueryError::TypeError, "Found to_year({:?}), expected to_year(integer)", &t)
+                    bail!(QueryError::TypeError, "Found {:?}({:?}), expected {:?}(integer)", function, &t, function)
+                }
+                if t.is_scalar {
+                    (QueryPlan::Constant(row_eval::eval(expr, &[], &[])?, false), t)
+                } else {
+                    let function = DateFunction::from_func1(function).unwrap();
+                    (QueryPlan::MapTimestamps(Box::new(decode(plan, &t)), function), t.decoded())
                 }
-                let decoded = match t.codec.clone() {
-                    Some(codec) => *codec.decode(Box::new(plan)),
-                    None => plan,
+            }
+            Func1(function @ IsNull, ref inner) | Func1(function @ IsNotNull, ref inner) => {
+                let (plan, t) = QueryPlan::create_query_plan(inner, filter, columns)?;
+                if t.is_scalar {
+                    bail!(QueryError::NotImplemented, "{:?} of constant", function)
+                }
+                let plan = match (function, QueryPlan::not_null_plan(inner, filter, columns)?) {
+                    (IsNotNull, Some(not_null)) => not_null,
+                    (_, Some(not_null)) => QueryPlan::Not(Box::new(not_null)),
+                    (IsNotNull, None) => QueryPlan::ConstantExpand(Box::new(plan), 1),
+                    (_, None) => QueryPlan::ConstantExpand(Box::new(plan), 0),
                 };
-                (QueryPlan::ToYear(Box::new(decoded)), t.decoded())
+                (plan, Type::bit_vec())
             }
             Const(ref v) => (QueryPlan::Constant(v.clone(), false), Type::scalar(v.get_type())),
             ref x => bail!(QueryError::NotImplemented, "{:?}.compile_vec()", x),
         })
     }
 
+    fn create_arithmetic_plan<'a>(
+        function: Func2Type,
+        expr: &Expr,
+        lhs: &Expr,
+        rhs: &Expr,
+        filter: Filter,
+        columns: &'a HashMap<String, Arc<Column>>) -> Result<(QueryPlan, Type), QueryError> {
+        use self::Func2Type::*;
+        let (plan_lhs, type_lhs) = QueryPlan::create_query_plan(lhs, filter, columns)?;
+        let (plan_rhs, type_rhs) = QueryPlan::create_query_plan(rhs, filter, columns)?;
+        match (type_lhs.decoded, type_rhs.decoded) {
+            (BasicType::Integer, BasicType::Integer) => {}
+            (BasicType::Integer, BasicType::Float)
+            | (BasicType::Float, BasicType::Integer)
+            | (BasicType::Float, BasicType::Float) =>
+                return QueryPlan::create_float_arithmetic_plan(function, expr, plan_lhs, &type_lhs, plan_rhs, &type_rhs),
+            _ => bail!(QueryError::TypeError, "{:?} {:?} {:?}", type_lhs, function, type_rhs),
+        }
+        let plan = match (type_lhs.is_scalar, type_rhs.is_scalar) {
+            (true, true) => {
+                // Division by zero evaluates to NULL
+                let value = row_eval::eval(expr, &[], &[])?;
+                let t = Type::scalar(value.get_type());
+                return Ok((QueryPlan::Constant(value, false), t));
+            }
+            (false, true) => {
+                let (plan_lhs, plan_rhs) = (Box::new(decode(plan_lhs, &type_lhs)), Box::new(plan_rhs));
+                match function {
+                    Add => QueryPlan::AddVS(EncodingType::I64, plan_lhs, plan_rhs),
+                    Subtract => QueryPlan::SubtractVS(plan_lhs, plan_rhs),
+                    Multiply => QueryPlan::MultiplyVS(plan_lhs, plan_rhs),
+                    Divide => QueryPlan::DivideVS(plan_lhs, plan_rhs),
+                    Modulo => QueryPlan::ModuloVS(plan_lhs, plan_rhs),
+                    _ => bail!(QueryError::FatalError, "{:?} is not an arithmetic operator", function),
+                }
+            }
+            (true, false) => {
+                let (plan_lhs, plan_rhs) = (Box::new(plan_lhs), Box::new(decode(plan_rhs, &type_rhs)));
+                match function {
+                    Add => QueryPlan::AddVS(EncodingType::I64, plan_rhs, plan_lhs),
+                    Multiply => QueryPlan::MultiplyVS(plan_rhs, plan_lhs),
+                    // c - x = -x + c
+                    Subtract => QueryPlan::AddVS(
+                        EncodingType::I64,
+                        Box::new(QueryPlan::MultiplyVS(plan_rhs, Box::new(QueryPlan::Constant(RawVal::Int(-1), true)))),
+                        plan_lhs),
+                    Divide => QueryPlan::DivideSV(plan_lhs, plan_rhs),
+                    Modulo => QueryPlan::ModuloSV(plan_lhs, plan_rhs),
+                    _ => bail!(QueryError::FatalError, "{:?} is not an arithmetic operator", function),
+                }
+            }
+            (false, false) => {
+                let (plan_lhs, plan_rhs) = (Box::new(decode(plan_lhs, &type_lhs)), Box::new(decode(plan_rhs, &type_rhs)));
+                match function {
+                    Add => QueryPlan::AddVV(plan_lhs, plan_rhs),
+                    Subtract => QueryPlan::SubtractVV(plan_lhs, plan_rhs),
+                    Multiply => QueryPlan::MultiplyVV(plan_lhs, plan_rhs),
+                    Divide => QueryPlan::DivideVV(plan_lhs, plan_rhs),
+                    Modulo => QueryPlan::ModuloVV(plan_lhs, plan_rhs),
+                    _ => bail!(QueryError::FatalError, "{:?} is not an arithmetic operator", function),
+                }
+            }
+        };
+        Ok((plan, Type::unencoded(BasicType::Integer).mutable()))
+    }
+
+    /// Arithmetic where at least one side is a float. Integer vectors are converted to floats,
+    /// integer constants are converted when the operator reads them.
+    fn create_float_arithmetic_plan(
+        function: Func2Type,
+        expr: &Expr,
+        plan_lhs: QueryPlan,
+        type_lhs: &Type,
+        plan_rhs: QueryPlan,
+        type_rhs: &Type) -> Result<(QueryPlan, Type), QueryError> {
+        use self::Func2Type::*;
+        let plan = match (type_lhs.is_scalar, type_rhs.is_scalar) {
+            (true, true) =>
+                return Ok((QueryPlan::Constant(row_eval::eval(expr, &[], &[])?, false), Type::scalar(BasicType::Float))),
+            (false, true) =>
+                QueryPlan::FloatArithmeticVS(function, Box::new(as_float(plan_lhs, type_lhs)), Box::new(plan_rhs)),
+            (true, false) => {
+                let (plan_lhs, plan_rhs) = (Box::new(plan_lhs), Box::new(as_float(plan_rhs, type_rhs)));
+                match function {
+                    Add | Multiply => QueryPlan::FloatArithmeticVS(function, plan_rhs, plan_lhs),
+                    // c - x = -x + c
+                    Subtract => QueryPlan::FloatArithmeticVS(
+                        Add,
+                        Box::new(QueryPlan::FloatArithmeticVS(
+                            Multiply, plan_rhs, Box::new(QueryPlan::Constant(RawVal::Float(OrderedFloat(-1.0)), true)))),
+                        plan_lhs),
+                    Divide | Modulo => QueryPlan::FloatArithmeticSV(function, plan_lhs, plan_rhs),
+                    _ => bail!(QueryError::FatalError, "{:?} is not an arithmetic operator", function),
+                }
+            }
+            (false, false) => QueryPlan::FloatArithmeticVV(
+                function, Box::new(as_float(plan_lhs, type_lhs)), Box::new(as_float(plan_rhs, type_rhs))),
+        };
+        Ok((plan, Type::unencoded(BasicType::Float).mutable()))
+    }
+
+    fn create_comparison_plan<'a>(
+        function: Func2Type,
+        expr: &Expr,
+        lhs: &Expr,
+        rhs: &Expr,
+        filter: Filter,
+        columns: &'a HashMap<String, Arc<Column>>) -> Result<(QueryPlan, Type), QueryError> {
+        use self::Func2Type::*;
+        let (mut plan_lhs, mut type_lhs) = QueryPlan::create_query_plan(lhs, filter, columns)?;
+        let (mut plan_rhs, mut type_rhs) = QueryPlan::create_query_plan(rhs, filter, columns)?;
+        let is_equality = function == Equals || function == NotEquals;
+        match (type_lhs.decoded, type_rhs.decoded) {
+            (BasicType::Integer, BasicType::Integer) => {}
+            (BasicType::Float, BasicType::Float) => {}
+            // Integer vectors are compared as floats, integer constants are converted when the operator reads them
+            (BasicType::Integer, BasicType::Float) if !type_lhs.is_scalar => {
+                plan_lhs = as_float(plan_lhs, &type_lhs);
+                type_lhs = Type::unencoded(BasicType::Float);
+            }
+            (BasicType::Float, BasicType::Integer) if !type_rhs.is_scalar => {
+                plan_rhs = as_float(plan_rhs, &type_rhs);
+                type_rhs = Type::unencoded(BasicType::Float);
+            }
+            (BasicType::Integer, BasicType::Float) | (BasicType::Float, BasicType::Integer) => {}
+            (BasicType::String, BasicType::String) if is_equality => {}
+            _ => bail!(QueryError::TypeError, "{:?} {:?} {:?}", type_lhs, function, type_rhs),
+        }
+        let plan = match (type_lhs.is_scalar, type_rhs.is_scalar) {
+            (false, true) => QueryPlan::comparison_vs(function, plan_lhs, &type_lhs, plan_rhs)?,
+            // Move the constant to the right hand side: `c < x` is equivalent to `x > c`
+            (true, false) => {
+                let mirrored = match function {
+                    LT => GT,
+                    LTE => GTE,
+                    GT => LT,
+                    GTE => LTE,
+                    _ => function,
+                };
+                QueryPlan::comparison_vs(mirrored, plan_rhs, &type_rhs, plan_lhs)?
+            }
+            // Only < and <= have vector-vector operators, `x > y` is evaluated as `y < x`
+            (false, false) => match function {
+                GT => QueryPlan::comparison_vv(LT, plan_rhs, &type_rhs, plan_lhs, &type_lhs)?,
+                GTE => QueryPlan::comparison_vv(LTE, plan_rhs, &type_rhs, plan_lhs, &type_lhs)?,
+                _ => QueryPlan::comparison_vv(function, plan_lhs, &type_lhs, plan_rhs, &type_rhs)?,
+            },
+            (true, true) => bail!(QueryError::NotImplemented, "{:?} between two constants", function),
+        };
+        // Comparisons involving NULL are never true
+        let plan = QueryPlan::exclude_nulls(plan, expr, filter, columns)?;
+        Ok((plan, Type::new(BasicType::Boolean, None).mutable()))
+    }
+
+    fn create_in_plan<'a>(
+        expr: &Expr,
+        lhs: &Expr,
+        items: &[Expr],
+        filter: Filter,
+        columns: &'a HashMap<String, Arc<Column>>) -> Result<(QueryPlan, Type), QueryError> {
+        let (plan_lhs, type_lhs) = QueryPlan::create_query_plan(lhs, filter, columns)?;
+        let strings = items.iter()
+            .map(|item| match *item {
+                Expr::Const(RawVal::Str(ref s)) => Some(s.to_string()),
+                _ => None,
+            })
+            .collect::<Option<Vec<_>>>();
+        // Dictionary encoded strings can be checked with a single lookup into the set of matching dictionary indices
+        if let (Some(mut strings), Some(codec)) = (strings, type_lhs.codec.clone()) {
+            if type_lhs.is_encoded() && type_lhs.decoded == BasicType::String && !type_lhs.is_scalar {
+                strings.sort();
+                strings.dedup();
+                if let Some(set) = codec.encode_str_set(strings) {
+                    let plan = QueryPlan::TableLookup(Box::new(plan_lhs), type_lhs.encoding_type(), set, EncodingType::BitVec);
+                    return Ok((QueryPlan::exclude_nulls(plan, expr, filter, columns)?, Type::bit_vec()));
+                }
+            }
+        }
+        // Otherwise, `x IN (a, b)` is evaluated as `x = a OR x = b`
+        let disjunction = items.iter()
+            .map(|item| Expr::func(Func2Type::Equals, lhs.clone(), item.clone()))
+            .fold1(|disjunction, equals| Expr::func(Func2Type::Or, disjunction, equals));
+        match disjunction {
+            Some(disjunction) => QueryPlan::create_query_plan(&disjunction, filter, columns),
+            None => bail!(QueryError::InvalidQuery, "IN list must not be empty"),
+        }
+    }
+
+    fn create_regex_plan<'a>(
+        expr: &Expr,
+        lhs: &Expr,
+        regex: &str,
+        filter: Filter,
+        columns: &'a HashMap<String, Arc<Column>>) -> Result<(QueryPlan, Type), QueryError> {
+        if let Err(err) = Regex::new(regex) {
+            bail!(QueryError::TypeError, "Invalid regex {}: {}", regex, err)
+        }
+        let (plan, t) = QueryPlan::create_query_plan(lhs, filter, columns)?;
+        if t.decoded != BasicType::String {
+            bail!(QueryError::TypeError, "Found regex({:?}, {:?}), expected regex(string, string)", &t, regex)
+        }
+        if t.is_scalar {
+            bail!(QueryError::NotImplemented, "regex of constant")
+        }
+        // Dictionary encoded strings only evaluate the regex once for each dictionary entry
+        let encoded_matches = match t.codec {
+            Some(ref codec) if t.is_encoded() => codec.encode_regex(regex),
+            _ => None,
+        };
+        let plan = match encoded_matches {
+            Some(matches) => QueryPlan::TableLookup(Box::new(plan), t.encoding_type(), matches, EncodingType::BitVec),
+            None => QueryPlan::RegexMatch(Box::new(decode(plan, &t)), regex.to_string()),
+        };
+        Ok((QueryPlan::exclude_nulls(plan, expr, filter, columns)?, Type::bit_vec()))
+    }
+
+    /// Sets all elements of the boolean `plan` to 0 for rows in which `expr` is NULL.
+    fn exclude_nulls<'a>(
+        plan: QueryPlan,
+        expr: &Expr,
+        filter: Filter,
+        columns: &'a HashMap<String, Arc<Column>>) -> Result<QueryPlan, QueryError> {
+        Ok(QueryPlan::null_maps(expr, filter, columns)?
+            .into_iter()
+            .fold(plan, |plan, null_map| QueryPlan::AndNot(Box::new(plan), Box::new(null_map))))
+    }
+
+    /// Returns plans for the null maps of all columns that cause `expr` to be NULL.
+    /// Each null map is 1 for all (selected) rows in which the column is NULL.
+    fn null_maps<'a>(
+        expr: &Expr,
+        filter: Filter,
+        columns: &'a HashMap<String, Arc<Column>>) -> Result<Vec<QueryPlan>, QueryError> {
+        let mut colnames = HashSet::new();
+        expr.add_nullable_colnames(&mut colnames);
+        let mut colnames = colnames.into_iter().collect::<Vec<_>>();
+        colnames.sort();
+        let mut null_maps = Vec::new();
+        for name in colnames {
+            if let Some(column) = columns.get(&name) {
+                if let Some(section) = column.null_map_section() {
+                    let null_map = QueryPlan::ReadColumnSection(name, section, Some((0, 1)));
+                    null_maps.push(apply_filter(null_map, EncodingType::U8, filter));
+                } else if column.basic_type() == BasicType::Null {
+                    let (plan, _) = QueryPlan::create_query_plan(&Expr::ColName(name), filter, columns)?;
+                    null_maps.push(QueryPlan::ConstantExpand(Box::new(plan), 1));
+                }
+            }
+        }
+        let mut conditionals = Vec::new();
+        expr.add_conditionals(&mut conditionals);
+        for conditional in conditionals {
+            match conditional {
+                Expr::Func2(Func2Type::Filtered, _, ref predicate) => {
+                    let is_true = match QueryPlan::is_true_plan(predicate, filter, columns)? {
+                        Some(is_true) => is_true,
+                        None => bail!(QueryError::NotImplemented, "FILTER (WHERE {:?}) with constant predicate", predicate),
+                    };
+                    null_maps.push(QueryPlan::Not(Box::new(is_true)));
+                }
+                // NULL if the branch that is taken is NULL
+                Expr::If(ref cond, ref lhs, ref rhs) => {
+                    let cond_true = match QueryPlan::is_true_plan(cond, filter, columns)? {
+                        Some(cond_true) => cond_true,
+                        None => {
+                            let branch = if row_eval::is_true(&row_eval::eval(cond, &[], &[])?) { lhs } else { rhs };
+                            null_maps.extend(QueryPlan::null_maps(branch, filter, columns)?);
+                            continue;
+                        }
+                    };
+                    let lhs_not_null = QueryPlan::not_null_plan(lhs, filter, columns)?;
+                    let rhs_not_null = QueryPlan::not_null_plan(rhs, filter, columns)?;
+                    match (lhs_not_null, rhs_not_null) {
+                        (None, None) => {}
+                        (Some(lhs_not_null), None) =>
+                            null_maps.push(QueryPlan::AndNot(Box::new(cond_true), Box::new(lhs_not_null))),
+                        (None, Some(rhs_not_null)) =>
+                            null_maps.push(QueryPlan::AndNot(
+                                Box::new(QueryPlan::Not(Box::new(rhs_not_null))), Box::new(cond_true))),
+                        (Some(lhs_not_null), Some(rhs_not_null)) =>
+                            null_maps.push(QueryPlan::Blend(
+                                Box::new(cond_true),
+                                Box::new(QueryPlan::Not(Box::new(lhs_not_null))),
+                                Box::new(QueryPlan::Not(Box::new(rhs_not_null))),
+                                EncodingType::BitVec)),
+                    }
+                }
+                // NULL if an integer divisor is 0, floats follow IEEE 754 and yield inf or NaN instead
+                Expr::Func2(Func2Type::Divide, ref dividend, ref divisor)
+                | Expr::Func2(Func2Type::Modulo, ref dividend, ref divisor) => {
+                    let (plan_dividend, type_dividend) = QueryPlan::create_query_plan(dividend, filter, columns)?;
+                    let (_, type_divisor) = QueryPlan::create_query_plan(divisor, filter, columns)?;
+                    if type_dividend.decoded != BasicType::Integer || type_divisor.decoded != BasicType::Integer {
+                        continue;
+                    }
+                    if !type_divisor.is_scalar {
+                        let is_zero = Expr::Func2(Func2Type::Equals, divisor.clone(), Box::new(Expr::Const(RawVal::Int(0))));
+                        let (is_zero, _) = QueryPlan::create_query_plan(&is_zero, filter, columns)?;
+                        null_maps.push(is_zero);
+                    } else if !type_dividend.is_scalar && row_eval::eval(divisor, &[], &[])? == RawVal::Int(0) {
+                        null_maps.push(QueryPlan::ConstantExpand(Box::new(plan_dividend), 1));
+                    }
+                }
+                _ => bail!(QueryError::FatalError, "{:?} is not conditional", conditional),
+            }
+        }
+        Ok(null_maps)
+    }
+
+    /// Returns a plan that is 1 for all (selected) rows in which `cond` is true (and not NULL),
+    /// or `None` if `cond` is constant.
+    fn is_true_plan<'a>(
+        cond: &Expr,
+        filter: Filter,
+        columns: &'a HashMap<String, Arc<Column>>) -> Result<Option<QueryPlan>, QueryError> {
+        let (plan, t) = QueryPlan::create_query_plan(cond, filter, columns)?;
+        if t.decoded != BasicType::Boolean {
+            bail!(QueryError::TypeError, "Found condition {:?}, expected boolean", t)
+        }
+        if t.is_scalar {
+            return Ok(None);
+        }
+        Ok(Some(QueryPlan::exclude_nulls(plan, cond, filter, columns)?))
+    }
+
+    fn create_if_plan<'a>(
+        cond: &Expr,
+        lhs: &Expr,
+        rhs: &Expr,
+        filter: Filter,
+        columns: &'a HashMap<String, Arc<Column>>) -> Result<(QueryPlan, Type), QueryError> {
+        let cond_true = match QueryPlan::is_true_plan(cond, filter, columns)? {
+            Some(cond_true) => cond_true,
+            None => {
+                let branch = if row_eval::is_true(&row_eval::eval(cond, &[], &[])?) { lhs } else { rhs };
+                return QueryPlan::create_query_plan(branch, filter, columns);
+            }
+        };
+        // If all results are string constants, the result is computed as indices into a constant dictionary
+        // which (unlike strings) can be packed together with other grouping keys
+        let mut entries = Vec::new();
+        if constant_strings(lhs, &mut entries) && constant_strings(rhs, &mut entries) {
+            entries.sort_unstable();
+            entries.dedup();
+            let (lhs, rhs) = (encode_constant_strings(lhs, &entries), encode_constant_strings(rhs, &entries));
+            let (plan, _) = QueryPlan::create_if_plan(cond, &lhs, &rhs, filter, columns)?;
+            let entries = entries.into_iter().map(|entry| entry.to_string()).collect::<Vec<_>>();
+            let dictionary = |section| QueryPlan::ConstantDictionary(entries.clone(), section);
+            let codec = Codec::computed_dictionary(EncodingType::I64, dictionary(0), dictionary(1));
+            return Ok((plan, Type::encoded(codec)));
+        }
+        let (plan_lhs, type_lhs) = QueryPlan::create_query_plan(lhs, filter, columns)?;
+        let (plan_rhs, type_rhs) = QueryPlan::create_query_plan(rhs, filter, columns)?;
+        // Both branches are converted into the same unencoded type
+        let t = match (type_lhs.decoded, type_rhs.decoded) {
+            (BasicType::Integer, BasicType::Integer) => Type::unencoded(BasicType::Integer),
+            (BasicType::Integer, BasicType::Float)
+            | (BasicType::Float, BasicType::Integer)
+            | (BasicType::Float, BasicType::Float) => Type::unencoded(BasicType::Float),
+            (BasicType::String, BasicType::String) => Type::unencoded(BasicType::String),
+            (BasicType::Boolean, BasicType::Boolean) if !type_lhs.is_scalar && !type_rhs.is_scalar => Type::bit_vec(),
+            _ => bail!(QueryError::TypeError, "Found if(_, {:?}, {:?}), expected branches of the same type", type_lhs, type_rhs),
+        };
+        let if_true = if_branch(plan_lhs, &type_lhs, &t, &cond_true)?;
+        let if_false = if_branch(plan_rhs, &type_rhs, &t, &cond_true)?;
+        let plan = QueryPlan::Blend(Box::new(cond_true), Box::new(if_true), Box::new(if_false), t.encoding_type());
+        if t.decoded == BasicType::String {
+            // Dictionary encoding allows grouping by the result
+            Ok(map_strings(vec![(plan, t)], StringFunction::Concat(vec![None])))
+        } else {
+            Ok((plan, t))
+        }
+    }
+
+    /// Returns a plan that is 1 for all (selected) rows in which `expr` is not NULL,
+    /// or `None` if `expr` can't be NULL.
+    pub fn not_null_plan<'a>(
+        expr: &Expr,
+        filter: Filter,
+        columns: &'a HashMap<String, Arc<Column>>) -> Result<Option<QueryPlan>, QueryError> {
+        let mut null_maps = QueryPlan::null_maps(expr, filter, columns)?.into_iter();
+        Ok(null_maps.next().map(|first| null_maps.fold(
+            QueryPlan::Not(Box::new(first)),
+            |plan, null_map| QueryPlan::AndNot(Box::new(plan), Box::new(null_map)))))
+    }
+
+    fn comparison_vs(function: Func2Type, plan: QueryPlan, t: &Type, constant: QueryPlan) -> Result<QueryPlan, QueryError> {
+        use self::Func2Type::*;
+        let constant = match t.codec {
+            Some(ref codec) if t.is_encoded() => match t.decoded {
+                BasicType::String => *codec.encode_str(Box::new(constant)),
+                _ => QueryPlan::EncodeIntConstant(Box::new(constant), codec.clone()),
+            },
+            _ => constant,
+        };
+        let (plan, constant) = (Box::new(plan), Box::new(constant));
+        let encoding_type = t.encoding_type();
+        Ok(match function {
+            LT => QueryPlan::LessThanVS(encoding_type, plan, constant),
+            LTE => QueryPlan::LessThanEqualsVS(encoding_type, plan, constant),
+            GT => QueryPlan::GreaterThanVS(encoding_type, plan, constant),
+            GTE => QueryPlan::GreaterThanEqualsVS(encoding_type, plan, constant),
+            Equals => QueryPlan::EqualsVS(encoding_type, plan, constant),
+            NotEquals => QueryPlan::NotEqualsVS(encoding_type, plan, constant),
+            _ => bail!(QueryError::FatalError, "{:?} is not a comparison", function),
+        })
+    }
+
+    fn comparison_vv(function: Func2Type,
+                     plan_lhs: QueryPlan,
+                     type_lhs: &Type,
+                     plan_rhs: QueryPlan,
+                     type_rhs: &Type) -> Result<QueryPlan, QueryError> {
+        use self::Func2Type::*;
+        // Both sides are compared by the same operator, which requires their values to have the same type
+        if type_lhs.decoded != type_rhs.decoded {
+            bail!(QueryError::TypeError, "{:?} {:?} {:?}", type_lhs, function, type_rhs)
+        }
+        // If both sides share the same codec, equality (and order, if the codec preserves it) can be determined
+        // directly on the encoded values
+        let same_encoding = match (&type_lhs.codec, &type_rhs.codec) {
+            (&Some(ref l), &Some(ref r)) if type_lhs.is_encoded() && type_rhs.is_encoded() => l.is_equivalent(r),
+            _ => !type_lhs.is_encoded() && !type_rhs.is_encoded(),
+        };
+        let is_equality = function == Equals || function == NotEquals;
+        let (plan_lhs, plan_rhs, encoding_type) = if same_encoding && (is_equality || type_lhs.is_order_preserving()) {
+            (plan_lhs, plan_rhs, type_lhs.encoding_type())
+        } else {
+            (decode(plan_lhs, type_lhs), decode(plan_rhs, type_rhs), type_lhs.decoded().encoding_type())
+        };
+        let (plan_lhs, plan_rhs) = (Box::new(plan_lhs), Box::new(plan_rhs));
+        Ok(match function {
+            LT => QueryPlan::LessThanVV(encoding_type, plan_lhs, plan_rhs),
+            LTE => QueryPlan::LessThanEqualsVV(encoding_type, plan_lhs, plan_rhs),
+            Equals => QueryPlan::EqualsVV(encoding_type, plan_lhs, plan_rhs),
+            NotEquals => QueryPlan::NotEqualsVV(encoding_type, plan_lhs, plan_rhs),
+            _ => bail!(QueryError::FatalError, "No vector-vector operator for {:?}", function),
+        })
+    }
+
+    /// Compiles the grouping key for the grouping set of `exprs` that consists of the (sorted) indices in
+    /// `grouping_set`. The grouping key of a coarser grouping set is computed from the packed grouping key of all
+    /// `exprs` (`EncodedGroupByPlaceholder`) by only retaining the bit fields of its expressions, which allows its
+    /// results to be derived from the results of the finest grouping. All rows are grouped into a single group by an
+    /// empty grouping set, which decodes to a single constant column.
     pub fn compile_grouping_key<'b>(
         exprs: &[Expr],
+        grouping_set: &[usize],
         filter: Filter,
         columns: &'b HashMap<String, Arc<Column>>)
         -> Result<(QueryPlan, Type, i64, Vec<(QueryPlan, Type)>), QueryError> {
-        if exprs.len() == 1 {
-            QueryPlan::create_query_plan(&exprs[0], filter, columns)
-                .map(|(gk_plan, gk_type)| {
-                    let max_cardinality = QueryPlan::encoding_range(&gk_plan).map_or(1 << 62, |i| i.1);
-                    if QueryPlan::encoding_range(&gk_plan).is_none() {
-                        println!("Unknown range for {:?}", &gk_plan);
-                    }
-                    let decoded_group_by = gk_type.codec.clone().map_or(
-                        QueryPlan::EncodedGroupByPlaceholder,
-                        |codec| *codec.decode(Box::new(QueryPlan::EncodedGroupByPlaceholder)));
-                    (gk_plan.clone(), gk_type.clone(), max_cardinality, vec![(decoded_group_by, gk_type.decoded())])
-                })
+        if grouping_set.is_empty() {
+            let plan = if exprs.is_empty() {
+                // Any column determines the number of rows, but columns without any values might not be stored
+                let colname = match columns.iter().find(|&(_, column)| column.basic_type() != BasicType::Null)
+                    .or_else(|| columns.iter().next()) {
+                    Some((colname, _)) => colname.clone(),
+                    None => bail!(QueryError::FatalError, "Grouping key requires at least one column"),
+                };
+                QueryPlan::create_query_plan(&Expr::ColName(colname), filter, columns)?.0
+            } else {
+                QueryPlan::EncodedGroupByPlaceholder
+            };
+            let t = Type::encoded(Codec::opaque(
+                EncodingType::U8, BasicType::Integer, true, true, true, true));
+            let decode_plan = syntax::cast(QueryPlan::EncodedGroupByPlaceholder, EncodingType::U8, EncodingType::I64);
+            Ok((QueryPlan::ConstantExpand(Box::new(plan), 0), t, 0, vec![(decode_plan, Type::unencoded(BasicType::Integer))]))
+        } else if exprs.len() == 1 {
+            let (gk_plan, gk_type) = boolean_as_integer(QueryPlan::create_query_plan(&exprs[0], filter, columns)?);
+            if gk_type.decoded == BasicType::Float {
+                bail!(QueryError::NotImplemented, "Grouping by float {:?}", &exprs[0])
+            }
+            // Negative grouping keys can't be used as indices and require hashmap grouping
+            let max_cardinality = match QueryPlan::encoding_range(&gk_plan) {
+                Some((min, max)) if min >= 0 => max,
+                _ => 1 << 62,
+            };
+            if QueryPlan::encoding_range(&gk_plan).is_none() {
+                println!("Unknown range for {:?}", &gk_plan);
+            }
+            let decoded_group_by = gk_type.codec.clone().map_or(
+                QueryPlan::EncodedGroupByPlaceholder,
+                |codec| *codec.decode(Box::new(QueryPlan::EncodedGroupByPlaceholder)));
+            Ok((gk_plan.clone(), gk_type.clone(), max_cardinality, vec![(decoded_group_by, gk_type.decoded())]))
         } else {
             let mut total_width = 0;
             let mut largest_key = 0;
             let mut plan = None;
             let mut decode_plans = Vec::with_capacity(exprs.len());
             let mut order_preserving = true;
-            for expr in exprs.iter().rev() {
-                let (query_plan, plan_type) = QueryPlan::create_query_plan(expr, filter, columns)?;
+            let mut fields = Vec::with_capacity(grouping_set.len());
+            for (i, expr) in exprs.iter().enumerate().rev() {
+                let (query_plan, plan_type) = boolean_as_integer(QueryPlan::create_query_plan(expr, filter, columns)?);
                 if let Some((min, max)) = QueryPlan::encoding_range(&query_plan) {
                     fn bits(max: i64) -> i64 {
                         ((max + 1) as f64).log2().ceil() as i64
@@ -437,6 +1557,10 @@ impl QueryPlan {
                     // TODO(clemens): more intelligent criterion. threshold should probably be a function of total width.
                     let subtract_offset = bits(max) - bits(max - min) > 1 || min < 0;
                     let adjusted_max = if subtract_offset { max - min } else { max };
+                    if total_width + bits(adjusted_max) > 64 {
+                        plan = None;
+                        break;
+                    }
                     order_preserving = order_preserving && plan_type.is_order_preserving();
                     let query_plan = if subtract_offset {
                         QueryPlan::AddVS(plan_type.encoding_type(),
@@ -472,9 +1596,11 @@ impl QueryPlan {
                     if let Some(codec) = plan_type.codec.clone() {
                         decode_plan = *codec.decode(Box::new(decode_plan));
                     }
-                    decode_plans.push((decode_plan, plan_type.decoded()));
-
-                    largest_key += adjusted_max << total_width;
+                    if grouping_set.contains(&i) {
+                        decode_plans.push((decode_plan, plan_type.decoded()));
+                        fields.push((total_width, bits(adjusted_max)));
+                        largest_key += adjusted_max << total_width;
+                    }
                     total_width += bits(adjusted_max);
                 } else {
                     plan = None;
@@ -482,8 +1608,17 @@ impl QueryPlan {
                 }
             }
 
-            if let Some(plan) = plan {
+            if let Some(mut plan) = plan {
                 if total_width <= 64 {
+                    if grouping_set.len() < exprs.len() {
+                        let key = QueryPlan::EncodedGroupByPlaceholder;
+                        plan = fields.iter().fold(
+                            QueryPlan::BitUnpack(Box::new(key.clone()), 0, 0),
+                            |packed, &(shift, width)| QueryPlan::BitPack(
+                                Box::new(packed),
+                                Box::new(QueryPlan::BitUnpack(Box::new(key.clone()), shift as u8, width as u8)),
+                                shift));
+                    }
                     decode_plans.reverse();
                     let t = Type::encoded(Codec::opaque(
                         EncodingType::I64, BasicType::Integer, false, order_preserving, true, true));
@@ -503,17 +1638,58 @@ impl QueryPlan {
         use self::QueryPlan::*;
         match *self {
             ReadColumnSection(_, _, range) => range,
-            ToYear(ref timestamps) => timestamps.encoding_range().map(|(min, max)|
-                (NaiveDateTime::from_timestamp(min, 0).year() as i64,
-                 NaiveDateTime::from_timestamp(max, 0).year() as i64)
-            ),
+            MapTimestamps(ref timestamps, function) => function.range(timestamps.encoding_range()),
             Filter(ref plan, _, _) => plan.encoding_range(),
             // TODO(clemens): this is just wrong
+            DivideVS(_, box Constant(RawVal::Int(0), _)) => Some((0, 0)),
             DivideVS(ref left, box Constant(RawVal::Int(c), _)) =>
-                left.encoding_range().map(|(min, max)|
-                    if c > 0 { (min / c, max / c) } else { (max / c, min / c) }),
+                left.encoding_range().and_then(|(min, max)|
+                    if c > 0 { Some((min / c, max / c)) } else { Some((max.checked_div(c)?, min.checked_div(c)?)) }),
             AddVS(_, ref left, box Constant(RawVal::Int(c), _)) =>
-                left.encoding_range().map(|(min, max)| (min + c, max + c)),
+                left.encoding_range().and_then(|(min, max)| Some((min.checked_add(c)?, max.checked_add(c)?))),
+            SubtractVS(ref left, box Constant(RawVal::Int(c), _)) =>
+                left.encoding_range().and_then(|(min, max)| Some((min.checked_sub(c)?, max.checked_sub(c)?))),
+            MultiplyVS(ref left, box Constant(RawVal::Int(c), _)) =>
+                left.encoding_range().and_then(|(min, max)| multiplication_range((min, max), (c, c))),
+            ModuloVS(ref left, box Constant(RawVal::Int(c), _)) =>
+                modulo_range(left.encoding_range(), (c, c)),
+            AddVV(ref left, ref right) => match (left.encoding_range(), right.encoding_range()) {
+                (Some((lmin, lmax)), Some((rmin, rmax))) => Some((lmin.checked_add(rmin)?, lmax.checked_add(rmax)?)),
+                _ => None,
+            },
+            SubtractVV(ref left, ref right) => match (left.encoding_range(), right.encoding_range()) {
+                (Some((lmin, lmax)), Some((rmin, rmax))) => Some((lmin.checked_sub(rmax)?, lmax.checked_sub(rmin)?)),
+                _ => None,
+            },
+            MultiplyVV(ref left, ref right) => match (left.encoding_range(), right.encoding_range()) {
+                (Some(left), Some(right)) => multiplication_range(left, right),
+                _ => None,
+            },
+            DivideSV(box Constant(RawVal::Int(c), _), ref right) => match right.encoding_range() {
+                Some((rmin, _)) if c >= 0 && rmin >= 0 => Some((0, c)),
+                _ => Some((-c.checked_abs()?, c.checked_abs()?)),
+            },
+            // The remainder has the sign of the dividend
+            ModuloSV(box Constant(RawVal::Int(c), _), _) => if c >= 0 { Some((0, c)) } else { Some((c, 0)) },
+            // Dividing by any nonzero integer doesn't increase the absolute value, dividing by zero is NULL and computed as 0
+            DivideVV(ref left, ref right) => match (left.encoding_range()?, right.encoding_range()) {
+                ((lmin, lmax), Some((rmin, _))) if lmin >= 0 && rmin >= 0 => Some((0, lmax)),
+                ((lmin, lmax), _) => {
+                    let bound = cmp::max(lmin.checked_abs()?, lmax.checked_abs()?);
+                    Some((-bound, bound))
+                }
+            },
+            ModuloVV(ref left, ref right) => modulo_range(left.encoding_range(), right.encoding_range()?),
+            Not(_) | AndNot(_, _) | TableLookup(_, _, _, EncodingType::BitVec) | RegexMatch(_, _) => Some((0, 1)),
+            // Mapping dictionary entries can only reduce the number of distinct entries
+            TableLookup(ref indices, _, box MapStrings(_, _, _), _) => indices.encoding_range().map(|(_, max)| (0, max)),
+            ConstantExpand(_, value) => Some((value as i64, value as i64)),
+            ExpandScalar(box Constant(RawVal::Int(c), _), _, _) => Some((c, c)),
+            Blend(_, _, _, EncodingType::BitVec) => Some((0, 1)),
+            Blend(_, ref if_true, ref if_false, EncodingType::I64) => {
+                let ((tmin, tmax), (fmin, fmax)) = (if_true.encoding_range()?, if_false.encoding_range()?);
+                Some((cmp::min(tmin, fmin), cmp::max(tmax, fmax)))
+            }
             Cast(ref left, _, _) => left.encoding_range(),
             LZ4Decode(ref plan, _, _) => plan.encoding_range(),
             DeltaDecode(ref plan, _) => plan.encoding_range(),
@@ -522,6 +1698,21 @@ impl QueryPlan {
     }
 }
 
+fn multiplication_range((lmin, lmax): (i64, i64), (rmin, rmax): (i64, i64)) -> Option<(i64, i64)> {
+    let products = [lmin.checked_mul(rmin)?, lmin.checked_mul(rmax)?, lmax.checked_mul(rmin)?, lmax.checked_mul(rmax)?];
+    Some((*products.iter().min().unwrap(), *products.iter().max().unwrap()))
+}
+
+// The result of `x % y` takes the sign of `x` and is smaller in magnitude than `y`
+fn modulo_range(left: Option<(i64, i64)>, (rmin, rmax): (i64, i64)) -> Option<(i64, i64)> {
+    // Modulo by zero is NULL and computed as 0
+    let bound = cmp::max(cmp::max(rmin.checked_abs()?, rmax.checked_abs()?), 1) - 1;
+    match left {
+        Some((lmin, lmax)) if lmin >= 0 => Some((0, cmp::min(lmax, bound))),
+        _ => Some((-bound, bound)),
+    }
+}
+
 fn replace_common_subexpression(plan: QueryPlan, executor: &mut QueryExecutor) -> (Box<QueryPlan>, [u8; 16]) {
     unsafe {
         use std::intrinsics::discriminant_value;
@@ -559,6 +1750,78 @@ fn replace_common_subexpression(plan: QueryPlan, executor: &mut QueryExecutor) -
                 hasher.input(&s3);
                 InverseDictLookup(dict_indices, dict_data, constant)
             }
+            InverseDictLookupSet(dict_indices, dict_data, constants) => {
+                let (dict_indices, s1) = replace_common_subexpression(*dict_indices, executor);
+                let (dict_data, s2) = replace_common_subexpression(*dict_data, executor);
+                hasher.input(&s1);
+                hasher.input(&s2);
+                for constant in &constants {
+                    hasher.input(&constant.len().to_bytes());
+                    hasher.input_str(constant);
+                }
+                InverseDictLookupSet(dict_indices, dict_data, constants)
+            }
+            TableLookup(indices, t, table, table_type) => {
+                let (indices, s1) = replace_common_subexpression(*indices, executor);
+                let (table, s2) = replace_common_subexpression(*table, executor);
+                hasher.input(&s1);
+                hasher.input(&s2);
+                hasher.input(&discriminant_value(&t).to_bytes());
+                hasher.input(&discriminant_value(&table_type).to_bytes());
+                TableLookup(indices, t, table, table_type)
+            }
+            DictionaryEntries(dict_indices, dict_data) => {
+                let (dict_indices, s1) = replace_common_subexpression(*dict_indices, executor);
+                let (dict_data, s2) = replace_common_subexpression(*dict_data, executor);
+                hasher.input(&s1);
+                hasher.input(&s2);
+                DictionaryEntries(dict_indices, dict_data)
+            }
+            MapStrings(inputs, function, section) => {
+                let inputs = inputs.into_iter()
+                    .map(|input| {
+                        let (input, s) = replace_common_subexpression(input, executor);
+                        hasher.input(&s);
+                        *input
+                    })
+                    .collect();
+                hasher.input_str(&format!("{:?}", function));
+                hasher.input(&section.to_bytes());
+                MapStrings(inputs, function, section)
+            }
+            ConstantDictionary(entries, section) => {
+                for entry in &entries {
+                    hasher.input(&entry.len().to_bytes());
+                    hasher.input_str(entry);
+                }
+                hasher.input(&section.to_bytes());
+                ConstantDictionary(entries, section)
+            }
+            StringLength(plan) => {
+                let (plan, s1) = replace_common_subexpression(*plan, executor);
+                hasher.input(&s1);
+                StringLength(plan)
+            }
+            HashValues(plan, t) => {
+                let (plan, s1) = replace_common_subexpression(*plan, executor);
+                hasher.input(&s1);
+                hasher.input_str(&format!("{:?}", t));
+                HashValues(plan, t)
+            }
+            RegexMatch(plan, regex) => {
+                let (plan, s1) = replace_common_subexpression(*plan, executor);
+                hasher.input(&s1);
+                hasher.input_str(&regex);
+                RegexMatch(plan, regex)
+            }
+            DictRegexMatch(dict_indices, dict_data, regex) => {
+                let (dict_indices, s1) = replace_common_subexpression(*dict_indices, executor);
+                let (dict_data, s2) = replace_common_subexpression(*dict_data, executor);
+                hasher.input(&s1);
+                hasher.input(&s2);
+                hasher.input_str(&regex);
+                DictRegexMatch(dict_indices, dict_data, regex)
+            }
             Cast(plan, initial_type, target_type) => {
                 let (plan, s1) = replace_common_subexpression(*plan, executor);
                 hasher.input(&s1);
@@ -642,6 +1905,30 @@ fn replace_common_subexpression(plan: QueryPlan, executor: &mut QueryExecutor) -
                 hasher.input(&discriminant_value(&left_type).to_bytes());
                 LessThanVS(left_type, lhs, rhs)
             }
+            LessThanEqualsVS(left_type, lhs, rhs) => {
+                let (lhs, s1) = replace_common_subexpression(*lhs, executor);
+                let (rhs, s2) = replace_common_subexpression(*rhs, executor);
+                hasher.input(&s1);
+                hasher.input(&s2);
+                hasher.input(&discriminant_value(&left_type).to_bytes());
+                LessThanEqualsVS(left_type, lhs, rhs)
+            }
+            GreaterThanVS(left_type, lhs, rhs) => {
+                let (lhs, s1) = replace_common_subexpression(*lhs, executor);
+                let (rhs, s2) = replace_common_subexpression(*rhs, executor);
+                hasher.input(&s1);
+                hasher.input(&s2);
+                hasher.input(&discriminant_value(&left_type).to_bytes());
+                GreaterThanVS(left_type, lhs, rhs)
+            }
+            GreaterThanEqualsVS(left_type, lhs, rhs) => {
+                let (lhs, s1) = replace_common_subexpression(*lhs, executor);
+                let (rhs, s2) = replace_common_subexpression(*rhs, executor);
+                hasher.input(&s1);
+                hasher.input(&s2);
+                hasher.input(&discriminant_value(&left_type).to_bytes());
+                GreaterThanEqualsVS(left_type, lhs, rhs)
+            }
             EqualsVS(left_type, lhs, rhs) => {
                 let (lhs, s1) = replace_common_subexpression(*lhs, executor);
                 let (rhs, s2) = replace_common_subexpression(*rhs, executor);
@@ -658,6 +1945,38 @@ fn replace_common_subexpression(plan: QueryPlan, executor: &mut QueryExecutor) -
                 hasher.input(&discriminant_value(&left_type).to_bytes());
                 NotEqualsVS(left_type, lhs, rhs)
             }
+            LessThanVV(left_type, lhs, rhs) => {
+                let (lhs, s1) = replace_common_subexpression(*lhs, executor);
+                let (rhs, s2) = replace_common_subexpression(*rhs, executor);
+                hasher.input(&s1);
+                hasher.input(&s2);
+                hasher.input(&discriminant_value(&left_type).to_bytes());
+                LessThanVV(left_type, lhs, rhs)
+            }
+            LessThanEqualsVV(left_type, lhs, rhs) => {
+                let (lhs, s1) = replace_common_subexpression(*lhs, executor);
+                let (rhs, s2) = replace_common_subexpression(*rhs, executor);
+                hasher.input(&s1);
+                hasher.input(&s2);
+                hasher.input(&discriminant_value(&left_type).to_bytes());
+                LessThanEqualsVV(left_type, lhs, rhs)
+            }
+            EqualsVV(left_type, lhs, rhs) => {
+                let (lhs, s1) = replace_common_subexpression(*lhs, executor);
+                let (rhs, s2) = replace_common_subexpression(*rhs, executor);
+                hasher.input(&s1);
+                hasher.input(&s2);
+                hasher.input(&discriminant_value(&left_type).to_bytes());
+                EqualsVV(left_type, lhs, rhs)
+            }
+            NotEqualsVV(left_type, lhs, rhs) => {
+                let (lhs, s1) = replace_common_subexpression(*lhs, executor);
+                let (rhs, s2) = replace_common_subexpression(*rhs, executor);
+                hasher.input(&s1);
+                hasher.input(&s2);
+                hasher.input(&discriminant_value(&left_type).to_bytes());
+                NotEqualsVV(left_type, lhs, rhs)
+            }
             DivideVS(lhs, rhs) => {
                 let (lhs, s1) = replace_common_subexpression(*lhs, executor);
                 let (rhs, s2) = replace_common_subexpression(*rhs, executor);
@@ -665,6 +1984,100 @@ fn replace_common_subexpression(plan: QueryPlan, executor: &mut QueryExecutor) -
                 hasher.input(&s2);
                 DivideVS(lhs, rhs)
             }
+            SubtractVS(lhs, rhs) => {
+                let (lhs, s1) = replace_common_subexpression(*lhs, executor);
+                let (rhs, s2) = replace_common_subexpression(*rhs, executor);
+                hasher.input(&s1);
+                hasher.input(&s2);
+                SubtractVS(lhs, rhs)
+            }
+            MultiplyVS(lhs, rhs) => {
+                let (lhs, s1) = replace_common_subexpression(*lhs, executor);
+                let (rhs, s2) = replace_common_subexpression(*rhs, executor);
+                hasher.input(&s1);
+                hasher.input(&s2);
+                MultiplyVS(lhs, rhs)
+            }
+            ModuloVS(lhs, rhs) => {
+                let (lhs, s1) = replace_common_subexpression(*lhs, executor);
+                let (rhs, s2) = replace_common_subexpression(*rhs, executor);
+                hasher.input(&s1);
+                hasher.input(&s2);
+                ModuloVS(lhs, rhs)
+            }
+            AddVV(lhs, rhs) => {
+                let (lhs, s1) = replace_common_subexpression(*lhs, executor);
+                let (rhs, s2) = replace_common_subexpression(*rhs, executor);
+                hasher.input(&s1);
+                hasher.input(&s2);
+                AddVV(lhs, rhs)
+            }
+            SubtractVV(lhs, rhs) => {
+                let (lhs, s1) = replace_common_subexpression(*lhs, executor);
+                let (rhs, s2) = replace_common_subexpression(*rhs, executor);
+                hasher.input(&s1);
+                hasher.input(&s2);
+                SubtractVV(lhs, rhs)
+            }
+            MultiplyVV(lhs, rhs) => {
+                let (lhs, s1) = replace_common_subexpression(*lhs, executor);
+                let (rhs, s2) = replace_common_subexpression(*rhs, executor);
+                hasher.input(&s1);
+                hasher.input(&s2);
+                MultiplyVV(lhs, rhs)
+            }
+            DivideVV(lhs, rhs) => {
+                let (lhs, s1) = replace_common_subexpression(*lhs, executor);
+                let (rhs, s2) = replace_common_subexpression(*rhs, executor);
+                hasher.input(&s1);
+                hasher.input(&s2);
+                DivideVV(lhs, rhs)
+            }
+            ModuloVV(lhs, rhs) => {
+                let (lhs, s1) = replace_common_subexpression(*lhs, executor);
+                let (rhs, s2) = replace_common_subexpression(*rhs, executor);
+                hasher.input(&s1);
+                hasher.input(&s2);
+                ModuloVV(lhs, rhs)
+            }
+            DivideSV(lhs, rhs) => {
+                let (lhs, s1) = replace_common_subexpression(*lhs, executor);
+                let (rhs, s2) = replace_common_subexpression(*rhs, executor);
+                hasher.input(&s1);
+                hasher.input(&s2);
+                DivideSV(lhs, rhs)
+            }
+            ModuloSV(lhs, rhs) => {
+                let (lhs, s1) = replace_common_subexpression(*lhs, executor);
+                let (rhs, s2) = replace_common_subexpression(*rhs, executor);
+                hasher.input(&s1);
+                hasher.input(&s2);
+                ModuloSV(lhs, rhs)
+            }
+            FloatArithmeticVS(function, lhs, rhs) => {
+                let (lhs, s1) = replace_common_subexpression(*lhs, executor);
+                let (rhs, s2) = replace_common_subexpression(*rhs, executor);
+                hasher.input(&s1);
+                hasher.input(&s2);
+                hasher.input_str(&format!("{:?}", function));
+                FloatArithmeticVS(function, lhs, rhs)
+            }
+            FloatArithmeticSV(function, lhs, rhs) => {
+                let (lhs, s1) = replace_common_subexpression(*lhs, executor);
+                let (rhs, s2) = replace_common_subexpression(*rhs, executor);
+                hasher.input(&s1);
+                hasher.input(&s2);
+                hasher.input_str(&format!("{:?}", function));
+                FloatArithmeticSV(function, lhs, rhs)
+            }
+            FloatArithmeticVV(function, lhs, rhs) => {
+                let (lhs, s1) = replace_common_subexpression(*lhs, executor);
+                let (rhs, s2) = replace_common_subexpression(*rhs, executor);
+                hasher.input(&s1);
+                hasher.input(&s2);
+                hasher.input_str(&format!("{:?}", function));
+                FloatArithmeticVV(function, lhs, rhs)
+            }
             AddVS(left_type, lhs, rhs) => {
                 let (lhs, s1) = replace_common_subexpression(*lhs, executor);
                 let (rhs, s2) = replace_common_subexpression(*rhs, executor);
@@ -687,10 +2100,23 @@ fn replace_common_subexpression(plan: QueryPlan, executor: &mut QueryExecutor) -
                 hasher.input(&s2);
                 Or(lhs, rhs)
             }
-            ToYear(plan) => {
+            Not(plan) => {
+                let (plan, s1) = replace_common_subexpression(*plan, executor);
+                hasher.input(&s1);
+                Not(plan)
+            }
+            AndNot(lhs, rhs) => {
+                let (lhs, s1) = replace_common_subexpression(*lhs, executor);
+                let (rhs, s2) = replace_common_subexpression(*rhs, executor);
+                hasher.input(&s1);
+                hasher.input(&s2);
+                AndNot(lhs, rhs)
+            }
+            MapTimestamps(plan, function) => {
                 let (plan, s1) = replace_common_subexpression(*plan, executor);
                 hasher.input(&s1);
-                ToYear(plan)
+                hasher.input_str(&format!("{:?}", function));
+                MapTimestamps(plan, function)
             }
             SortIndices(plan, descending) => {
                 let (plan, s1) = replace_common_subexpression(*plan, executor);
@@ -706,6 +2132,16 @@ fn replace_common_subexpression(plan: QueryPlan, executor: &mut QueryExecutor) -
                 hasher.input(&[desc as u8]);
                 TopN(plan, t, n, desc)
             }
+            TopNPerGroup(plan, t, grouping, n, desc) => {
+                let (plan, s1) = replace_common_subexpression(*plan, executor);
+                let (grouping, s2) = replace_common_subexpression(*grouping, executor);
+                hasher.input(&s1);
+                hasher.input(&s2);
+                hasher.input(&discriminant_value(&t).to_bytes());
+                hasher.input(&n.to_bytes());
+                hasher.input(&[desc as u8]);
+                TopNPerGroup(plan, t, grouping, n, desc)
+            }
             Select(lhs, rhs, t) => {
                 let (lhs, s1) = replace_common_subexpression(*lhs, executor);
                 let (rhs, s2) = replace_common_subexpression(*rhs, executor);
@@ -722,15 +2158,57 @@ fn replace_common_subexpression(plan: QueryPlan, executor: &mut QueryExecutor) -
                 hasher.input(&discriminant_value(&t).to_bytes());
                 Filter(plan, t, filter)
             }
-            EncodedGroupByPlaceholder => EncodedGroupByPlaceholder,
+            FillNulls(plan, t, not_null, fill) => {
+                let (plan, s1) = replace_common_subexpression(*plan, executor);
+                let (not_null, s2) = replace_common_subexpression(*not_null, executor);
+                let (fill, s3) = replace_common_subexpression(*fill, executor);
+                hasher.input(&s1);
+                hasher.input(&s2);
+                hasher.input(&s3);
+                hasher.input(&discriminant_value(&t).to_bytes());
+                FillNulls(plan, t, not_null, fill)
+            }
+            Blend(condition, if_true, if_false, t) => {
+                let (condition, s1) = replace_common_subexpression(*condition, executor);
+                let (if_true, s2) = replace_common_subexpression(*if_true, executor);
+                let (if_false, s3) = replace_common_subexpression(*if_false, executor);
+                hasher.input(&s1);
+                hasher.input(&s2);
+                hasher.input(&s3);
+                hasher.input(&discriminant_value(&t).to_bytes());
+                Blend(condition, if_true, if_false, t)
+            }
+            // The grouping keys of different grouping sets are distinct buffers
+            EncodedGroupByPlaceholder => {
+                if let Some(buffer) = executor.encoded_group_by() {
+                    hasher.input(&buffer.0.to_bytes());
+                }
+                EncodedGroupByPlaceholder
+            }
             Constant(val, show) => {
+                hasher.input(&discriminant_value(&val).to_bytes());
                 match val {
                     RawVal::Int(i) => hasher.input(&i.to_bytes()),
+                    RawVal::Float(f) => hasher.input(&f.0.to_bits().to_bytes()),
                     RawVal::Str(ref s) => hasher.input_str(s),
                     RawVal::Null => {}
                 }
                 Constant(val, show)
             }
+            ConstantExpand(plan, value) => {
+                let (plan, s1) = replace_common_subexpression(*plan, executor);
+                hasher.input(&s1);
+                hasher.input(&[value]);
+                ConstantExpand(plan, value)
+            }
+            ExpandScalar(value, plan, t) => {
+                let (value, s1) = replace_common_subexpression(*value, executor);
+                let (plan, s2) = replace_common_subexpression(*plan, executor);
+                hasher.input(&s1);
+                hasher.input(&s2);
+                hasher.input(&discriminant_value(&t).to_bytes());
+                ExpandScalar(value, plan, t)
+            }
         };
 
         hasher.result(&mut signature);
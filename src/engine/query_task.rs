@@ -1,8 +1,8 @@
-use std::cmp;
 use std::collections::HashMap;
 use std::collections::HashSet;
 use std::iter::Iterator;
 use std::mem;
+use std::usize;
 use std::sync::Arc;
 use std::sync::Mutex;
 use std::sync::atomic::{AtomicUsize, AtomicBool, Ordering};
@@ -11,7 +11,14 @@ use ::QueryError;
 use QueryResult;
 use engine::aggregator::*;
 use engine::batch_merging::*;
-use engine::query::Query;
+use engine::join::{self, HashJoin};
+use engine::moments::Statistic;
+use engine::query::{JoinType, Query};
+use engine::query_plan::missing_column;
+use engine::row_eval;
+use engine::typed_vec::AnyVec;
+use engine::types::{BasicType, EncodingType};
+use ingest::ordered_float::OrderedFloat;
 use ingest::raw_val::RawVal;
 use mem_store::partition::Partition;
 use mem_store::column::Column;
@@ -28,7 +35,14 @@ pub struct QueryTask {
     partitions: Vec<Arc<Partition>>,
     referenced_cols: HashSet<String>,
     output_colnames: Vec<String>,
-    aggregate: Vec<Aggregator>,
+    projection: Vec<usize>,
+    result_columns: Vec<Expr>,
+    null_indicators: Vec<(usize, usize, RawVal)>,
+    averages: Vec<(usize, usize)>,
+    /// Aggregators of the query, and the index of the aggregation results they are derived from (see
+    /// `Query::aggregation_states`).
+    aggregate: Vec<(Aggregator, usize)>,
+    join: Option<HashJoin>,
     start_time_ns: u64,
     db: Arc<DiskStore>,
 
@@ -44,7 +58,8 @@ pub struct QueryTask {
 
 pub struct QueryState<'a> {
     completed_batches: usize,
-    partial_results: Vec<BatchResult<'a>>,
+    /// Results of each worker, with one result per grouping set.
+    partial_results: Vec<Vec<BatchResult<'a>>>,
     explains: Vec<String>,
     rows_scanned: usize,
     rows_collected: usize,
@@ -53,6 +68,8 @@ pub struct QueryState<'a> {
 
 pub struct QueryOutput {
     pub colnames: Vec<String>,
+    /// Types of the values of each column as determined by the query plans, `BasicType::Null` if no plan was run.
+    pub coltypes: Vec<BasicType>,
     pub rows: Vec<Vec<RawVal>>,
     pub query_plans: HashMap<String, u32>,
     pub stats: QueryStats,
@@ -76,33 +93,76 @@ impl Default for QueryStats {
 
 
 impl QueryTask {
-    pub fn new(mut query: Query, explain: bool, show: Vec<usize>, source: Vec<Arc<Partition>>, db: Arc<DiskStore>, sender: SharedSender<QueryResult>) -> QueryTask {
+    /// `joined` holds the partitions of the table joined by `query`, if any.
+    pub fn new(mut query: Query,
+               explain: bool,
+               show: Vec<usize>,
+               source: Vec<Arc<Partition>>,
+               joined: Vec<Arc<Partition>>,
+               db: Arc<DiskStore>,
+               sender: SharedSender<QueryResult>) -> Result<QueryTask, QueryError> {
         let start_time_ns = precise_time_ns();
+        let mut all_cols = find_all_cols(&source);
+        let mut nullable_cols = find_nullable_cols(&source);
+        let mut join_keys = None;
+        if let Some(join) = query.join.clone() {
+            let joined_cols = find_all_cols(&joined);
+            let (resolved, key, foreign_key) = join::resolve_join(query, &join, &all_cols, &joined_cols)?;
+            query = resolved;
+            let alias = join.alias.clone();
+            let qualify = move |col: &String| format!("{}.{}", alias, col);
+            // Columns of the joined table are NULL in rows without a matching row
+            match join.kind {
+                JoinType::Inner => nullable_cols.extend(find_nullable_cols(&joined).iter().map(&qualify)),
+                JoinType::Left => nullable_cols.extend(joined_cols.iter().map(&qualify)),
+            }
+            all_cols.extend(joined_cols.iter().map(&qualify));
+            join_keys = Some((join, key, foreign_key));
+        }
         if query.is_select_star() {
-            query.select = find_all_cols(&source).into_iter().map(Expr::ColName).collect();
+            query.select = all_cols.iter().cloned().map(Expr::ColName).collect();
         }
 
         let output_colnames = query.result_column_names();
-        let mut order_by_index = None;
-        if let Some(ref col) = query.order_by {
-            for (i, name) in output_colnames.iter().enumerate() {
-                if name == col {
-                    order_by_index = Some(i);
-                }
+        let projection = query.resolve_columns(&nullable_cols)?;
+        let result_columns = query.result_columns();
+        let null_indicators = find_null_indicators(&result_columns);
+        let averages = find_averages(&result_columns);
+        let mut referenced_cols = query.find_referenced_cols();
+        // Columns can't be validated for tables without partitions, e.g. the empty result of a subquery
+        if let Some(missing) = referenced_cols.iter().filter(|col| !source.is_empty() && !all_cols.contains(col)).min() {
+            return Err(missing_column(missing, all_cols.iter().map(|s| s.as_str())));
+        }
+        let (_, states) = query.aggregation_states();
+        let aggregate = query.aggregate.iter().map(|&(aggregate, _)| aggregate).zip(states).collect();
+        let join = match join_keys {
+            Some((join, key, foreign_key)) => {
+                let hash_join = HashJoin::build(&join, key.clone(), foreign_key, &referenced_cols, &joined, db.as_ref())?;
+                referenced_cols.insert(key);
+                Some(hash_join)
+            }
+            None => None,
+        };
+        // Some column is read to determine the number of rows of each partition
+        if referenced_cols.is_empty() {
+            if let Some(colname) = all_cols.iter().min() {
+                referenced_cols.insert(colname.clone());
             }
         }
-        query.order_by_index = order_by_index;
-        let referenced_cols = query.find_referenced_cols();
-        let aggregate = query.aggregate.iter().map(|&(aggregate, _)| aggregate).collect();
 
-        QueryTask {
+        let task = QueryTask {
             query,
             explain,
             show,
             partitions: source,
             referenced_cols,
             output_colnames,
+            projection,
+            result_columns,
+            null_indicators,
+            averages,
             aggregate,
+            join,
             start_time_ns,
             db,
 
@@ -117,22 +177,51 @@ impl QueryTask {
             batch_index: AtomicUsize::new(0),
             completed: AtomicBool::new(false),
             sender,
+        };
+        // There is no partition to run the query on, so the (empty) result is returned immediately
+        if task.partitions.is_empty() {
+            task.sender.send(Ok(QueryOutput {
+                colnames: task.output_colnames.clone(),
+                coltypes: vec![BasicType::Null; task.output_colnames.len()],
+                rows: Vec::new(),
+                query_plans: HashMap::new(),
+                stats: QueryStats {
+                    runtime_ns: precise_time_ns() - task.start_time_ns,
+                    rows_scanned: 0,
+                },
+            }));
+            task.completed.store(true, Ordering::SeqCst);
         }
+        Ok(task)
     }
 
     pub fn run(&self) {
         let mut rows_scanned = 0;
         let mut rows_collected = 0;
         let mut colstack = Vec::new();
-        let mut batch_results = Vec::<BatchResult>::new();
+        let grouping_sets = self.query.grouping_set_indices();
+        let mut batch_results = grouping_sets.iter().map(|_| Vec::<BatchResult>::new()).collect::<Vec<_>>();
         let mut explains = Vec::new();
         while let Some((partition, id)) = self.next_partition() {
             trace_start!("Batch {}", id);
             let show = self.show.iter().any(|&x| x == id);
-            let cols = partition.get_cols(&self.referenced_cols, self.db.as_ref());
+            let mut cols = partition.get_cols(&self.referenced_cols, self.db.as_ref());
+            if let Some(ref join) = self.join {
+                if let Err(error) = join.probe(&mut cols) {
+                    self.fail_with(error);
+                    return;
+                }
+            }
+            // Columns that only exist in some of the partitions (e.g. of tables matched by a wildcard) are NULL in the others
+            for colname in &self.referenced_cols {
+                if !cols.contains_key(colname) {
+                    cols.insert(colname.to_string(), Arc::new(Column::null(colname, partition.len())));
+                }
+            }
             rows_scanned += cols.iter().next().map_or(0, |c| c.1.len());
-            let (mut batch_result, explain) = match if self.aggregate.is_empty() {
+            let (partition_results, explain) = match if self.aggregate.is_empty() {
                 self.query.run(unsafe { mem::transmute(&cols) }, self.explain, show)
+                    .map(|(batch_result, explain)| (vec![batch_result], explain))
             } else {
                 self.query.run_aggregate(unsafe { mem::transmute(&cols) }, self.explain, show, id)
             } {
@@ -142,28 +231,32 @@ impl QueryTask {
                     return;
                 }
             };
-            colstack.push(cols);
-            rows_collected += batch_result.len();
             if let Some(explain) = explain {
                 explains.push(explain);
             }
 
-            // Merge only with previous batch results of same level to get O(n log n) complexity
-            while let Some(br) = batch_results.pop() {
-                if br.level == batch_result.level {
-                    match combine(br, batch_result, self.combined_limit()) {
-                        Ok(result) => batch_result = result,
-                        Err(error) => {
-                            self.fail_with(error);
-                            return;
-                        }
-                    };
-                } else {
-                    batch_results.push(br);
-                    break;
+            // The results of each grouping set are merged separately
+            for (mut batch_result, batch_results) in partition_results.into_iter().zip(batch_results.iter_mut()) {
+                rows_collected += batch_result.len();
+
+                // Merge only with previous batch results of same level to get O(n log n) complexity
+                while let Some(br) = batch_results.pop() {
+                    if br.level == batch_result.level {
+                        match combine(br, batch_result, self.combined_limit()) {
+                            Ok(result) => batch_result = result,
+                            Err(error) => {
+                                self.fail_with(error);
+                                return;
+                            }
+                        };
+                    } else {
+                        batch_results.push(br);
+                        break;
+                    }
                 }
+                batch_results.push(batch_result);
             }
-            batch_results.push(batch_result);
+            colstack.push(cols);
 
             if self.completed.load(Ordering::SeqCst) {
                 return;
@@ -173,10 +266,20 @@ impl QueryTask {
             }
         }
 
-        match QueryTask::combine_results(batch_results, self.combined_limit()) {
-            Ok(Some(result)) => self.push_result(result, rows_scanned, rows_collected, explains),
-            Err(error) => self.fail_with(error),
-            _ => {}
+        let mut results = Vec::with_capacity(batch_results.len());
+        for batch_results in batch_results {
+            match QueryTask::combine_results(batch_results, self.combined_limit()) {
+                Ok(Some(result)) => results.push(result),
+                Ok(None) => {}
+                Err(error) => {
+                    self.fail_with(error);
+                    results.clear();
+                    break;
+                }
+            }
+        }
+        if !results.is_empty() {
+            self.push_result(results, rows_scanned, rows_collected, explains);
         }
         // need to keep colstack alive, otherwise results may reference freed data
         self.push_colstack(colstack);
@@ -194,30 +297,39 @@ impl QueryTask {
         Ok(full_result)
     }
 
-    fn push_result(&self, result: BatchResult, rows_scanned: usize, rows_collected: usize, explains: Vec<String>) {
+    /// `results` holds the combined result of each grouping set.
+    fn push_result(&self, results: Vec<BatchResult>, rows_scanned: usize, rows_collected: usize, explains: Vec<String>) {
         let mut state = self.unsafe_state.lock().unwrap();
         if self.completed.load(Ordering::SeqCst) { return; }
-        state.completed_batches += result.batch_count;
+        state.completed_batches += results[0].batch_count;
         state.explains.extend(explains);
         state.rows_scanned += rows_scanned;
         state.rows_collected += rows_collected;
         unsafe {
-            let result = mem::transmute::<_, BatchResult<'static>>(result);
-            state.partial_results.push(result);
+            let results = mem::transmute::<_, Vec<BatchResult<'static>>>(results);
+            state.partial_results.push(results);
         }
         if state.completed_batches == self.partitions.len() || self.sufficient_rows(state.rows_collected) {
             let mut owned_results = Vec::with_capacity(0);
             mem::swap(&mut owned_results, &mut state.partial_results);
-            // TODO(clemens): Handle empty table
-            let full_result = match QueryTask::combine_results(owned_results, self.combined_limit()) {
-                Ok(result) => result.unwrap(),
-                Err(error) => {
-                    self.fail_with_no_lock(error);
-                    return;
+            let mut grouping_set_results = owned_results[0].iter().map(|_| Vec::new()).collect::<Vec<_>>();
+            for results in owned_results {
+                for (grouping_set_result, result) in grouping_set_results.iter_mut().zip(results) {
+                    grouping_set_result.push(result);
                 }
-            };
-            let final_result = self.convert_to_output_format(&full_result, state.rows_scanned, &state.explains);
-            self.sender.send(Ok(final_result));
+            }
+            let mut full_results = Vec::with_capacity(grouping_set_results.len());
+            for batch_results in grouping_set_results {
+                match QueryTask::combine_results(batch_results, self.combined_limit()) {
+                    Ok(result) => full_results.push(result.unwrap()),
+                    Err(error) => {
+                        self.fail_with_no_lock(error);
+                        return;
+                    }
+                }
+            }
+            let final_result = self.convert_to_output_format(&full_results, state.rows_scanned, &state.explains);
+            self.sender.send(final_result);
             self.completed.store(true, Ordering::SeqCst);
         }
     }
@@ -240,7 +352,8 @@ impl QueryTask {
     }
 
     fn sufficient_rows(&self, rows_collected: usize) -> bool {
-        let unordered_select = self.query.aggregate.is_empty() && self.query.order_by.is_none();
+        let unordered_select = self.query.aggregate.is_empty() && self.query.order_by.is_empty()
+            && self.query.limit_by.is_none();
         unordered_select && self.combined_limit() < rows_collected
     }
 
@@ -250,24 +363,98 @@ impl QueryTask {
     }
 
     fn convert_to_output_format(&self,
-                                full_result: &BatchResult,
+                                full_results: &[BatchResult],
                                 rows_scanned: usize,
-                                explains: &[String]) -> QueryOutput {
+                                explains: &[String]) -> Result<QueryOutput, QueryError> {
         let limit = self.query.limit.limit as usize;
         let offset = self.query.limit.offset as usize;
-        let mut result_rows = Vec::new();
-        let count = cmp::min(limit, full_result.len() - offset);
-        for i in offset..(count + offset) {
-            let mut record = Vec::with_capacity(self.output_colnames.len());
-            if let Some(ref gs) = full_result.group_by {
-                for g in gs {
-                    record.push(g.get_raw(i));
+        let sort_result = self.sort_result();
+        let mut rows = Vec::new();
+        let grouping_sets = self.query.grouping_set_indices();
+        for (full_result, grouping_set) in full_results.iter().zip(&grouping_sets) {
+            for i in 0..full_result.len() {
+                if !sort_result && self.query.limit_by.is_none() && rows.len() >= limit + offset { break; }
+                let mut record = Vec::with_capacity(self.result_columns.len());
+                if let Some(ref gs) = full_result.group_by {
+                    // Group by expressions that are not part of the grouping set are NULL
+                    let mut group_by_columns = gs.iter();
+                    for j in 0..self.query.group_by.len() {
+                        if grouping_set.contains(&j) {
+                            record.push(group_by_columns.next().unwrap().get_raw(i));
+                        } else {
+                            record.push(RawVal::Null);
+                        }
+                    }
+                }
+                if self.aggregate.is_empty() {
+                    for col in &full_result.select {
+                        record.push(col.get_raw(i));
+                    }
+                } else {
+                    for &(aggregator, state) in &self.aggregate {
+                        record.push(aggregate_value(aggregator, &*full_result.select[state], i));
+                    }
+                }
+                for &(column, indicator, ref null_value) in &self.null_indicators {
+                    if record[indicator] == *null_value {
+                        record[column] = RawVal::Null;
+                    }
+                }
+                for &(column, count) in &self.averages {
+                    record[column] = average(&record[column], &record[count]);
                 }
+                if let Some(ref having) = self.query.having {
+                    if !row_eval::is_true(&row_eval::eval(having, &self.result_columns, &record)?) {
+                        continue;
+                    }
+                }
+                rows.push(record);
             }
-            for col in &full_result.select {
-                record.push(col.get_raw(i));
+        }
+
+        if sort_result {
+            rows = row_eval::sort_rows(rows, &self.query.order_by, &self.result_columns)?;
+        }
+
+        if let Some(ref limit_by) = self.query.limit_by {
+            let mut counts = HashMap::new();
+            let mut limited_rows = Vec::with_capacity(rows.len());
+            for record in rows {
+                let mut group = Vec::with_capacity(limit_by.by.len());
+                for expr in &limit_by.by {
+                    group.push(row_eval::eval(expr, &self.result_columns, &record)?);
+                }
+                let count = counts.entry(group).or_insert(0);
+                *count += 1;
+                if *count > limit_by.limit.offset && *count <= limit_by.limit.offset + limit_by.limit.limit {
+                    limited_rows.push(record);
+                }
             }
-            result_rows.push(record);
+            rows = limited_rows;
+        }
+
+        let result_rows = rows.into_iter()
+            .skip(offset)
+            .take(limit)
+            .map(|record| self.projection.iter().map(|&i| record[i].clone()).collect())
+            .collect();
+
+        let mut coltypes = vec![BasicType::Null; self.query.group_by.len()];
+        for (full_result, grouping_set) in full_results.iter().zip(&grouping_sets) {
+            if let Some(ref gs) = full_result.group_by {
+                for (&j, g) in grouping_set.iter().zip(gs) {
+                    coltypes[j] = output_type(g.get_type());
+                }
+            }
+        }
+        if self.aggregate.is_empty() {
+            coltypes.extend(full_results[0].select.iter().map(|col| output_type(col.get_type())));
+        } else {
+            let select = &full_results[0].select;
+            coltypes.extend(self.aggregate.iter().map(|&(_, state)| output_type(select[state].get_type())));
+        }
+        for &(column, _) in &self.averages {
+            coltypes[column] = BasicType::Float;
         }
 
         let mut query_plans = HashMap::new();
@@ -275,19 +462,33 @@ impl QueryTask {
             *query_plans.entry(plan.to_owned()).or_insert(0) += 1
         }
 
-        QueryOutput {
+        Ok(QueryOutput {
             colnames: self.output_colnames.clone(),
+            coltypes: self.projection.iter().map(|&i| coltypes[i]).collect(),
             rows: result_rows,
             query_plans,
             stats: QueryStats {
                 runtime_ns: precise_time_ns() - self.start_time_ns,
                 rows_scanned,
             },
-        }
+        })
+    }
+
+    /// Whether the final result needs to be sorted, which is the case for sorted aggregation queries and
+    /// queries with multiple sort keys.
+    fn sort_result(&self) -> bool {
+        !self.query.order_by.is_empty() && self.query.order_by_index.is_none()
     }
 
     fn combined_limit(&self) -> usize {
-        (self.query.limit.limit + self.query.limit.offset) as usize
+        // HAVING, LIMIT BY and sorting aggregation results are only applied once all batches have been combined.
+        // The sort keys of other queries are applied while combining batches.
+        let sorted_aggregation = self.sort_result() && !self.aggregate.is_empty();
+        if self.query.having.is_some() || self.query.limit_by.is_some() || sorted_aggregation {
+            usize::MAX
+        } else {
+            (self.query.limit.limit + self.query.limit.offset) as usize
+        }
     }
 }
 
@@ -310,3 +511,91 @@ fn find_all_cols(source: &[Arc<Partition>]) -> Vec<String> {
 
     cols.into_iter().collect()
 }
+
+/// Finds the columns that contain NULL values or are missing from some of the partitions.
+fn find_nullable_cols(source: &[Arc<Partition>]) -> HashSet<String> {
+    let all_cols = find_all_cols(source);
+    let mut cols = HashSet::new();
+    for partition in source {
+        cols.extend(partition.nullable_cols().iter().cloned());
+        let partition_cols = partition.col_names();
+        cols.extend(all_cols.iter().filter(|col| !partition_cols.contains(col)).cloned());
+    }
+    cols
+}
+
+/// Finds the result columns that determine whether another result column is NULL.
+/// Returns the index of the column, the index of the indicator column and the value of the indicator for NULL values.
+fn find_null_indicators(result_columns: &[Expr]) -> Vec<(usize, usize, RawVal)> {
+    let mut null_indicators = Vec::new();
+    for (column, expr) in result_columns.iter().enumerate() {
+        let (indicator, null_value) = match *expr {
+            // Counts are never NULL
+            Expr::Aggregate(Aggregator::Count, _) |
+            Expr::Aggregate(Aggregator::CountDistinct, _) |
+            Expr::Aggregate(Aggregator::ApproxCountDistinct(_), _) => continue,
+            // Percentiles and moments are NULL if no values were inserted into their sketch
+            Expr::Aggregate(Aggregator::Percentile(_), _) |
+            Expr::Aggregate(Aggregator::Variance, _) |
+            Expr::Aggregate(Aggregator::StdDev, _) |
+            Expr::Aggregate(Aggregator::Covar, _) |
+            Expr::Aggregate(Aggregator::Corr, _) => continue,
+            // Aggregate over only NULL values
+            Expr::Aggregate(_, ref inner) => (Expr::Aggregate(Aggregator::Count, inner.clone()), RawVal::Int(0)),
+            _ => (Expr::func1(Func1Type::IsNull, expr.clone()), RawVal::Int(1)),
+        };
+        if let Some(index) = result_columns.iter().position(|c| *c == indicator) {
+            null_indicators.push((column, index, null_value));
+        }
+    }
+    null_indicators
+}
+
+/// Finds the result columns that hold the sums of averages.
+/// Returns the index of each such column and the index of the column that counts the values of the average.
+fn find_averages(result_columns: &[Expr]) -> Vec<(usize, usize)> {
+    let mut averages = Vec::new();
+    for (column, expr) in result_columns.iter().enumerate() {
+        if let Expr::Aggregate(Aggregator::Avg, ref inner) = *expr {
+            let count = Expr::Aggregate(Aggregator::Count, inner.clone());
+            if let Some(index) = result_columns.iter().position(|c| *c == count) {
+                averages.push((column, index));
+            }
+        }
+    }
+    averages
+}
+
+/// Type of the values returned by `AnyVec::get_raw` for data of type `t`, `BasicType::Val` if they may have
+/// different types.
+fn output_type(t: EncodingType) -> BasicType {
+    match t {
+        EncodingType::Str => BasicType::String,
+        EncodingType::I64 | EncodingType::U8 | EncodingType::U16 | EncodingType::U32 | EncodingType::U64
+        | EncodingType::ValueSet | EncodingType::HyperLogLog => BasicType::Integer,
+        EncodingType::F64 | EncodingType::TDigest | EncodingType::Moments => BasicType::Float,
+        EncodingType::Null => BasicType::Null,
+        _ => BasicType::Val,
+    }
+}
+
+/// Value of `aggregator` for group `i` of `results`, which hold its results or the aggregation states it is derived from.
+fn aggregate_value(aggregator: Aggregator, results: &AnyVec, i: usize) -> RawVal {
+    let estimate = match aggregator {
+        Aggregator::Percentile(quantile) => results.cast_ref_tdigest()[i].estimate(quantile.0),
+        Aggregator::Variance => results.cast_ref_moments()[i].estimate(Statistic::Variance),
+        Aggregator::StdDev => results.cast_ref_moments()[i].estimate(Statistic::StdDev),
+        Aggregator::Covar => results.cast_ref_moments()[i].estimate(Statistic::Covariance),
+        Aggregator::Corr => results.cast_ref_moments()[i].estimate(Statistic::Correlation),
+        _ => return results.get_raw(i),
+    };
+    estimate.map_or(RawVal::Null, |estimate| RawVal::Float(OrderedFloat(estimate)))
+}
+
+fn average(sum: &RawVal, count: &RawVal) -> RawVal {
+    match (sum, count) {
+        (&RawVal::Int(sum), &RawVal::Int(count)) if count != 0 => RawVal::Float(OrderedFloat(sum as f64 / count as f64)),
+        (&RawVal::Float(sum), &RawVal::Int(count)) if count != 0 => RawVal::Float(OrderedFloat(sum.0 / count as f64)),
+        _ => RawVal::Null,
+    }
+}
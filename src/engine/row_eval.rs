@@ -0,0 +1,243 @@
+use regex::Regex;
+use std::cmp;
+
+use ::QueryError;
+use engine::date_function::DateFunction;
+use engine::string_function::{self, StringFunction};
+use ingest::ordered_float::OrderedFloat;
+use ingest::raw_val::RawVal;
+use syntax::expression::*;
+
+
+/// Evaluates `expr` on a single row of a (fully combined) query result.
+/// `columns` holds the expressions that produced each value in `row`. Any subexpression that matches one of
+/// these is read from the row, everything else has to be computed from its subexpressions.
+/// Boolean results are represented as `RawVal::Int(0)` and `RawVal::Int(1)`.
+pub fn eval(expr: &Expr, columns: &[Expr], row: &[RawVal]) -> Result<RawVal, QueryError> {
+    use self::Expr::*;
+    use self::Func2Type::*;
+    if let Some(index) = columns.iter().position(|c| c == expr) {
+        return Ok(row[index].clone());
+    }
+    Ok(match *expr {
+        Const(ref value) => value.clone(),
+        Func1(Func1Type::Negate, ref inner) => match eval(inner, columns, row)? {
+            RawVal::Int(i) => RawVal::Int(-i),
+            RawVal::Float(f) => RawVal::Float(OrderedFloat(-f.0)),
+            RawVal::Null => RawVal::Null,
+            x => bail!(QueryError::TypeError, "Found -{}, expected -integer or -float", x),
+        },
+        Func1(Func1Type::Not, ref inner) => match eval(inner, columns, row)? {
+            RawVal::Null => RawVal::Null,
+            x => bool(!is_true(&x)),
+        },
+        Func1(function @ Func1Type::ToYear, ref inner)
+        | Func1(function @ Func1Type::ToMonth, ref inner)
+        | Func1(function @ Func1Type::ToDay, ref inner)
+        | Func1(function @ Func1Type::ToHour, ref inner)
+        | Func1(function @ Func1Type::DayOfWeek, ref inner)
+        | Func1(function @ Func1Type::DateTrunc(_), ref inner) => match eval(inner, columns, row)? {
+            RawVal::Int(ts) => match DateFunction::from_func1(function) {
+                Some(function) => RawVal::Int(function.apply(ts)),
+                None => bail!(QueryError::FatalError, "{:?} is not a date function", function),
+            },
+            RawVal::Null => RawVal::Null,
+            x => bail!(QueryError::TypeError, "Found {:?}({}), expected {:?}(integer)", function, x, function),
+        },
+        Func1(Func1Type::Length, ref inner) => match eval(inner, columns, row)? {
+            RawVal::Str(s) => RawVal::Int(string_function::length(&s)),
+            RawVal::Null => RawVal::Null,
+            x => bail!(QueryError::TypeError, "Found length({}), expected length(string)", x),
+        },
+        Func1(function @ Func1Type::Lower, ref inner)
+        | Func1(function @ Func1Type::Upper, ref inner)
+        | Func1(function @ Func1Type::Substr(_, _), ref inner) => match eval(inner, columns, row)? {
+            RawVal::Str(s) => match StringFunction::from_func1(function) {
+                Some(function) => RawVal::Str(function.apply(&[&s])),
+                None => bail!(QueryError::FatalError, "{:?} is not a string function", function),
+            },
+            RawVal::Null => RawVal::Null,
+            x => bail!(QueryError::TypeError, "Found {:?}({}), expected {:?}(string)", function, x, function),
+        },
+        Func1(Func1Type::IsNull, ref inner) => bool(eval(inner, columns, row)? == RawVal::Null),
+        Func1(Func1Type::IsNotNull, ref inner) => bool(eval(inner, columns, row)? != RawVal::Null),
+        // Three-valued logic: NULL represents an unknown truth value
+        Func2(And, ref lhs, ref rhs) => {
+            let (lhs, rhs) = (eval(lhs, columns, row)?, eval(rhs, columns, row)?);
+            if is_false(&lhs) || is_false(&rhs) {
+                bool(false)
+            } else if lhs == RawVal::Null || rhs == RawVal::Null {
+                RawVal::Null
+            } else {
+                bool(true)
+            }
+        }
+        Func2(Or, ref lhs, ref rhs) => {
+            let (lhs, rhs) = (eval(lhs, columns, row)?, eval(rhs, columns, row)?);
+            if is_true(&lhs) || is_true(&rhs) {
+                bool(true)
+            } else if lhs == RawVal::Null || rhs == RawVal::Null {
+                RawVal::Null
+            } else {
+                bool(false)
+            }
+        }
+        Func2(Filtered, ref inner, ref predicate) => {
+            if is_true(&eval(predicate, columns, row)?) {
+                eval(inner, columns, row)?
+            } else {
+                RawVal::Null
+            }
+        }
+        Func2(function, ref lhs, ref rhs) => {
+            let lhs = eval(lhs, columns, row)?;
+            let rhs = eval(rhs, columns, row)?;
+            eval_func2(function, lhs, rhs)?
+        }
+        In(ref lhs, ref items) => {
+            let lhs = eval(lhs, columns, row)?;
+            if lhs == RawVal::Null {
+                return Ok(RawVal::Null);
+            }
+            let mut result = bool(false);
+            for item in items {
+                match eval(item, columns, row)? {
+                    RawVal::Null => result = RawVal::Null,
+                    ref value if *value == lhs => return Ok(bool(true)),
+                    _ => {}
+                }
+            }
+            result
+        }
+        If(ref cond, ref lhs, ref rhs) => {
+            if is_true(&eval(cond, columns, row)?) {
+                eval(lhs, columns, row)?
+            } else {
+                eval(rhs, columns, row)?
+            }
+        }
+        ColName(ref name) => bail!(QueryError::FatalError, "Column {} is not part of the query result", name),
+        Aggregate(aggregator, ref inner) =>
+            bail!(QueryError::FatalError, "Aggregate {:?}({:?}) is not part of the query result", aggregator, inner),
+        Parameter(index, _) => bail!(QueryError::FatalError, "Parameter {} has not been bound", index),
+        Now => bail!(QueryError::FatalError, "now() has not been bound"),
+    })
+}
+
+/// Returns the name of a column referenced by `expr` that is not contained in any of the `columns`.
+pub fn unresolved_column<'a>(expr: &'a Expr, columns: &[Expr]) -> Option<&'a str> {
+    use self::Expr::*;
+    if columns.iter().any(|c| c == expr) {
+        return None;
+    }
+    match *expr {
+        ColName(ref name) => Some(name),
+        Func1(_, ref inner) | Aggregate(_, ref inner) => unresolved_column(inner, columns),
+        Func2(_, ref lhs, ref rhs) => unresolved_column(lhs, columns).or_else(|| unresolved_column(rhs, columns)),
+        In(ref lhs, ref items) => unresolved_column(lhs, columns)
+            .or_else(|| items.iter().filter_map(|item| unresolved_column(item, columns)).next()),
+        If(ref cond, ref lhs, ref rhs) => unresolved_column(cond, columns)
+            .or_else(|| unresolved_column(lhs, columns))
+            .or_else(|| unresolved_column(rhs, columns)),
+        Const(_) | Parameter(_, _) | Now => None,
+    }
+}
+
+/// Sorts the rows of a (fully combined) query result by the sort keys of `order_by`, see `eval`.
+pub fn sort_rows(rows: Vec<Vec<RawVal>>,
+                 order_by: &[(Expr, bool)],
+                 columns: &[Expr]) -> Result<Vec<Vec<RawVal>>, QueryError> {
+    let mut keyed_rows = Vec::with_capacity(rows.len());
+    for row in rows {
+        let mut key = Vec::with_capacity(order_by.len());
+        for &(ref expr, _) in order_by {
+            key.push(eval(expr, columns, &row)?);
+        }
+        keyed_rows.push((key, row));
+    }
+    keyed_rows.sort_by(|(key1, _), (key2, _)| {
+        for (i, &(_, desc)) in order_by.iter().enumerate() {
+            let ordering = cmp_sort_key(&key1[i], &key2[i], desc);
+            if ordering != cmp::Ordering::Equal {
+                return ordering;
+            }
+        }
+        cmp::Ordering::Equal
+    });
+    Ok(keyed_rows.into_iter().map(|(_, row)| row).collect())
+}
+
+/// Orders two values of a sort key. NULL values come last, regardless of whether the sort order is descending.
+pub fn cmp_sort_key(lhs: &RawVal, rhs: &RawVal, desc: bool) -> cmp::Ordering {
+    match (lhs, rhs) {
+        (&RawVal::Null, &RawVal::Null) => cmp::Ordering::Equal,
+        (&RawVal::Null, _) => cmp::Ordering::Greater,
+        (_, &RawVal::Null) => cmp::Ordering::Less,
+        _ if desc => rhs.cmp(lhs),
+        _ => lhs.cmp(rhs),
+    }
+}
+
+fn eval_func2(function: Func2Type, lhs: RawVal, rhs: RawVal) -> Result<RawVal, QueryError> {
+    use self::Func2Type::*;
+    Ok(match (function, lhs, rhs) {
+        (function, RawVal::Int(lhs), RawVal::Float(rhs)) =>
+            eval_func2(function, RawVal::Float(OrderedFloat(lhs as f64)), RawVal::Float(rhs))?,
+        (function, RawVal::Float(lhs), RawVal::Int(rhs)) =>
+            eval_func2(function, RawVal::Float(lhs), RawVal::Float(OrderedFloat(rhs as f64)))?,
+        (_, RawVal::Null, _) | (_, _, RawVal::Null) => RawVal::Null,
+        (Equals, lhs, rhs) => bool(lhs == rhs),
+        (NotEquals, lhs, rhs) => bool(lhs != rhs),
+        (LT, lhs, rhs) => bool(lhs < rhs),
+        (LTE, lhs, rhs) => bool(lhs <= rhs),
+        (GT, lhs, rhs) => bool(lhs > rhs),
+        (GTE, lhs, rhs) => bool(lhs >= rhs),
+        (Add, RawVal::Int(lhs), RawVal::Int(rhs)) => RawVal::Int(lhs.wrapping_add(rhs)),
+        (Subtract, RawVal::Int(lhs), RawVal::Int(rhs)) => RawVal::Int(lhs.wrapping_sub(rhs)),
+        (Multiply, RawVal::Int(lhs), RawVal::Int(rhs)) => RawVal::Int(lhs.wrapping_mul(rhs)),
+        (Divide, RawVal::Int(_), RawVal::Int(0)) => RawVal::Null,
+        (Divide, RawVal::Int(lhs), RawVal::Int(rhs)) => RawVal::Int(lhs.wrapping_div(rhs)),
+        (Modulo, RawVal::Int(_), RawVal::Int(0)) => RawVal::Null,
+        (Modulo, RawVal::Int(lhs), RawVal::Int(rhs)) => RawVal::Int(lhs.wrapping_rem(rhs)),
+        (function, RawVal::Float(lhs), RawVal::Float(rhs)) => float_arithmetic(function, lhs.0, rhs.0)?,
+        (RegexMatch, RawVal::Str(lhs), RawVal::Str(rhs)) => match Regex::new(&rhs) {
+            Ok(regex) => bool(regex.is_match(&lhs)),
+            Err(err) => bail!(QueryError::TypeError, "Invalid regex {}: {}", rhs, err),
+        },
+        (function @ Like, RawVal::Str(lhs), RawVal::Str(rhs))
+        | (function @ ILike, RawVal::Str(lhs), RawVal::Str(rhs)) => {
+            let regex = Regex::new(&string_function::like_to_regex(&rhs, function == ILike)).unwrap();
+            bool(regex.is_match(&lhs))
+        }
+        (Concat, RawVal::Str(lhs), RawVal::Str(rhs)) => RawVal::Str(lhs + &rhs),
+        (function, lhs, rhs) => bail!(QueryError::TypeError, "{:?}({}, {})", function, lhs, rhs),
+    })
+}
+
+fn float_arithmetic(function: Func2Type, lhs: f64, rhs: f64) -> Result<RawVal, QueryError> {
+    let result = match function {
+        Func2Type::Add => lhs + rhs,
+        Func2Type::Subtract => lhs - rhs,
+        Func2Type::Multiply => lhs * rhs,
+        Func2Type::Divide => lhs / rhs,
+        Func2Type::Modulo => lhs % rhs,
+        _ => bail!(QueryError::TypeError, "{:?}({}, {})", function, lhs, rhs),
+    };
+    Ok(RawVal::Float(OrderedFloat(result)))
+}
+
+pub fn is_true(value: &RawVal) -> bool {
+    match *value {
+        RawVal::Int(i) => i != 0,
+        RawVal::Float(f) => f.0 != 0.0,
+        RawVal::Str(_) | RawVal::Null => false,
+    }
+}
+
+fn is_false(value: &RawVal) -> bool {
+    *value != RawVal::Null && !is_true(value)
+}
+
+fn bool(value: bool) -> RawVal {
+    RawVal::Int(value as i64)
+}
@@ -0,0 +1,110 @@
+use std::cmp;
+
+use regex;
+
+use syntax::expression::Func1Type;
+
+
+/// String functions that map each (tuple of) input string(s) to a new string.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StringFunction {
+    Lower,
+    Upper,
+    /// SQL `substr(s, start, length)` where `start` is a 1-based character position.
+    Substr(i64, Option<i64>),
+    /// Concatenates strings. `None` stands for the next input, `Some` for a constant string.
+    Concat(Vec<Option<String>>),
+}
+
+impl StringFunction {
+    /// Returns the `StringFunction` that implements `function`, if any.
+    pub fn from_func1(function: Func1Type) -> Option<StringFunction> {
+        match function {
+            Func1Type::Lower => Some(StringFunction::Lower),
+            Func1Type::Upper => Some(StringFunction::Upper),
+            Func1Type::Substr(start, length) => Some(StringFunction::Substr(start, length)),
+            _ => None,
+        }
+    }
+
+    pub fn apply(&self, args: &[&str]) -> String {
+        match *self {
+            StringFunction::Lower => args[0].to_lowercase(),
+            StringFunction::Upper => args[0].to_uppercase(),
+            StringFunction::Substr(start, length) => {
+                // Characters before position 1 don't exist but still count towards `length`
+                let first = cmp::max(start, 1);
+                let end = match length {
+                    Some(length) => start.saturating_add(length),
+                    None => i64::max_value(),
+                };
+                if end <= first {
+                    return String::new();
+                }
+                args[0].chars()
+                    .skip((first - 1) as usize)
+                    .take((end - first) as usize)
+                    .collect()
+            }
+            StringFunction::Concat(ref parts) => {
+                let mut result = String::new();
+                let mut args = args.iter();
+                for part in parts {
+                    match *part {
+                        Some(ref constant) => result.push_str(constant),
+                        None => result.push_str(args.next().unwrap()),
+                    }
+                }
+                result
+            }
+        }
+    }
+}
+
+/// Number of characters in `s`.
+pub fn length(s: &str) -> i64 {
+    s.chars().count() as i64
+}
+
+/// Translates a SQL LIKE pattern into an equivalent regex.
+/// `%` matches any sequence of characters and `_` matches any single character.
+pub fn like_to_regex(pattern: &str, case_insensitive: bool) -> String {
+    let mut result = String::from(if case_insensitive { "(?is)^" } else { "(?s)^" });
+    let mut literal = String::new();
+    for c in pattern.chars() {
+        match c {
+            '%' | '_' => {
+                result.push_str(&regex::escape(&literal));
+                literal.clear();
+                result.push_str(if c == '%' { ".*" } else { "." });
+            }
+            _ => literal.push(c),
+        }
+    }
+    result.push_str(&regex::escape(&literal));
+    result.push('$');
+    result
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_substr() {
+        let substr = |start, length| StringFunction::Substr(start, length).apply(&["hello"]);
+        assert_eq!(substr(2, Some(3)), "ell");
+        assert_eq!(substr(2, None), "ello");
+        assert_eq!(substr(0, Some(2)), "h");
+        assert_eq!(substr(4, Some(10)), "lo");
+        assert_eq!(substr(7, None), "");
+        assert_eq!(substr(-3, Some(2)), "");
+    }
+
+    #[test]
+    fn test_like_to_regex() {
+        assert_eq!(like_to_regex("a%b_c", false), "(?s)^a.*b.c$");
+        assert_eq!(like_to_regex("1.5%", true), "(?is)^1\\.5.*$");
+    }
+}
@@ -0,0 +1,184 @@
+use std::cmp::Ordering;
+use std::f64;
+use std::f64::consts::PI;
+use std::mem;
+
+
+/// Bounds the number of centroids to roughly `COMPRESSION`, higher values are more accurate and use more memory.
+const COMPRESSION: f64 = 100.0;
+/// Number of inserted values that are buffered before they are merged into the centroids.
+const BUFFER_SIZE: usize = 500;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Centroid {
+    mean: f64,
+    weight: f64,
+}
+
+/// Merging t-digest that estimates quantiles of the values inserted into it.
+/// Centroids near the extreme quantiles are kept small, which makes estimates of tail latencies such as the 99th
+/// percentile much more accurate than estimates of the median.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TDigest {
+    centroids: Vec<Centroid>,
+    unmerged: Vec<Centroid>,
+    count: f64,
+    min: f64,
+    max: f64,
+}
+
+impl TDigest {
+    pub fn new() -> TDigest {
+        TDigest {
+            centroids: Vec::new(),
+            unmerged: Vec::new(),
+            count: 0.0,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+        }
+    }
+
+    pub fn insert(&mut self, value: f64) {
+        if value.is_nan() {
+            return;
+        }
+        self.unmerged.push(Centroid { mean: value, weight: 1.0 });
+        self.count += 1.0;
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+        if self.unmerged.len() >= BUFFER_SIZE {
+            self.compress();
+        }
+    }
+
+    /// Combines two digests into a digest of the values inserted into either.
+    pub fn merge(&mut self, other: &TDigest) {
+        if other.count == 0.0 {
+            return;
+        }
+        self.unmerged.extend(other.centroids.iter().chain(other.unmerged.iter()));
+        self.count += other.count;
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+        self.compress();
+    }
+
+    /// Merges all buffered values into the centroids.
+    pub fn compress(&mut self) {
+        if self.unmerged.is_empty() {
+            return;
+        }
+        let mut all = mem::replace(&mut self.unmerged, Vec::new());
+        all.extend(self.centroids.drain(..));
+        all.sort_unstable_by(|a, b| a.mean.partial_cmp(&b.mean).unwrap_or(Ordering::Equal));
+
+        let mut weight_so_far = 0.0;
+        let mut limit = self.count * k_inverse(k(0.0) + 1.0);
+        let mut current = all[0];
+        for &centroid in &all[1..] {
+            if weight_so_far + current.weight + centroid.weight <= limit {
+                current.weight += centroid.weight;
+                current.mean += (centroid.mean - current.mean) * centroid.weight / current.weight;
+            } else {
+                weight_so_far += current.weight;
+                self.centroids.push(current);
+                limit = self.count * k_inverse(k(weight_so_far / self.count) + 1.0);
+                current = centroid;
+            }
+        }
+        self.centroids.push(current);
+    }
+
+    /// Estimate of the `quantile` of the inserted values, or `None` if no values were inserted.
+    pub fn estimate(&self, quantile: f64) -> Option<f64> {
+        assert!(quantile >= 0.0 && quantile <= 1.0, "quantile {} outside of [0, 1]", quantile);
+        if !self.unmerged.is_empty() {
+            let mut compressed = self.clone();
+            compressed.compress();
+            return compressed.estimate(quantile);
+        }
+        if self.count == 0.0 {
+            return None;
+        }
+        // Each centroid represents values at ranks spread evenly around its center, which makes estimates exact while
+        // all centroids hold single values.
+        let rank = quantile * (self.count - 1.0);
+        let mut previous = (0.0, self.min);
+        let mut weight_so_far = 0.0;
+        for centroid in &self.centroids {
+            let center = weight_so_far + (centroid.weight - 1.0) / 2.0;
+            if rank <= center {
+                return Some(interpolate(previous, (center, centroid.mean), rank));
+            }
+            previous = (center, centroid.mean);
+            weight_so_far += centroid.weight;
+        }
+        Some(interpolate(previous, (self.count - 1.0, self.max), rank))
+    }
+}
+
+fn interpolate((x0, y0): (f64, f64), (x1, y1): (f64, f64), x: f64) -> f64 {
+    if x1 <= x0 {
+        y1
+    } else {
+        y0 + (y1 - y0) * (x - x0) / (x1 - x0)
+    }
+}
+
+/// Scale function that determines the maximum size of centroids at quantile `q`.
+fn k(q: f64) -> f64 {
+    COMPRESSION / (2.0 * PI) * (2.0 * q - 1.0).asin()
+}
+
+fn k_inverse(k: f64) -> f64 {
+    if k >= COMPRESSION / 4.0 {
+        1.0
+    } else {
+        ((k * 2.0 * PI / COMPRESSION).sin() + 1.0) / 2.0
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn digest(values: &[f64]) -> TDigest {
+        let mut digest = TDigest::new();
+        for &value in values {
+            digest.insert(value);
+        }
+        digest
+    }
+
+    #[test]
+    fn test_exact_for_few_values() {
+        let values = [4.0, 1.0, 3.0, 2.0];
+        let digest = digest(&values);
+        assert_eq!(TDigest::new().estimate(0.5), None);
+        assert_eq!(digest.estimate(0.0), Some(1.0));
+        assert_eq!(digest.estimate(0.5), Some(2.5));
+        assert_eq!(digest.estimate(0.75), Some(3.25));
+        assert_eq!(digest.estimate(1.0), Some(4.0));
+    }
+
+    #[test]
+    fn test_estimate() {
+        let values = (0..100_000).map(|i| ((i * 7919) % 100_000) as f64).collect::<Vec<_>>();
+        let digest = digest(&values);
+        for &quantile in &[0.5, 0.9, 0.99, 0.999] {
+            let estimate = digest.estimate(quantile).unwrap();
+            let exact = quantile * 99_999.0;
+            assert!((estimate - exact).abs() < 100.0, "{} {} {}", quantile, estimate, exact);
+        }
+    }
+
+    #[test]
+    fn test_merge() {
+        let values = (0..10_000).map(|i| ((i * 7919) % 10_000) as f64).collect::<Vec<_>>();
+        let mut left = digest(&values[..3000]);
+        left.merge(&digest(&values[3000..]));
+        left.merge(&TDigest::new());
+        assert!((left.estimate(0.99).unwrap() - 9899.01).abs() < 10.0, "{:?}", left.estimate(0.99));
+    }
+}
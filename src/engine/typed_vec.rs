@@ -1,9 +1,14 @@
+use engine::hyperloglog::HyperLogLog;
+use engine::moments::{Moments, Statistic};
+use engine::tdigest::TDigest;
 use engine::types::*;
 use heapsize::HeapSizeOf;
+use ingest::ordered_float::OrderedFloat;
 use ingest::raw_val::RawVal;
 use itertools::Itertools;
 use num::PrimInt;
 use std::cmp::min;
+use std::collections::HashSet;
 use std::fmt::{Debug, Display, Write};
 use std::fmt;
 use std::hash::Hash;
@@ -13,6 +18,9 @@ use std::string;
 
 pub type BoxedVec<'a> = Box<AnyVec<'a> + 'a>;
 
+/// Distinct values of a single group, the intermediate result of `COUNT(DISTINCT expr)`.
+pub type ValueSet = HashSet<RawVal>;
+
 
 pub trait AnyVec<'a>: Send + Sync {
     fn len(&self) -> usize;
@@ -27,24 +35,36 @@ pub trait AnyVec<'a>: Send + Sync {
     fn cast_ref_str<'b>(&'b self) -> &'b [&'a str] { panic!(self.type_error("cast_ref_str")) }
     fn cast_ref_usize(&self) -> &[usize] { panic!(self.type_error("cast_ref_usize")) }
     fn cast_ref_i64(&self) -> &[i64] { panic!(self.type_error("cast_ref_i64")) }
+    fn cast_ref_f64(&self) -> &[OrderedFloat] { panic!(self.type_error("cast_ref_f64")) }
     fn cast_ref_u64(&self) -> &[u64] { panic!(self.type_error("cast_ref_u64")) }
     fn cast_ref_u32<'b>(&'b self) -> &[u32] { panic!(self.type_error("cast_ref_u32")) }
     fn cast_ref_u16<'b>(&'b self) -> &[u16] { panic!(self.type_error("cast_ref_u16")) }
     fn cast_ref_u8<'b>(&'b self) -> &[u8] { panic!(self.type_error("cast_ref_u8")) }
     fn cast_ref_merge_op<'b>(&'b self) -> &[MergeOp] { panic!(self.type_error("cast_ref_merge_op")) }
     fn cast_ref_premerge<'b>(&'b self) -> &[Premerge] { panic!(self.type_error("cast_ref_merge_op")) }
+    fn cast_ref_value_set<'b>(&'b self) -> &[ValueSet] { panic!(self.type_error("cast_ref_value_set")) }
+    fn cast_ref_hyperloglog<'b>(&'b self) -> &[HyperLogLog] { panic!(self.type_error("cast_ref_hyperloglog")) }
+    fn cast_ref_tdigest<'b>(&'b self) -> &[TDigest] { panic!(self.type_error("cast_ref_tdigest")) }
+    fn cast_ref_moments<'b>(&'b self) -> &[Moments] { panic!(self.type_error("cast_ref_moments")) }
     fn cast_str_const(&self) -> string::String { panic!(self.type_error("cast_str_const")) }
+    fn cast_ref_str_const<'b>(&'b self) -> &'b str { panic!(self.type_error("cast_ref_str_const")) }
     fn cast_i64_const(&self) -> i64 { panic!(self.type_error("cast_i64_const")) }
+    fn cast_f64_const(&self) -> OrderedFloat { panic!(self.type_error("cast_f64_const")) }
 
     fn cast_ref_mut_str<'b>(&'b mut self) -> &'b mut Vec<&'a str> { panic!(self.type_error("cast_ref_mut_str")) }
     fn cast_ref_mut_usize(&mut self) -> &mut Vec<usize> { panic!(self.type_error("cast_ref_mut_usize")) }
     fn cast_ref_mut_i64(&mut self) -> &mut Vec<i64> { panic!(self.type_error("cast_ref_mut_i64")) }
+    fn cast_ref_mut_f64(&mut self) -> &mut Vec<OrderedFloat> { panic!(self.type_error("cast_ref_mut_f64")) }
     fn cast_ref_mut_u64(&mut self) -> &mut Vec<u64> { panic!(self.type_error("cast_ref_mut_u64")) }
     fn cast_ref_mut_u32(&mut self) -> &mut Vec<u32> { panic!(self.type_error("cast_ref_mut_u32")) }
     fn cast_ref_mut_u16(&mut self) -> &mut Vec<u16> { panic!(self.type_error("cast_ref_mut_u16")) }
     fn cast_ref_mut_u8(&mut self) -> &mut Vec<u8> { panic!(self.type_error("cast_ref_mut_u8")) }
     fn cast_ref_mut_merge_op(&mut self) -> &mut Vec<MergeOp> { panic!(self.type_error("cast_ref_merge_op")) }
     fn cast_ref_mut_premerge(&mut self) -> &mut Vec<Premerge> { panic!(self.type_error("cast_ref_merge_op")) }
+    fn cast_ref_mut_value_set(&mut self) -> &mut Vec<ValueSet> { panic!(self.type_error("cast_ref_mut_value_set")) }
+    fn cast_ref_mut_hyperloglog(&mut self) -> &mut Vec<HyperLogLog> { panic!(self.type_error("cast_ref_mut_hyperloglog")) }
+    fn cast_ref_mut_tdigest(&mut self) -> &mut Vec<TDigest> { panic!(self.type_error("cast_ref_mut_tdigest")) }
+    fn cast_ref_mut_moments(&mut self) -> &mut Vec<Moments> { panic!(self.type_error("cast_ref_mut_moments")) }
 
     fn display(&self) -> String;
 }
@@ -98,6 +118,11 @@ impl<'a> AnyVec<'a> for Vec<i64> {
     fn cast_ref_mut_i64(&mut self) -> &mut Vec<i64> { self }
 }
 
+impl<'a> AnyVec<'a> for Vec<OrderedFloat> {
+    fn cast_ref_f64(&self) -> &[OrderedFloat] { self }
+    fn cast_ref_mut_f64(&mut self) -> &mut Vec<OrderedFloat> { self }
+}
+
 impl<'a> AnyVec<'a> for Vec<u64> {
     fn cast_ref_u64(&self) -> &[u64] { self }
     fn cast_ref_mut_u64(&mut self) -> &mut Vec<u64> { self }
@@ -128,6 +153,85 @@ impl<'a> AnyVec<'a> for Vec<Premerge> {
     fn cast_ref_mut_premerge(&mut self) -> &mut Vec<Premerge> { self }
 }
 
+// Aggregation states are not `Copy`. They are sorted by the value returned by `get_raw`, and copied when sliced.
+impl<'a> AnyVec<'a> for Vec<ValueSet> {
+    fn len(&self) -> usize { Vec::len(self) }
+    fn get_raw(&self, i: usize) -> RawVal { RawVal::Int(self[i].len() as i64) }
+    fn get_type(&self) -> EncodingType { EncodingType::ValueSet }
+    fn sort_indices_desc(&self, indices: &mut Vec<usize>) { sort_indices_by_value(self, indices, true) }
+    fn sort_indices_asc(&self, indices: &mut Vec<usize>) { sort_indices_by_value(self, indices, false) }
+    fn type_error(&self, func_name: &str) -> String { format!("Vec<ValueSet>.{}", func_name) }
+    fn extend(&mut self, other: BoxedVec<'a>, count: usize) -> Option<BoxedVec<'a>> { extend_states(self, other, count) }
+    fn slice_box<'b>(&'b self, from: usize, to: usize) -> BoxedVec<'b> where 'a: 'b { slice_states(self, from, to) }
+    fn cast_ref_value_set(&self) -> &[ValueSet] { self }
+    fn cast_ref_mut_value_set(&mut self) -> &mut Vec<ValueSet> { self }
+
+    fn display(&self) -> String {
+        let lengths = self.iter().map(|set| set.len()).collect::<Vec<_>>();
+        format!("Vec<ValueSet>(lengths {})", display_slice(&lengths, 100))
+    }
+}
+
+impl<'a> AnyVec<'a> for Vec<HyperLogLog> {
+    fn len(&self) -> usize { Vec::len(self) }
+    fn get_raw(&self, i: usize) -> RawVal { RawVal::Int(self[i].estimate().round() as i64) }
+    fn get_type(&self) -> EncodingType { EncodingType::HyperLogLog }
+    fn sort_indices_desc(&self, indices: &mut Vec<usize>) { sort_indices_by_value(self, indices, true) }
+    fn sort_indices_asc(&self, indices: &mut Vec<usize>) { sort_indices_by_value(self, indices, false) }
+    fn type_error(&self, func_name: &str) -> String { format!("Vec<HyperLogLog>.{}", func_name) }
+    fn extend(&mut self, other: BoxedVec<'a>, count: usize) -> Option<BoxedVec<'a>> { extend_states(self, other, count) }
+    fn slice_box<'b>(&'b self, from: usize, to: usize) -> BoxedVec<'b> where 'a: 'b { slice_states(self, from, to) }
+    fn cast_ref_hyperloglog(&self) -> &[HyperLogLog] { self }
+    fn cast_ref_mut_hyperloglog(&mut self) -> &mut Vec<HyperLogLog> { self }
+
+    fn display(&self) -> String {
+        let estimates = self.iter().map(|hll| hll.estimate().round() as i64).collect::<Vec<_>>();
+        format!("Vec<HyperLogLog>(estimates {})", display_slice(&estimates, 100))
+    }
+}
+
+impl<'a> AnyVec<'a> for Vec<TDigest> {
+    fn len(&self) -> usize { Vec::len(self) }
+    // The median, percentiles estimate their quantile from the digest when the query result is converted
+    fn get_raw(&self, i: usize) -> RawVal {
+        self[i].estimate(0.5).map_or(RawVal::Null, |estimate| RawVal::Float(OrderedFloat(estimate)))
+    }
+    fn get_type(&self) -> EncodingType { EncodingType::TDigest }
+    fn sort_indices_desc(&self, indices: &mut Vec<usize>) { sort_indices_by_value(self, indices, true) }
+    fn sort_indices_asc(&self, indices: &mut Vec<usize>) { sort_indices_by_value(self, indices, false) }
+    fn type_error(&self, func_name: &str) -> String { format!("Vec<TDigest>.{}", func_name) }
+    fn extend(&mut self, other: BoxedVec<'a>, count: usize) -> Option<BoxedVec<'a>> { extend_states(self, other, count) }
+    fn slice_box<'b>(&'b self, from: usize, to: usize) -> BoxedVec<'b> where 'a: 'b { slice_states(self, from, to) }
+    fn cast_ref_tdigest(&self) -> &[TDigest] { self }
+    fn cast_ref_mut_tdigest(&mut self) -> &mut Vec<TDigest> { self }
+
+    fn display(&self) -> String {
+        let estimates = (0..self.len()).map(|i| self.get_raw(i)).collect::<Vec<_>>();
+        format!("Vec<TDigest>(estimates {})", display_slice(&estimates, 100))
+    }
+}
+
+impl<'a> AnyVec<'a> for Vec<Moments> {
+    fn len(&self) -> usize { Vec::len(self) }
+    // The variance of `x`, statistics are estimated from the moments when the query result is converted
+    fn get_raw(&self, i: usize) -> RawVal {
+        self[i].estimate(Statistic::Variance).map_or(RawVal::Null, |estimate| RawVal::Float(OrderedFloat(estimate)))
+    }
+    fn get_type(&self) -> EncodingType { EncodingType::Moments }
+    fn sort_indices_desc(&self, indices: &mut Vec<usize>) { sort_indices_by_value(self, indices, true) }
+    fn sort_indices_asc(&self, indices: &mut Vec<usize>) { sort_indices_by_value(self, indices, false) }
+    fn type_error(&self, func_name: &str) -> String { format!("Vec<Moments>.{}", func_name) }
+    fn extend(&mut self, other: BoxedVec<'a>, count: usize) -> Option<BoxedVec<'a>> { extend_states(self, other, count) }
+    fn slice_box<'b>(&'b self, from: usize, to: usize) -> BoxedVec<'b> where 'a: 'b { slice_states(self, from, to) }
+    fn cast_ref_moments(&self) -> &[Moments] { self }
+    fn cast_ref_mut_moments(&mut self) -> &mut Vec<Moments> { self }
+
+    fn display(&self) -> String {
+        let estimates = (0..self.len()).map(|i| self.get_raw(i)).collect::<Vec<_>>();
+        format!("Vec<Moments>(estimates {})", display_slice(&estimates, 100))
+    }
+}
+
 
 impl<'a, T: GenericVec<T> + 'a> AnyVec<'a> for &'a [T] {
     fn len(&self) -> usize { <[T]>::len(self) }
@@ -167,6 +271,10 @@ impl<'a> AnyVec<'a> for &'a [i64] {
     fn cast_ref_i64<'b>(&'b self) -> &'b [i64] { self }
 }
 
+impl<'a> AnyVec<'a> for &'a [OrderedFloat] {
+    fn cast_ref_f64<'b>(&'b self) -> &'b [OrderedFloat] { self }
+}
+
 impl<'a> AnyVec<'a> for &'a [u64] {
     fn cast_ref_u64<'b>(&'b self) -> &'b [u64] { self }
 }
@@ -203,7 +311,7 @@ impl<'a> AnyVec<'a> for usize {
     fn sort_indices_asc(&self, _indices: &mut Vec<usize>) { panic!("EmptyVector.sort_indices_asc") }
     fn type_error(&self, func_name: &str) -> String { format!("EmptyVector.{}", func_name) }
     fn extend(&mut self, _other: BoxedVec<'a>, _count: usize) -> Option<BoxedVec<'a>> { panic!("EmptyVector.extend") }
-    fn slice_box<'b>(&'b self, from: usize, to: usize) -> BoxedVec<'b> where 'a: 'b { Box::new(from - min(to, *self)) }
+    fn slice_box<'b>(&'b self, from: usize, to: usize) -> BoxedVec<'b> where 'a: 'b { Box::new(min(to, *self) - from) }
 
     fn display(&self) -> String { format!("null({})", self) }
 }
@@ -223,12 +331,25 @@ impl<'a> AnyVec<'a> for RawVal {
             _ => panic!("{}.cast_str_const", &self),
         }
     }
+    fn cast_ref_str_const<'b>(&'b self) -> &'b str {
+        match self {
+            RawVal::Str(s) => s,
+            _ => panic!("{}.cast_ref_str_const", &self),
+        }
+    }
     fn cast_i64_const(&self) -> i64 {
         match self {
             RawVal::Int(i) => *i,
             _ => panic!("{}.cast_i64_const", &self),
         }
     }
+    fn cast_f64_const(&self) -> OrderedFloat {
+        match self {
+            RawVal::Float(f) => *f,
+            RawVal::Int(i) => OrderedFloat(*i as f64),
+            _ => panic!("{}.cast_f64_const", &self),
+        }
+    }
 
     fn display(&self) -> String { format!("Scalar({})", self) }
 }
@@ -266,6 +387,13 @@ impl GenericVec<i64> for i64 {
     fn t() -> EncodingType { EncodingType::I64 }
 }
 
+impl GenericVec<OrderedFloat> for OrderedFloat {
+    fn unwrap<'a, 'b>(vec: &'b AnyVec<'a>) -> &'b [OrderedFloat] where OrderedFloat: 'a { vec.cast_ref_f64() }
+    fn unwrap_mut<'a, 'b>(vec: &'b mut AnyVec<'a>) -> &'b mut Vec<OrderedFloat> where OrderedFloat: 'a { vec.cast_ref_mut_f64() }
+    fn wrap_one(value: OrderedFloat) -> RawVal { RawVal::Float(value) }
+    fn t() -> EncodingType { EncodingType::F64 }
+}
+
 impl GenericVec<u64> for u64 {
     fn unwrap<'a, 'b>(vec: &'b AnyVec<'a>) -> &'b [u64] where u64: 'a { vec.cast_ref_u64() }
     fn unwrap_mut<'a, 'b>(vec: &'b mut AnyVec<'a>) -> &'b mut Vec<u64> where u64: 'a { vec.cast_ref_mut_u64() }
@@ -300,6 +428,57 @@ impl<'c> GenericVec<&'c str> for &'c str {
 }
 
 
+/// Intermediate aggregation results that are not `Copy`, such as the `ValueSet` of a group.
+pub trait AggregationState: Clone + Debug + Send + Sync + Sized + 'static {
+    fn unwrap<'a, 'b>(vec: &'b AnyVec<'a>) -> &'b [Self];
+    fn unwrap_mut<'a, 'b>(vec: &'b mut AnyVec<'a>) -> &'b mut Vec<Self>;
+    /// Combines the partial results of the same group from different partitions.
+    fn merge(&mut self, other: Self);
+}
+
+fn sort_indices_by_value<'a>(states: &AnyVec<'a>, indices: &mut Vec<usize>, desc: bool) {
+    if desc {
+        indices.sort_by(|&i, &j| states.get_raw(i).cmp(&states.get_raw(j)).reverse());
+    } else {
+        indices.sort_by_key(|&i| states.get_raw(i));
+    }
+}
+
+fn extend_states<'a, T: AggregationState>(states: &mut Vec<T>, other: BoxedVec<'a>, count: usize) -> Option<BoxedVec<'a>> {
+    let other = T::unwrap(&*other);
+    states.extend_from_slice(&other[0..min(other.len(), count)]);
+    None
+}
+
+fn slice_states<'b, T: AggregationState>(states: &[T], from: usize, to: usize) -> BoxedVec<'b> where Vec<T>: AnyVec<'b> {
+    Box::new(states[from..min(to, states.len())].to_vec())
+}
+
+impl AggregationState for ValueSet {
+    fn unwrap<'a, 'b>(vec: &'b AnyVec<'a>) -> &'b [ValueSet] { vec.cast_ref_value_set() }
+    fn unwrap_mut<'a, 'b>(vec: &'b mut AnyVec<'a>) -> &'b mut Vec<ValueSet> { vec.cast_ref_mut_value_set() }
+    fn merge(&mut self, other: ValueSet) { self.extend(other) }
+}
+
+impl AggregationState for HyperLogLog {
+    fn unwrap<'a, 'b>(vec: &'b AnyVec<'a>) -> &'b [HyperLogLog] { vec.cast_ref_hyperloglog() }
+    fn unwrap_mut<'a, 'b>(vec: &'b mut AnyVec<'a>) -> &'b mut Vec<HyperLogLog> { vec.cast_ref_mut_hyperloglog() }
+    fn merge(&mut self, other: HyperLogLog) { HyperLogLog::merge(self, &other) }
+}
+
+impl AggregationState for TDigest {
+    fn unwrap<'a, 'b>(vec: &'b AnyVec<'a>) -> &'b [TDigest] { vec.cast_ref_tdigest() }
+    fn unwrap_mut<'a, 'b>(vec: &'b mut AnyVec<'a>) -> &'b mut Vec<TDigest> { vec.cast_ref_mut_tdigest() }
+    fn merge(&mut self, other: TDigest) { TDigest::merge(self, &other) }
+}
+
+impl AggregationState for Moments {
+    fn unwrap<'a, 'b>(vec: &'b AnyVec<'a>) -> &'b [Moments] { vec.cast_ref_moments() }
+    fn unwrap_mut<'a, 'b>(vec: &'b mut AnyVec<'a>) -> &'b mut Vec<Moments> { vec.cast_ref_mut_moments() }
+    fn merge(&mut self, other: Moments) { Moments::merge(self, &other) }
+}
+
+
 pub trait GenericIntVec<T>: GenericVec<T> + CastUsize + PrimInt + Hash + 'static {}
 
 impl<T> GenericIntVec<T> for T where T: GenericVec<T> + CastUsize + PrimInt + Copy + Hash + 'static {}
@@ -312,6 +491,10 @@ impl ConstType<i64> for i64 {
     fn unwrap(vec: &AnyVec) -> i64 { vec.cast_i64_const() }
 }
 
+impl ConstType<OrderedFloat> for OrderedFloat {
+    fn unwrap(vec: &AnyVec) -> OrderedFloat { vec.cast_f64_const() }
+}
+
 impl ConstType<String> for String {
     fn unwrap(vec: &AnyVec) -> String { vec.cast_str_const() }
 }
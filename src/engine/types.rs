@@ -4,6 +4,7 @@ use mem_store::*;
 pub enum EncodingType {
     Str,
     I64,
+    F64,
     USize,
     Val,
     Null,
@@ -17,6 +18,10 @@ pub enum EncodingType {
 
     Premerge,
     MergeOp,
+    ValueSet,
+    HyperLogLog,
+    TDigest,
+    Moments,
 }
 
 impl EncodingType {
@@ -24,9 +29,14 @@ impl EncodingType {
         match self {
             EncodingType::Str => BasicType::String,
             EncodingType::I64 => BasicType::Integer,
+            EncodingType::F64 => BasicType::Float,
             EncodingType::Val => BasicType::Val,
             EncodingType::Null => BasicType::Null,
             EncodingType::BitVec => BasicType::Boolean,
+            EncodingType::ValueSet => BasicType::ValueSet,
+            EncodingType::HyperLogLog => BasicType::HyperLogLog,
+            EncodingType::TDigest => BasicType::TDigest,
+            EncodingType::Moments => BasicType::Moments,
             _ => panic!("{:?} does not have a corresponding BasicType", &self)
         }
     }
@@ -36,9 +46,18 @@ impl EncodingType {
 pub enum BasicType {
     String,
     Integer,
+    Float,
     Val,
     Null,
     Boolean,
+    /// Sets of distinct values, see `typed_vec::ValueSet`.
+    ValueSet,
+    /// Sketches of distinct values, see `hyperloglog::HyperLogLog`.
+    HyperLogLog,
+    /// Sketches of quantiles, see `tdigest::TDigest`.
+    TDigest,
+    /// Moments of one or two variables, see `moments::Moments`.
+    Moments,
 }
 
 impl BasicType {
@@ -46,9 +65,14 @@ impl BasicType {
         match *self {
             BasicType::String => EncodingType::Str,
             BasicType::Integer => EncodingType::I64,
+            BasicType::Float => EncodingType::F64,
             BasicType::Val => EncodingType::Val,
             BasicType::Null => EncodingType::Null,
             BasicType::Boolean => EncodingType::BitVec,
+            BasicType::ValueSet => EncodingType::ValueSet,
+            BasicType::HyperLogLog => EncodingType::HyperLogLog,
+            BasicType::TDigest => EncodingType::TDigest,
+            BasicType::Moments => EncodingType::Moments,
         }
     }
 }
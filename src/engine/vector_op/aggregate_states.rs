@@ -0,0 +1,72 @@
+use std::marker::PhantomData;
+use std::mem;
+
+use engine::vector_op::*;
+use engine::*;
+
+
+/// Merges the aggregation states of each group, e.g. to derive the states of a coarser grouping set from the states
+/// of the finest grouping. Groups without any states are dropped, so the grouping key has to number groups in order of
+/// their first occurrence (as hashmap grouping does).
+#[derive(Debug)]
+pub struct AggregateStates<T, U> {
+    input: BufferRef,
+    grouping: BufferRef,
+    output: BufferRef,
+    max_index: BufferRef,
+    states: Vec<Option<T>>,
+    u: PhantomData<U>,
+}
+
+impl<'a, T: AggregationState, U: GenericIntVec<U>> AggregateStates<T, U> where Vec<T>: AnyVec<'a> {
+    pub fn boxed(input: BufferRef, grouping: BufferRef, output: BufferRef, max_index: BufferRef) -> BoxedOperator<'a> {
+        Box::new(AggregateStates::<T, U> {
+            input,
+            grouping,
+            output,
+            max_index,
+            states: Vec::new(),
+            u: PhantomData,
+        })
+    }
+}
+
+impl<'a, T: AggregationState, U: GenericIntVec<U>> VecOperator<'a> for AggregateStates<T, U> where Vec<T>: AnyVec<'a> {
+    fn execute(&mut self, _: bool, scratchpad: &mut Scratchpad<'a>) {
+        let input = scratchpad.get_any(self.input);
+        let grouping = scratchpad.get::<U>(self.grouping);
+
+        let len = scratchpad.get_const::<i64>(self.max_index) as usize + 1;
+        if len > self.states.len() {
+            self.states.resize(len, None);
+        }
+
+        // Input states are cloned because they are also part of the results of the finest grouping
+        for (g, state) in grouping.iter().zip(T::unwrap(&*input)) {
+            match self.states[g.cast_usize()] {
+                Some(ref mut merged) => merged.merge(state.clone()),
+                ref mut empty => *empty = Some(state.clone()),
+            }
+        }
+    }
+
+    fn finalize(&mut self, scratchpad: &mut Scratchpad<'a>) {
+        let states = mem::replace(&mut self.states, Vec::new());
+        scratchpad.set(self.output, Box::new(states.into_iter().flat_map(|state| state).collect::<Vec<T>>()));
+    }
+
+    fn init(&mut self, _: usize, _: usize, scratchpad: &mut Scratchpad<'a>) {
+        scratchpad.set(self.output, Box::new(Vec::<T>::new()));
+    }
+
+    fn inputs(&self) -> Vec<BufferRef> { vec![self.grouping, self.input, self.max_index] }
+    fn outputs(&self) -> Vec<BufferRef> { vec![self.output] }
+    fn can_stream_input(&self, _: BufferRef) -> bool { false }
+    fn can_stream_output(&self, _: BufferRef) -> bool { false }
+    fn allocates(&self) -> bool { true }
+
+    fn display_op(&self, _: bool) -> String {
+        format!("{}[{}] |= {}", self.output, self.grouping, self.input)
+    }
+    fn display_output(&self) -> bool { false }
+}
@@ -0,0 +1,78 @@
+use std::marker::PhantomData;
+use std::mem;
+
+use engine::hyperloglog::HyperLogLog;
+use engine::vector_op::*;
+use engine::*;
+
+
+/// Inserts the `hashes` of each group into a HyperLogLog sketch. Rows for which `not_null` is 0 are skipped.
+#[derive(Debug)]
+pub struct VecHyperLogLog<U> {
+    hashes: BufferRef,
+    grouping: BufferRef,
+    not_null: Option<BufferRef>,
+    output: BufferRef,
+    max_index: BufferRef,
+    precision: u8,
+    sketches: Vec<HyperLogLog>,
+    u: PhantomData<U>,
+}
+
+impl<U: GenericIntVec<U>> VecHyperLogLog<U> {
+    pub fn boxed<'a>(hashes: BufferRef, grouping: BufferRef, not_null: Option<BufferRef>, output: BufferRef, max_index: BufferRef, precision: u8) -> BoxedOperator<'a> {
+        Box::new(VecHyperLogLog::<U> {
+            hashes,
+            grouping,
+            not_null,
+            output,
+            max_index,
+            precision,
+            sketches: Vec::new(),
+            u: PhantomData,
+        })
+    }
+}
+
+impl<'a, U: GenericIntVec<U>> VecOperator<'a> for VecHyperLogLog<U> {
+    fn execute(&mut self, _: bool, scratchpad: &mut Scratchpad<'a>) {
+        let hashes = scratchpad.get::<u64>(self.hashes);
+        let grouping = scratchpad.get::<U>(self.grouping);
+        let not_null = self.not_null.map(|not_null| scratchpad.get::<u8>(not_null));
+
+        let len = scratchpad.get_const::<i64>(self.max_index) as usize + 1;
+        if len > self.sketches.len() {
+            self.sketches.resize(len, HyperLogLog::new(self.precision));
+        }
+
+        for (i, (g, &hash)) in grouping.iter().zip(hashes.iter()).enumerate() {
+            if not_null.as_ref().map_or(true, |not_null| not_null[i] > 0) {
+                self.sketches[g.cast_usize()].insert(hash);
+            }
+        }
+    }
+
+    fn finalize(&mut self, scratchpad: &mut Scratchpad<'a>) {
+        let sketches = mem::replace(&mut self.sketches, Vec::new());
+        scratchpad.set(self.output, Box::new(sketches));
+    }
+
+    fn init(&mut self, _: usize, _: usize, scratchpad: &mut Scratchpad<'a>) {
+        scratchpad.set(self.output, Box::new(Vec::<HyperLogLog>::new()));
+    }
+
+    fn inputs(&self) -> Vec<BufferRef> {
+        let mut inputs = vec![self.grouping, self.hashes, self.max_index];
+        inputs.extend(self.not_null);
+        inputs
+    }
+    fn outputs(&self) -> Vec<BufferRef> { vec![self.output] }
+    fn can_stream_input(&self, _: BufferRef) -> bool { true }
+    fn can_stream_output(&self, _: BufferRef) -> bool { false }
+    fn allocates(&self) -> bool { true }
+
+    fn display_op(&self, _: bool) -> String {
+        format!("{}[{}] |= hll{}({})", self.output, self.grouping, self.precision, self.hashes)
+    }
+    fn display_output(&self) -> bool { false }
+}
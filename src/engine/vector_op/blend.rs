@@ -0,0 +1,43 @@
+use std::marker::PhantomData;
+
+use engine::*;
+use engine::vector_op::vector_operator::*;
+
+
+/// Selects the element of `if_true` in rows where `condition` is nonzero and the element of `if_false` in all other
+/// rows. Constant branches are expanded into vectors by the query planner, see `QueryPlan::create_if_plan`.
+#[derive(Debug)]
+pub struct Blend<T> {
+    pub condition: BufferRef,
+    pub if_true: BufferRef,
+    pub if_false: BufferRef,
+    pub output: BufferRef,
+    pub t: PhantomData<T>,
+}
+
+impl<'a, T: GenericVec<T> + 'a> VecOperator<'a> for Blend<T> {
+    fn execute(&mut self, stream: bool, scratchpad: &mut Scratchpad<'a>) {
+        let condition = scratchpad.get::<u8>(self.condition);
+        let if_true = scratchpad.get::<T>(self.if_true);
+        let if_false = scratchpad.get::<T>(self.if_false);
+        let mut output = scratchpad.get_mut::<T>(self.output);
+        if stream { output.clear(); }
+        for (i, &c) in condition.iter().enumerate() {
+            output.push(if c > 0 { if_true[i] } else { if_false[i] });
+        }
+    }
+
+    fn init(&mut self, _: usize, batch_size: usize, scratchpad: &mut Scratchpad<'a>) {
+        scratchpad.set(self.output, AnyVec::owned(Vec::<T>::with_capacity(batch_size)));
+    }
+
+    fn inputs(&self) -> Vec<BufferRef> { vec![self.condition, self.if_true, self.if_false] }
+    fn outputs(&self) -> Vec<BufferRef> { vec![self.output] }
+    fn can_stream_input(&self, _: BufferRef) -> bool { true }
+    fn can_stream_output(&self, _: BufferRef) -> bool { true }
+    fn allocates(&self) -> bool { true }
+
+    fn display_op(&self, _: bool) -> String {
+        format!("if({}, {}, {})", self.condition, self.if_true, self.if_false)
+    }
+}
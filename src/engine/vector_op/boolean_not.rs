@@ -0,0 +1,34 @@
+use engine::typed_vec::AnyVec;
+use engine::vector_op::vector_operator::*;
+
+
+#[derive(Debug)]
+pub struct BooleanNot {
+    pub input: BufferRef,
+    pub output: BufferRef,
+}
+
+impl<'a> VecOperator<'a> for BooleanNot {
+    fn execute(&mut self, stream: bool, scratchpad: &mut Scratchpad<'a>) {
+        let input = scratchpad.get::<u8>(self.input);
+        let mut output = scratchpad.get_mut::<u8>(self.output);
+        if stream { output.clear() }
+        for &b in input.iter() {
+            output.push(b ^ 1);
+        }
+    }
+
+    fn init(&mut self, _: usize, batch_size: usize, scratchpad: &mut Scratchpad<'a>) {
+        scratchpad.set(self.output, AnyVec::owned(Vec::<u8>::with_capacity(batch_size)));
+    }
+
+    fn inputs(&self) -> Vec<BufferRef> { vec![self.input] }
+    fn outputs(&self) -> Vec<BufferRef> { vec![self.output] }
+    fn can_stream_input(&self, _: BufferRef) -> bool { true }
+    fn can_stream_output(&self, _: BufferRef) -> bool { true }
+    fn allocates(&self) -> bool { true }
+
+    fn display_op(&self, _: bool) -> String {
+        format!("!{}", self.input)
+    }
+}
@@ -44,3 +44,47 @@ impl<'a, T: GenericVec<T> + 'a, U: GenericIntVec<U>> VecOperator<'a> for Compact
     }
 }
 
+/// `Compact` for aggregation states, which can't be copied.
+#[derive(Debug)]
+pub struct CompactStates<T, U> {
+    data: BufferRef,
+    select: BufferRef,
+    t: PhantomData<T>,
+    u: PhantomData<U>,
+}
+
+impl<'a, T: AggregationState, U: GenericIntVec<U>> CompactStates<T, U> where Vec<T>: AnyVec<'a> {
+    pub fn boxed(data: BufferRef, select: BufferRef) -> BoxedOperator<'a> {
+        Box::new(CompactStates::<T, U> { data, select, t: PhantomData, u: PhantomData })
+    }
+}
+
+impl<'a, T: AggregationState, U: GenericIntVec<U>> VecOperator<'a> for CompactStates<T, U> where Vec<T>: AnyVec<'a> {
+    fn execute(&mut self, _: bool, scratchpad: &mut Scratchpad<'a>) {
+        let mut data = scratchpad.collect(self.data);
+        {
+            let data = T::unwrap_mut(&mut *data);
+            let select = scratchpad.get::<U>(self.select);
+            let mut j = 0;
+            for (i, &s) in select.iter().take(data.len()).enumerate() {
+                if s > U::zero() {
+                    data.swap(i, j);
+                    j += 1;
+                }
+            }
+            data.truncate(j);
+        }
+        scratchpad.set(self.data, data);
+    }
+
+    fn inputs(&self) -> Vec<BufferRef> { vec![self.data, self.select] }
+    fn outputs(&self) -> Vec<BufferRef> { vec![self.data] }
+    fn can_stream_input(&self, _: BufferRef) -> bool { false }
+    fn can_stream_output(&self, _: BufferRef) -> bool { false }
+    fn allocates(&self) -> bool { false }
+
+    fn display_op(&self, _: bool) -> String {
+        format!("{}[{} > 0]", self.data, self.select)
+    }
+}
+
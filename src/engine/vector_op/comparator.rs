@@ -1,3 +1,5 @@
+use ingest::ordered_float::OrderedFloat;
+
 pub trait Comparator<T> {
     fn cmp(left: T, right: T) -> bool;
     fn cmp_eq(left: T, right: T) -> bool;
@@ -33,6 +35,12 @@ impl Comparator<i64> for CmpLessThan {
     fn is_less_than() -> bool { true }
 }
 
+impl Comparator<OrderedFloat> for CmpLessThan {
+    fn cmp(left: OrderedFloat, right: OrderedFloat) -> bool { left < right }
+    fn cmp_eq(left: OrderedFloat, right: OrderedFloat) -> bool { left <= right }
+    fn is_less_than() -> bool { true }
+}
+
 impl<'a> Comparator<&'a str> for CmpLessThan {
     fn cmp(left: &str, right: &str) -> bool { left < right }
     fn cmp_eq(left: &str, right: &str) -> bool { left <= right }
@@ -67,6 +75,12 @@ impl Comparator<i64> for CmpGreaterThan {
     fn is_less_than() -> bool { false }
 }
 
+impl Comparator<OrderedFloat> for CmpGreaterThan {
+    fn cmp(left: OrderedFloat, right: OrderedFloat) -> bool { left > right }
+    fn cmp_eq(left: OrderedFloat, right: OrderedFloat) -> bool { left >= right }
+    fn is_less_than() -> bool { false }
+}
+
 impl<'a> Comparator<&'a str> for CmpGreaterThan {
     fn cmp(left: &str, right: &str) -> bool { left > right }
     fn cmp_eq(left: &str, right: &str) -> bool { left >= right }
@@ -0,0 +1,71 @@
+use std::marker::PhantomData;
+
+use engine::typed_vec::{AnyVec, ConstType, GenericVec};
+use engine::vector_op::vector_operator::*;
+
+
+/// Outputs `value` once for every element of `input`.
+#[derive(Debug)]
+pub struct ConstantExpand {
+    pub value: u8,
+    pub input: BufferRef,
+    pub output: BufferRef,
+}
+
+impl<'a> VecOperator<'a> for ConstantExpand {
+    fn execute(&mut self, stream: bool, scratchpad: &mut Scratchpad<'a>) {
+        let len = scratchpad.get_any(self.input).len();
+        let mut output = scratchpad.get_mut::<u8>(self.output);
+        if stream { output.clear() }
+        let new_len = output.len() + len;
+        output.resize(new_len, self.value);
+    }
+
+    fn init(&mut self, _: usize, batch_size: usize, scratchpad: &mut Scratchpad<'a>) {
+        scratchpad.set(self.output, AnyVec::owned(Vec::<u8>::with_capacity(batch_size)));
+    }
+
+    fn inputs(&self) -> Vec<BufferRef> { vec![self.input] }
+    fn outputs(&self) -> Vec<BufferRef> { vec![self.output] }
+    fn can_stream_input(&self, _: BufferRef) -> bool { true }
+    fn can_stream_output(&self, _: BufferRef) -> bool { true }
+    fn allocates(&self) -> bool { true }
+
+    fn display_op(&self, _: bool) -> String {
+        format!("[{}; len({})]", self.value, self.input)
+    }
+}
+
+/// Outputs the scalar `value` once for every element of `input`.
+#[derive(Debug)]
+pub struct ExpandScalar<T> {
+    pub value: BufferRef,
+    pub input: BufferRef,
+    pub output: BufferRef,
+    pub t: PhantomData<T>,
+}
+
+impl<'a, T: GenericVec<T> + ConstType<T> + 'a> VecOperator<'a> for ExpandScalar<T> {
+    fn execute(&mut self, stream: bool, scratchpad: &mut Scratchpad<'a>) {
+        let value = scratchpad.get_const::<T>(self.value);
+        let len = scratchpad.get_any(self.input).len();
+        let mut output = scratchpad.get_mut::<T>(self.output);
+        if stream { output.clear() }
+        let new_len = output.len() + len;
+        output.resize(new_len, value);
+    }
+
+    fn init(&mut self, _: usize, batch_size: usize, scratchpad: &mut Scratchpad<'a>) {
+        scratchpad.set(self.output, AnyVec::owned(Vec::<T>::with_capacity(batch_size)));
+    }
+
+    fn inputs(&self) -> Vec<BufferRef> { vec![self.value, self.input] }
+    fn outputs(&self) -> Vec<BufferRef> { vec![self.output] }
+    fn can_stream_input(&self, _: BufferRef) -> bool { true }
+    fn can_stream_output(&self, _: BufferRef) -> bool { true }
+    fn allocates(&self) -> bool { true }
+
+    fn display_op(&self, _: bool) -> String {
+        format!("[{}; len({})]", self.value, self.input)
+    }
+}
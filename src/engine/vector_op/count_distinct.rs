@@ -0,0 +1,182 @@
+use std::collections::HashSet;
+use std::hash::Hash;
+use std::marker::PhantomData;
+use std::mem;
+
+use engine::vector_op::*;
+use engine::*;
+use ingest::raw_val::RawVal;
+
+
+/// Collects the set of distinct values of each group. Rows for which `not_null` is 0 are skipped.
+#[derive(Debug)]
+pub struct VecDistinct<T: GenericVec<T> + Hash, U> {
+    input: BufferRef,
+    grouping: BufferRef,
+    not_null: Option<BufferRef>,
+    output: BufferRef,
+    max_index: BufferRef,
+    sets: Vec<HashSet<T>>,
+    u: PhantomData<U>,
+}
+
+impl<T, U> VecDistinct<T, U> where
+    T: GenericVec<T> + Hash, U: GenericIntVec<U> {
+    pub fn boxed<'a>(input: BufferRef, grouping: BufferRef, not_null: Option<BufferRef>, output: BufferRef, max_index: BufferRef) -> BoxedOperator<'a>
+        where T: 'a {
+        Box::new(VecDistinct::<T, U> {
+            input,
+            grouping,
+            not_null,
+            output,
+            max_index,
+            sets: Vec::new(),
+            u: PhantomData,
+        })
+    }
+}
+
+impl<'a, T: 'a, U> VecOperator<'a> for VecDistinct<T, U> where
+    T: GenericVec<T> + Hash, U: GenericIntVec<U> {
+    fn execute(&mut self, _: bool, scratchpad: &mut Scratchpad<'a>) {
+        let input = scratchpad.get::<T>(self.input);
+        let grouping = scratchpad.get::<U>(self.grouping);
+        let not_null = self.not_null.map(|not_null| scratchpad.get::<u8>(not_null));
+
+        let len = scratchpad.get_const::<i64>(self.max_index) as usize + 1;
+        if len > self.sets.len() {
+            self.sets.resize(len, HashSet::new());
+        }
+
+        for (i, (g, &value)) in grouping.iter().zip(input.iter()).enumerate() {
+            if not_null.as_ref().map_or(true, |not_null| not_null[i] > 0) {
+                self.sets[g.cast_usize()].insert(value);
+            }
+        }
+    }
+
+    fn finalize(&mut self, scratchpad: &mut Scratchpad<'a>) {
+        let sets = mem::replace(&mut self.sets, Vec::new()).into_iter()
+            .map(|set| set.into_iter().map(T::wrap_one).collect::<ValueSet>())
+            .collect::<Vec<_>>();
+        scratchpad.set(self.output, Box::new(sets));
+    }
+
+    fn init(&mut self, _: usize, _: usize, scratchpad: &mut Scratchpad<'a>) {
+        scratchpad.set(self.output, Box::new(Vec::<ValueSet>::new()));
+    }
+
+    fn inputs(&self) -> Vec<BufferRef> {
+        let mut inputs = vec![self.grouping, self.input, self.max_index];
+        inputs.extend(self.not_null);
+        inputs
+    }
+    fn outputs(&self) -> Vec<BufferRef> { vec![self.output] }
+    fn can_stream_input(&self, _: BufferRef) -> bool { true }
+    fn can_stream_output(&self, _: BufferRef) -> bool { false }
+    fn allocates(&self) -> bool { true }
+
+    fn display_op(&self, _: bool) -> String {
+        format!("{}[{}] |= {{{}}}", self.output, self.grouping, self.input)
+    }
+    fn display_output(&self) -> bool { false }
+}
+
+/// Collects the set of distinct values of each group for dictionary encoded `input`.
+/// The dictionary indices of each group are recorded in a bitmap, which is translated into the `dictionary` entries it
+/// references once all rows have been processed.
+#[derive(Debug)]
+pub struct VecDistinctDictionary<T, U> {
+    input: BufferRef,
+    grouping: BufferRef,
+    not_null: Option<BufferRef>,
+    dictionary: BufferRef,
+    output: BufferRef,
+    max_index: BufferRef,
+    bitmaps: Vec<Vec<u64>>,
+    t: PhantomData<T>,
+    u: PhantomData<U>,
+}
+
+impl<T, U> VecDistinctDictionary<T, U> where
+    T: GenericIntVec<T>, U: GenericIntVec<U> {
+    pub fn boxed<'a>(input: BufferRef, grouping: BufferRef, not_null: Option<BufferRef>, dictionary: BufferRef, output: BufferRef, max_index: BufferRef) -> BoxedOperator<'a> {
+        Box::new(VecDistinctDictionary::<T, U> {
+            input,
+            grouping,
+            not_null,
+            dictionary,
+            output,
+            max_index,
+            bitmaps: Vec::new(),
+            t: PhantomData,
+            u: PhantomData,
+        })
+    }
+}
+
+impl<'a, T, U> VecOperator<'a> for VecDistinctDictionary<T, U> where
+    T: GenericIntVec<T>, U: GenericIntVec<U> {
+    fn execute(&mut self, _: bool, scratchpad: &mut Scratchpad<'a>) {
+        let input = scratchpad.get::<T>(self.input);
+        let grouping = scratchpad.get::<U>(self.grouping);
+        let not_null = self.not_null.map(|not_null| scratchpad.get::<u8>(not_null));
+
+        let len = scratchpad.get_const::<i64>(self.max_index) as usize + 1;
+        if len > self.bitmaps.len() {
+            self.bitmaps.resize(len, Vec::new());
+        }
+
+        for (i, (g, index)) in grouping.iter().zip(input.iter()).enumerate() {
+            if not_null.as_ref().map_or(true, |not_null| not_null[i] > 0) {
+                let bitmap = &mut self.bitmaps[g.cast_usize()];
+                let index = index.cast_usize();
+                if index / 64 >= bitmap.len() {
+                    bitmap.resize(index / 64 + 1, 0);
+                }
+                bitmap[index / 64] |= 1 << (index % 64);
+            }
+        }
+    }
+
+    fn finalize(&mut self, scratchpad: &mut Scratchpad<'a>) {
+        let sets = {
+            let dictionary = scratchpad.get::<&str>(self.dictionary);
+            self.bitmaps.iter()
+                .map(|bitmap| {
+                    let mut set = ValueSet::new();
+                    for (i, &word) in bitmap.iter().enumerate() {
+                        let mut bits = word;
+                        while bits != 0 {
+                            let index = i * 64 + bits.trailing_zeros() as usize;
+                            set.insert(RawVal::Str(dictionary[index].to_string()));
+                            bits &= bits - 1;
+                        }
+                    }
+                    set
+                })
+                .collect::<Vec<_>>()
+        };
+        self.bitmaps.clear();
+        scratchpad.set(self.output, Box::new(sets));
+    }
+
+    fn init(&mut self, _: usize, _: usize, scratchpad: &mut Scratchpad<'a>) {
+        scratchpad.set(self.output, Box::new(Vec::<ValueSet>::new()));
+    }
+
+    fn inputs(&self) -> Vec<BufferRef> {
+        let mut inputs = vec![self.grouping, self.input, self.max_index, self.dictionary];
+        inputs.extend(self.not_null);
+        inputs
+    }
+    fn outputs(&self) -> Vec<BufferRef> { vec![self.output] }
+    fn can_stream_input(&self, i: BufferRef) -> bool { i != self.dictionary }
+    fn can_stream_output(&self, _: BufferRef) -> bool { false }
+    fn allocates(&self) -> bool { true }
+
+    fn display_op(&self, _: bool) -> String {
+        format!("{}[{}] |= {{{}[{}]}}", self.output, self.grouping, self.dictionary, self.input)
+    }
+    fn display_output(&self) -> bool { false }
+}
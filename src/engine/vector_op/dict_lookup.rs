@@ -1,3 +1,4 @@
+use std::collections::HashSet;
 use std::marker::PhantomData;
 use std::mem;
 use std::str;
@@ -92,3 +93,121 @@ impl<'a> VecOperator<'a> for InverseDictLookup {
         format!("inverse_dict_lookup({}, {}, {})", self.dict_indices, self.dict_data, self.constant)
     }
 }
+
+/// Determines which dictionary entries are contained in `constants`.
+/// The output has one element per dictionary entry which is 1 if the entry is contained in `constants` and 0 otherwise.
+#[derive(Debug)]
+pub struct InverseDictLookupSet {
+    pub dict_indices: BufferRef,
+    pub dict_data: BufferRef,
+    pub constants: HashSet<String>,
+    pub output: BufferRef,
+}
+
+impl<'a> VecOperator<'a> for InverseDictLookupSet {
+    fn execute(&mut self, _: bool, scratchpad: &mut Scratchpad<'a>) {
+        let result = {
+            let dict_indices = scratchpad.get::<u64>(self.dict_indices);
+            let dict_data = scratchpad.get::<u8>(self.dict_data);
+            dict_indices.iter()
+                .map(|offset_len| {
+                    let offset = (offset_len >> 24) as usize;
+                    let len = (offset_len & 0xffffff) as usize;
+                    let entry = unsafe {
+                        str::from_utf8_unchecked(&dict_data[offset..(offset + len)])
+                    };
+                    self.constants.contains(entry) as u8
+                })
+                .collect::<Vec<u8>>()
+        };
+        scratchpad.set(self.output, AnyVec::owned(result));
+    }
+
+    fn inputs(&self) -> Vec<BufferRef> { vec![self.dict_indices, self.dict_data] }
+    fn outputs(&self) -> Vec<BufferRef> { vec![self.output] }
+    fn can_stream_input(&self, _: BufferRef) -> bool { false }
+    fn can_stream_output(&self, _: BufferRef) -> bool { false }
+    fn allocates(&self) -> bool { true }
+
+    fn display_op(&self, _: bool) -> String {
+        let mut constants = self.constants.iter().map(|c| format!("{:?}", c)).collect::<Vec<_>>();
+        constants.sort();
+        format!("inverse_dict_lookup({}, {}, {{{}}})", self.dict_indices, self.dict_data, constants.join(", "))
+    }
+}
+
+/// Looks up each of the `indices` in a `table` with one entry per dictionary entry.
+/// Used to apply the results of functions that are evaluated once per dictionary entry, e.g. the mask produced by
+/// `InverseDictLookupSet`.
+#[derive(Debug)]
+pub struct TableLookup<T, U> {
+    pub indices: BufferRef,
+    pub table: BufferRef,
+    pub output: BufferRef,
+    pub t: PhantomData<(T, U)>,
+}
+
+impl<'a, T: GenericIntVec<T>, U: GenericVec<U> + 'a> VecOperator<'a> for TableLookup<T, U> {
+    fn execute(&mut self, stream: bool, scratchpad: &mut Scratchpad<'a>) {
+        let indices = scratchpad.get::<T>(self.indices);
+        let table = scratchpad.get::<U>(self.table);
+        let mut output = scratchpad.get_mut::<U>(self.output);
+        if stream { output.clear(); }
+        for i in indices.iter() {
+            output.push(table[i.cast_usize()]);
+        }
+    }
+
+    fn init(&mut self, _: usize, batch_size: usize, scratchpad: &mut Scratchpad<'a>) {
+        scratchpad.set(self.output, AnyVec::owned(Vec::<U>::with_capacity(batch_size)));
+    }
+
+    fn inputs(&self) -> Vec<BufferRef> { vec![self.indices, self.table] }
+    fn outputs(&self) -> Vec<BufferRef> { vec![self.output] }
+    fn can_stream_input(&self, buffer: BufferRef) -> bool { buffer == self.indices }
+    fn can_stream_output(&self, _: BufferRef) -> bool { true }
+    fn allocates(&self) -> bool { true }
+
+    fn display_op(&self, _: bool) -> String {
+        format!("{}[{}]", self.table, self.indices)
+    }
+}
+
+/// Unpacks all dictionary entries into a vector of strings.
+#[derive(Debug)]
+pub struct DictionaryEntries {
+    pub dict_indices: BufferRef,
+    pub dict_data: BufferRef,
+    pub output: BufferRef,
+}
+
+impl<'a> VecOperator<'a> for DictionaryEntries {
+    fn execute(&mut self, _: bool, scratchpad: &mut Scratchpad<'a>) {
+        let entries = {
+            let dict_indices = scratchpad.get::<u64>(self.dict_indices);
+            let dict_data = scratchpad.get::<u8>(self.dict_data);
+            dict_indices.iter()
+                .map(|offset_len| {
+                    let offset = (offset_len >> 24) as usize;
+                    let len = (offset_len & 0xffffff) as usize;
+                    // TODO(clemens): eliminate transmute?
+                    let entry: &'a str = unsafe {
+                        mem::transmute(str::from_utf8_unchecked(&dict_data[offset..(offset + len)]))
+                    };
+                    entry
+                })
+                .collect::<Vec<&'a str>>()
+        };
+        scratchpad.set(self.output, Box::new(entries));
+    }
+
+    fn inputs(&self) -> Vec<BufferRef> { vec![self.dict_indices, self.dict_data] }
+    fn outputs(&self) -> Vec<BufferRef> { vec![self.output] }
+    fn can_stream_input(&self, _: BufferRef) -> bool { false }
+    fn can_stream_output(&self, _: BufferRef) -> bool { false }
+    fn allocates(&self) -> bool { true }
+
+    fn display_op(&self, _: bool) -> String {
+        format!("dictionary_entries({}, {})", self.dict_indices, self.dict_data)
+    }
+}
@@ -14,8 +14,13 @@ impl<'a> VecOperator<'a> for DivideVS {
         if stream { output.clear(); }
         let data = scratchpad.get::<i64>(self.lhs);
         let c = scratchpad.get_const::<i64>(self.rhs);
-        for d in data.iter() {
-            output.push(d / c);
+        // All rows are NULL when dividing by zero (see `QueryPlan::null_maps`), so their value is irrelevant
+        if c == 0 {
+            output.extend(data.iter().map(|_| 0));
+        } else {
+            for d in data.iter() {
+                output.push(d.wrapping_div(c));
+            }
         }
     }
 
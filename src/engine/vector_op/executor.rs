@@ -9,6 +9,7 @@ use engine::vector_op::*;
 pub struct QueryExecutor<'a> {
     ops: Vec<Box<VecOperator<'a> + 'a>>,
     ops_cache: HashMap<[u8; 16], BufferRef>,
+    outputs_cache: HashMap<[u8; 16], Vec<BufferRef>>,
     stages: Vec<ExecutorStage>,
     encoded_group_by: Option<BufferRef>,
     count: usize,
@@ -22,6 +23,15 @@ struct ExecutorStage {
     stream: bool,
 }
 
+/// Operators assigned to a stage under construction, together with all of their direct and indirect producers and
+/// consumers.
+#[derive(Default)]
+struct StageMembers {
+    ops: HashSet<usize>,
+    ancestors: HashSet<usize>,
+    descendants: HashSet<usize>,
+}
+
 impl<'a> QueryExecutor<'a> {
     pub fn named_buffer(&mut self, name: &'static str) -> BufferRef {
         let buffer = BufferRef(self.count, name);
@@ -60,6 +70,15 @@ impl<'a> QueryExecutor<'a> {
         self.ops_cache.insert(signature, self.last_buffer);
     }
 
+    /// Output buffers of a previously added operator with multiple outputs.
+    pub fn get_outputs(&self, signature: &[u8; 16]) -> Option<Vec<BufferRef>> {
+        self.outputs_cache.get(signature).cloned()
+    }
+
+    pub fn cache_outputs(&mut self, signature: [u8; 16], outputs: Vec<BufferRef>) {
+        self.outputs_cache.insert(signature, outputs);
+    }
+
     pub fn run(&mut self, len: usize, scratchpad: &mut Scratchpad<'a>, show: bool) {
         for stage in 0..self.stages.len() {
             self.run_stage(len, stage, scratchpad, show);
@@ -95,15 +114,49 @@ impl<'a> QueryExecutor<'a> {
             }
         }
 
+        // Determine all direct and indirect producers of each operator (producers are always added before consumers)
+        let mut ancestors = vec![HashSet::new(); self.ops.len()];
+        for (i, op) in self.ops.iter().enumerate() {
+            let mut op_ancestors = HashSet::new();
+            for input in op.inputs() {
+                for &p in &producers[input.0] {
+                    op_ancestors.insert(p);
+                    op_ancestors.extend(ancestors[p].iter().cloned());
+                }
+            }
+            ancestors[i] = op_ancestors;
+        }
+        let mut descendants = vec![HashSet::new(); self.ops.len()];
+        for (i, op_ancestors) in ancestors.iter().enumerate() {
+            for &p in op_ancestors {
+                descendants[p].insert(i);
+            }
+        }
+        // Adding an operator to a stage is not possible if it would make the stage depend on itself via some operator
+        // outside of the stage, e.g. when a stage both produces the input and consumes the output of a nonstreaming
+        // operator. The stage is acyclic before adding `op`, so any new cycle has to pass through `op`.
+        let creates_cycle = |op: usize, stage: &StageMembers| {
+            let outside = |other: &&usize| **other != op && !stage.ops.contains(*other);
+            ancestors[op].iter().filter(outside).any(|other| stage.descendants.contains(other))
+                || descendants[op].iter().filter(outside).any(|other| stage.ancestors.contains(other))
+        };
+        let add_to_stage = |op: usize, stage: &mut StageMembers| {
+            stage.ops.insert(op);
+            stage.ancestors.extend(ancestors[op].iter().cloned());
+            stage.descendants.extend(descendants[op].iter().cloned());
+        };
+
         // Group operators into stages
         let mut visited = vec![false; self.ops.len()];
         let mut stages = vec![];
         loop {
             // Find an op that hasn't been assigned to a stage yet
             let mut to_visit = vec![];
+            let mut members = StageMembers::default();
             for i in 0..self.ops.len() {
                 if !visited[i] {
                     to_visit.push(i as usize);
+                    add_to_stage(i, &mut members);
                     visited[i] = true;
                     break;
                 }
@@ -122,8 +175,9 @@ impl<'a> QueryExecutor<'a> {
                         for &p in &producers[input.0] {
                             let can_stream =
                                 self.ops[p].can_stream_output(input) && !streaming_disabled[p];
-                            if !visited[p] && can_stream {
+                            if !visited[p] && can_stream && !creates_cycle(p, &members) {
                                 to_visit.push(p);
+                                add_to_stage(p, &mut members);
                                 visited[p] = true;
                                 stream = stream || self.ops[p].allocates();
                             }
@@ -134,8 +188,10 @@ impl<'a> QueryExecutor<'a> {
                 for output in op.outputs() {
                     if op.can_stream_output(output) && !streaming_disabled[current] {
                         for &consumer in &consumers[output.0] {
-                            if !visited[consumer] && self.ops[consumer].can_stream_input(output) {
+                            if !visited[consumer] && self.ops[consumer].can_stream_input(output)
+                                && !creates_cycle(consumer, &members) {
                                 to_visit.push(consumer);
+                                add_to_stage(consumer, &mut members);
                                 visited[consumer] = true;
                                 stream = stream || self.ops[consumer].allocates();
                             }
@@ -152,15 +208,20 @@ impl<'a> QueryExecutor<'a> {
                 for output in self.ops[op].outputs() {
                     if self.ops[op].can_stream_output(output) {
                         for &consumer in &consumers[output.0] {
-                            streaming_consumers |= self.ops[consumer].can_stream_input(output);
-                            block_consumers |= !self.ops[consumer].can_stream_input(output);
+                            // Consumers in other stages require the full output
+                            let streamed = members.ops.contains(&consumer) && self.ops[consumer].can_stream_input(output);
+                            streaming_consumers |= streamed;
+                            block_consumers |= !streamed;
                         }
                     }
                 }
                 (op, streaming_consumers && !block_consumers)
             }).collect();
             // TODO(clemens): Make streaming possible for stages reading from temp results
-            stages.push(ExecutorStage { ops, stream: stream && has_streaming_producer })
+            let reads_temp_results = members.ops.iter().any(|&op|
+                self.ops[op].inputs().into_iter().any(|input|
+                    self.ops[op].can_stream_input(input) && producers[input.0].iter().any(|p| !members.ops.contains(p))));
+            stages.push(ExecutorStage { ops, stream: stream && has_streaming_producer && !reads_temp_results })
         }
 
         // TODO(clemens): need some kind of "anti-dependency" or "consume" marker to enforce ordering of e.g. NonzeroCompact
@@ -272,6 +333,7 @@ impl<'a> Default for QueryExecutor<'a> {
         QueryExecutor {
             ops: vec![],
             ops_cache: HashMap::default(),
+            outputs_cache: HashMap::default(),
             stages: vec![],
             encoded_group_by: None,
             count: 0,
@@ -306,3 +368,45 @@ impl<'a> fmt::Display for QueryExecutor<'a> {
     }
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug)]
+    struct MockOperator {
+        inputs: Vec<(BufferRef, bool)>,
+        outputs: Vec<(BufferRef, bool)>,
+    }
+
+    impl<'a> VecOperator<'a> for MockOperator {
+        fn execute(&mut self, _: bool, _: &mut Scratchpad<'a>) {}
+        fn inputs(&self) -> Vec<BufferRef> { self.inputs.iter().map(|&(b, _)| b).collect() }
+        fn outputs(&self) -> Vec<BufferRef> { self.outputs.iter().map(|&(b, _)| b).collect() }
+        fn can_stream_input(&self, i: BufferRef) -> bool { self.inputs.iter().any(|&(b, s)| b.0 == i.0 && s) }
+        fn can_stream_output(&self, i: BufferRef) -> bool { self.outputs.iter().any(|&(b, s)| b.0 == i.0 && s) }
+        fn allocates(&self) -> bool { true }
+        fn is_streaming_producer(&self) -> bool { self.inputs.is_empty() }
+        fn display_op(&self, _: bool) -> String { "mock".to_string() }
+    }
+
+    #[test]
+    fn test_partition_avoids_cycles() {
+        let mut executor = QueryExecutor::default();
+        let a = executor.named_buffer("a");
+        let b = executor.named_buffer("b");
+        let c = executor.named_buffer("c");
+        let d = executor.named_buffer("d");
+        // Streaming producer with two outputs
+        executor.push(Box::new(MockOperator { inputs: vec![], outputs: vec![(a, true), (b, true)] }));
+        // Requires all of `a` and produces `c` only once finished
+        executor.push(Box::new(MockOperator { inputs: vec![(a, false)], outputs: vec![(c, false)] }));
+        // Could stream `b` from the producer, but has to wait for `c`
+        executor.push(Box::new(MockOperator { inputs: vec![(b, true), (c, true)], outputs: vec![(d, true)] }));
+
+        let stages = executor.partition().iter()
+            .map(|stage| stage.ops.iter().map(|&(op, _)| op).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        assert_eq!(stages, vec![vec![0], vec![1], vec![2]]);
+    }
+}
@@ -0,0 +1,42 @@
+use std::marker::PhantomData;
+
+use engine::*;
+use engine::vector_op::vector_operator::*;
+
+
+/// Replaces all elements of `input` for which `not_null` is 0 with the constant `fill`.
+#[derive(Debug)]
+pub struct FillNulls<T> {
+    pub input: BufferRef,
+    pub not_null: BufferRef,
+    pub fill: BufferRef,
+    pub output: BufferRef,
+    pub t: PhantomData<T>,
+}
+
+impl<'a, T: 'a> VecOperator<'a> for FillNulls<T> where T: GenericVec<T> + ConstType<T> {
+    fn execute(&mut self, stream: bool, scratchpad: &mut Scratchpad<'a>) {
+        let data = scratchpad.get::<T>(self.input);
+        let not_null = scratchpad.get::<u8>(self.not_null);
+        let fill = scratchpad.get_const::<T>(self.fill);
+        let mut output = scratchpad.get_mut::<T>(self.output);
+        if stream { output.clear(); }
+        for (&d, &not_null) in data.iter().zip(not_null.iter()) {
+            output.push(if not_null > 0 { d } else { fill });
+        }
+    }
+
+    fn init(&mut self, _: usize, batch_size: usize, scratchpad: &mut Scratchpad<'a>) {
+        scratchpad.set(self.output, AnyVec::owned(Vec::<T>::with_capacity(batch_size)));
+    }
+
+    fn inputs(&self) -> Vec<BufferRef> { vec![self.input, self.not_null, self.fill] }
+    fn outputs(&self) -> Vec<BufferRef> { vec![self.output] }
+    fn can_stream_input(&self, _: BufferRef) -> bool { true }
+    fn can_stream_output(&self, _: BufferRef) -> bool { true }
+    fn allocates(&self) -> bool { true }
+
+    fn display_op(&self, _: bool) -> String {
+        format!("{}[{}] ?? {}", self.input, self.not_null, self.fill)
+    }
+}
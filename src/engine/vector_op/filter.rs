@@ -43,3 +43,31 @@ impl<'a, T: 'a> VecOperator<'a> for Filter<T> where T: GenericVec<T> {
     }
 }
 
+
+/// `Filter` for columns that only contain NULL values, which are represented by their length.
+#[derive(Debug)]
+pub struct FilterNull {
+    pub filter: BufferRef,
+    pub output: BufferRef,
+}
+
+impl<'a> VecOperator<'a> for FilterNull {
+    fn execute(&mut self, _: bool, scratchpad: &mut Scratchpad<'a>) {
+        let count = scratchpad.get::<u8>(self.filter).iter().filter(|&&select| select > 0).count();
+        scratchpad.set(self.output, Box::new(count));
+    }
+
+    fn init(&mut self, _: usize, _: usize, scratchpad: &mut Scratchpad<'a>) {
+        scratchpad.set(self.output, Box::new(0usize));
+    }
+
+    fn inputs(&self) -> Vec<BufferRef> { vec![self.filter] }
+    fn outputs(&self) -> Vec<BufferRef> { vec![self.output] }
+    fn can_stream_input(&self, _: BufferRef) -> bool { true }
+    fn can_stream_output(&self, _: BufferRef) -> bool { true }
+    fn allocates(&self) -> bool { true }
+
+    fn display_op(&self, _: bool) -> String {
+        format!("null[{}]", self.filter)
+    }
+}
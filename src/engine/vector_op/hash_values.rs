@@ -0,0 +1,92 @@
+use std::hash::Hasher;
+use std::marker::PhantomData;
+
+use seahash::SeaHasher;
+
+use engine::*;
+use engine::vector_op::vector_operator::*;
+use ingest::ordered_float::OrderedFloat;
+
+
+/// Computes the `HashValue` of each element of `input`.
+#[derive(Debug)]
+pub struct HashValues<T> {
+    pub input: BufferRef,
+    pub output: BufferRef,
+    pub t: PhantomData<T>,
+}
+
+impl<'a, T: 'a> VecOperator<'a> for HashValues<T> where T: GenericVec<T> + HashValue {
+    fn execute(&mut self, stream: bool, scratchpad: &mut Scratchpad<'a>) {
+        let data = scratchpad.get::<T>(self.input);
+        let mut output = scratchpad.get_mut::<u64>(self.output);
+        if stream { output.clear(); }
+        for x in data.iter() {
+            output.push(x.hash_value());
+        }
+    }
+
+    fn init(&mut self, _: usize, batch_size: usize, scratchpad: &mut Scratchpad<'a>) {
+        scratchpad.set(self.output, AnyVec::owned(Vec::<u64>::with_capacity(batch_size)));
+    }
+
+    fn inputs(&self) -> Vec<BufferRef> { vec![self.input] }
+    fn outputs(&self) -> Vec<BufferRef> { vec![self.output] }
+    fn can_stream_input(&self, _: BufferRef) -> bool { true }
+    fn can_stream_output(&self, _: BufferRef) -> bool { true }
+    fn allocates(&self) -> bool { true }
+
+    fn display_op(&self, _: bool) -> String {
+        format!("hash({})", self.input)
+    }
+}
+
+/// Hash that is consistent across partitions and encodings: integers hash the same as their `i64` representation,
+/// and floats with integer values hash the same as the corresponding integer.
+pub trait HashValue {
+    fn hash_value(&self) -> u64;
+}
+
+fn hash_i64(value: i64) -> u64 {
+    let mut hasher = SeaHasher::new();
+    hasher.write_u64(value as u64);
+    hasher.finish()
+}
+
+impl HashValue for u8 {
+    fn hash_value(&self) -> u64 { hash_i64(i64::from(*self)) }
+}
+
+impl HashValue for u16 {
+    fn hash_value(&self) -> u64 { hash_i64(i64::from(*self)) }
+}
+
+impl HashValue for u32 {
+    fn hash_value(&self) -> u64 { hash_i64(i64::from(*self)) }
+}
+
+impl HashValue for i64 {
+    fn hash_value(&self) -> u64 { hash_i64(*self) }
+}
+
+impl HashValue for OrderedFloat {
+    fn hash_value(&self) -> u64 {
+        let value = self.0;
+        if value.fract() == 0.0 && value.abs() < 9.2e18 {
+            hash_i64(value as i64)
+        } else {
+            let mut hasher = SeaHasher::new();
+            hasher.write_u8(1);
+            hasher.write_u64(value.to_bits());
+            hasher.finish()
+        }
+    }
+}
+
+impl<'a> HashValue for &'a str {
+    fn hash_value(&self) -> u64 {
+        let mut hasher = SeaHasher::new();
+        hasher.write(self.as_bytes());
+        hasher.finish()
+    }
+}
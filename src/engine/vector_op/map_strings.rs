@@ -0,0 +1,131 @@
+use engine::string_function::{self, StringFunction};
+use engine::typed_vec::AnyVec;
+use engine::vector_op::vector_operator::*;
+
+
+/// Applies `function` to each row of the `inputs` and dictionary encodes the results.
+/// Outputs the index of each result, and the offset/length pairs and data of a sorted dictionary that contains
+/// each distinct result exactly once.
+#[derive(Debug)]
+pub struct MapStrings {
+    pub inputs: Vec<BufferRef>,
+    pub function: StringFunction,
+    pub indices: BufferRef,
+    pub dict_indices: BufferRef,
+    pub dict_data: BufferRef,
+}
+
+impl<'a> VecOperator<'a> for MapStrings {
+    fn execute(&mut self, _: bool, scratchpad: &mut Scratchpad<'a>) {
+        let (indices, dict_indices, dict_data) = {
+            let inputs = self.inputs.iter().map(|&input| scratchpad.get::<&str>(input)).collect::<Vec<_>>();
+            let len = inputs.iter().map(|input| input.len()).min().unwrap_or(0);
+            let mut args = Vec::with_capacity(inputs.len());
+            let results = (0..len)
+                .map(|i| {
+                    args.clear();
+                    for input in &inputs {
+                        args.push(input[i]);
+                    }
+                    self.function.apply(&args)
+                })
+                .collect::<Vec<_>>();
+
+            let mut dictionary = results.iter().map(|s| s.as_str()).collect::<Vec<_>>();
+            dictionary.sort_unstable();
+            dictionary.dedup();
+            let indices = results.iter()
+                .map(|s| dictionary.binary_search(&s.as_str()).unwrap() as u32)
+                .collect::<Vec<_>>();
+            let mut dict_indices = Vec::with_capacity(dictionary.len());
+            let mut dict_data = Vec::new();
+            for entry in dictionary {
+                dict_indices.push(((dict_data.len() as u64) << 24) | entry.len() as u64);
+                dict_data.extend_from_slice(entry.as_bytes());
+            }
+            (indices, dict_indices, dict_data)
+        };
+        scratchpad.set(self.indices, AnyVec::owned(indices));
+        scratchpad.set(self.dict_indices, AnyVec::owned(dict_indices));
+        scratchpad.set(self.dict_data, AnyVec::owned(dict_data));
+        // Decoded strings reference the dictionary data
+        scratchpad.pin(self.dict_data);
+    }
+
+    fn inputs(&self) -> Vec<BufferRef> { self.inputs.clone() }
+    fn outputs(&self) -> Vec<BufferRef> { vec![self.indices, self.dict_indices, self.dict_data] }
+    fn can_stream_input(&self, _: BufferRef) -> bool { false }
+    fn can_stream_output(&self, _: BufferRef) -> bool { false }
+    fn allocates(&self) -> bool { true }
+
+    fn display_op(&self, _: bool) -> String {
+        let inputs = self.inputs.iter().map(|input| format!("{}", input)).collect::<Vec<_>>();
+        format!("{:?}({})", self.function, inputs.join(", "))
+    }
+}
+
+/// Outputs the offset/length pairs and data of a dictionary with the (sorted, distinct) `entries`.
+#[derive(Debug)]
+pub struct ConstantDictionary {
+    pub entries: Vec<String>,
+    pub dict_indices: BufferRef,
+    pub dict_data: BufferRef,
+}
+
+impl<'a> VecOperator<'a> for ConstantDictionary {
+    fn execute(&mut self, _: bool, _: &mut Scratchpad<'a>) {}
+
+    fn init(&mut self, _: usize, _: usize, scratchpad: &mut Scratchpad<'a>) {
+        let mut dict_indices = Vec::with_capacity(self.entries.len());
+        let mut dict_data = Vec::new();
+        for entry in &self.entries {
+            dict_indices.push(((dict_data.len() as u64) << 24) | entry.len() as u64);
+            dict_data.extend_from_slice(entry.as_bytes());
+        }
+        scratchpad.set(self.dict_indices, AnyVec::owned(dict_indices));
+        scratchpad.set(self.dict_data, AnyVec::owned(dict_data));
+        // Decoded strings reference the dictionary data
+        scratchpad.pin(self.dict_data);
+    }
+
+    fn inputs(&self) -> Vec<BufferRef> { vec![] }
+    fn outputs(&self) -> Vec<BufferRef> { vec![self.dict_indices, self.dict_data] }
+    fn can_stream_input(&self, _: BufferRef) -> bool { false }
+    fn can_stream_output(&self, _: BufferRef) -> bool { true }
+    fn allocates(&self) -> bool { true }
+
+    fn display_op(&self, _: bool) -> String {
+        format!("{:?}", self.entries)
+    }
+}
+
+#[derive(Debug)]
+pub struct StringLength {
+    pub input: BufferRef,
+    pub output: BufferRef,
+}
+
+impl<'a> VecOperator<'a> for StringLength {
+    fn execute(&mut self, stream: bool, scratchpad: &mut Scratchpad<'a>) {
+        let strings = scratchpad.get::<&str>(self.input);
+        let mut lengths = scratchpad.get_mut::<i64>(self.output);
+        if stream { lengths.clear() }
+        for s in strings.iter() {
+            lengths.push(string_function::length(s));
+        }
+    }
+
+    fn init(&mut self, _: usize, batch_size: usize, scratchpad: &mut Scratchpad<'a>) {
+        scratchpad.set(self.output, AnyVec::owned(Vec::<i64>::with_capacity(batch_size)));
+    }
+
+    fn inputs(&self) -> Vec<BufferRef> { vec![self.input] }
+    fn outputs(&self) -> Vec<BufferRef> { vec![self.output] }
+    fn can_stream_input(&self, _: BufferRef) -> bool { true }
+    fn can_stream_output(&self, _: BufferRef) -> bool { true }
+    fn allocates(&self) -> bool { true }
+
+    fn display_op(&self, _: bool) -> String {
+        format!("length({})", self.input)
+    }
+}
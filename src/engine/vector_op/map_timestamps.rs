@@ -1,21 +1,21 @@
-use chrono::{NaiveDateTime, Datelike};
-
+use engine::date_function::DateFunction;
 use engine::vector_op::vector_operator::*;
 
 
 #[derive(Debug)]
-pub struct ToYear {
+pub struct MapTimestamps {
     pub input: BufferRef,
     pub output: BufferRef,
+    pub function: DateFunction,
 }
 
-impl<'a> VecOperator<'a> for ToYear {
+impl<'a> VecOperator<'a> for MapTimestamps {
     fn execute(&mut self, stream: bool, scratchpad: &mut Scratchpad<'a>) {
         let timestamps = scratchpad.get::<i64>(self.input);
-        let mut years = scratchpad.get_mut::<i64>(self.output);
-        if stream { years.clear() }
+        let mut output = scratchpad.get_mut::<i64>(self.output);
+        if stream { output.clear() }
         for ts in timestamps.iter() {
-            years.push(NaiveDateTime::from_timestamp(*ts, 0).year() as i64);
+            output.push(self.function.apply(*ts));
         }
     }
 
@@ -30,7 +30,6 @@ impl<'a> VecOperator<'a> for ToYear {
     fn allocates(&self) -> bool { true }
 
     fn display_op(&self, _: bool) -> String {
-        format!("to_year({})", self.input)
+        format!("{:?}({})", self.function, self.input)
     }
 }
-
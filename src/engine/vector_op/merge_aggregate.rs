@@ -1,25 +1,42 @@
+use std::marker::PhantomData;
+use std::mem;
+
 use engine::aggregator::Aggregator;
 use engine::typed_vec::MergeOp;
 use engine::vector_op::*;
 use engine::*;
+use errors::QueryError;
+use ingest::ordered_float::OrderedFloat;
 
 
 #[derive(Debug)]
-pub struct MergeAggregate {
+pub struct MergeAggregate<T> {
     pub merge_ops: BufferRef,
     pub left: BufferRef,
     pub right: BufferRef,
     pub aggregated: BufferRef,
     pub aggregator: Aggregator,
+    pub combine: fn(T, T) -> T,
+}
+
+impl<'a, T: Aggregate + 'a> MergeAggregate<T> {
+    pub fn boxed(merge_ops: BufferRef,
+                 left: BufferRef,
+                 right: BufferRef,
+                 aggregated: BufferRef,
+                 aggregator: Aggregator) -> Result<BoxedOperator<'a>, QueryError> {
+        let combine = T::combine(aggregator)?;
+        Ok(Box::new(MergeAggregate { merge_ops, left, right, aggregated, aggregator, combine }))
+    }
 }
 
-impl<'a> VecOperator<'a> for MergeAggregate {
+impl<'a, T: Aggregate + 'a> VecOperator<'a> for MergeAggregate<T> {
     fn execute(&mut self, _: bool, scratchpad: &mut Scratchpad<'a>) {
         let aggregated = {
             let ops = scratchpad.get::<MergeOp>(self.merge_ops);
-            let left = scratchpad.get::<i64>(self.left);
-            let right = scratchpad.get::<i64>(self.right);
-            merge_aggregate(&ops, &left, &right, self.aggregator)
+            let left = scratchpad.get::<T>(self.left);
+            let right = scratchpad.get::<T>(self.right);
+            merge_aggregate(&ops, &left, &right, self.combine)
         };
         scratchpad.set(self.aggregated, aggregated);
     }
@@ -35,7 +52,57 @@ impl<'a> VecOperator<'a> for MergeAggregate {
     }
 }
 
-fn merge_aggregate<'a>(ops: &[MergeOp], left: &[i64], right: &[i64], aggregator: Aggregator) -> BoxedVec<'a> {
+/// Merges aggregation states which can't be copied, e.g. sets of distinct values by taking their union.
+#[derive(Debug)]
+pub struct MergeStates<T> {
+    pub merge_ops: BufferRef,
+    pub left: BufferRef,
+    pub right: BufferRef,
+    pub aggregated: BufferRef,
+    pub t: PhantomData<T>,
+}
+
+impl<'a, T: AggregationState> VecOperator<'a> for MergeStates<T> where Vec<T>: AnyVec<'a> {
+    fn execute(&mut self, _: bool, scratchpad: &mut Scratchpad<'a>) {
+        let mut left = scratchpad.collect(self.left);
+        let mut right = scratchpad.collect(self.right);
+        let aggregated = {
+            let ops = scratchpad.get::<MergeOp>(self.merge_ops);
+            merge_states(
+                &ops,
+                mem::replace(T::unwrap_mut(&mut *left), Vec::new()),
+                mem::replace(T::unwrap_mut(&mut *right), Vec::new()))
+        };
+        scratchpad.set(self.aggregated, Box::new(aggregated));
+    }
+
+    fn inputs(&self) -> Vec<BufferRef> { vec![self.left, self.right, self.merge_ops] }
+    fn outputs(&self) -> Vec<BufferRef> { vec![self.aggregated] }
+    fn can_stream_input(&self, _: BufferRef) -> bool { false }
+    fn can_stream_output(&self, _: BufferRef) -> bool { false }
+    fn allocates(&self) -> bool { true }
+
+    fn display_op(&self, _: bool) -> String {
+        format!("merge_states({}; {}, {})", self.merge_ops, self.left, self.right)
+    }
+}
+
+/// Types of aggregation results.
+pub trait Aggregate: GenericVec<Self> {
+    fn combine(aggregator: Aggregator) -> Result<fn(Self, Self) -> Self, QueryError>;
+}
+
+impl Aggregate for i64 {
+    fn combine(aggregator: Aggregator) -> Result<fn(i64, i64) -> i64, QueryError> { aggregator.combine_i64() }
+}
+
+impl Aggregate for OrderedFloat {
+    fn combine(aggregator: Aggregator) -> Result<fn(OrderedFloat, OrderedFloat) -> OrderedFloat, QueryError> {
+        aggregator.combine_f64()
+    }
+}
+
+fn merge_aggregate<'a, T: Aggregate + 'a>(ops: &[MergeOp], left: &[T], right: &[T], combine: fn(T, T) -> T) -> BoxedVec<'a> {
     let mut result = Vec::with_capacity(ops.len());
     let mut i = 0;
     let mut j = 0;
@@ -58,7 +125,7 @@ fn merge_aggregate<'a>(ops: &[MergeOp], left: &[i64], right: &[i64], aggregator:
             MergeOp::MergeRight => {
                 // TODO(clemens): make inlining of aggregator operation possible
                 let last = result.len() - 1;
-                result[last] = aggregator.combine_i64(result[last], right[j]);
+                result[last] = combine(result[last], right[j]);
                 j += 1;
             }
         }
@@ -66,3 +133,19 @@ fn merge_aggregate<'a>(ops: &[MergeOp], left: &[i64], right: &[i64], aggregator:
     AnyVec::owned(result)
 }
 
+fn merge_states<T: AggregationState>(ops: &[MergeOp], left: Vec<T>, right: Vec<T>) -> Vec<T> {
+    let mut result = Vec::with_capacity(ops.len());
+    let mut left = left.into_iter();
+    let mut right = right.into_iter();
+    for op in ops {
+        match *op {
+            MergeOp::TakeLeft => result.push(left.next().unwrap()),
+            MergeOp::TakeRight => result.push(right.next().unwrap()),
+            MergeOp::MergeRight => {
+                let last = result.len() - 1;
+                result[last].merge(right.next().unwrap());
+            }
+        }
+    }
+    result
+}
@@ -0,0 +1,129 @@
+use std::cmp;
+use std::fmt;
+use std::f64;
+use std::marker::PhantomData;
+
+use engine::typed_vec::AnyVec;
+use engine::vector_op::*;
+use engine::*;
+use ingest::ordered_float::OrderedFloat;
+
+
+#[derive(Debug)]
+pub struct VecMinMax<T, U, M> {
+    input: BufferRef,
+    grouping: BufferRef,
+    output: BufferRef,
+    max_index: BufferRef,
+    t: PhantomData<T>,
+    u: PhantomData<U>,
+    m: PhantomData<M>,
+}
+
+impl<T, U, M> VecMinMax<T, U, M> where
+    T: GenericVec<T> + Extremes, U: GenericIntVec<U> + CastUsize, M: MinMax {
+    pub fn boxed<'a>(input: BufferRef, grouping: BufferRef, output: BufferRef, max_index: BufferRef) -> BoxedOperator<'a>
+        where T: 'a, M: 'a {
+        Box::new(VecMinMax::<T, U, M> {
+            input,
+            grouping,
+            output,
+            max_index,
+            t: PhantomData,
+            u: PhantomData,
+            m: PhantomData,
+        })
+    }
+}
+
+impl<'a, T: 'a, U, M> VecOperator<'a> for VecMinMax<T, U, M> where
+    T: GenericVec<T> + Extremes, U: GenericIntVec<U>, M: MinMax {
+    fn execute(&mut self, _: bool, scratchpad: &mut Scratchpad<'a>) {
+        let nums = scratchpad.get::<T>(self.input);
+        let grouping = scratchpad.get::<U>(self.grouping);
+        let mut accumulators = scratchpad.get_mut::<T>(self.output);
+
+        let len = scratchpad.get_const::<i64>(self.max_index) as usize + 1;
+        if len > accumulators.len() {
+            accumulators.resize(len, M::neutral());
+        }
+
+        for (i, n) in grouping.iter().zip(nums.iter()) {
+            let accumulator = &mut accumulators[i.cast_usize()];
+            *accumulator = M::combine(*accumulator, *n);
+        }
+    }
+
+    fn init(&mut self, _: usize, _: usize, scratchpad: &mut Scratchpad<'a>) {
+        scratchpad.set(self.output, AnyVec::owned(Vec::<T>::with_capacity(0)));
+    }
+
+    fn inputs(&self) -> Vec<BufferRef> { vec![self.grouping, self.input, self.max_index] }
+    fn outputs(&self) -> Vec<BufferRef> { vec![self.output] }
+    fn can_stream_input(&self, _: BufferRef) -> bool { true }
+    fn can_stream_output(&self, _: BufferRef) -> bool { false }
+    fn allocates(&self) -> bool { true }
+
+    fn display_op(&self, _: bool) -> String {
+        format!("{}[{}] {}= {}", self.output, self.grouping, M::symbol(), self.input)
+    }
+    fn display_output(&self) -> bool { false }
+}
+
+/// Smallest and largest value of a type, used as the initial value of grouped minima and maxima.
+pub trait Extremes {
+    fn smallest() -> Self;
+    fn largest() -> Self;
+}
+
+impl Extremes for u8 {
+    fn smallest() -> u8 { u8::min_value() }
+    fn largest() -> u8 { u8::max_value() }
+}
+
+impl Extremes for u16 {
+    fn smallest() -> u16 { u16::min_value() }
+    fn largest() -> u16 { u16::max_value() }
+}
+
+impl Extremes for u32 {
+    fn smallest() -> u32 { u32::min_value() }
+    fn largest() -> u32 { u32::max_value() }
+}
+
+impl Extremes for i64 {
+    fn smallest() -> i64 { i64::min_value() }
+    fn largest() -> i64 { i64::max_value() }
+}
+
+impl Extremes for OrderedFloat {
+    fn smallest() -> OrderedFloat { OrderedFloat(f64::NEG_INFINITY) }
+    // NaN is ordered after all other floats
+    fn largest() -> OrderedFloat { OrderedFloat(f64::NAN) }
+}
+
+pub trait MinMax: fmt::Debug {
+    fn neutral<T: Extremes>() -> T;
+    fn combine<T: Ord>(accumulator: T, elem: T) -> T;
+    fn symbol() -> &'static str;
+}
+
+#[derive(Debug)]
+pub struct Minimum;
+
+impl MinMax for Minimum {
+    fn neutral<T: Extremes>() -> T { T::largest() }
+    #[inline]
+    fn combine<T: Ord>(accumulator: T, elem: T) -> T { cmp::min(accumulator, elem) }
+    fn symbol() -> &'static str { "min" }
+}
+
+#[derive(Debug)]
+pub struct Maximum;
+
+impl MinMax for Maximum {
+    fn neutral<T: Extremes>() -> T { T::smallest() }
+    #[inline]
+    fn combine<T: Ord>(accumulator: T, elem: T) -> T { cmp::max(accumulator, elem) }
+    fn symbol() -> &'static str { "max" }
+}
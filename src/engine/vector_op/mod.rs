@@ -3,36 +3,54 @@ pub mod executor;
 pub mod comparator;
 
 mod addition_vs;
+mod aggregate_states;
+mod approx_count_distinct;
 mod bit_unpack;
+mod blend;
 mod bool_op;
+mod boolean_not;
 mod column_ops;
 mod compact;
 mod constant;
+mod constant_expand;
 mod constant_vec;
 mod count;
+mod count_distinct;
 mod delta_decode;
 mod dict_lookup;
 mod division_vs;
 mod encode_const;
 mod exists;
 mod filter;
+mod fill_nulls;
+mod hash_values;
 mod hashmap_grouping;
+mod map_strings;
+mod map_timestamps;
 mod merge;
 mod merge_aggregate;
 mod merge_deduplicate;
 mod merge_drop;
 mod merge_keep;
+mod min_max;
+mod moments;
 mod nonzero_compact;
 mod nonzero_indices;
 mod parameterized_vec_vec_int_op;
+mod percentile;
+mod regex_match;
 mod select;
 mod sort_indices;
 mod sum;
-mod to_year;
 mod top_n;
 mod unpack_strings;
 mod type_conversion;
 mod vec_const_bool_op;
+mod vec_const_float_op;
+mod vec_const_int_op;
+mod vec_vec_bool_op;
+mod vec_vec_float_op;
+mod vec_vec_int_op;
 #[cfg(feature = "enable_lz4")]
 mod lz4_decode;
 pub mod merge_deduplicate_partitioned;
@@ -0,0 +1,85 @@
+use std::marker::PhantomData;
+use std::mem;
+
+use engine::moments::Moments;
+use engine::vector_op::*;
+use engine::*;
+use ingest::ordered_float::OrderedFloat;
+
+
+/// Accumulates the moments of `x` (and `y`, if given) for each group. Rows for which `not_null` is 0 are skipped.
+#[derive(Debug)]
+pub struct VecMoments<U> {
+    x: BufferRef,
+    y: Option<BufferRef>,
+    grouping: BufferRef,
+    not_null: Option<BufferRef>,
+    output: BufferRef,
+    max_index: BufferRef,
+    moments: Vec<Moments>,
+    u: PhantomData<U>,
+}
+
+impl<U: GenericIntVec<U>> VecMoments<U> {
+    pub fn boxed<'a>(x: BufferRef, y: Option<BufferRef>, grouping: BufferRef, not_null: Option<BufferRef>, output: BufferRef, max_index: BufferRef) -> BoxedOperator<'a> {
+        Box::new(VecMoments::<U> {
+            x,
+            y,
+            grouping,
+            not_null,
+            output,
+            max_index,
+            moments: Vec::new(),
+            u: PhantomData,
+        })
+    }
+}
+
+impl<'a, U: GenericIntVec<U>> VecOperator<'a> for VecMoments<U> {
+    fn execute(&mut self, _: bool, scratchpad: &mut Scratchpad<'a>) {
+        let x = scratchpad.get::<OrderedFloat>(self.x);
+        let y = self.y.map(|y| scratchpad.get::<OrderedFloat>(y));
+        let grouping = scratchpad.get::<U>(self.grouping);
+        let not_null = self.not_null.map(|not_null| scratchpad.get::<u8>(not_null));
+
+        let len = scratchpad.get_const::<i64>(self.max_index) as usize + 1;
+        if len > self.moments.len() {
+            self.moments.resize(len, Moments::new());
+        }
+
+        for (i, (g, x)) in grouping.iter().zip(x.iter()).enumerate() {
+            if not_null.as_ref().map_or(true, |not_null| not_null[i] > 0) {
+                let y = y.as_ref().map_or(x.0, |y| y[i].0);
+                self.moments[g.cast_usize()].insert(x.0, y);
+            }
+        }
+    }
+
+    fn finalize(&mut self, scratchpad: &mut Scratchpad<'a>) {
+        let moments = mem::replace(&mut self.moments, Vec::new());
+        scratchpad.set(self.output, Box::new(moments));
+    }
+
+    fn init(&mut self, _: usize, _: usize, scratchpad: &mut Scratchpad<'a>) {
+        scratchpad.set(self.output, Box::new(Vec::<Moments>::new()));
+    }
+
+    fn inputs(&self) -> Vec<BufferRef> {
+        let mut inputs = vec![self.grouping, self.x, self.max_index];
+        inputs.extend(self.y);
+        inputs.extend(self.not_null);
+        inputs
+    }
+    fn outputs(&self) -> Vec<BufferRef> { vec![self.output] }
+    fn can_stream_input(&self, _: BufferRef) -> bool { true }
+    fn can_stream_output(&self, _: BufferRef) -> bool { false }
+    fn allocates(&self) -> bool { true }
+
+    fn display_op(&self, _: bool) -> String {
+        match self.y {
+            Some(y) => format!("{}[{}] |= moments({}, {})", self.output, self.grouping, self.x, y),
+            None => format!("{}[{}] |= moments({})", self.output, self.grouping, self.x),
+        }
+    }
+    fn display_output(&self) -> bool { false }
+}
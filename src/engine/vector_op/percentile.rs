@@ -0,0 +1,80 @@
+use std::marker::PhantomData;
+use std::mem;
+
+use engine::tdigest::TDigest;
+use engine::vector_op::*;
+use engine::*;
+use ingest::ordered_float::OrderedFloat;
+
+
+/// Inserts the values of each group into a t-digest. Rows for which `not_null` is 0 are skipped.
+#[derive(Debug)]
+pub struct VecTDigest<U> {
+    input: BufferRef,
+    grouping: BufferRef,
+    not_null: Option<BufferRef>,
+    output: BufferRef,
+    max_index: BufferRef,
+    digests: Vec<TDigest>,
+    u: PhantomData<U>,
+}
+
+impl<U: GenericIntVec<U>> VecTDigest<U> {
+    pub fn boxed<'a>(input: BufferRef, grouping: BufferRef, not_null: Option<BufferRef>, output: BufferRef, max_index: BufferRef) -> BoxedOperator<'a> {
+        Box::new(VecTDigest::<U> {
+            input,
+            grouping,
+            not_null,
+            output,
+            max_index,
+            digests: Vec::new(),
+            u: PhantomData,
+        })
+    }
+}
+
+impl<'a, U: GenericIntVec<U>> VecOperator<'a> for VecTDigest<U> {
+    fn execute(&mut self, _: bool, scratchpad: &mut Scratchpad<'a>) {
+        let input = scratchpad.get::<OrderedFloat>(self.input);
+        let grouping = scratchpad.get::<U>(self.grouping);
+        let not_null = self.not_null.map(|not_null| scratchpad.get::<u8>(not_null));
+
+        let len = scratchpad.get_const::<i64>(self.max_index) as usize + 1;
+        if len > self.digests.len() {
+            self.digests.resize(len, TDigest::new());
+        }
+
+        for (i, (g, value)) in grouping.iter().zip(input.iter()).enumerate() {
+            if not_null.as_ref().map_or(true, |not_null| not_null[i] > 0) {
+                self.digests[g.cast_usize()].insert(value.0);
+            }
+        }
+    }
+
+    fn finalize(&mut self, scratchpad: &mut Scratchpad<'a>) {
+        let mut digests = mem::replace(&mut self.digests, Vec::new());
+        for digest in &mut digests {
+            digest.compress();
+        }
+        scratchpad.set(self.output, Box::new(digests));
+    }
+
+    fn init(&mut self, _: usize, _: usize, scratchpad: &mut Scratchpad<'a>) {
+        scratchpad.set(self.output, Box::new(Vec::<TDigest>::new()));
+    }
+
+    fn inputs(&self) -> Vec<BufferRef> {
+        let mut inputs = vec![self.grouping, self.input, self.max_index];
+        inputs.extend(self.not_null);
+        inputs
+    }
+    fn outputs(&self) -> Vec<BufferRef> { vec![self.output] }
+    fn can_stream_input(&self, _: BufferRef) -> bool { true }
+    fn can_stream_output(&self, _: BufferRef) -> bool { false }
+    fn allocates(&self) -> bool { true }
+
+    fn display_op(&self, _: bool) -> String {
+        format!("{}[{}] |= tdigest({})", self.output, self.grouping, self.input)
+    }
+    fn display_output(&self) -> bool { false }
+}
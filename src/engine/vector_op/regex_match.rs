@@ -0,0 +1,79 @@
+use std::str;
+
+use regex::Regex;
+
+use engine::typed_vec::AnyVec;
+use engine::vector_op::vector_operator::*;
+
+
+#[derive(Debug)]
+pub struct RegexMatch {
+    pub input: BufferRef,
+    pub regex: Regex,
+    pub output: BufferRef,
+}
+
+impl<'a> VecOperator<'a> for RegexMatch {
+    fn execute(&mut self, stream: bool, scratchpad: &mut Scratchpad<'a>) {
+        let strings = scratchpad.get::<&str>(self.input);
+        let mut matches = scratchpad.get_mut::<u8>(self.output);
+        if stream { matches.clear() }
+        for s in strings.iter() {
+            matches.push(self.regex.is_match(s) as u8);
+        }
+    }
+
+    fn init(&mut self, _: usize, batch_size: usize, scratchpad: &mut Scratchpad<'a>) {
+        scratchpad.set(self.output, AnyVec::owned(Vec::<u8>::with_capacity(batch_size)));
+    }
+
+    fn inputs(&self) -> Vec<BufferRef> { vec![self.input] }
+    fn outputs(&self) -> Vec<BufferRef> { vec![self.output] }
+    fn can_stream_input(&self, _: BufferRef) -> bool { true }
+    fn can_stream_output(&self, _: BufferRef) -> bool { true }
+    fn allocates(&self) -> bool { true }
+
+    fn display_op(&self, _: bool) -> String {
+        format!("regex({}, {:?})", self.input, self.regex.as_str())
+    }
+}
+
+/// Evaluates `regex` once for each dictionary entry.
+/// The output has one element per dictionary entry which is 1 if the entry matches and 0 otherwise.
+#[derive(Debug)]
+pub struct DictRegexMatch {
+    pub dict_indices: BufferRef,
+    pub dict_data: BufferRef,
+    pub regex: Regex,
+    pub output: BufferRef,
+}
+
+impl<'a> VecOperator<'a> for DictRegexMatch {
+    fn execute(&mut self, _: bool, scratchpad: &mut Scratchpad<'a>) {
+        let result = {
+            let dict_indices = scratchpad.get::<u64>(self.dict_indices);
+            let dict_data = scratchpad.get::<u8>(self.dict_data);
+            dict_indices.iter()
+                .map(|offset_len| {
+                    let offset = (offset_len >> 24) as usize;
+                    let len = (offset_len & 0xffffff) as usize;
+                    let entry = unsafe {
+                        str::from_utf8_unchecked(&dict_data[offset..(offset + len)])
+                    };
+                    self.regex.is_match(entry) as u8
+                })
+                .collect::<Vec<u8>>()
+        };
+        scratchpad.set(self.output, AnyVec::owned(result));
+    }
+
+    fn inputs(&self) -> Vec<BufferRef> { vec![self.dict_indices, self.dict_data] }
+    fn outputs(&self) -> Vec<BufferRef> { vec![self.output] }
+    fn can_stream_input(&self, _: BufferRef) -> bool { false }
+    fn can_stream_output(&self, _: BufferRef) -> bool { false }
+    fn allocates(&self) -> bool { true }
+
+    fn display_op(&self, _: bool) -> String {
+        format!("regex({}, {}, {:?})", self.dict_indices, self.dict_data, self.regex.as_str())
+    }
+}
@@ -39,3 +39,57 @@ impl<'a, T: 'a> VecOperator<'a> for Select<T> where T: GenericVec<T> {
     }
 }
 
+/// `Select` for aggregation states, which can't be copied.
+#[derive(Debug)]
+pub struct SelectStates<T> {
+    pub input: BufferRef,
+    pub indices: BufferRef,
+    pub output: BufferRef,
+    pub t: PhantomData<T>,
+}
+
+impl<'a, T: AggregationState> VecOperator<'a> for SelectStates<T> where Vec<T>: AnyVec<'a> {
+    fn execute(&mut self, _: bool, scratchpad: &mut Scratchpad<'a>) {
+        let output = {
+            let data = scratchpad.get_any(self.input);
+            let data = T::unwrap(&*data);
+            let indices = scratchpad.get::<usize>(self.indices);
+            indices.iter().map(|&i| data[i].clone()).collect::<Vec<T>>()
+        };
+        scratchpad.set(self.output, Box::new(output));
+    }
+
+    fn inputs(&self) -> Vec<BufferRef> { vec![self.input, self.indices] }
+    fn outputs(&self) -> Vec<BufferRef> { vec![self.output] }
+    fn can_stream_input(&self, _: BufferRef) -> bool { false }
+    fn can_stream_output(&self, _: BufferRef) -> bool { false }
+    fn allocates(&self) -> bool { true }
+
+    fn display_op(&self, _: bool) -> String {
+        format!("{}[{}]", self.input, self.indices)
+    }
+}
+
+/// `Select` for columns that only contain NULL values, which are represented by their length.
+#[derive(Debug)]
+pub struct SelectNull {
+    pub indices: BufferRef,
+    pub output: BufferRef,
+}
+
+impl<'a> VecOperator<'a> for SelectNull {
+    fn execute(&mut self, _: bool, scratchpad: &mut Scratchpad<'a>) {
+        let count = scratchpad.get::<usize>(self.indices).len();
+        scratchpad.set(self.output, Box::new(count));
+    }
+
+    fn inputs(&self) -> Vec<BufferRef> { vec![self.indices] }
+    fn outputs(&self) -> Vec<BufferRef> { vec![self.output] }
+    fn can_stream_input(&self, _: BufferRef) -> bool { false }
+    fn can_stream_output(&self, _: BufferRef) -> bool { false }
+    fn allocates(&self) -> bool { true }
+
+    fn display_op(&self, _: bool) -> String {
+        format!("null[{}]", self.indices)
+    }
+}
@@ -3,6 +3,7 @@ use std::marker::PhantomData;
 use engine::typed_vec::AnyVec;
 use engine::vector_op::*;
 use engine::*;
+use ingest::ordered_float::OrderedFloat;
 
 
 #[derive(Debug)]
@@ -61,3 +62,56 @@ impl<'a, T, U> VecOperator<'a> for VecSum<T, U> where
     }
     fn display_output(&self) -> bool { false }
 }
+
+#[derive(Debug)]
+pub struct VecFloatSum<U> {
+    input: BufferRef,
+    grouping: BufferRef,
+    output: BufferRef,
+    max_index: BufferRef,
+    u: PhantomData<U>,
+}
+
+impl<U> VecFloatSum<U> where U: GenericIntVec<U> + CastUsize {
+    pub fn boxed<'a>(input: BufferRef, grouping: BufferRef, output: BufferRef, max_index: BufferRef) -> BoxedOperator<'a> {
+        Box::new(VecFloatSum::<U> {
+            input,
+            grouping,
+            output,
+            max_index,
+            u: PhantomData,
+        })
+    }
+}
+
+impl<'a, U> VecOperator<'a> for VecFloatSum<U> where U: GenericIntVec<U> {
+    fn execute(&mut self, _: bool, scratchpad: &mut Scratchpad<'a>) {
+        let nums = scratchpad.get::<OrderedFloat>(self.input);
+        let grouping = scratchpad.get::<U>(self.grouping);
+        let mut sums = scratchpad.get_mut::<OrderedFloat>(self.output);
+
+        let len = scratchpad.get_const::<i64>(self.max_index) as usize + 1;
+        if len > sums.len() {
+            sums.resize(len, OrderedFloat(0.0));
+        }
+
+        for (i, n) in grouping.iter().zip(nums.iter()) {
+            sums[i.cast_usize()].0 += n.0;
+        }
+    }
+
+    fn init(&mut self, _: usize, _: usize, scratchpad: &mut Scratchpad<'a>) {
+        scratchpad.set(self.output, AnyVec::owned(Vec::<OrderedFloat>::with_capacity(0)));
+    }
+
+    fn inputs(&self) -> Vec<BufferRef> { vec![self.grouping, self.input, self.max_index] }
+    fn outputs(&self) -> Vec<BufferRef> { vec![self.output] }
+    fn can_stream_input(&self, _: BufferRef) -> bool { true }
+    fn can_stream_output(&self, _: BufferRef) -> bool { false }
+    fn allocates(&self) -> bool { true }
+
+    fn display_op(&self, _: bool) -> String {
+        format!("{}[{}] += {}", self.output, self.grouping, self.input)
+    }
+    fn display_output(&self) -> bool { false }
+}
@@ -1,5 +1,6 @@
 use std::cell::Ref;
 use std::cmp;
+use std::collections::HashMap;
 use std::fmt;
 use std::marker::PhantomData;
 
@@ -91,6 +92,80 @@ impl<'a, T: GenericVec<T> + 'a, C: Comparator<T> + fmt::Debug> VecOperator<'a> f
     }
 }
 
+/// Like `TopN`, but retains the top `n` rows separately for each distinct value of the (packed) `grouping` key.
+/// Outputs the indices of the retained rows of all groups, ordered by their keys.
+#[derive(Debug)]
+pub struct TopNPerGroup<T, C> {
+    pub input: BufferRef,
+    pub grouping: BufferRef,
+    pub indices: BufferRef,
+    pub n: usize,
+    pub last_index: usize,
+    /// Keys and indices of the rows retained for each group, which form a heap once the group has `n` rows.
+    pub groups: HashMap<i64, (Vec<T>, Vec<usize>)>,
+    pub c: PhantomData<C>,
+}
+
+impl<'a, T: GenericVec<T> + 'a, C: Comparator<T> + fmt::Debug> VecOperator<'a> for TopNPerGroup<T, C> {
+    fn init(&mut self, _: usize, _: usize, scratchpad: &mut Scratchpad<'a>) {
+        scratchpad.set(self.indices, AnyVec::owned(Vec::<usize>::new()));
+    }
+
+    fn execute(&mut self, _: bool, scratchpad: &mut Scratchpad<'a>) {
+        let input = scratchpad.get::<T>(self.input);
+        let grouping = scratchpad.get::<i64>(self.grouping);
+        let n = self.n;
+        for (i, (&key, &group)) in input.iter().zip(grouping.iter()).enumerate() {
+            let index = self.last_index + i;
+            let &mut (ref mut keys, ref mut indices) = self.groups.entry(group)
+                .or_insert_with(|| (Vec::with_capacity(n), Vec::with_capacity(n)));
+            if keys.len() < n {
+                keys.push(key);
+                indices.push(index);
+                if keys.len() == n {
+                    // Sorted keys form a heap with the key that is replaced next at its root
+                    let mut entries = keys.iter().cloned().zip(indices.iter().cloned()).collect::<Vec<_>>();
+                    if C::is_less_than() {
+                        entries.sort_unstable_by(|a, b| b.cmp(a));
+                    } else {
+                        entries.sort_unstable();
+                    }
+                    for (j, (key, index)) in entries.into_iter().enumerate() {
+                        keys[j] = key;
+                        indices[j] = index;
+                    }
+                }
+            } else if C::cmp(key, keys[0]) {
+                heap_replace::<_, C>(keys, indices, key, index, 0);
+            }
+        }
+        self.last_index += input.len();
+    }
+
+    fn finalize(&mut self, scratchpad: &mut Scratchpad<'a>) {
+        let mut entries = self.groups.values()
+            .flat_map(|&(ref keys, ref indices)| keys.iter().cloned().zip(indices.iter().cloned()))
+            .collect::<Vec<_>>();
+        if C::is_less_than() {
+            entries.sort_unstable();
+        } else {
+            entries.sort_unstable_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(&b.1)));
+        }
+        let output = entries.into_iter().map(|(_, index)| index).collect::<Vec<_>>();
+        scratchpad.set(self.indices, AnyVec::owned(output));
+    }
+
+    fn inputs(&self) -> Vec<BufferRef> { vec![self.input, self.grouping] }
+    fn outputs(&self) -> Vec<BufferRef> { vec![self.indices] }
+    fn can_stream_input(&self, _: BufferRef) -> bool { true }
+    fn can_stream_output(&self, _: BufferRef) -> bool { false }
+    fn allocates(&self) -> bool { true }
+
+    fn display_op(&self, _: bool) -> String {
+        format!("top_n_per_group({}, {})", self.input, self.grouping)
+    }
+}
+
 #[inline]
 fn heap_replace<T: PartialOrd + Copy, C: Comparator<T>>(keys: &mut [T], values: &mut [usize], key: T, value: usize, mut node: usize) {
     while 2 * node + 1 < keys.len() {
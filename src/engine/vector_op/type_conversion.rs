@@ -2,6 +2,7 @@ use std::marker::PhantomData;
 
 use engine::*;
 use engine::vector_op::vector_operator::*;
+use ingest::ordered_float::OrderedFloat;
 
 
 #[derive(Debug)]
@@ -77,3 +78,7 @@ impl Cast<i64> for u8 { fn cast(self) -> i64 { i64::from(self) } }
 impl Cast<i64> for u16 { fn cast(self) -> i64 { i64::from(self) } }
 
 impl Cast<i64> for u32 { fn cast(self) -> i64 { i64::from(self) } }
+
+impl Cast<OrderedFloat> for u8 { fn cast(self) -> OrderedFloat { OrderedFloat(f64::from(self)) } }
+
+impl Cast<OrderedFloat> for i64 { fn cast(self) -> OrderedFloat { OrderedFloat(self as f64) } }
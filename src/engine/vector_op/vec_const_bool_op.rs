@@ -69,6 +69,69 @@ impl<T: Into<i64> + Copy> BoolOperation<T, i64> for LessThanInt<T> {
     fn symbol() -> &'static str { "<" }
 }
 
+#[derive(Debug)]
+pub struct LessThan<T> { t: PhantomData<T> }
+
+impl<T: PartialOrd> BoolOperation<T, T> for LessThan<T> {
+    #[inline]
+    fn perform(l: &T, r: &T) -> u8 { (l < r) as u8 }
+    fn symbol() -> &'static str { "<" }
+}
+
+#[derive(Debug)]
+pub struct LessThanEqualsInt<T> { t: PhantomData<T> }
+
+impl<T: Into<i64> + Copy> BoolOperation<T, i64> for LessThanEqualsInt<T> {
+    #[inline]
+    fn perform(l: &T, r: &i64) -> u8 { (Into::<i64>::into(*l) <= *r) as u8 }
+    fn symbol() -> &'static str { "<=" }
+}
+
+#[derive(Debug)]
+pub struct LessThanEquals<T> { t: PhantomData<T> }
+
+impl<T: PartialOrd> BoolOperation<T, T> for LessThanEquals<T> {
+    #[inline]
+    fn perform(l: &T, r: &T) -> u8 { (l <= r) as u8 }
+    fn symbol() -> &'static str { "<=" }
+}
+
+#[derive(Debug)]
+pub struct GreaterThanInt<T> { t: PhantomData<T> }
+
+impl<T: Into<i64> + Copy> BoolOperation<T, i64> for GreaterThanInt<T> {
+    #[inline]
+    fn perform(l: &T, r: &i64) -> u8 { (Into::<i64>::into(*l) > *r) as u8 }
+    fn symbol() -> &'static str { ">" }
+}
+
+#[derive(Debug)]
+pub struct GreaterThan<T> { t: PhantomData<T> }
+
+impl<T: PartialOrd> BoolOperation<T, T> for GreaterThan<T> {
+    #[inline]
+    fn perform(l: &T, r: &T) -> u8 { (l > r) as u8 }
+    fn symbol() -> &'static str { ">" }
+}
+
+#[derive(Debug)]
+pub struct GreaterThanEqualsInt<T> { t: PhantomData<T> }
+
+impl<T: Into<i64> + Copy> BoolOperation<T, i64> for GreaterThanEqualsInt<T> {
+    #[inline]
+    fn perform(l: &T, r: &i64) -> u8 { (Into::<i64>::into(*l) >= *r) as u8 }
+    fn symbol() -> &'static str { ">=" }
+}
+
+#[derive(Debug)]
+pub struct GreaterThanEquals<T> { t: PhantomData<T> }
+
+impl<T: PartialOrd> BoolOperation<T, T> for GreaterThanEquals<T> {
+    #[inline]
+    fn perform(l: &T, r: &T) -> u8 { (l >= r) as u8 }
+    fn symbol() -> &'static str { ">=" }
+}
+
 #[derive(Debug)]
 pub struct Equals<T> { t: PhantomData<T> }
 
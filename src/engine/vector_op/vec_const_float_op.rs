@@ -0,0 +1,135 @@
+use std::fmt;
+use std::marker::PhantomData;
+
+use engine::*;
+use engine::vector_op::vector_operator::*;
+use engine::vector_op::vec_const_int_op::*;
+use ingest::ordered_float::OrderedFloat;
+
+
+#[derive(Debug)]
+pub struct VecConstFloatOperator<Op> {
+    lhs: BufferRef,
+    rhs: BufferRef,
+    output: BufferRef,
+    op: PhantomData<Op>,
+}
+
+impl<Op> VecConstFloatOperator<Op> {
+    pub fn new(lhs: BufferRef, rhs: BufferRef, output: BufferRef) -> VecConstFloatOperator<Op> {
+        VecConstFloatOperator {
+            lhs,
+            rhs,
+            output,
+            op: PhantomData,
+        }
+    }
+}
+
+impl<'a, Op: FloatOperation + fmt::Debug> VecOperator<'a> for VecConstFloatOperator<Op> {
+    fn execute(&mut self, stream: bool, scratchpad: &mut Scratchpad<'a>) {
+        let mut output = scratchpad.get_mut::<OrderedFloat>(self.output);
+        if stream { output.clear(); }
+        let data = scratchpad.get::<OrderedFloat>(self.lhs);
+        let c = scratchpad.get_const::<OrderedFloat>(self.rhs).0;
+        for &d in data.iter() {
+            output.push(OrderedFloat(Op::perform(d.0, c)));
+        }
+    }
+
+    fn init(&mut self, _: usize, batch_size: usize, scratchpad: &mut Scratchpad<'a>) {
+        scratchpad.set(self.output, AnyVec::owned(Vec::<OrderedFloat>::with_capacity(batch_size)));
+    }
+
+    fn inputs(&self) -> Vec<BufferRef> { vec![self.lhs, self.rhs] }
+    fn outputs(&self) -> Vec<BufferRef> { vec![self.output] }
+    fn can_stream_input(&self, _: BufferRef) -> bool { true }
+    fn can_stream_output(&self, _: BufferRef) -> bool { true }
+    fn allocates(&self) -> bool { true }
+
+    fn display_op(&self, _: bool) -> String {
+        format!("{} {} {}", self.lhs, Op::symbol(), self.rhs)
+    }
+}
+
+/// Same as `VecConstFloatOperator`, but with the constant on the left hand side.
+#[derive(Debug)]
+pub struct ConstVecFloatOperator<Op> {
+    lhs: BufferRef,
+    rhs: BufferRef,
+    output: BufferRef,
+    op: PhantomData<Op>,
+}
+
+impl<Op> ConstVecFloatOperator<Op> {
+    pub fn new(lhs: BufferRef, rhs: BufferRef, output: BufferRef) -> ConstVecFloatOperator<Op> {
+        ConstVecFloatOperator {
+            lhs,
+            rhs,
+            output,
+            op: PhantomData,
+        }
+    }
+}
+
+impl<'a, Op: FloatOperation + fmt::Debug> VecOperator<'a> for ConstVecFloatOperator<Op> {
+    fn execute(&mut self, stream: bool, scratchpad: &mut Scratchpad<'a>) {
+        let mut output = scratchpad.get_mut::<OrderedFloat>(self.output);
+        if stream { output.clear(); }
+        let c = scratchpad.get_const::<OrderedFloat>(self.lhs).0;
+        let data = scratchpad.get::<OrderedFloat>(self.rhs);
+        for &d in data.iter() {
+            output.push(OrderedFloat(Op::perform(c, d.0)));
+        }
+    }
+
+    fn init(&mut self, _: usize, batch_size: usize, scratchpad: &mut Scratchpad<'a>) {
+        scratchpad.set(self.output, AnyVec::owned(Vec::<OrderedFloat>::with_capacity(batch_size)));
+    }
+
+    fn inputs(&self) -> Vec<BufferRef> { vec![self.lhs, self.rhs] }
+    fn outputs(&self) -> Vec<BufferRef> { vec![self.output] }
+    fn can_stream_input(&self, _: BufferRef) -> bool { true }
+    fn can_stream_output(&self, _: BufferRef) -> bool { true }
+    fn allocates(&self) -> bool { true }
+
+    fn display_op(&self, _: bool) -> String {
+        format!("{} {} {}", self.lhs, Op::symbol(), self.rhs)
+    }
+}
+
+/// Unlike integer division, floating point division and modulo by zero follow IEEE 754 and yield inf or NaN.
+pub trait FloatOperation {
+    fn perform(lhs: f64, rhs: f64) -> f64;
+    fn symbol() -> &'static str;
+}
+
+impl FloatOperation for Addition {
+    #[inline]
+    fn perform(l: f64, r: f64) -> f64 { l + r }
+    fn symbol() -> &'static str { "+" }
+}
+
+impl FloatOperation for Subtraction {
+    #[inline]
+    fn perform(l: f64, r: f64) -> f64 { l - r }
+    fn symbol() -> &'static str { "-" }
+}
+
+impl FloatOperation for Multiplication {
+    #[inline]
+    fn perform(l: f64, r: f64) -> f64 { l * r }
+    fn symbol() -> &'static str { "*" }
+}
+
+impl FloatOperation for Division {
+    #[inline]
+    fn perform(l: f64, r: f64) -> f64 { l / r }
+    fn symbol() -> &'static str { "/" }
+}
+
+impl FloatOperation for Modulo {
+    #[inline]
+    fn perform(l: f64, r: f64) -> f64 { l % r }
+    fn symbol() -> &'static str { "%" }
+}
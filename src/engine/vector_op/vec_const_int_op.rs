@@ -0,0 +1,149 @@
+use std::fmt;
+use std::marker::PhantomData;
+
+use engine::*;
+use engine::vector_op::vector_operator::*;
+
+
+#[derive(Debug)]
+pub struct VecConstIntOperator<Op> {
+    lhs: BufferRef,
+    rhs: BufferRef,
+    output: BufferRef,
+    op: PhantomData<Op>,
+}
+
+impl<Op> VecConstIntOperator<Op> {
+    pub fn new(lhs: BufferRef, rhs: BufferRef, output: BufferRef) -> VecConstIntOperator<Op> {
+        VecConstIntOperator {
+            lhs,
+            rhs,
+            output,
+            op: PhantomData,
+        }
+    }
+}
+
+impl<'a, Op: IntegerOperation + fmt::Debug> VecOperator<'a> for VecConstIntOperator<Op> {
+    fn execute(&mut self, stream: bool, scratchpad: &mut Scratchpad<'a>) {
+        let mut output = scratchpad.get_mut::<i64>(self.output);
+        if stream { output.clear(); }
+        let data = scratchpad.get::<i64>(self.lhs);
+        let c = scratchpad.get_const::<i64>(self.rhs);
+        for &d in data.iter() {
+            output.push(Op::perform(d, c));
+        }
+    }
+
+    fn init(&mut self, _: usize, batch_size: usize, scratchpad: &mut Scratchpad<'a>) {
+        scratchpad.set(self.output, AnyVec::owned(Vec::<i64>::with_capacity(batch_size)));
+    }
+
+    fn inputs(&self) -> Vec<BufferRef> { vec![self.lhs, self.rhs] }
+    fn outputs(&self) -> Vec<BufferRef> { vec![self.output] }
+    fn can_stream_input(&self, _: BufferRef) -> bool { true }
+    fn can_stream_output(&self, _: BufferRef) -> bool { true }
+    fn allocates(&self) -> bool { true }
+
+    fn display_op(&self, _: bool) -> String {
+        format!("{} {} {}", self.lhs, Op::symbol(), self.rhs)
+    }
+}
+
+/// Same as `VecConstIntOperator`, but with the constant on the left hand side.
+#[derive(Debug)]
+pub struct ConstVecIntOperator<Op> {
+    lhs: BufferRef,
+    rhs: BufferRef,
+    output: BufferRef,
+    op: PhantomData<Op>,
+}
+
+impl<Op> ConstVecIntOperator<Op> {
+    pub fn new(lhs: BufferRef, rhs: BufferRef, output: BufferRef) -> ConstVecIntOperator<Op> {
+        ConstVecIntOperator {
+            lhs,
+            rhs,
+            output,
+            op: PhantomData,
+        }
+    }
+}
+
+impl<'a, Op: IntegerOperation + fmt::Debug> VecOperator<'a> for ConstVecIntOperator<Op> {
+    fn execute(&mut self, stream: bool, scratchpad: &mut Scratchpad<'a>) {
+        let mut output = scratchpad.get_mut::<i64>(self.output);
+        if stream { output.clear(); }
+        let c = scratchpad.get_const::<i64>(self.lhs);
+        let data = scratchpad.get::<i64>(self.rhs);
+        for &d in data.iter() {
+            output.push(Op::perform(c, d));
+        }
+    }
+
+    fn init(&mut self, _: usize, batch_size: usize, scratchpad: &mut Scratchpad<'a>) {
+        scratchpad.set(self.output, AnyVec::owned(Vec::<i64>::with_capacity(batch_size)));
+    }
+
+    fn inputs(&self) -> Vec<BufferRef> { vec![self.lhs, self.rhs] }
+    fn outputs(&self) -> Vec<BufferRef> { vec![self.output] }
+    fn can_stream_input(&self, _: BufferRef) -> bool { true }
+    fn can_stream_output(&self, _: BufferRef) -> bool { true }
+    fn allocates(&self) -> bool { true }
+
+    fn display_op(&self, _: bool) -> String {
+        format!("{} {} {}", self.lhs, Op::symbol(), self.rhs)
+    }
+}
+
+pub trait IntegerOperation {
+    fn perform(lhs: i64, rhs: i64) -> i64;
+    fn symbol() -> &'static str;
+}
+
+#[derive(Debug)]
+pub struct Addition;
+
+impl IntegerOperation for Addition {
+    #[inline]
+    fn perform(l: i64, r: i64) -> i64 { l.wrapping_add(r) }
+    fn symbol() -> &'static str { "+" }
+}
+
+#[derive(Debug)]
+pub struct Subtraction;
+
+impl IntegerOperation for Subtraction {
+    #[inline]
+    fn perform(l: i64, r: i64) -> i64 { l.wrapping_sub(r) }
+    fn symbol() -> &'static str { "-" }
+}
+
+#[derive(Debug)]
+pub struct Multiplication;
+
+impl IntegerOperation for Multiplication {
+    #[inline]
+    fn perform(l: i64, r: i64) -> i64 { l.wrapping_mul(r) }
+    fn symbol() -> &'static str { "*" }
+}
+
+// Division and modulo by zero are NULL (see `QueryPlan::null_maps`), the value computed for them is irrelevant
+
+#[derive(Debug)]
+pub struct Division;
+
+impl IntegerOperation for Division {
+    #[inline]
+    fn perform(l: i64, r: i64) -> i64 { if r == 0 { 0 } else { l.wrapping_div(r) } }
+    fn symbol() -> &'static str { "/" }
+}
+
+#[derive(Debug)]
+pub struct Modulo;
+
+impl IntegerOperation for Modulo {
+    #[inline]
+    fn perform(l: i64, r: i64) -> i64 { if r == 0 { 0 } else { l.wrapping_rem(r) } }
+    fn symbol() -> &'static str { "%" }
+}
@@ -0,0 +1,67 @@
+use std::fmt;
+use std::marker::PhantomData;
+
+use engine::*;
+use engine::vector_op::vector_operator::*;
+use engine::vector_op::vec_const_bool_op::BoolOperation;
+
+
+#[derive(Debug)]
+pub struct VecVecBoolOperator<T, U, Op> {
+    lhs: BufferRef,
+    rhs: BufferRef,
+    output: BufferRef,
+    t: PhantomData<T>,
+    u: PhantomData<U>,
+    op: PhantomData<Op>,
+}
+
+impl<'a, T: 'a, U: 'a, Op> VecVecBoolOperator<T, U, Op> where
+    T: GenericVec<T>, U: GenericVec<U>, Op: BoolOperation<T, U> {
+    pub fn new(lhs: BufferRef, rhs: BufferRef, output: BufferRef) -> VecVecBoolOperator<T, U, Op> {
+        VecVecBoolOperator {
+            lhs,
+            rhs,
+            output,
+            t: PhantomData,
+            u: PhantomData,
+            op: PhantomData,
+        }
+    }
+}
+
+impl<'a, T: 'a, U: 'a, Op> VecOperator<'a> for VecVecBoolOperator<T, U, Op> where
+    T: GenericVec<T>, U: GenericVec<U>, Op: BoolOperation<T, U> + fmt::Debug {
+    fn execute(&mut self, stream: bool, scratchpad: &mut Scratchpad<'a>) {
+        let lhs = scratchpad.get::<T>(self.lhs);
+        let rhs = scratchpad.get::<U>(self.rhs);
+        let mut output = scratchpad.get_mut::<u8>(self.output);
+        if stream { output.clear(); }
+        for (l, r) in lhs.iter().zip(rhs.iter()) {
+            output.push(Op::perform(l, r));
+        }
+    }
+
+    fn init(&mut self, _: usize, batch_size: usize, scratchpad: &mut Scratchpad<'a>) {
+        scratchpad.set(self.output, AnyVec::owned(Vec::<u8>::with_capacity(batch_size)));
+    }
+
+    fn inputs(&self) -> Vec<BufferRef> { vec![self.lhs, self.rhs] }
+    fn outputs(&self) -> Vec<BufferRef> { vec![self.output] }
+    fn can_stream_input(&self, _: BufferRef) -> bool { true }
+    fn can_stream_output(&self, _: BufferRef) -> bool { true }
+    fn allocates(&self) -> bool { true }
+
+    fn display_op(&self, _: bool) -> String {
+        format!("{} {} {}", self.lhs, Op::symbol(), self.rhs)
+    }
+}
+
+#[derive(Debug)]
+pub struct AndNot;
+
+impl BoolOperation<u8, u8> for AndNot {
+    #[inline]
+    fn perform(l: &u8, r: &u8) -> u8 { l & (r ^ 1) }
+    fn symbol() -> &'static str { "& !" }
+}
@@ -0,0 +1,53 @@
+use std::fmt;
+use std::marker::PhantomData;
+
+use engine::*;
+use engine::vector_op::vector_operator::*;
+use engine::vector_op::vec_const_float_op::FloatOperation;
+use ingest::ordered_float::OrderedFloat;
+
+
+#[derive(Debug)]
+pub struct VecVecFloatOperator<Op> {
+    lhs: BufferRef,
+    rhs: BufferRef,
+    output: BufferRef,
+    op: PhantomData<Op>,
+}
+
+impl<Op> VecVecFloatOperator<Op> {
+    pub fn new(lhs: BufferRef, rhs: BufferRef, output: BufferRef) -> VecVecFloatOperator<Op> {
+        VecVecFloatOperator {
+            lhs,
+            rhs,
+            output,
+            op: PhantomData,
+        }
+    }
+}
+
+impl<'a, Op: FloatOperation + fmt::Debug> VecOperator<'a> for VecVecFloatOperator<Op> {
+    fn execute(&mut self, stream: bool, scratchpad: &mut Scratchpad<'a>) {
+        let mut output = scratchpad.get_mut::<OrderedFloat>(self.output);
+        if stream { output.clear(); }
+        let lhs = scratchpad.get::<OrderedFloat>(self.lhs);
+        let rhs = scratchpad.get::<OrderedFloat>(self.rhs);
+        for (&l, &r) in lhs.iter().zip(rhs.iter()) {
+            output.push(OrderedFloat(Op::perform(l.0, r.0)));
+        }
+    }
+
+    fn init(&mut self, _: usize, batch_size: usize, scratchpad: &mut Scratchpad<'a>) {
+        scratchpad.set(self.output, AnyVec::owned(Vec::<OrderedFloat>::with_capacity(batch_size)));
+    }
+
+    fn inputs(&self) -> Vec<BufferRef> { vec![self.lhs, self.rhs] }
+    fn outputs(&self) -> Vec<BufferRef> { vec![self.output] }
+    fn can_stream_input(&self, _: BufferRef) -> bool { true }
+    fn can_stream_output(&self, _: BufferRef) -> bool { true }
+    fn allocates(&self) -> bool { true }
+
+    fn display_op(&self, _: bool) -> String {
+        format!("{} {} {}", self.lhs, Op::symbol(), self.rhs)
+    }
+}
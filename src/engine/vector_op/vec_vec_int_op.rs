@@ -0,0 +1,52 @@
+use std::fmt;
+use std::marker::PhantomData;
+
+use engine::*;
+use engine::vector_op::vector_operator::*;
+use engine::vector_op::vec_const_int_op::IntegerOperation;
+
+
+#[derive(Debug)]
+pub struct VecVecIntOperator<Op> {
+    lhs: BufferRef,
+    rhs: BufferRef,
+    output: BufferRef,
+    op: PhantomData<Op>,
+}
+
+impl<Op> VecVecIntOperator<Op> {
+    pub fn new(lhs: BufferRef, rhs: BufferRef, output: BufferRef) -> VecVecIntOperator<Op> {
+        VecVecIntOperator {
+            lhs,
+            rhs,
+            output,
+            op: PhantomData,
+        }
+    }
+}
+
+impl<'a, Op: IntegerOperation + fmt::Debug> VecOperator<'a> for VecVecIntOperator<Op> {
+    fn execute(&mut self, stream: bool, scratchpad: &mut Scratchpad<'a>) {
+        let mut output = scratchpad.get_mut::<i64>(self.output);
+        if stream { output.clear(); }
+        let lhs = scratchpad.get::<i64>(self.lhs);
+        let rhs = scratchpad.get::<i64>(self.rhs);
+        for (&l, &r) in lhs.iter().zip(rhs.iter()) {
+            output.push(Op::perform(l, r));
+        }
+    }
+
+    fn init(&mut self, _: usize, batch_size: usize, scratchpad: &mut Scratchpad<'a>) {
+        scratchpad.set(self.output, AnyVec::owned(Vec::<i64>::with_capacity(batch_size)));
+    }
+
+    fn inputs(&self) -> Vec<BufferRef> { vec![self.lhs, self.rhs] }
+    fn outputs(&self) -> Vec<BufferRef> { vec![self.output] }
+    fn can_stream_input(&self, _: BufferRef) -> bool { true }
+    fn can_stream_output(&self, _: BufferRef) -> bool { true }
+    fn allocates(&self) -> bool { true }
+
+    fn display_op(&self, _: bool) -> String {
+        format!("{} {} {}", self.lhs, Op::symbol(), self.rhs)
+    }
+}
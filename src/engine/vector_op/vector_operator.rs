@@ -1,6 +1,7 @@
 use std::borrow::BorrowMut;
 use std::cell::{RefCell, Ref, RefMut};
 use std::collections::HashMap;
+use std::collections::HashSet;
 use std::fmt::Write;
 use std::fmt;
 use std::intrinsics::type_name;
@@ -12,46 +13,69 @@ use regex::Regex;
 
 use engine::*;
 use engine::aggregator::Aggregator;
+use engine::date_function::DateFunction;
+use engine::string_function::StringFunction;
 use engine::typed_vec::AnyVec;
 use engine::types::EncodingType;
 use engine::vector_op::comparator::*;
+use errors::QueryError;
+use ingest::ordered_float::OrderedFloat;
 use ingest::raw_val::RawVal;
+use syntax::expression::Func2Type;
 use mem_store::*;
 
 use engine::vector_op::addition_vs::AdditionVS;
+use engine::vector_op::aggregate_states::AggregateStates;
+use engine::vector_op::approx_count_distinct::VecHyperLogLog;
 use engine::vector_op::bit_unpack::BitUnpackOperator;
+use engine::vector_op::blend::Blend;
 use engine::vector_op::bool_op::*;
+use engine::vector_op::boolean_not::BooleanNot;
 use engine::vector_op::column_ops::*;
-use engine::vector_op::compact::Compact;
+use engine::vector_op::compact::{Compact, CompactStates};
 use engine::vector_op::constant::Constant;
+use engine::vector_op::constant_expand::{ConstantExpand, ExpandScalar};
 use engine::vector_op::constant_vec::ConstantVec;
 use engine::vector_op::count::VecCount;
+use engine::vector_op::count_distinct::*;
 use engine::vector_op::delta_decode::*;
 use engine::vector_op::dict_lookup::*;
 use engine::vector_op::division_vs::DivideVS;
 use engine::vector_op::encode_const::*;
 use engine::vector_op::exists::Exists;
-use engine::vector_op::filter::Filter;
+use engine::vector_op::filter::{Filter, FilterNull};
+use engine::vector_op::fill_nulls::FillNulls;
+use engine::vector_op::hash_values::HashValues;
 use engine::vector_op::hashmap_grouping::HashMapGrouping;
+use engine::vector_op::map_strings::*;
 use engine::vector_op::merge::Merge;
-use engine::vector_op::merge_aggregate::MergeAggregate;
+use engine::vector_op::merge_aggregate::{MergeAggregate, MergeStates};
 use engine::vector_op::merge_deduplicate::MergeDeduplicate;
 use engine::vector_op::merge_deduplicate_partitioned::MergeDeduplicatePartitioned;
 use engine::vector_op::merge_drop::MergeDrop;
 use engine::vector_op::merge_keep::MergeKeep;
+use engine::vector_op::min_max::*;
+use engine::vector_op::moments::VecMoments;
 use engine::vector_op::nonzero_compact::NonzeroCompact;
 use engine::vector_op::nonzero_indices::NonzeroIndices;
 use engine::vector_op::parameterized_vec_vec_int_op::*;
+use engine::vector_op::percentile::VecTDigest;
 use engine::vector_op::partition::Partition;
-use engine::vector_op::select::Select;
+use engine::vector_op::regex_match::*;
+use engine::vector_op::select::{Select, SelectNull, SelectStates};
 use engine::vector_op::sort_indices::SortIndices;
 use engine::vector_op::subpartition::SubPartition;
-use engine::vector_op::sum::VecSum;
-use engine::vector_op::to_year::ToYear;
-use engine::vector_op::top_n::TopN;
+use engine::vector_op::sum::{VecFloatSum, VecSum};
+use engine::vector_op::map_timestamps::MapTimestamps;
+use engine::vector_op::top_n::{TopN, TopNPerGroup};
 use engine::vector_op::type_conversion::TypeConversionOperator;
 use engine::vector_op::unpack_strings::UnpackStrings;
 use engine::vector_op::vec_const_bool_op::*;
+use engine::vector_op::vec_const_int_op::*;
+use engine::vector_op::vec_vec_bool_op::{AndNot, VecVecBoolOperator};
+use engine::vector_op::vec_vec_int_op::VecVecIntOperator;
+use engine::vector_op::vec_const_float_op::{ConstVecFloatOperator, VecConstFloatOperator};
+use engine::vector_op::vec_vec_float_op::VecVecFloatOperator;
 
 
 pub type BoxedOperator<'a> = Box<VecOperator<'a> + 'a>;
@@ -226,6 +250,34 @@ impl<'a> VecOperator<'a> {
         Box::new(InverseDictLookup { dict_indices, dict_data, constant, output })
     }
 
+    pub fn inverse_dict_lookup_set(dict_indices: BufferRef, dict_data: BufferRef, constants: HashSet<String>, output: BufferRef) -> BoxedOperator<'a> {
+        Box::new(InverseDictLookupSet { dict_indices, dict_data, constants, output })
+    }
+
+    pub fn table_lookup(indices: BufferRef, table: BufferRef, output: BufferRef, t: EncodingType, table_type: EncodingType) -> BoxedOperator<'a> {
+        match table_type {
+            EncodingType::U8 | EncodingType::BitVec => VecOperator::table_lookup_t::<u8>(indices, table, output, t),
+            EncodingType::U32 => VecOperator::table_lookup_t::<u32>(indices, table, output, t),
+            EncodingType::I64 => VecOperator::table_lookup_t::<i64>(indices, table, output, t),
+            EncodingType::U64 => VecOperator::table_lookup_t::<u64>(indices, table, output, t),
+            _ => panic!("table_lookup not supported for table type {:?}", table_type),
+        }
+    }
+
+    fn table_lookup_t<U: GenericVec<U> + 'a>(indices: BufferRef, table: BufferRef, output: BufferRef, t: EncodingType) -> BoxedOperator<'a> {
+        match t {
+            EncodingType::U8 => Box::new(TableLookup::<u8, U> { indices, table, output, t: PhantomData }),
+            EncodingType::U16 => Box::new(TableLookup::<u16, U> { indices, table, output, t: PhantomData }),
+            EncodingType::U32 => Box::new(TableLookup::<u32, U> { indices, table, output, t: PhantomData }),
+            EncodingType::I64 => Box::new(TableLookup::<i64, U> { indices, table, output, t: PhantomData }),
+            _ => panic!("table_lookup not supported for type {:?}", t),
+        }
+    }
+
+    pub fn dictionary_entries(dict_indices: BufferRef, dict_data: BufferRef, output: BufferRef) -> BoxedOperator<'a> {
+        Box::new(DictionaryEntries { dict_indices, dict_data, output })
+    }
+
     pub fn encode_int_const(constant: BufferRef, output: BufferRef, codec: Codec) -> BoxedOperator<'a> {
         Box::new(EncodeIntConstant { constant, output, codec })
     }
@@ -233,21 +285,47 @@ impl<'a> VecOperator<'a> {
     pub fn filter(t: EncodingType, input: BufferRef, filter: BufferRef, output: BufferRef) -> BoxedOperator<'a> {
         match t {
             EncodingType::I64 => Box::new(Filter::<i64> { input, filter, output, t: PhantomData }),
+            EncodingType::F64 => Box::new(Filter::<OrderedFloat> { input, filter, output, t: PhantomData }),
             EncodingType::U32 => Box::new(Filter::<u32> { input, filter, output, t: PhantomData }),
             EncodingType::U16 => Box::new(Filter::<u16> { input, filter, output, t: PhantomData }),
-            EncodingType::U8 => Box::new(Filter::<u8> { input, filter, output, t: PhantomData }),
+            EncodingType::U8 | EncodingType::BitVec => Box::new(Filter::<u8> { input, filter, output, t: PhantomData }),
             EncodingType::Str => Box::new(Filter::<&str> { input, filter, output, t: PhantomData }),
+            EncodingType::Null => Box::new(FilterNull { filter, output }),
             _ => panic!("filter not supported for type {:?}", t),
         }
     }
 
+    pub fn blend(condition: BufferRef, if_true: BufferRef, if_false: BufferRef, output: BufferRef, t: EncodingType) -> BoxedOperator<'a> {
+        match t {
+            EncodingType::U8 | EncodingType::BitVec => Box::new(Blend::<u8> { condition, if_true, if_false, output, t: PhantomData }),
+            EncodingType::I64 => Box::new(Blend::<i64> { condition, if_true, if_false, output, t: PhantomData }),
+            EncodingType::F64 => Box::new(Blend::<OrderedFloat> { condition, if_true, if_false, output, t: PhantomData }),
+            EncodingType::Str => Box::new(Blend::<&str> { condition, if_true, if_false, output, t: PhantomData }),
+            _ => panic!("blend not supported for type {:?}", t),
+        }
+    }
+
+    pub fn fill_nulls(input: BufferRef, not_null: BufferRef, fill: BufferRef, output: BufferRef, t: EncodingType) -> BoxedOperator<'a> {
+        match t {
+            EncodingType::I64 => Box::new(FillNulls::<i64> { input, not_null, fill, output, t: PhantomData }),
+            EncodingType::F64 => Box::new(FillNulls::<OrderedFloat> { input, not_null, fill, output, t: PhantomData }),
+            _ => panic!("fill_nulls not supported for type {:?}", t),
+        }
+    }
+
     pub fn select(t: EncodingType, input: BufferRef, indices: BufferRef, output: BufferRef) -> BoxedOperator<'a> {
         match t {
             EncodingType::I64 => Box::new(Select::<i64> { input, indices, output, t: PhantomData }),
+            EncodingType::F64 => Box::new(Select::<OrderedFloat> { input, indices, output, t: PhantomData }),
             EncodingType::U32 => Box::new(Select::<u32> { input, indices, output, t: PhantomData }),
             EncodingType::U16 => Box::new(Select::<u16> { input, indices, output, t: PhantomData }),
-            EncodingType::U8 => Box::new(Select::<u8> { input, indices, output, t: PhantomData }),
+            EncodingType::U8 | EncodingType::BitVec => Box::new(Select::<u8> { input, indices, output, t: PhantomData }),
             EncodingType::Str => Box::new(Select::<&str> { input, indices, output, t: PhantomData }),
+            EncodingType::ValueSet => Box::new(SelectStates::<typed_vec::ValueSet> { input, indices, output, t: PhantomData }),
+            EncodingType::HyperLogLog => Box::new(SelectStates::<hyperloglog::HyperLogLog> { input, indices, output, t: PhantomData }),
+            EncodingType::TDigest => Box::new(SelectStates::<tdigest::TDigest> { input, indices, output, t: PhantomData }),
+            EncodingType::Moments => Box::new(SelectStates::<moments::Moments> { input, indices, output, t: PhantomData }),
+            EncodingType::Null => Box::new(SelectNull { indices, output }),
             _ => panic!("filter not supported for type {:?}", t),
         }
     }
@@ -260,16 +338,62 @@ impl<'a> VecOperator<'a> {
         Box::new(ConstantVec { val, output })
     }
 
+    pub fn constant_expand(value: u8, input: BufferRef, output: BufferRef) -> BoxedOperator<'a> {
+        Box::new(ConstantExpand { value, input, output })
+    }
+
+    pub fn expand_scalar(value: BufferRef, input: BufferRef, output: BufferRef, t: EncodingType) -> BoxedOperator<'a> {
+        match t {
+            EncodingType::I64 => Box::new(ExpandScalar::<i64> { value, input, output, t: PhantomData }),
+            EncodingType::F64 => Box::new(ExpandScalar::<OrderedFloat> { value, input, output, t: PhantomData }),
+            _ => panic!("expand_scalar not supported for type {:?}", t),
+        }
+    }
+
     pub fn less_than_vs(t: EncodingType, lhs: BufferRef, rhs: BufferRef, output: BufferRef) -> BoxedOperator<'a> {
         match t {
             EncodingType::U8 => Box::new(VecConstBoolOperator::<u8, i64, LessThanInt<u8>>::new(lhs, rhs, output)),
             EncodingType::U16 => Box::new(VecConstBoolOperator::<u16, i64, LessThanInt<u16>>::new(lhs, rhs, output)),
             EncodingType::U32 => Box::new(VecConstBoolOperator::<u32, i64, LessThanInt<u32>>::new(lhs, rhs, output)),
             EncodingType::I64 => Box::new(VecConstBoolOperator::<i64, i64, LessThanInt<i64>>::new(lhs, rhs, output)),
+            EncodingType::F64 => Box::new(VecConstBoolOperator::<OrderedFloat, OrderedFloat, LessThan<OrderedFloat>>::new(lhs, rhs, output)),
             _ => panic!("less_than_vs not supported for type {:?}", t),
         }
     }
 
+    pub fn less_than_equals_vs(t: EncodingType, lhs: BufferRef, rhs: BufferRef, output: BufferRef) -> BoxedOperator<'a> {
+        match t {
+            EncodingType::U8 => Box::new(VecConstBoolOperator::<u8, i64, LessThanEqualsInt<u8>>::new(lhs, rhs, output)),
+            EncodingType::U16 => Box::new(VecConstBoolOperator::<u16, i64, LessThanEqualsInt<u16>>::new(lhs, rhs, output)),
+            EncodingType::U32 => Box::new(VecConstBoolOperator::<u32, i64, LessThanEqualsInt<u32>>::new(lhs, rhs, output)),
+            EncodingType::I64 => Box::new(VecConstBoolOperator::<i64, i64, LessThanEqualsInt<i64>>::new(lhs, rhs, output)),
+            EncodingType::F64 => Box::new(VecConstBoolOperator::<OrderedFloat, OrderedFloat, LessThanEquals<OrderedFloat>>::new(lhs, rhs, output)),
+            _ => panic!("less_than_equals_vs not supported for type {:?}", t),
+        }
+    }
+
+    pub fn greater_than_vs(t: EncodingType, lhs: BufferRef, rhs: BufferRef, output: BufferRef) -> BoxedOperator<'a> {
+        match t {
+            EncodingType::U8 => Box::new(VecConstBoolOperator::<u8, i64, GreaterThanInt<u8>>::new(lhs, rhs, output)),
+            EncodingType::U16 => Box::new(VecConstBoolOperator::<u16, i64, GreaterThanInt<u16>>::new(lhs, rhs, output)),
+            EncodingType::U32 => Box::new(VecConstBoolOperator::<u32, i64, GreaterThanInt<u32>>::new(lhs, rhs, output)),
+            EncodingType::I64 => Box::new(VecConstBoolOperator::<i64, i64, GreaterThanInt<i64>>::new(lhs, rhs, output)),
+            EncodingType::F64 => Box::new(VecConstBoolOperator::<OrderedFloat, OrderedFloat, GreaterThan<OrderedFloat>>::new(lhs, rhs, output)),
+            _ => panic!("greater_than_vs not supported for type {:?}", t),
+        }
+    }
+
+    pub fn greater_than_equals_vs(t: EncodingType, lhs: BufferRef, rhs: BufferRef, output: BufferRef) -> BoxedOperator<'a> {
+        match t {
+            EncodingType::U8 => Box::new(VecConstBoolOperator::<u8, i64, GreaterThanEqualsInt<u8>>::new(lhs, rhs, output)),
+            EncodingType::U16 => Box::new(VecConstBoolOperator::<u16, i64, GreaterThanEqualsInt<u16>>::new(lhs, rhs, output)),
+            EncodingType::U32 => Box::new(VecConstBoolOperator::<u32, i64, GreaterThanEqualsInt<u32>>::new(lhs, rhs, output)),
+            EncodingType::I64 => Box::new(VecConstBoolOperator::<i64, i64, GreaterThanEqualsInt<i64>>::new(lhs, rhs, output)),
+            EncodingType::F64 => Box::new(VecConstBoolOperator::<OrderedFloat, OrderedFloat, GreaterThanEquals<OrderedFloat>>::new(lhs, rhs, output)),
+            _ => panic!("greater_than_equals_vs not supported for type {:?}", t),
+        }
+    }
+
     pub fn equals_vs(t: EncodingType, lhs: BufferRef, rhs: BufferRef, output: BufferRef) -> BoxedOperator<'a> {
         match t {
             EncodingType::Str => Box::new(VecConstBoolOperator::<_, _, EqualsString>::new(lhs, rhs, output)),
@@ -277,6 +401,7 @@ impl<'a> VecOperator<'a> {
             EncodingType::U16 => Box::new(VecConstBoolOperator::<_, _, EqualsInt<u16>>::new(lhs, rhs, output)),
             EncodingType::U32 => Box::new(VecConstBoolOperator::<_, _, EqualsInt<u32>>::new(lhs, rhs, output)),
             EncodingType::I64 => Box::new(VecConstBoolOperator::<_, _, Equals<i64>>::new(lhs, rhs, output)),
+            EncodingType::F64 => Box::new(VecConstBoolOperator::<_, _, Equals<OrderedFloat>>::new(lhs, rhs, output)),
             _ => panic!("equals_vs not supported for type {:?}", t),
         }
     }
@@ -288,10 +413,57 @@ impl<'a> VecOperator<'a> {
             EncodingType::U16 => Box::new(VecConstBoolOperator::<_, _, NotEqualsInt<u16>>::new(lhs, rhs, output)),
             EncodingType::U32 => Box::new(VecConstBoolOperator::<_, _, NotEqualsInt<u32>>::new(lhs, rhs, output)),
             EncodingType::I64 => Box::new(VecConstBoolOperator::<_, _, NotEquals<i64>>::new(lhs, rhs, output)),
+            EncodingType::F64 => Box::new(VecConstBoolOperator::<_, _, NotEquals<OrderedFloat>>::new(lhs, rhs, output)),
             _ => panic!("not_equals_vs not supported for type {:?}", t),
         }
     }
 
+    pub fn less_than_vv(t: EncodingType, lhs: BufferRef, rhs: BufferRef, output: BufferRef) -> BoxedOperator<'a> {
+        match t {
+            EncodingType::U8 => Box::new(VecVecBoolOperator::<u8, u8, LessThan<u8>>::new(lhs, rhs, output)),
+            EncodingType::U16 => Box::new(VecVecBoolOperator::<u16, u16, LessThan<u16>>::new(lhs, rhs, output)),
+            EncodingType::U32 => Box::new(VecVecBoolOperator::<u32, u32, LessThan<u32>>::new(lhs, rhs, output)),
+            EncodingType::I64 => Box::new(VecVecBoolOperator::<i64, i64, LessThan<i64>>::new(lhs, rhs, output)),
+            EncodingType::F64 => Box::new(VecVecBoolOperator::<OrderedFloat, OrderedFloat, LessThan<OrderedFloat>>::new(lhs, rhs, output)),
+            _ => panic!("less_than_vv not supported for type {:?}", t),
+        }
+    }
+
+    pub fn less_than_equals_vv(t: EncodingType, lhs: BufferRef, rhs: BufferRef, output: BufferRef) -> BoxedOperator<'a> {
+        match t {
+            EncodingType::U8 => Box::new(VecVecBoolOperator::<u8, u8, LessThanEquals<u8>>::new(lhs, rhs, output)),
+            EncodingType::U16 => Box::new(VecVecBoolOperator::<u16, u16, LessThanEquals<u16>>::new(lhs, rhs, output)),
+            EncodingType::U32 => Box::new(VecVecBoolOperator::<u32, u32, LessThanEquals<u32>>::new(lhs, rhs, output)),
+            EncodingType::I64 => Box::new(VecVecBoolOperator::<i64, i64, LessThanEquals<i64>>::new(lhs, rhs, output)),
+            EncodingType::F64 => Box::new(VecVecBoolOperator::<OrderedFloat, OrderedFloat, LessThanEquals<OrderedFloat>>::new(lhs, rhs, output)),
+            _ => panic!("less_than_equals_vv not supported for type {:?}", t),
+        }
+    }
+
+    pub fn equals_vv(t: EncodingType, lhs: BufferRef, rhs: BufferRef, output: BufferRef) -> BoxedOperator<'a> {
+        match t {
+            EncodingType::Str => Box::new(VecVecBoolOperator::<&str, &str, Equals<&str>>::new(lhs, rhs, output)),
+            EncodingType::U8 => Box::new(VecVecBoolOperator::<u8, u8, Equals<u8>>::new(lhs, rhs, output)),
+            EncodingType::U16 => Box::new(VecVecBoolOperator::<u16, u16, Equals<u16>>::new(lhs, rhs, output)),
+            EncodingType::U32 => Box::new(VecVecBoolOperator::<u32, u32, Equals<u32>>::new(lhs, rhs, output)),
+            EncodingType::I64 => Box::new(VecVecBoolOperator::<i64, i64, Equals<i64>>::new(lhs, rhs, output)),
+            EncodingType::F64 => Box::new(VecVecBoolOperator::<OrderedFloat, OrderedFloat, Equals<OrderedFloat>>::new(lhs, rhs, output)),
+            _ => panic!("equals_vv not supported for type {:?}", t),
+        }
+    }
+
+    pub fn not_equals_vv(t: EncodingType, lhs: BufferRef, rhs: BufferRef, output: BufferRef) -> BoxedOperator<'a> {
+        match t {
+            EncodingType::Str => Box::new(VecVecBoolOperator::<&str, &str, NotEquals<&str>>::new(lhs, rhs, output)),
+            EncodingType::U8 => Box::new(VecVecBoolOperator::<u8, u8, NotEquals<u8>>::new(lhs, rhs, output)),
+            EncodingType::U16 => Box::new(VecVecBoolOperator::<u16, u16, NotEquals<u16>>::new(lhs, rhs, output)),
+            EncodingType::U32 => Box::new(VecVecBoolOperator::<u32, u32, NotEquals<u32>>::new(lhs, rhs, output)),
+            EncodingType::I64 => Box::new(VecVecBoolOperator::<i64, i64, NotEquals<i64>>::new(lhs, rhs, output)),
+            EncodingType::F64 => Box::new(VecVecBoolOperator::<OrderedFloat, OrderedFloat, NotEquals<OrderedFloat>>::new(lhs, rhs, output)),
+            _ => panic!("not_equals_vv not supported for type {:?}", t),
+        }
+    }
+
     pub fn divide_vs(lhs: BufferRef, rhs: BufferRef, output: BufferRef) -> BoxedOperator<'a> {
         Box::new(DivideVS { lhs, rhs, output })
     }
@@ -306,6 +478,76 @@ impl<'a> VecOperator<'a> {
         }
     }
 
+    pub fn subtraction_vs(lhs: BufferRef, rhs: BufferRef, output: BufferRef) -> BoxedOperator<'a> {
+        Box::new(VecConstIntOperator::<Subtraction>::new(lhs, rhs, output))
+    }
+
+    pub fn multiplication_vs(lhs: BufferRef, rhs: BufferRef, output: BufferRef) -> BoxedOperator<'a> {
+        Box::new(VecConstIntOperator::<Multiplication>::new(lhs, rhs, output))
+    }
+
+    pub fn modulo_vs(lhs: BufferRef, rhs: BufferRef, output: BufferRef) -> BoxedOperator<'a> {
+        Box::new(VecConstIntOperator::<Modulo>::new(lhs, rhs, output))
+    }
+
+    pub fn division_sv(lhs: BufferRef, rhs: BufferRef, output: BufferRef) -> BoxedOperator<'a> {
+        Box::new(ConstVecIntOperator::<Division>::new(lhs, rhs, output))
+    }
+
+    pub fn modulo_sv(lhs: BufferRef, rhs: BufferRef, output: BufferRef) -> BoxedOperator<'a> {
+        Box::new(ConstVecIntOperator::<Modulo>::new(lhs, rhs, output))
+    }
+
+    pub fn addition_vv(lhs: BufferRef, rhs: BufferRef, output: BufferRef) -> BoxedOperator<'a> {
+        Box::new(VecVecIntOperator::<Addition>::new(lhs, rhs, output))
+    }
+
+    pub fn subtraction_vv(lhs: BufferRef, rhs: BufferRef, output: BufferRef) -> BoxedOperator<'a> {
+        Box::new(VecVecIntOperator::<Subtraction>::new(lhs, rhs, output))
+    }
+
+    pub fn multiplication_vv(lhs: BufferRef, rhs: BufferRef, output: BufferRef) -> BoxedOperator<'a> {
+        Box::new(VecVecIntOperator::<Multiplication>::new(lhs, rhs, output))
+    }
+
+    pub fn division_vv(lhs: BufferRef, rhs: BufferRef, output: BufferRef) -> BoxedOperator<'a> {
+        Box::new(VecVecIntOperator::<Division>::new(lhs, rhs, output))
+    }
+
+    pub fn modulo_vv(lhs: BufferRef, rhs: BufferRef, output: BufferRef) -> BoxedOperator<'a> {
+        Box::new(VecVecIntOperator::<Modulo>::new(lhs, rhs, output))
+    }
+
+    pub fn float_arithmetic_vs(function: Func2Type, lhs: BufferRef, rhs: BufferRef, output: BufferRef) -> BoxedOperator<'a> {
+        match function {
+            Func2Type::Add => Box::new(VecConstFloatOperator::<Addition>::new(lhs, rhs, output)),
+            Func2Type::Subtract => Box::new(VecConstFloatOperator::<Subtraction>::new(lhs, rhs, output)),
+            Func2Type::Multiply => Box::new(VecConstFloatOperator::<Multiplication>::new(lhs, rhs, output)),
+            Func2Type::Divide => Box::new(VecConstFloatOperator::<Division>::new(lhs, rhs, output)),
+            Func2Type::Modulo => Box::new(VecConstFloatOperator::<Modulo>::new(lhs, rhs, output)),
+            _ => panic!("float_arithmetic_vs not supported for {:?}", function),
+        }
+    }
+
+    pub fn float_arithmetic_sv(function: Func2Type, lhs: BufferRef, rhs: BufferRef, output: BufferRef) -> BoxedOperator<'a> {
+        match function {
+            Func2Type::Divide => Box::new(ConstVecFloatOperator::<Division>::new(lhs, rhs, output)),
+            Func2Type::Modulo => Box::new(ConstVecFloatOperator::<Modulo>::new(lhs, rhs, output)),
+            _ => panic!("float_arithmetic_sv not supported for {:?}", function),
+        }
+    }
+
+    pub fn float_arithmetic_vv(function: Func2Type, lhs: BufferRef, rhs: BufferRef, output: BufferRef) -> BoxedOperator<'a> {
+        match function {
+            Func2Type::Add => Box::new(VecVecFloatOperator::<Addition>::new(lhs, rhs, output)),
+            Func2Type::Subtract => Box::new(VecVecFloatOperator::<Subtraction>::new(lhs, rhs, output)),
+            Func2Type::Multiply => Box::new(VecVecFloatOperator::<Multiplication>::new(lhs, rhs, output)),
+            Func2Type::Divide => Box::new(VecVecFloatOperator::<Division>::new(lhs, rhs, output)),
+            Func2Type::Modulo => Box::new(VecVecFloatOperator::<Modulo>::new(lhs, rhs, output)),
+            _ => panic!("float_arithmetic_vv not supported for {:?}", function),
+        }
+    }
+
     pub fn or(lhs: BufferRef, rhs: BufferRef) -> BoxedOperator<'a> {
         BooleanOperator::<BooleanOr>::compare(lhs, rhs)
     }
@@ -314,6 +556,14 @@ impl<'a> VecOperator<'a> {
         BooleanOperator::<BooleanAnd>::compare(lhs, rhs)
     }
 
+    pub fn not(input: BufferRef, output: BufferRef) -> BoxedOperator<'a> {
+        Box::new(BooleanNot { input, output })
+    }
+
+    pub fn and_not(lhs: BufferRef, rhs: BufferRef, output: BufferRef) -> BoxedOperator<'a> {
+        Box::new(VecVecBoolOperator::<u8, u8, AndNot>::new(lhs, rhs, output))
+    }
+
     pub fn bit_shift_left_add(lhs: BufferRef, rhs: BufferRef, output: BufferRef, shift_amount: i64) -> BoxedOperator<'a> {
         Box::new(ParameterizedVecVecIntegerOperator::<BitShiftLeftAdd>::new(lhs, rhs, output, shift_amount))
     }
@@ -340,14 +590,49 @@ impl<'a> VecOperator<'a> {
             (I64, U8) => Box::new(TypeConversionOperator::<i64, u8>::new(inner, output)),
             (I64, U16) => Box::new(TypeConversionOperator::<i64, u16>::new(inner, output)),
             (I64, U32) => Box::new(TypeConversionOperator::<i64, u32>::new(inner, output)),
+            (I64, F64) => Box::new(TypeConversionOperator::<i64, OrderedFloat>::new(inner, output)),
+
+            (U8, F64) => Box::new(TypeConversionOperator::<u8, OrderedFloat>::new(inner, output)),
 
             (U8, U8) | (U16, U16) | (U32, U32) | (I64, I64) => panic!("type_conversion from type {:?} to itself", initial_type),
             _ => panic!("type_conversion not supported for types {:?} -> {:?}", initial_type, target_type)
         }
     }
 
-    pub fn to_year(input: BufferRef, output: BufferRef) -> BoxedOperator<'a> {
-        Box::new(ToYear { input, output })
+    pub fn regex_match(input: BufferRef, regex: &str, output: BufferRef) -> BoxedOperator<'a> {
+        Box::new(RegexMatch { input, regex: Regex::new(regex).unwrap(), output })
+    }
+
+    pub fn dict_regex_match(dict_indices: BufferRef, dict_data: BufferRef, regex: &str, output: BufferRef) -> BoxedOperator<'a> {
+        Box::new(DictRegexMatch { dict_indices, dict_data, regex: Regex::new(regex).unwrap(), output })
+    }
+
+    pub fn map_strings(inputs: Vec<BufferRef>, function: StringFunction, indices: BufferRef, dict_indices: BufferRef, dict_data: BufferRef) -> BoxedOperator<'a> {
+        Box::new(MapStrings { inputs, function, indices, dict_indices, dict_data })
+    }
+
+    pub fn constant_dictionary(entries: Vec<String>, dict_indices: BufferRef, dict_data: BufferRef) -> BoxedOperator<'a> {
+        Box::new(ConstantDictionary { entries, dict_indices, dict_data })
+    }
+
+    pub fn string_length(input: BufferRef, output: BufferRef) -> BoxedOperator<'a> {
+        Box::new(StringLength { input, output })
+    }
+
+    pub fn hash_values(input: BufferRef, output: BufferRef, t: EncodingType) -> BoxedOperator<'a> {
+        match t {
+            EncodingType::U8 => Box::new(HashValues::<u8> { input, output, t: PhantomData }),
+            EncodingType::U16 => Box::new(HashValues::<u16> { input, output, t: PhantomData }),
+            EncodingType::U32 => Box::new(HashValues::<u32> { input, output, t: PhantomData }),
+            EncodingType::I64 => Box::new(HashValues::<i64> { input, output, t: PhantomData }),
+            EncodingType::F64 => Box::new(HashValues::<OrderedFloat> { input, output, t: PhantomData }),
+            EncodingType::Str => Box::new(HashValues::<&str> { input, output, t: PhantomData }),
+            _ => panic!("hash_values not supported for type {:?}", t),
+        }
+    }
+
+    pub fn map_timestamps(input: BufferRef, function: DateFunction, output: BufferRef) -> BoxedOperator<'a> {
+        Box::new(MapTimestamps { input, output, function })
     }
 
     pub fn summation(input: BufferRef,
@@ -374,6 +659,60 @@ impl<'a> VecOperator<'a> {
             (I64, U16) => VecSum::<i64, u16>::boxed(input, grouping, output, max_index),
             (I64, U32) => VecSum::<i64, u32>::boxed(input, grouping, output, max_index),
             (I64, I64) => VecSum::<i64, i64>::boxed(input, grouping, output, max_index),
+            (F64, U8) => VecFloatSum::<u8>::boxed(input, grouping, output, max_index),
+            (F64, U16) => VecFloatSum::<u16>::boxed(input, grouping, output, max_index),
+            (F64, U32) => VecFloatSum::<u32>::boxed(input, grouping, output, max_index),
+            (F64, I64) => VecFloatSum::<i64>::boxed(input, grouping, output, max_index),
+            (pt, gt) => panic!("invalid aggregation types {:?}, {:?}", pt, gt),
+        }
+    }
+
+    pub fn minimum(input: BufferRef,
+                   grouping: BufferRef,
+                   output: BufferRef,
+                   input_type: EncodingType,
+                   grouping_type: EncodingType,
+                   max_index: BufferRef) -> BoxedOperator<'a> {
+        VecOperator::min_max::<Minimum>(input, grouping, output, input_type, grouping_type, max_index)
+    }
+
+    pub fn maximum(input: BufferRef,
+                   grouping: BufferRef,
+                   output: BufferRef,
+                   input_type: EncodingType,
+                   grouping_type: EncodingType,
+                   max_index: BufferRef) -> BoxedOperator<'a> {
+        VecOperator::min_max::<Maximum>(input, grouping, output, input_type, grouping_type, max_index)
+    }
+
+    fn min_max<M: MinMax + 'a>(input: BufferRef,
+                                            grouping: BufferRef,
+                                            output: BufferRef,
+                                            input_type: EncodingType,
+                                            grouping_type: EncodingType,
+                                            max_index: BufferRef) -> BoxedOperator<'a> {
+        use self::EncodingType::*;
+        match (input_type, grouping_type) {
+            (U8, U8) => VecMinMax::<u8, u8, M>::boxed(input, grouping, output, max_index),
+            (U8, U16) => VecMinMax::<u8, u16, M>::boxed(input, grouping, output, max_index),
+            (U8, U32) => VecMinMax::<u8, u32, M>::boxed(input, grouping, output, max_index),
+            (U8, I64) => VecMinMax::<u8, i64, M>::boxed(input, grouping, output, max_index),
+            (U16, U8) => VecMinMax::<u16, u8, M>::boxed(input, grouping, output, max_index),
+            (U16, U16) => VecMinMax::<u16, u16, M>::boxed(input, grouping, output, max_index),
+            (U16, U32) => VecMinMax::<u16, u32, M>::boxed(input, grouping, output, max_index),
+            (U16, I64) => VecMinMax::<u16, i64, M>::boxed(input, grouping, output, max_index),
+            (U32, U8) => VecMinMax::<u32, u8, M>::boxed(input, grouping, output, max_index),
+            (U32, U16) => VecMinMax::<u32, u16, M>::boxed(input, grouping, output, max_index),
+            (U32, U32) => VecMinMax::<u32, u32, M>::boxed(input, grouping, output, max_index),
+            (U32, I64) => VecMinMax::<u32, i64, M>::boxed(input, grouping, output, max_index),
+            (I64, U8) => VecMinMax::<i64, u8, M>::boxed(input, grouping, output, max_index),
+            (I64, U16) => VecMinMax::<i64, u16, M>::boxed(input, grouping, output, max_index),
+            (I64, U32) => VecMinMax::<i64, u32, M>::boxed(input, grouping, output, max_index),
+            (I64, I64) => VecMinMax::<i64, i64, M>::boxed(input, grouping, output, max_index),
+            (F64, U8) => VecMinMax::<OrderedFloat, u8, M>::boxed(input, grouping, output, max_index),
+            (F64, U16) => VecMinMax::<OrderedFloat, u16, M>::boxed(input, grouping, output, max_index),
+            (F64, U32) => VecMinMax::<OrderedFloat, u32, M>::boxed(input, grouping, output, max_index),
+            (F64, I64) => VecMinMax::<OrderedFloat, i64, M>::boxed(input, grouping, output, max_index),
             (pt, gt) => panic!("invalid aggregation types {:?}, {:?}", pt, gt),
         }
     }
@@ -388,6 +727,133 @@ impl<'a> VecOperator<'a> {
         }
     }
 
+    pub fn count_distinct(input: BufferRef,
+                          grouping: BufferRef,
+                          not_null: Option<BufferRef>,
+                          output: BufferRef,
+                          input_type: EncodingType,
+                          grouping_type: EncodingType,
+                          max_index: BufferRef) -> BoxedOperator<'a> {
+        match (input_type, grouping_type) {
+            (I64, U8) => VecDistinct::<i64, u8>::boxed(input, grouping, not_null, output, max_index),
+            (I64, U16) => VecDistinct::<i64, u16>::boxed(input, grouping, not_null, output, max_index),
+            (I64, U32) => VecDistinct::<i64, u32>::boxed(input, grouping, not_null, output, max_index),
+            (I64, I64) => VecDistinct::<i64, i64>::boxed(input, grouping, not_null, output, max_index),
+            (F64, U8) => VecDistinct::<OrderedFloat, u8>::boxed(input, grouping, not_null, output, max_index),
+            (F64, U16) => VecDistinct::<OrderedFloat, u16>::boxed(input, grouping, not_null, output, max_index),
+            (F64, U32) => VecDistinct::<OrderedFloat, u32>::boxed(input, grouping, not_null, output, max_index),
+            (F64, I64) => VecDistinct::<OrderedFloat, i64>::boxed(input, grouping, not_null, output, max_index),
+            (Str, U8) => VecDistinct::<&str, u8>::boxed(input, grouping, not_null, output, max_index),
+            (Str, U16) => VecDistinct::<&str, u16>::boxed(input, grouping, not_null, output, max_index),
+            (Str, U32) => VecDistinct::<&str, u32>::boxed(input, grouping, not_null, output, max_index),
+            (Str, I64) => VecDistinct::<&str, i64>::boxed(input, grouping, not_null, output, max_index),
+            (pt, gt) => panic!("invalid aggregation types {:?}, {:?}", pt, gt),
+        }
+    }
+
+    pub fn count_distinct_dictionary(input: BufferRef,
+                                     grouping: BufferRef,
+                                     not_null: Option<BufferRef>,
+                                     dictionary: BufferRef,
+                                     output: BufferRef,
+                                     input_type: EncodingType,
+                                     grouping_type: EncodingType,
+                                     max_index: BufferRef) -> BoxedOperator<'a> {
+        match (input_type, grouping_type) {
+            (U8, U8) => VecDistinctDictionary::<u8, u8>::boxed(input, grouping, not_null, dictionary, output, max_index),
+            (U8, U16) => VecDistinctDictionary::<u8, u16>::boxed(input, grouping, not_null, dictionary, output, max_index),
+            (U8, U32) => VecDistinctDictionary::<u8, u32>::boxed(input, grouping, not_null, dictionary, output, max_index),
+            (U8, I64) => VecDistinctDictionary::<u8, i64>::boxed(input, grouping, not_null, dictionary, output, max_index),
+            (U16, U8) => VecDistinctDictionary::<u16, u8>::boxed(input, grouping, not_null, dictionary, output, max_index),
+            (U16, U16) => VecDistinctDictionary::<u16, u16>::boxed(input, grouping, not_null, dictionary, output, max_index),
+            (U16, U32) => VecDistinctDictionary::<u16, u32>::boxed(input, grouping, not_null, dictionary, output, max_index),
+            (U16, I64) => VecDistinctDictionary::<u16, i64>::boxed(input, grouping, not_null, dictionary, output, max_index),
+            (U32, U8) => VecDistinctDictionary::<u32, u8>::boxed(input, grouping, not_null, dictionary, output, max_index),
+            (U32, U16) => VecDistinctDictionary::<u32, u16>::boxed(input, grouping, not_null, dictionary, output, max_index),
+            (U32, U32) => VecDistinctDictionary::<u32, u32>::boxed(input, grouping, not_null, dictionary, output, max_index),
+            (U32, I64) => VecDistinctDictionary::<u32, i64>::boxed(input, grouping, not_null, dictionary, output, max_index),
+            (I64, U8) => VecDistinctDictionary::<i64, u8>::boxed(input, grouping, not_null, dictionary, output, max_index),
+            (I64, U16) => VecDistinctDictionary::<i64, u16>::boxed(input, grouping, not_null, dictionary, output, max_index),
+            (I64, U32) => VecDistinctDictionary::<i64, u32>::boxed(input, grouping, not_null, dictionary, output, max_index),
+            (I64, I64) => VecDistinctDictionary::<i64, i64>::boxed(input, grouping, not_null, dictionary, output, max_index),
+            (pt, gt) => panic!("invalid aggregation types {:?}, {:?}", pt, gt),
+        }
+    }
+
+    pub fn approx_count_distinct(hashes: BufferRef,
+                                 grouping: BufferRef,
+                                 not_null: Option<BufferRef>,
+                                 output: BufferRef,
+                                 grouping_type: EncodingType,
+                                 max_index: BufferRef,
+                                 precision: u8) -> BoxedOperator<'a> {
+        match grouping_type {
+            U8 => VecHyperLogLog::<u8>::boxed(hashes, grouping, not_null, output, max_index, precision),
+            U16 => VecHyperLogLog::<u16>::boxed(hashes, grouping, not_null, output, max_index, precision),
+            U32 => VecHyperLogLog::<u32>::boxed(hashes, grouping, not_null, output, max_index, precision),
+            I64 => VecHyperLogLog::<i64>::boxed(hashes, grouping, not_null, output, max_index, precision),
+            t => panic!("unsupported type {:?} for grouping key", t),
+        }
+    }
+
+    pub fn percentile(input: BufferRef,
+                      grouping: BufferRef,
+                      not_null: Option<BufferRef>,
+                      output: BufferRef,
+                      grouping_type: EncodingType,
+                      max_index: BufferRef) -> BoxedOperator<'a> {
+        match grouping_type {
+            U8 => VecTDigest::<u8>::boxed(input, grouping, not_null, output, max_index),
+            U16 => VecTDigest::<u16>::boxed(input, grouping, not_null, output, max_index),
+            U32 => VecTDigest::<u32>::boxed(input, grouping, not_null, output, max_index),
+            I64 => VecTDigest::<i64>::boxed(input, grouping, not_null, output, max_index),
+            t => panic!("unsupported type {:?} for grouping key", t),
+        }
+    }
+
+    pub fn moments(x: BufferRef,
+                   y: Option<BufferRef>,
+                   grouping: BufferRef,
+                   not_null: Option<BufferRef>,
+                   output: BufferRef,
+                   grouping_type: EncodingType,
+                   max_index: BufferRef) -> BoxedOperator<'a> {
+        match grouping_type {
+            U8 => VecMoments::<u8>::boxed(x, y, grouping, not_null, output, max_index),
+            U16 => VecMoments::<u16>::boxed(x, y, grouping, not_null, output, max_index),
+            U32 => VecMoments::<u32>::boxed(x, y, grouping, not_null, output, max_index),
+            I64 => VecMoments::<i64>::boxed(x, y, grouping, not_null, output, max_index),
+            t => panic!("unsupported type {:?} for grouping key", t),
+        }
+    }
+
+    pub fn aggregate_states(input: BufferRef,
+                            grouping: BufferRef,
+                            output: BufferRef,
+                            input_type: EncodingType,
+                            grouping_type: EncodingType,
+                            max_index: BufferRef) -> BoxedOperator<'a> {
+        match (input_type, grouping_type) {
+            (ValueSet, U8) => AggregateStates::<typed_vec::ValueSet, u8>::boxed(input, grouping, output, max_index),
+            (ValueSet, U16) => AggregateStates::<typed_vec::ValueSet, u16>::boxed(input, grouping, output, max_index),
+            (ValueSet, U32) => AggregateStates::<typed_vec::ValueSet, u32>::boxed(input, grouping, output, max_index),
+            (ValueSet, I64) => AggregateStates::<typed_vec::ValueSet, i64>::boxed(input, grouping, output, max_index),
+            (HyperLogLog, U8) => AggregateStates::<hyperloglog::HyperLogLog, u8>::boxed(input, grouping, output, max_index),
+            (HyperLogLog, U16) => AggregateStates::<hyperloglog::HyperLogLog, u16>::boxed(input, grouping, output, max_index),
+            (HyperLogLog, U32) => AggregateStates::<hyperloglog::HyperLogLog, u32>::boxed(input, grouping, output, max_index),
+            (HyperLogLog, I64) => AggregateStates::<hyperloglog::HyperLogLog, i64>::boxed(input, grouping, output, max_index),
+            (TDigest, U8) => AggregateStates::<tdigest::TDigest, u8>::boxed(input, grouping, output, max_index),
+            (TDigest, U16) => AggregateStates::<tdigest::TDigest, u16>::boxed(input, grouping, output, max_index),
+            (TDigest, U32) => AggregateStates::<tdigest::TDigest, u32>::boxed(input, grouping, output, max_index),
+            (TDigest, I64) => AggregateStates::<tdigest::TDigest, i64>::boxed(input, grouping, output, max_index),
+            (Moments, U8) => AggregateStates::<moments::Moments, u8>::boxed(input, grouping, output, max_index),
+            (Moments, U16) => AggregateStates::<moments::Moments, u16>::boxed(input, grouping, output, max_index),
+            (Moments, U32) => AggregateStates::<moments::Moments, u32>::boxed(input, grouping, output, max_index),
+            (Moments, I64) => AggregateStates::<moments::Moments, i64>::boxed(input, grouping, output, max_index),
+            (pt, gt) => panic!("invalid aggregation types {:?}, {:?}", pt, gt),
+        }
+    }
+
     pub fn exists(grouping: BufferRef, output: BufferRef, grouping_type: EncodingType, max_index: BufferRef) -> BoxedOperator<'a> {
         match grouping_type {
             EncodingType::U8 => Exists::<u8>::boxed(grouping, output, max_index),
@@ -455,6 +921,26 @@ impl<'a> VecOperator<'a> {
             (I64, U16) => Compact::<i64, u16>::boxed(data, select),
             (I64, U32) => Compact::<i64, u32>::boxed(data, select),
             (I64, I64) => Compact::<i64, i64>::boxed(data, select),
+            (F64, U8) => Compact::<OrderedFloat, u8>::boxed(data, select),
+            (F64, U16) => Compact::<OrderedFloat, u16>::boxed(data, select),
+            (F64, U32) => Compact::<OrderedFloat, u32>::boxed(data, select),
+            (F64, I64) => Compact::<OrderedFloat, i64>::boxed(data, select),
+            (ValueSet, U8) => CompactStates::<typed_vec::ValueSet, u8>::boxed(data, select),
+            (ValueSet, U16) => CompactStates::<typed_vec::ValueSet, u16>::boxed(data, select),
+            (ValueSet, U32) => CompactStates::<typed_vec::ValueSet, u32>::boxed(data, select),
+            (ValueSet, I64) => CompactStates::<typed_vec::ValueSet, i64>::boxed(data, select),
+            (HyperLogLog, U8) => CompactStates::<hyperloglog::HyperLogLog, u8>::boxed(data, select),
+            (HyperLogLog, U16) => CompactStates::<hyperloglog::HyperLogLog, u16>::boxed(data, select),
+            (HyperLogLog, U32) => CompactStates::<hyperloglog::HyperLogLog, u32>::boxed(data, select),
+            (HyperLogLog, I64) => CompactStates::<hyperloglog::HyperLogLog, i64>::boxed(data, select),
+            (TDigest, U8) => CompactStates::<tdigest::TDigest, u8>::boxed(data, select),
+            (TDigest, U16) => CompactStates::<tdigest::TDigest, u16>::boxed(data, select),
+            (TDigest, U32) => CompactStates::<tdigest::TDigest, u32>::boxed(data, select),
+            (TDigest, I64) => CompactStates::<tdigest::TDigest, i64>::boxed(data, select),
+            (Moments, U8) => CompactStates::<moments::Moments, u8>::boxed(data, select),
+            (Moments, U16) => CompactStates::<moments::Moments, u16>::boxed(data, select),
+            (Moments, U32) => CompactStates::<moments::Moments, u32>::boxed(data, select),
+            (Moments, I64) => CompactStates::<moments::Moments, i64>::boxed(data, select),
             t => panic!("unsupported type {:?} for grouping key", t),
         }
     }
@@ -484,6 +970,7 @@ impl<'a> VecOperator<'a> {
         if desc {
             match t {
                 I64 => Box::new(TopN::<i64, CmpGreaterThan> { input, keys: keys_out, indices: indices_out, last_index: 0, n, t: PhantomData, c: PhantomData }),
+                F64 => Box::new(TopN::<OrderedFloat, CmpGreaterThan> { input, keys: keys_out, indices: indices_out, last_index: 0, n, t: PhantomData, c: PhantomData }),
                 U32 => Box::new(TopN::<u32, CmpGreaterThan> { input, keys: keys_out, indices: indices_out, last_index: 0, n, t: PhantomData, c: PhantomData }),
                 U16 => Box::new(TopN::<u16, CmpGreaterThan> { input, keys: keys_out, indices: indices_out, last_index: 0, n, t: PhantomData, c: PhantomData }),
                 U8 => Box::new(TopN::<u8, CmpGreaterThan> { input, keys: keys_out, indices: indices_out, last_index: 0, n, t: PhantomData, c: PhantomData }),
@@ -493,6 +980,7 @@ impl<'a> VecOperator<'a> {
         } else {
             match t {
                 I64 => Box::new(TopN::<i64, CmpLessThan> { input, keys: keys_out, indices: indices_out, last_index: 0, n, t: PhantomData, c: PhantomData }),
+                F64 => Box::new(TopN::<OrderedFloat, CmpLessThan> { input, keys: keys_out, indices: indices_out, last_index: 0, n, t: PhantomData, c: PhantomData }),
                 U32 => Box::new(TopN::<u32, CmpLessThan> { input, keys: keys_out, indices: indices_out, last_index: 0, n, t: PhantomData, c: PhantomData }),
                 U16 => Box::new(TopN::<u16, CmpLessThan> { input, keys: keys_out, indices: indices_out, last_index: 0, n, t: PhantomData, c: PhantomData }),
                 U8 => Box::new(TopN::<u8, CmpLessThan> { input, keys: keys_out, indices: indices_out, last_index: 0, n, t: PhantomData, c: PhantomData }),
@@ -502,6 +990,30 @@ impl<'a> VecOperator<'a> {
         }
     }
 
+    pub fn top_n_per_group(input: BufferRef, grouping: BufferRef, indices_out: BufferRef, t: EncodingType, n: usize, desc: bool) -> BoxedOperator<'a> {
+        if desc {
+            match t {
+                I64 => Box::new(TopNPerGroup::<i64, CmpGreaterThan> { input, grouping, indices: indices_out, n, last_index: 0, groups: HashMap::default(), c: PhantomData }),
+                F64 => Box::new(TopNPerGroup::<OrderedFloat, CmpGreaterThan> { input, grouping, indices: indices_out, n, last_index: 0, groups: HashMap::default(), c: PhantomData }),
+                U32 => Box::new(TopNPerGroup::<u32, CmpGreaterThan> { input, grouping, indices: indices_out, n, last_index: 0, groups: HashMap::default(), c: PhantomData }),
+                U16 => Box::new(TopNPerGroup::<u16, CmpGreaterThan> { input, grouping, indices: indices_out, n, last_index: 0, groups: HashMap::default(), c: PhantomData }),
+                U8 => Box::new(TopNPerGroup::<u8, CmpGreaterThan> { input, grouping, indices: indices_out, n, last_index: 0, groups: HashMap::default(), c: PhantomData }),
+                Str => Box::new(TopNPerGroup::<&str, CmpGreaterThan> { input, grouping, indices: indices_out, n, last_index: 0, groups: HashMap::default(), c: PhantomData }),
+                _ => panic!("top_n_per_group not supported for type {:?}", t),
+            }
+        } else {
+            match t {
+                I64 => Box::new(TopNPerGroup::<i64, CmpLessThan> { input, grouping, indices: indices_out, n, last_index: 0, groups: HashMap::default(), c: PhantomData }),
+                F64 => Box::new(TopNPerGroup::<OrderedFloat, CmpLessThan> { input, grouping, indices: indices_out, n, last_index: 0, groups: HashMap::default(), c: PhantomData }),
+                U32 => Box::new(TopNPerGroup::<u32, CmpLessThan> { input, grouping, indices: indices_out, n, last_index: 0, groups: HashMap::default(), c: PhantomData }),
+                U16 => Box::new(TopNPerGroup::<u16, CmpLessThan> { input, grouping, indices: indices_out, n, last_index: 0, groups: HashMap::default(), c: PhantomData }),
+                U8 => Box::new(TopNPerGroup::<u8, CmpLessThan> { input, grouping, indices: indices_out, n, last_index: 0, groups: HashMap::default(), c: PhantomData }),
+                Str => Box::new(TopNPerGroup::<&str, CmpLessThan> { input, grouping, indices: indices_out, n, last_index: 0, groups: HashMap::default(), c: PhantomData }),
+                _ => panic!("top_n_per_group not supported for type {:?}", t),
+            }
+        }
+    }
+
     pub fn merge_deduplicate(left: BufferRef,
                              right: BufferRef,
                              merged_out: BufferRef,
@@ -515,6 +1027,8 @@ impl<'a> VecOperator<'a> {
                 Box::new(MergeDeduplicate::<u8> { left, right, deduplicated: merged_out, merge_ops: ops_out, t: PhantomData }),
             (EncodingType::I64, EncodingType::I64) =>
                 Box::new(MergeDeduplicate::<i64> { left, right, deduplicated: merged_out, merge_ops: ops_out, t: PhantomData }),
+            (EncodingType::F64, EncodingType::F64) =>
+                Box::new(MergeDeduplicate::<OrderedFloat> { left, right, deduplicated: merged_out, merge_ops: ops_out, t: PhantomData }),
             (t1, t2) => panic!("merge_deduplicate types {:?}, {:?}", t1, t2),
         }
     }
@@ -530,6 +1044,8 @@ impl<'a> VecOperator<'a> {
                 Box::new(Partition::<&str> { left, right, partitioning: partition_out, limit, t: PhantomData }),
             (EncodingType::I64, EncodingType::I64) =>
                 Box::new(Partition::<i64> { left, right, partitioning: partition_out, limit, t: PhantomData }),
+            (EncodingType::F64, EncodingType::F64) =>
+                Box::new(Partition::<OrderedFloat> { left, right, partitioning: partition_out, limit, t: PhantomData }),
             (t1, t2) => panic!("partition types {:?}, {:?}", t1, t2),
         }
     }
@@ -546,6 +1062,8 @@ impl<'a> VecOperator<'a> {
                 Box::new(SubPartition::<&str> { partitioning, left, right, sub_partitioning: subpartition_out, t: PhantomData }),
             (EncodingType::I64, EncodingType::I64) =>
                 Box::new(SubPartition::<i64> { partitioning, left, right, sub_partitioning: subpartition_out, t: PhantomData }),
+            (EncodingType::F64, EncodingType::F64) =>
+                Box::new(SubPartition::<OrderedFloat> { partitioning, left, right, sub_partitioning: subpartition_out, t: PhantomData }),
             (t1, t2) => panic!("partition types {:?}, {:?}", t1, t2),
         }
     }
@@ -562,6 +1080,8 @@ impl<'a> VecOperator<'a> {
                 Box::new(MergeDeduplicatePartitioned::<&str> { partitioning, left, right, deduplicated: merged_out, merge_ops: ops_out, t: PhantomData }),
             (EncodingType::I64, EncodingType::I64) =>
                 Box::new(MergeDeduplicatePartitioned::<i64> { partitioning, left, right, deduplicated: merged_out, merge_ops: ops_out, t: PhantomData }),
+            (EncodingType::F64, EncodingType::F64) =>
+                Box::new(MergeDeduplicatePartitioned::<OrderedFloat> { partitioning, left, right, deduplicated: merged_out, merge_ops: ops_out, t: PhantomData }),
             (t1, t2) => panic!("merge_deduplicate_partitioned types {:?}, {:?}", t1, t2),
         }
     }
@@ -577,6 +1097,8 @@ impl<'a> VecOperator<'a> {
                 Box::new(MergeDrop::<&str> { merge_ops, left, right, deduplicated: merged_out, t: PhantomData }),
             (EncodingType::I64, EncodingType::I64) =>
                 Box::new(MergeDrop::<i64> { merge_ops, left, right, deduplicated: merged_out, t: PhantomData }),
+            (EncodingType::F64, EncodingType::F64) =>
+                Box::new(MergeDrop::<OrderedFloat> { merge_ops, left, right, deduplicated: merged_out, t: PhantomData }),
             (t1, t2) => panic!("merge_drop types {:?}, {:?}", t1, t2),
         }
     }
@@ -585,8 +1107,21 @@ impl<'a> VecOperator<'a> {
                            left: BufferRef,
                            right: BufferRef,
                            aggregated_out: BufferRef,
-                           aggregator: Aggregator) -> BoxedOperator<'a> {
-        Box::new(MergeAggregate { merge_ops, left, right, aggregated: aggregated_out, aggregator })
+                           aggregator: Aggregator,
+                           t: EncodingType) -> Result<BoxedOperator<'a>, QueryError> {
+        Ok(match t {
+            EncodingType::I64 => MergeAggregate::<i64>::boxed(merge_ops, left, right, aggregated_out, aggregator)?,
+            EncodingType::F64 => MergeAggregate::<OrderedFloat>::boxed(merge_ops, left, right, aggregated_out, aggregator)?,
+            EncodingType::ValueSet =>
+                Box::new(MergeStates::<typed_vec::ValueSet> { merge_ops, left, right, aggregated: aggregated_out, t: PhantomData }),
+            EncodingType::HyperLogLog =>
+                Box::new(MergeStates::<hyperloglog::HyperLogLog> { merge_ops, left, right, aggregated: aggregated_out, t: PhantomData }),
+            EncodingType::TDigest =>
+                Box::new(MergeStates::<tdigest::TDigest> { merge_ops, left, right, aggregated: aggregated_out, t: PhantomData }),
+            EncodingType::Moments =>
+                Box::new(MergeStates::<moments::Moments> { merge_ops, left, right, aggregated: aggregated_out, t: PhantomData }),
+            _ => bail!(QueryError::FatalError, "merge_aggregate not supported for type {:?}", t),
+        })
     }
 
     pub fn merge(left: BufferRef,
@@ -603,6 +1138,8 @@ impl<'a> VecOperator<'a> {
                     Box::new(Merge::<&str, CmpGreaterThan> { left, right, merged: merged_out, merge_ops: ops_out, limit, t: PhantomData, c: PhantomData }),
                 (EncodingType::I64, EncodingType::I64) =>
                     Box::new(Merge::<i64, CmpGreaterThan> { left, right, merged: merged_out, merge_ops: ops_out, limit, t: PhantomData, c: PhantomData }),
+                (EncodingType::F64, EncodingType::F64) =>
+                    Box::new(Merge::<OrderedFloat, CmpGreaterThan> { left, right, merged: merged_out, merge_ops: ops_out, limit, t: PhantomData, c: PhantomData }),
                 (t1, t2) => panic!("merge types {:?}, {:?}", t1, t2),
             }
         } else {
@@ -611,6 +1148,8 @@ impl<'a> VecOperator<'a> {
                     Box::new(Merge::<&str, CmpLessThan> { left, right, merged: merged_out, merge_ops: ops_out, limit, t: PhantomData, c: PhantomData }),
                 (EncodingType::I64, EncodingType::I64) =>
                     Box::new(Merge::<i64, CmpLessThan> { left, right, merged: merged_out, merge_ops: ops_out, limit, t: PhantomData, c: PhantomData }),
+                (EncodingType::F64, EncodingType::F64) =>
+                    Box::new(Merge::<OrderedFloat, CmpLessThan> { left, right, merged: merged_out, merge_ops: ops_out, limit, t: PhantomData, c: PhantomData }),
                 (t1, t2) => panic!("merge types {:?}, {:?}", t1, t2),
             }
         }
@@ -627,6 +1166,8 @@ impl<'a> VecOperator<'a> {
                 Box::new(MergeKeep::<&str> { merge_ops, left, right, merged: merged_out, t: PhantomData }),
             (EncodingType::I64, EncodingType::I64) =>
                 Box::new(MergeKeep::<i64> { merge_ops, left, right, merged: merged_out, t: PhantomData }),
+            (EncodingType::F64, EncodingType::F64) =>
+                Box::new(MergeKeep::<OrderedFloat> { merge_ops, left, right, merged: merged_out, t: PhantomData }),
             (t1, t2) => panic!("merge_keep types {:?}, {:?}", t1, t2),
         }
     }
@@ -1,17 +1,17 @@
+use std::fmt;
+
 #[derive(Fail, Debug)]
 pub enum QueryError {
-    #[fail(display = "Failed to parser query. Chars remaining: {}", _0)]
-    SytaxErrorCharsRemaining(String),
-    #[fail(display = "Failed to parser query. Bytes remaining: {:?}", _0)]
-    SyntaxErrorBytesRemaining(Vec<u8>),
-    #[fail(display = "Failed to parser query: {}", _0)]
-    ParseError(String),
+    #[fail(display = "{}", _0)]
+    SyntaxError(SyntaxError),
     #[fail(display = "Some assumption was violated. This is a bug: {}", _0)]
     FatalError(String),
     #[fail(display = "Not implemented: {}", _0)]
     NotImplemented(String),
     #[fail(display = "Type error: {}", _0)]
     TypeError(String),
+    #[fail(display = "Invalid query: {}", _0)]
+    InvalidQuery(String),
 }
 
 #[macro_export]
@@ -37,3 +37,65 @@ macro_rules! ensure {
         }
     };
 }
+
+/// Position and cause of a syntax error in a query.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyntaxError {
+    /// Byte offset of the offending token within the query.
+    pub offset: usize,
+    /// Line (starting at 1) that contains the offending token.
+    pub line: usize,
+    /// Column (starting at 1, counted in characters) of the offending token.
+    pub column: usize,
+    /// Descriptions of the tokens that would have been valid at `offset`, e.g. `FROM` or `expression`.
+    pub expected: Vec<String>,
+    /// The offending token, or `None` if the query ended unexpectedly.
+    pub found: Option<String>,
+    /// The line of the query that contains the offending token.
+    pub source_line: String,
+}
+
+impl SyntaxError {
+    pub fn new(query: &str, offset: usize, expected: Vec<String>) -> SyntaxError {
+        let line_start = query[..offset].rfind('\n').map(|i| i + 1).unwrap_or(0);
+        let line_end = query[offset..].find('\n').map(|i| offset + i).unwrap_or(query.len());
+        let rest = &query[offset..line_end];
+        let ident_len = rest.find(|c: char| !(c.is_alphanumeric() || c == '_')).unwrap_or(rest.len());
+        let found = match rest.chars().next() {
+            None => None,
+            Some(_) if ident_len > 0 => Some(rest[..ident_len].to_string()),
+            Some(c) => Some(c.to_string()),
+        };
+        SyntaxError {
+            offset,
+            line: query[..offset].matches('\n').count() + 1,
+            column: query[line_start..offset].chars().count() + 1,
+            expected,
+            found,
+            source_line: query[line_start..line_end].to_string(),
+        }
+    }
+
+    /// Renders the line of the query that contains the error with a caret underneath the offending token.
+    pub fn highlight(&self) -> String {
+        let width = self.found.as_ref().map(|found| found.chars().count()).unwrap_or(1);
+        format!("{}\n{}{}", self.source_line, " ".repeat(self.column - 1), "^".repeat(width))
+    }
+}
+
+impl fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Syntax error at line {}, column {}", self.line, self.column)?;
+        if let Some((last, init)) = self.expected.split_last() {
+            if init.is_empty() {
+                write!(f, ": expected {}", last)?;
+            } else {
+                write!(f, ": expected {} or {}", init.join(", "), last)?;
+            }
+        }
+        match self.found {
+            Some(ref found) => write!(f, ", found `{}`", found),
+            None => write!(f, ", found end of query"),
+        }
+    }
+}
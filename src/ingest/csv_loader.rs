@@ -3,11 +3,11 @@ extern crate flate2;
 
 use mem_store::column::*;
 use mem_store::column_builder::*;
-use mem_store::strings::fast_build_string_column;
 use scheduler::*;
 use self::flate2::read::GzDecoder;
 use std::collections::{HashMap, HashSet};
 use std::fs::File;
+use std::io::Read;
 use std::ops::BitOr;
 use std::str;
 use std::sync::Arc;
@@ -68,32 +68,47 @@ impl Options {
 }
 
 pub fn ingest_file(ldb: &InnerLocustDB, opts: &Options) -> Result<(), String> {
-    // Can't combine these two branches because csv::Reader takes a type param which differs for creating from Reader/File
-    if opts.unzip {
-        let f = File::open(&opts.filename).map_err(|x| x.to_string())?;
-        let decoded = GzDecoder::new(f);
-        let mut reader = csv::ReaderBuilder::new()
-            .has_headers(opts.colnames.is_none())
-            .from_reader(decoded);
-        let headers = match opts.colnames {
-            Some(ref colnames) => colnames.clone(),
-            None => reader.headers().unwrap().iter().map(str::to_owned).collect()
-        };
-        auto_ingest(ldb, reader.records().map(|r| r.unwrap()), &headers, opts)
-    } else {
-        let mut reader = csv::ReaderBuilder::new()
-            .has_headers(opts.colnames.is_none())
-            .from_path(&opts.filename)
-            .map_err(|x| x.to_string())?;
-        let headers = match opts.colnames {
-            Some(ref colnames) => colnames.clone(),
-            None => reader.headers().unwrap().iter().map(str::to_owned).collect()
-        };
-        auto_ingest(ldb, reader.records().map(|r| r.unwrap()), &headers, opts)
+    // Whether a column is float is decided on the whole file, otherwise partitions that happen to only contain
+    // integral values would be stored as integer columns
+    let float = {
+        let mut reader = open_reader(opts)?;
+        let headers = headers(&mut reader, opts);
+        float_columns(reader.records().map(|r| r.unwrap()), headers.len())
+    };
+    let mut reader = open_reader(opts)?;
+    let headers = headers(&mut reader, opts);
+    auto_ingest(ldb, reader.records().map(|r| r.unwrap()), &headers, &float, opts)
+}
+
+fn open_reader(opts: &Options) -> Result<csv::Reader<Box<Read>>, String> {
+    let f = File::open(&opts.filename).map_err(|x| x.to_string())?;
+    let input: Box<Read> = if opts.unzip { Box::new(GzDecoder::new(f)) } else { Box::new(f) };
+    Ok(csv::ReaderBuilder::new()
+        .has_headers(opts.colnames.is_none())
+        .from_reader(input))
+}
+
+fn headers(reader: &mut csv::Reader<Box<Read>>, opts: &Options) -> Vec<String> {
+    match opts.colnames {
+        Some(ref colnames) => colnames.clone(),
+        None => reader.headers().unwrap().iter().map(str::to_owned).collect()
+    }
+}
+
+fn float_columns<T>(records: T, column_count: usize) -> Vec<bool>
+    where T: Iterator<Item=csv::StringRecord> {
+    let mut float = vec![false; column_count];
+    for row in records {
+        for (i, val) in row.iter().enumerate() {
+            if !float[i] {
+                float[i] = ColType::determine(val).contains_float;
+            }
+        }
     }
+    float
 }
 
-fn auto_ingest<T>(ldb: &InnerLocustDB, records: T, colnames: &[String], opts: &Options) -> Result<(), String>
+fn auto_ingest<T>(ldb: &InnerLocustDB, records: T, colnames: &[String], float: &[bool], opts: &Options) -> Result<(), String>
     where T: Iterator<Item=csv::StringRecord> {
     let ignore = colnames.iter().map(|x| opts.ignore_cols.contains(x)).collect::<Vec<_>>();
     let string = colnames.iter().map(|x| opts.always_string.contains(x)).collect::<Vec<_>>();
@@ -107,26 +122,26 @@ fn auto_ingest<T>(ldb: &InnerLocustDB, records: T, colnames: &[String], opts: &O
         }
 
         if row_num % opts.partition_size == opts.partition_size - 1 {
-            let partition = create_batch(&mut raw_cols, colnames, &opts.extractors, &ignore, &string);
+            let partition = create_batch(&mut raw_cols, colnames, &opts.extractors, &ignore, &string, float);
             ldb.store_partition(&opts.tablename, partition);
         }
         row_num += 1;
     }
 
     if row_num % opts.partition_size != 0 {
-        let partition = create_batch(&mut raw_cols, colnames, &opts.extractors, &ignore, &string);
+        let partition = create_batch(&mut raw_cols, colnames, &opts.extractors, &ignore, &string, float);
         ldb.store_partition(&opts.tablename, partition);
     }
     Ok(())
 }
 
-fn create_batch(cols: &mut [RawCol], colnames: &[String], extractors: &IngestionTransform, ignore: &[bool], string: &[bool]) -> Vec<Arc<Column>> {
+fn create_batch(cols: &mut [RawCol], colnames: &[String], extractors: &IngestionTransform, ignore: &[bool], string: &[bool], float: &[bool]) -> Vec<Arc<Column>> {
     let mut mem_store = Vec::new();
     for (i, col) in cols.iter_mut().enumerate() {
         if !ignore[i] {
             let new_column = match extractors.get(&colnames[i]) {
                 Some(extractor) => col.extract(&colnames[i], extractor),
-                None => col.finalize(&colnames[i], string[i]),
+                None => col.finalize(&colnames[i], string[i], float[i]),
             };
             mem_store.push(new_column);
         }
@@ -179,22 +194,39 @@ impl RawCol {
         self.values.push(elem);
     }
 
-    fn finalize(&mut self, name: &str, string: bool) -> Arc<Column> {
+    fn finalize(&mut self, name: &str, string: bool, float: bool) -> Arc<Column> {
         let result = if self.types.contains_string || string {
-            fast_build_string_column(name, self.values.iter(), self.values.len())
+            let mut builder = FastStringColBuilder::new();
+            for s in self.values.iter() {
+                if s.is_empty() {
+                    builder.push_null();
+                } else {
+                    builder.push(&s);
+                }
+            }
+            builder.finalize(name)
+        } else if float && (self.types.contains_float || self.types.contains_int) {
+            let mut builder = FloatColBuilder::new();
+            for s in self.values.iter() {
+                if s.is_empty() {
+                    builder.push_null();
+                } else if let Ok(float) = s.parse::<f64>() {
+                    builder.push(&float);
+                } else {
+                    unreachable!("{} should be parseable as float. {} {:?}", s, name, self.types)
+                }
+            }
+            builder.finalize(name)
         } else if self.types.contains_int {
             let mut builder = IntColBuilder::new();
             for s in self.values.iter() {
-                let int = if s.is_empty() {
-                    0
+                if s.is_empty() {
+                    builder.push_null();
                 } else if let Ok(int) = s.parse::<i64>() {
-                    int
-                } else if let Ok(float) = s.parse::<f64>() {
-                    float as i64
+                    builder.push(&int);
                 } else {
-                    unreachable!("{} should be parseable as int or float. {} {:?}", s, name, self.types)
-                };
-                builder.push(&int);
+                    unreachable!("{} should be parseable as int. {} {:?}", s, name, self.types)
+                }
             }
             builder.finalize(name)
         } else {
@@ -224,35 +256,42 @@ impl RawCol {
 struct ColType {
     contains_string: bool,
     contains_int: bool,
+    contains_float: bool,
     contains_null: bool,
 }
 
 impl ColType {
-    fn new(string: bool, int: bool, null: bool) -> ColType {
-        ColType { contains_string: string, contains_int: int, contains_null: null }
+    fn new(string: bool, int: bool, float: bool, null: bool) -> ColType {
+        ColType { contains_string: string, contains_int: int, contains_float: float, contains_null: null }
     }
 
     fn string() -> ColType {
-        ColType::new(true, false, false)
+        ColType::new(true, false, false, false)
     }
 
     fn int() -> ColType {
-        ColType::new(false, true, false)
+        ColType::new(false, true, false, false)
+    }
+
+    fn float() -> ColType {
+        ColType::new(false, false, true, false)
     }
 
     fn null() -> ColType {
-        ColType::new(false, false, true)
+        ColType::new(false, false, false, true)
     }
 
     fn nothing() -> ColType {
-        ColType::new(false, false, false)
+        ColType::new(false, false, false, false)
     }
 
     fn determine(s: &str) -> ColType {
         if s.is_empty() {
             ColType::null()
-        } else if s.parse::<i64>().is_ok() || s.parse::<f64>().is_ok() {
+        } else if s.parse::<i64>().is_ok() {
             ColType::int()
+        } else if s.parse::<f64>().is_ok() {
+            ColType::float()
         } else {
             ColType::string()
         }
@@ -265,6 +304,7 @@ impl BitOr for ColType {
         ColType {
             contains_string: self.contains_string | rhs.contains_string,
             contains_int: self.contains_int | rhs.contains_int,
+            contains_float: self.contains_float | rhs.contains_float,
             contains_null: self.contains_null | rhs.contains_null,
         }
     }
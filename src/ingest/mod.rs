@@ -1,5 +1,6 @@
 pub mod csv_loader;
 pub mod raw_val;
+pub mod ordered_float;
 pub mod input_column;
 pub mod buffer;
 pub mod extractor;
@@ -0,0 +1,67 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::f64;
+
+use heapsize::HeapSizeOf;
+
+
+/// `f64` with a total order so that floats can be sorted, grouped and hashed like any other column type.
+/// NaN is equal to itself and larger than every other value, `-0.0` is equal to `0.0`.
+#[derive(Copy, Clone, Default)]
+pub struct OrderedFloat(pub f64);
+
+impl OrderedFloat {
+    fn canonical_bits(&self) -> u64 {
+        if self.0.is_nan() {
+            f64::NAN.to_bits()
+        } else if self.0 == 0.0 {
+            0
+        } else {
+            self.0.to_bits()
+        }
+    }
+}
+
+impl Ord for OrderedFloat {
+    fn cmp(&self, other: &OrderedFloat) -> Ordering {
+        self.0.partial_cmp(&other.0)
+            .unwrap_or_else(|| self.0.is_nan().cmp(&other.0.is_nan()))
+    }
+}
+
+impl PartialOrd for OrderedFloat {
+    fn partial_cmp(&self, other: &OrderedFloat) -> Option<Ordering> { Some(self.cmp(other)) }
+}
+
+impl PartialEq for OrderedFloat {
+    fn eq(&self, other: &OrderedFloat) -> bool { self.cmp(other) == Ordering::Equal }
+}
+
+impl Eq for OrderedFloat {}
+
+impl Hash for OrderedFloat {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.canonical_bits().hash(state)
+    }
+}
+
+impl fmt::Debug for OrderedFloat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self.0)
+    }
+}
+
+impl fmt::Display for OrderedFloat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl HeapSizeOf for OrderedFloat {
+    fn heap_size_of_children(&self) -> usize { 0 }
+}
+
+impl From<f64> for OrderedFloat {
+    fn from(value: f64) -> OrderedFloat { OrderedFloat(value) }
+}
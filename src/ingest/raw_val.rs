@@ -1,10 +1,12 @@
 use std::fmt;
 use engine::types::BasicType;
+use ingest::ordered_float::OrderedFloat;
 
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Hash, HeapSizeOf)]
 pub enum RawVal {
     Int(i64),
+    Float(OrderedFloat),
     Str(String),
     Null,
 }
@@ -13,6 +15,7 @@ impl RawVal {
     pub fn get_type(&self) -> BasicType {
         match *self {
             RawVal::Int(_) => BasicType::Integer,
+            RawVal::Float(_) => BasicType::Float,
             RawVal::Str(_) => BasicType::String,
             RawVal::Null => BasicType::Null,
         }
@@ -24,6 +27,7 @@ impl fmt::Display for RawVal {
         match *self {
             RawVal::Null => write!(f, "null"),
             RawVal::Int(i) => write!(f, "{}", i),
+            RawVal::Float(x) => write!(f, "{}", x),
             RawVal::Str(ref s) => write!(f, "\"{}\"", s),
         }
     }
@@ -43,13 +43,15 @@ mod stringpack;
 pub mod unit_fmt;
 
 pub use engine::query_task::QueryOutput;
-pub use errors::QueryError;
+pub use errors::{QueryError, SyntaxError};
 pub use ingest::csv_loader::Options as LoadOptions;
 pub use ingest::extractor;
 pub use ingest::nyc_taxi_data;
+pub use ingest::ordered_float::OrderedFloat;
 pub use ingest::raw_val::RawVal as Value;
 pub use locustdb::LocustDB as LocustDB;
 pub use locustdb::Options as Options;
+pub use locustdb::PreparedQuery;
 pub use mem_store::table::TableStats;
 pub use disk_store::noop_storage::NoopStorage;
 
@@ -1,21 +1,33 @@
-use std::str;
+use std::cmp;
+use std::collections::HashMap;
+use std::iter;
+use std::mem;
 use std::sync::Arc;
 
 use futures_channel::oneshot;
 use futures_core::*;
 use futures_util::FutureExt;
+use futures_util::future::join_all;
 use futures_executor::block_on;
-use nom;
 use num_cpus;
 
 use QueryError;
 use QueryResult;
+use Value;
 use disk_store::interface::*;
 use disk_store::noop_storage::NoopStorage;
+use engine::query::Query;
 use engine::query_task::QueryTask;
+use engine::row_eval;
+use engine::types::BasicType;
 use ingest::csv_loader::{CSVIngestionTask, Options as LoadOptions};
+use ingest::ordered_float::OrderedFloat;
+use ingest::raw_val::RawVal;
 use mem_store::*;
+use mem_store::table::Table;
 use scheduler::*;
+use syntax::expression::Expr;
+use syntax::limit::LimitClause;
 use syntax::parser;
 use trace::{Trace, TraceBuilder};
 
@@ -24,6 +36,21 @@ pub struct LocustDB {
     inner_locustdb: Arc<InnerLocustDB>
 }
 
+/// Query that has been parsed once and can be executed repeatedly with different arguments, see `LocustDB::prepare`.
+#[derive(Clone)]
+pub struct PreparedQuery {
+    query: Query,
+}
+
+impl PreparedQuery {
+    /// Names of the parameters of the query in the order in which their arguments have to be supplied.
+    /// Unnamed parameters (`?`) have no name, named parameters (`$name`) occupy a single position even if they are
+    /// referenced multiple times.
+    pub fn parameters(&self) -> Vec<Option<String>> {
+        self.query.parameters()
+    }
+}
+
 #[derive(Clone)]
 pub struct Options {
     pub threads: usize,
@@ -46,41 +73,104 @@ impl LocustDB {
     }
 
     pub fn run_query(&self, query: &str, explain: bool, show: Vec<usize>) -> Box<Future<Item=(QueryResult, Trace), Error=oneshot::Canceled>> {
-        let (sender, receiver) = oneshot::channel();
+        match parser::parse(query) {
+            Ok(query) => self.run_bound(query.bind(&[]), explain, show),
+            Err(err) => LocustDB::failed_query(QueryError::SyntaxError(err)),
+        }
+    }
+
+    /// Parses `query`, which may contain parameters (`?` or `$name`) that are bound to values on each execution.
+    pub fn prepare(&self, query: &str) -> Result<PreparedQuery, QueryError> {
+        parser::parse(query)
+            .map(|query| PreparedQuery { query })
+            .map_err(QueryError::SyntaxError)
+    }
+
+    /// Runs `query` with its parameters bound to `args`, see `PreparedQuery::parameters`.
+    pub fn execute(&self, query: &PreparedQuery, args: &[Value], explain: bool, show: Vec<usize>) -> Box<Future<Item=(QueryResult, Trace), Error=oneshot::Canceled>> {
+        self.run_bound(query.query.bind(args), explain, show)
+    }
+
+    fn run_bound(&self, query: Result<Query, QueryError>, explain: bool, show: Vec<usize>) -> Box<Future<Item=(QueryResult, Trace), Error=oneshot::Canceled>> {
+        match query {
+            Ok(query) => LocustDB::run_with(self.inner_locustdb.clone(), query, HashMap::new(), explain, show),
+            Err(err) => LocustDB::failed_query(err),
+        }
+    }
 
-        // TODO(clemens): perform compilation and table snapshot in asynchronous task?
-        let query = match parser::parse_query(query.as_bytes()) {
-            nom::IResult::Done(remaining, query) => {
-                if !remaining.is_empty() {
-                    let error = match str::from_utf8(remaining) {
-                        Ok(chars) => QueryError::SytaxErrorCharsRemaining(chars.to_owned()),
-                        Err(_) => QueryError::SyntaxErrorBytesRemaining(remaining.to_vec()),
-                    };
-                    return Box::new(future::ok((Err(error), TraceBuilder::new("empty".to_owned()).finalize())));
+    /// Runs the subqueries of `query` one after another, materializing each result as a temporary table that can be
+    /// referenced by later subqueries and the query itself. Temporary tables in `tables` shadow tables of the database.
+    /// The queries of a UNION ALL are run concurrently and their results are concatenated.
+    fn run_with(inner: Arc<InnerLocustDB>,
+                mut query: Query,
+                mut tables: HashMap<String, Arc<Table>>,
+                explain: bool,
+                show: Vec<usize>) -> Box<Future<Item=(QueryResult, Trace), Error=oneshot::Canceled>> {
+        if !query.with.is_empty() {
+            let (name, subquery) = query.with.remove(0);
+            let result = LocustDB::run_with(inner.clone(), subquery, tables.clone(), false, vec![]);
+            return Box::new(result.and_then(move |(result, _)| match result {
+                Ok(output) => {
+                    let table = Table::temporary(&name, &output.colnames, output.rows);
+                    tables.insert(name, Arc::new(table));
+                    LocustDB::run_with(inner, query, tables, explain, show)
                 }
-                query
+                Err(err) => LocustDB::failed_query(err),
+            }));
+        }
+
+        if !query.union_all.is_empty() {
+            if query.limit_by.is_some() {
+                return LocustDB::failed_query(
+                    QueryError::NotImplemented("LIMIT BY clause of UNION ALL".to_string()));
             }
-            nom::IResult::Error(err) => return Box::new(future::ok((
-                Err(QueryError::ParseError(format!("{:?}", err))),
-                TraceBuilder::new("empty".to_owned()).finalize()))),
-            nom::IResult::Incomplete(needed) => return Box::new(future::ok((
-                Err(QueryError::ParseError(format!("Incomplete. Needed: {:?}", needed))),
-                TraceBuilder::new("empty".to_owned()).finalize()))),
-        };
+            let union_all = mem::replace(&mut query.union_all, Vec::new());
+            let order_by = mem::replace(&mut query.order_by, Vec::new());
+            let limit = mem::replace(&mut query.limit, LimitClause { limit: u64::MAX, offset: 0 });
+            let results = iter::once(query).chain(union_all)
+                .map(|query| LocustDB::run_with(inner.clone(), query, tables.clone(), explain, show.clone()))
+                .collect::<Vec<_>>();
+            return Box::new(join_all(results).map(move |results| {
+                let mut results = results.into_iter();
+                let (first, trace) = results.next().unwrap();
+                let union = results.fold(first, |union, (result, _)| append(union, result));
+                (order_and_limit(union, &order_by, &limit), trace)
+            }));
+        }
 
+        let (sender, receiver) = oneshot::channel();
+        // Wildcard table names only match tables of the database, never the temporary tables in `tables`
+        let snapshot = |table: &str| match tables.get(table) {
+            Some(temporary) => Some(temporary.snapshot()),
+            None => inner.snapshot(table),
+        };
         // TODO(clemens): A table may not exist on all nodes, so querying empty table is valid and should return empty result.
-        let data = match self.inner_locustdb.snapshot(&query.table) {
+        let data = match snapshot(&query.table) {
             Some(data) => data,
-            None => return Box::new(future::ok((
-                Err(QueryError::NotImplemented(format!("Table {} does not exist!", &query.table))),
-                TraceBuilder::new("empty".to_owned()).finalize()))),
+            None => return LocustDB::failed_query(
+                QueryError::NotImplemented(format!("Table {} does not exist!", &query.table))),
+        };
+        let joined = match query.join {
+            Some(ref join) => match snapshot(&join.table) {
+                Some(joined) => joined,
+                None => return LocustDB::failed_query(
+                    QueryError::NotImplemented(format!("Table {} does not exist!", &join.table))),
+            },
+            None => Vec::new(),
+        };
+        let task = match QueryTask::new(
+            query, explain, show, data, joined, inner.storage.clone(), SharedSender::new(sender)) {
+            Ok(task) => task,
+            Err(err) => return LocustDB::failed_query(err),
         };
-        let task = QueryTask::new(
-            query, explain, show, data, self.inner_locustdb.storage.clone(), SharedSender::new(sender));
-        let trace_receiver = self.schedule(task);
+        let trace_receiver = inner.schedule(task);
         Box::new(receiver.join(trace_receiver))
     }
 
+    fn failed_query(err: QueryError) -> Box<Future<Item=(QueryResult, Trace), Error=oneshot::Canceled>> {
+        Box::new(future::ok((Err(err), TraceBuilder::new("empty".to_owned()).finalize())))
+    }
+
     pub fn load_csv(&self, options: LoadOptions) -> impl Future<Item=Result<(), String>, Error=oneshot::Canceled> {
         let (sender, receiver) = oneshot::channel();
         let task = CSVIngestionTask::new(
@@ -92,19 +182,9 @@ impl LocustDB {
     }
 
     pub fn ast(&self, query: &str) -> String {
-        match parser::parse_query(query.as_bytes()) {
-            nom::IResult::Done(remaining, query) => {
-                if !remaining.is_empty() {
-                    match str::from_utf8(remaining) {
-                        Ok(chars) => format!("Chars remaining: {}", chars),
-                        Err(_) => format!("Bytes remaining: {:?}", &remaining),
-                    }
-                } else {
-                    format!("{:#?}", query)
-                }
-            }
-            nom::IResult::Error(err) => format!("Parse error: {:?}", err),
-            nom::IResult::Incomplete(needed) => format!("Incomplete. Needed: {:?}", needed),
+        match parser::parse(query) {
+            Ok(query) => format!("{:#?}", query),
+            Err(err) => format!("{}\n{}", err.highlight(), err),
         }
     }
 
@@ -159,6 +239,81 @@ impl LocustDB {
     }
 }
 
+/// Appends the rows of `result` to the rows of `union`, failing if either of them failed or if their columns have
+/// different types. Columns are matched by position and named after the columns of `union`. Integer columns are
+/// converted to floats when they are combined with float columns.
+fn append(union: QueryResult, result: QueryResult) -> QueryResult {
+    let (mut union, mut result) = (union?, result?);
+    if union.colnames.len() != result.colnames.len() {
+        bail!(QueryError::InvalidQuery, "Queries of UNION ALL have {} and {} columns",
+              union.colnames.len(), result.colnames.len());
+    }
+    for i in 0..union.coltypes.len() {
+        match (union.coltypes[i], result.coltypes[i]) {
+            (BasicType::Integer, BasicType::Float) => {
+                int_to_float(&mut union.rows, i);
+                union.coltypes[i] = BasicType::Float;
+            }
+            (BasicType::Float, BasicType::Integer) => int_to_float(&mut result.rows, i),
+            // Columns without values are compatible with any column
+            (BasicType::Null, rhs_type) => union.coltypes[i] = rhs_type,
+            (_, BasicType::Null) => {}
+            (BasicType::Val, _) | (_, BasicType::Val) => union.coltypes[i] = BasicType::Val,
+            (lhs_type, rhs_type) => if lhs_type != rhs_type {
+                bail!(QueryError::InvalidQuery, "Column `{}` of UNION ALL has types {:?} and {:?}",
+                      union.colnames[i], lhs_type, rhs_type);
+            },
+        }
+    }
+    union.rows.extend(result.rows);
+    for (plan, count) in result.query_plans {
+        *union.query_plans.entry(plan).or_insert(0) += count;
+    }
+    union.stats.runtime_ns = cmp::max(union.stats.runtime_ns, result.stats.runtime_ns);
+    union.stats.rows_scanned += result.stats.rows_scanned;
+    Ok(union)
+}
+
+/// Sorts the concatenated rows of a UNION ALL and applies its LIMIT clause. Sort keys refer to columns of the result
+/// by name or position.
+fn order_and_limit(union: QueryResult, order_by: &[(Expr, bool)], limit: &LimitClause) -> QueryResult {
+    let mut union = union?;
+    if !order_by.is_empty() {
+        let columns = union.colnames.iter().cloned().map(Expr::ColName).collect::<Vec<_>>();
+        let mut resolved = Vec::with_capacity(order_by.len());
+        for &(ref expr, desc) in order_by {
+            let expr = match *expr {
+                Expr::Const(RawVal::Int(ordinal)) => {
+                    if ordinal < 1 || ordinal as usize > columns.len() {
+                        bail!(QueryError::InvalidQuery, "ORDER BY position {} is not in select list", ordinal)
+                    }
+                    columns[ordinal as usize - 1].clone()
+                }
+                ref expr => expr.clone(),
+            };
+            if let Some(name) = row_eval::unresolved_column(&expr, &columns) {
+                bail!(QueryError::InvalidQuery, "ORDER BY of UNION ALL references `{}`, which is not a column of the result", name);
+            }
+            resolved.push((expr, desc));
+        }
+        let rows = mem::replace(&mut union.rows, Vec::new());
+        union.rows = row_eval::sort_rows(rows, &resolved, &columns)?;
+    }
+    union.rows = mem::replace(&mut union.rows, Vec::new()).into_iter()
+        .skip(limit.offset as usize)
+        .take(limit.limit as usize)
+        .collect();
+    Ok(union)
+}
+
+fn int_to_float(rows: &mut [Vec<RawVal>], index: usize) {
+    for row in rows {
+        if let RawVal::Int(i) = row[index] {
+            row[index] = RawVal::Float(OrderedFloat(i as f64));
+        }
+    }
+}
+
 impl Drop for LocustDB {
     fn drop(&mut self) {
         self.inner_locustdb.stop();
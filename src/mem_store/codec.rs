@@ -13,6 +13,9 @@ pub struct Codec {
     is_order_preserving: bool,
     is_positive_integer: bool,
     is_fixed_width: bool,
+    /// Plans that compute the data sections referenced by `PushDataSection` for values that are not stored in a column.
+    #[ignore_heap_size_of = "Only used during query planning"]
+    computed_sections: Option<Vec<QueryPlan>>,
 }
 
 impl Codec {
@@ -35,6 +38,7 @@ impl Codec {
             is_order_preserving,
             is_positive_integer,
             is_fixed_width,
+            computed_sections: None,
         }
     }
 
@@ -48,6 +52,7 @@ impl Codec {
             is_order_preserving: true,
             is_positive_integer: true,
             is_fixed_width: true,
+            computed_sections: None,
         }
     }
 
@@ -78,9 +83,21 @@ impl Codec {
             is_order_preserving,
             is_positive_integer,
             is_fixed_width,
+            computed_sections: None,
         }
     }
 
+    /// Dictionary encoding with a dictionary that is computed by `dict_indices` and `dict_data`.
+    pub fn computed_dictionary(t: EncodingType, dict_indices: QueryPlan, dict_data: QueryPlan) -> Codec {
+        let mut codec = Codec::new(vec![
+            CodecOp::PushDataSection(0),
+            CodecOp::PushDataSection(1),
+            CodecOp::DictLookup(t),
+        ]);
+        codec.computed_sections = Some(vec![dict_indices, dict_data]);
+        codec
+    }
+
     pub fn with_lz4(&self, decoded_length: usize) -> Codec {
         let mut ops = vec![CodecOp::LZ4(self.encoding_type, decoded_length)];
         for &op in &self.ops {
@@ -123,12 +140,7 @@ impl Codec {
                         t,
                         EncodingType::I64))
                 }
-                CodecOp::PushDataSection(section_index) => {
-                    Box::new(QueryPlan::ReadColumnSection(
-                        self.column_name.to_string(),
-                        section_index,
-                        None))
-                }
+                CodecOp::PushDataSection(section_index) => self.section(section_index),
                 CodecOp::DictLookup(t) => {
                     let dict_data = stack.pop().unwrap();
                     let dict_indices = stack.pop().unwrap();
@@ -159,6 +171,7 @@ impl Codec {
             Codec::new(rest)
         };
         new_codec.set_column_name(&self.column_name);
+        new_codec.computed_sections = self.computed_sections.clone();
         (new_codec, self.decode_ops(&fixed_width, plan))
     }
 
@@ -171,19 +184,57 @@ impl Codec {
     pub fn is_elementwise_decodable(&self) -> bool { self.is_fixed_width }
     pub fn is_identity(&self) -> bool { self.ops.is_empty() }
 
+    /// Returns true if `other` maps encoded values to decoded values in the same way as this codec.
+    /// Data sections are specific to a column, so codecs that reference them are only equivalent for the same column.
+    pub fn is_equivalent(&self, other: &Codec) -> bool {
+        let references_data_section = self.ops.iter().any(|op| match *op {
+            CodecOp::PushDataSection(_) => true,
+            _ => false,
+        });
+        self.ops == other.ops
+            && !self.ops.contains(&CodecOp::Unknown)
+            && (!references_data_section || self.column_name == other.column_name)
+            && self.computed_sections.is_none() && other.computed_sections.is_none()
+    }
+
     pub fn encode_str(&self, string_const: Box<QueryPlan>) -> Box<QueryPlan> {
+        match self.dictionary() {
+            Some((dict_indices, dict_data)) =>
+                Box::new(QueryPlan::InverseDictLookup(dict_indices, dict_data, string_const)),
+            None => panic!("encode_str not supported for {:?}", &self.ops),
+        }
+    }
+
+    /// Returns a plan that determines which encoded values correspond to one of the `strings`,
+    /// or `None` if the set of encoded values can't be computed for this codec.
+    pub fn encode_str_set(&self, strings: Vec<String>) -> Option<Box<QueryPlan>> {
+        self.dictionary().map(|(dict_indices, dict_data)|
+            Box::new(QueryPlan::InverseDictLookupSet(dict_indices, dict_data, strings)))
+    }
+
+    /// Returns a plan that determines which encoded values match `regex`,
+    /// or `None` if the set of encoded values can't be computed for this codec.
+    pub fn encode_regex(&self, regex: &str) -> Option<Box<QueryPlan>> {
+        self.dictionary().map(|(dict_indices, dict_data)|
+            Box::new(QueryPlan::DictRegexMatch(dict_indices, dict_data, regex.to_string())))
+    }
+
+    /// Returns plans for the offset/length pairs and data of the dictionary if this codec is a plain dictionary lookup.
+    pub fn dictionary(&self) -> Option<(Box<QueryPlan>, Box<QueryPlan>)> {
         match self.ops[..] {
-            [CodecOp::PushDataSection(1), CodecOp::PushDataSection(2), CodecOp::DictLookup(_)] =>
-                Box::new(QueryPlan::InverseDictLookup(
-                    Box::new(QueryPlan::ReadColumnSection(
-                        self.column_name.to_string(), 1, None)),
-                    Box::new(QueryPlan::ReadColumnSection(
-                        self.column_name.to_string(), 2, None)),
-                    string_const)),
-            _ => panic!("encode_str not supported for {:?}", &self.ops),
+            [CodecOp::PushDataSection(dict_indices), CodecOp::PushDataSection(dict_data), CodecOp::DictLookup(_)] =>
+                Some((self.section(dict_indices), self.section(dict_data))),
+            _ => None,
         }
     }
 
+    fn section(&self, index: usize) -> Box<QueryPlan> {
+        Box::new(match self.computed_sections {
+            Some(ref sections) => sections[index].clone(),
+            None => QueryPlan::ReadColumnSection(self.column_name.to_string(), index, None),
+        })
+    }
+
     pub fn encode_int(&self, x: i64) -> RawVal {
         if let CodecOp::Add(_, y) = self.ops[0] {
             assert_eq!(self.ops.len(), 1);
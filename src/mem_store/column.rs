@@ -3,6 +3,7 @@ use std::fmt;
 use mem_store::*;
 use engine::typed_vec::AnyVec;
 use engine::types::*;
+use ingest::ordered_float::OrderedFloat;
 use mem_store::lz4;
 
 use heapsize::HeapSizeOf;
@@ -15,6 +16,7 @@ pub struct Column {
     range: Option<(i64, i64)>,
     codec: Codec,
     data: Vec<DataSection>,
+    nulls: Option<Vec<u8>>,
 }
 
 impl Column {
@@ -35,6 +37,7 @@ impl Column {
             range,
             codec,
             data,
+            nulls: None,
         }
     }
 
@@ -45,9 +48,15 @@ impl Column {
             range: None,
             codec: Codec::identity(BasicType::Null),
             data: vec![DataSection::Null(len)],
+            nulls: None,
         }
     }
 
+    /// Marks all rows for which `nulls` is 1 as NULL. The values stored for these rows are meaningless.
+    pub fn set_nulls(&mut self, nulls: Option<Vec<u8>>) {
+        self.nulls = nulls;
+    }
+
     pub fn lz4_encode(&mut self) {
         if cfg!(feature = "enable_lz4") {
             let (encoded, worth_it) = self.data[0].lz4_encode();
@@ -68,8 +77,14 @@ impl Column {
     pub fn full_type(&self) -> Type {
         Type::new(self.basic_type(), Some(self.codec()))
     }
+    pub fn nulls(&self) -> Option<&[u8]> { self.nulls.as_ref().map(|n| &n[..]) }
+    pub fn has_nulls(&self) -> bool { self.nulls.is_some() || self.basic_type() == BasicType::Null }
+    /// Index of the data section that contains the null map (1 for NULL values, 0 otherwise), if there is one.
+    pub fn null_map_section(&self) -> Option<usize> { self.nulls.as_ref().map(|_| self.data.len()) }
     pub fn data_sections(&self) -> Vec<&AnyVec> {
-        self.data.iter().map(|d| d.to_any_vec()).collect()
+        self.data.iter().map(|d| d.to_any_vec())
+            .chain(self.nulls.iter().map(|n| n as &AnyVec))
+            .collect()
     }
 
     pub fn mem_tree(&self, tree: &mut MemTreeColumn, depth: usize) {
@@ -124,6 +139,7 @@ pub enum DataSection {
     U32(Vec<u32>),
     U64(Vec<u64>),
     I64(Vec<i64>),
+    F64(Vec<OrderedFloat>),
     Null(usize),
 }
 
@@ -135,6 +151,7 @@ impl DataSection {
             DataSection::U32(ref x) => x,
             DataSection::U64(ref x) => x,
             DataSection::I64(ref x) => x,
+            DataSection::F64(ref x) => x,
             DataSection::Null(ref x) => x,
         }
     }
@@ -146,6 +163,7 @@ impl DataSection {
             DataSection::U32(ref x) => x.len(),
             DataSection::U64(ref x) => x.len(),
             DataSection::I64(ref x) => x.len(),
+            DataSection::F64(ref x) => x.len(),
             DataSection::Null(ref x) => *x,
         }
     }
@@ -157,6 +175,7 @@ impl DataSection {
             DataSection::U32(ref x) => x.capacity(),
             DataSection::U64(ref x) => x.capacity(),
             DataSection::I64(ref x) => x.capacity(),
+            DataSection::F64(ref x) => x.capacity(),
             DataSection::Null(ref x) => *x,
         }
     }
@@ -168,6 +187,7 @@ impl DataSection {
             DataSection::U32(_) => EncodingType::U32,
             DataSection::U64(_) => EncodingType::U64,
             DataSection::I64(_) => EncodingType::I64,
+            DataSection::F64(_) => EncodingType::F64,
             DataSection::Null(_) => EncodingType::Null,
         }
     }
@@ -205,6 +225,8 @@ impl DataSection {
                 let len = encoded.len();
                 (DataSection::U8(encoded), len * 100 < x.len() * 8 * min_reduction)
             }
+            // LZ4 decoding is not implemented for floats
+            DataSection::F64(ref x) => (DataSection::F64(x.clone()), false),
             DataSection::Null(ref x) => (DataSection::Null(*x), false)
         }
     }
@@ -217,6 +239,7 @@ impl DataSection {
                 DataSection::U32(ref mut x) => x.shrink_to_fit(),
                 DataSection::U64(ref mut x) => x.shrink_to_fit(),
                 DataSection::I64(ref mut x) => x.shrink_to_fit(),
+                DataSection::F64(ref mut x) => x.shrink_to_fit(),
                 DataSection::Null(_) => {}
             }
         }
@@ -231,6 +254,7 @@ impl HeapSizeOf for DataSection {
             DataSection::U32(ref x) => x.heap_size_of_children(),
             DataSection::U64(ref x) => x.heap_size_of_children(),
             DataSection::I64(ref x) => x.heap_size_of_children(),
+            DataSection::F64(ref x) => x.heap_size_of_children(),
             DataSection::Null(_) => 0,
         }
     }
@@ -271,3 +295,9 @@ impl From<Vec<i64>> for DataSection {
     }
 }
 
+impl From<Vec<OrderedFloat>> for DataSection {
+    fn from(vec: Vec<OrderedFloat>) -> Self {
+        assert_eq!(vec.len(), vec.capacity());
+        DataSection::F64(vec)
+    }
+}
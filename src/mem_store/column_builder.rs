@@ -5,6 +5,7 @@ use std::collections::hash_set::HashSet;
 use std::rc::Rc;
 use std::sync::Arc;
 
+use ingest::ordered_float::OrderedFloat;
 use mem_store::integers::*;
 use mem_store::column::*;
 use mem_store::strings::*;
@@ -12,6 +13,7 @@ use mem_store::strings::*;
 
 pub trait ColumnBuilder<T: ?Sized> {
     fn push(&mut self, elem: &T);
+    fn push_null(&mut self);
     fn finalize(self, name: &str) -> Arc<Column>;
 }
 
@@ -37,14 +39,61 @@ impl ColumnBuilder<str> for StringColBuilder {
         self.uniques.insert(str_opt);
     }
 
+    fn push_null(&mut self) {
+        self.data.push(None);
+        self.uniques.insert(None);
+    }
+
     fn finalize(self, name: &str) -> Arc<Column> {
         build_string_column(name, &self.data, self.uniques)
     }
 }
 
 
+/// Builds string columns from borrowed values without copying them, see `fast_build_string_column`.
+pub struct FastStringColBuilder<'a> {
+    data: Vec<&'a str>,
+    nulls: Option<Vec<u8>>,
+    all_null: bool,
+}
+
+impl<'a> FastStringColBuilder<'a> {
+    pub fn new() -> FastStringColBuilder<'a> {
+        FastStringColBuilder {
+            data: Vec::new(),
+            nulls: None,
+            all_null: true,
+        }
+    }
+}
+
+impl<'a> ColumnBuilder<&'a str> for FastStringColBuilder<'a> {
+    fn push(&mut self, elem: &&'a str) {
+        self.all_null = false;
+        if let Some(ref mut nulls) = self.nulls {
+            nulls.push(0);
+        }
+        self.data.push(*elem);
+    }
+
+    fn push_null(&mut self) {
+        let len = self.data.len();
+        self.nulls.get_or_insert_with(|| vec![0; len]).push(1);
+        self.data.push("");
+    }
+
+    fn finalize(self, name: &str) -> Arc<Column> {
+        if self.all_null {
+            return Arc::new(Column::null(name, self.data.len()));
+        }
+        fast_build_string_column(name, self.data.iter().cloned(), self.data.len(), self.nulls)
+    }
+}
+
+
 pub struct IntColBuilder {
     data: Vec<i64>,
+    nulls: Option<Vec<u8>>,
     min: i64,
     max: i64,
     increasing: u64,
@@ -56,6 +105,7 @@ impl IntColBuilder {
     pub fn new() -> IntColBuilder {
         IntColBuilder {
             data: Vec::new(),
+            nulls: None,
             min: i64::MAX,
             max: i64::MIN,
             increasing: 0,
@@ -77,14 +127,80 @@ impl ColumnBuilder<i64> for IntColBuilder {
             self.allow_delta_encode = false;
         };
         self.last = elem;
+        if let Some(ref mut nulls) = self.nulls {
+            nulls.push(0);
+        }
         self.data.push(elem);
     }
 
-    fn finalize(self, name: &str) -> Arc<Column> {
+    fn push_null(&mut self) {
+        let len = self.data.len();
+        self.nulls.get_or_insert_with(|| vec![0; len]).push(1);
+        self.data.push(0);
+    }
+
+    fn finalize(mut self, name: &str) -> Arc<Column> {
+        if let Some(ref mut nulls) = self.nulls {
+            if self.min > self.max {
+                return Arc::new(Column::null(name, self.data.len()));
+            }
+            // Store NULL values as the smallest value of the column so they don't widen the range of the encoding
+            for (value, &null) in self.data.iter_mut().zip(nulls.iter()) {
+                if null == 1 { *value = self.min }
+            }
+            nulls.shrink_to_fit();
+        }
         // TODO(clemens): heuristic for deciding delta encoding could probably be improved
-        let delta_encode = self.allow_delta_encode &&
+        let delta_encode = self.allow_delta_encode && self.nulls.is_none() &&
             (self.increasing * 10 > self.data.len() as u64 * 9 && cfg!(feature = "enable_lz4"));
-        IntegerColumn::new_boxed(name, self.data, self.min, self.max, delta_encode)
+        IntegerColumn::new_boxed(name, self.data, self.min, self.max, delta_encode, self.nulls)
+    }
+}
+
+
+pub struct FloatColBuilder {
+    data: Vec<OrderedFloat>,
+    nulls: Option<Vec<u8>>,
+    all_null: bool,
+}
+
+impl FloatColBuilder {
+    pub fn new() -> FloatColBuilder {
+        FloatColBuilder {
+            data: Vec::new(),
+            nulls: None,
+            all_null: true,
+        }
+    }
+}
+
+impl ColumnBuilder<f64> for FloatColBuilder {
+    #[inline]
+    fn push(&mut self, elem: &f64) {
+        self.all_null = false;
+        if let Some(ref mut nulls) = self.nulls {
+            nulls.push(0);
+        }
+        self.data.push(OrderedFloat(*elem));
+    }
+
+    fn push_null(&mut self) {
+        let len = self.data.len();
+        self.nulls.get_or_insert_with(|| vec![0; len]).push(1);
+        self.data.push(OrderedFloat(0.0));
+    }
+
+    fn finalize(mut self, name: &str) -> Arc<Column> {
+        if self.all_null {
+            return Arc::new(Column::null(name, self.data.len()));
+        }
+        if let Some(ref mut nulls) = self.nulls {
+            nulls.shrink_to_fit();
+        }
+        self.data.shrink_to_fit();
+        let mut column = Column::new(name, self.data.len(), None, vec![], vec![DataSection::F64(self.data)]);
+        column.set_nulls(self.nulls);
+        Arc::new(column)
     }
 }
 
@@ -9,7 +9,12 @@ use mem_store::*;
 pub struct IntegerColumn;
 
 impl IntegerColumn {
-    pub fn new_boxed(name: &str, mut values: Vec<i64>, mut min: i64, mut max: i64, delta_encode: bool) -> Arc<Column> {
+    pub fn new_boxed(name: &str,
+                     mut values: Vec<i64>,
+                     mut min: i64,
+                     mut max: i64,
+                     delta_encode: bool,
+                     nulls: Option<Vec<u8>>) -> Arc<Column> {
         let original_range = Some((min, max));
         let min0 = min;
         let max0 = max;
@@ -56,6 +61,7 @@ impl IntegerColumn {
                     vec![DataSection::I64(values)])
             }
         };
+        column.set_nulls(nulls);
         column.lz4_encode();
         Arc::new(column)
     }
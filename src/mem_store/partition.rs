@@ -15,6 +15,7 @@ pub struct Partition {
     id: PartitionID,
     len: usize,
     cols: Vec<(ColumnKey, Mutex<ColumnHandle>)>,
+    nullable_cols: HashSet<String>,
     lru: LRU,
 }
 
@@ -38,6 +39,10 @@ impl Partition {
         Partition {
             id,
             len: cols[0].len(),
+            nullable_cols: cols.iter()
+                .filter(|c| c.has_nulls())
+                .map(|c| c.name().to_string())
+                .collect(),
             cols: cols.into_iter()
                 .map(|c| {
                     let key = (id, c.name().to_string());
@@ -49,13 +54,14 @@ impl Partition {
         }
     }
 
-    pub fn nonresident(id: PartitionID, len: usize, cols: &[String], lru: LRU) -> Partition {
+    pub fn nonresident(id: PartitionID, len: usize, cols: &[String], nullable_cols: &[String], lru: LRU) -> Partition {
         Partition {
             id,
             len,
             cols: cols.iter()
                 .map(|name| ((id, name.to_string()), Mutex::new(ColumnHandle::NonResident)))
                 .collect(),
+            nullable_cols: nullable_cols.iter().cloned().collect(),
             lru,
         }
     }
@@ -99,6 +105,9 @@ impl Partition {
         names
     }
 
+    /// Names of all columns that contain NULL values.
+    pub fn nullable_cols(&self) -> &HashSet<String> { &self.nullable_cols }
+
     pub fn restore(&self, col: Arc<Column>) {
         for (key, c) in &self.cols {
             if key.1 == col.name() {
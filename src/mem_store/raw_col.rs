@@ -2,6 +2,7 @@ use std::iter::repeat;
 use std::ops::BitOr;
 use std::sync::Arc;
 
+use ingest::ordered_float::OrderedFloat;
 use ingest::raw_val::RawVal;
 use mem_store::*;
 use mem_store::column_builder::*;
@@ -58,7 +59,19 @@ impl MixedCol {
                 match v {
                     RawVal::Str(s) => builder.push(&s),
                     RawVal::Int(i) => builder.push(&i.to_string()),
-                    RawVal::Null => builder.push(""),
+                    RawVal::Float(f) => builder.push(&f.to_string()),
+                    RawVal::Null => builder.push_null(),
+                }
+            }
+            builder.finalize(name)
+        } else if self.types.contains_float {
+            let mut builder = FloatColBuilder::new();
+            for v in self.data {
+                match v {
+                    RawVal::Str(_) => panic!("Unexpected string in float column!"),
+                    RawVal::Int(i) => builder.push(&(i as f64)),
+                    RawVal::Float(f) => builder.push(&f.0),
+                    RawVal::Null => builder.push_null(),
                 }
             }
             builder.finalize(name)
@@ -67,8 +80,9 @@ impl MixedCol {
             for v in self.data {
                 match v {
                     RawVal::Str(_) => panic!("Unexpected string in int column!"),
+                    RawVal::Float(_) => panic!("Unexpected float in int column!"),
                     RawVal::Int(i) => builder.push(&i),
-                    RawVal::Null => builder.push(&0),
+                    RawVal::Null => builder.push_null(),
                 }
             }
             builder.finalize(name)
@@ -82,35 +96,41 @@ impl MixedCol {
 struct ColType {
     contains_string: bool,
     contains_int: bool,
+    contains_float: bool,
     contains_null: bool,
 }
 
 impl ColType {
-    fn new(string: bool, int: bool, null: bool) -> ColType {
-        ColType { contains_string: string, contains_int: int, contains_null: null }
+    fn new(string: bool, int: bool, float: bool, null: bool) -> ColType {
+        ColType { contains_string: string, contains_int: int, contains_float: float, contains_null: null }
     }
 
     fn string() -> ColType {
-        ColType::new(true, false, false)
+        ColType::new(true, false, false, false)
     }
 
     fn int() -> ColType {
-        ColType::new(false, true, false)
+        ColType::new(false, true, false, false)
+    }
+
+    fn float() -> ColType {
+        ColType::new(false, false, true, false)
     }
 
     fn null() -> ColType {
-        ColType::new(false, false, true)
+        ColType::new(false, false, false, true)
     }
 
     fn nothing() -> ColType {
-        ColType::new(false, false, false)
+        ColType::new(false, false, false, false)
     }
 
     fn determine(v: &RawVal) -> ColType {
         match *v {
             RawVal::Null => ColType::null(),
             RawVal::Str(_) => ColType::string(),
-            RawVal::Int(_) => ColType::int()
+            RawVal::Int(_) => ColType::int(),
+            RawVal::Float(_) => ColType::float(),
         }
     }
 }
@@ -121,6 +141,7 @@ impl BitOr for ColType {
         ColType {
             contains_string: self.contains_string | rhs.contains_string,
             contains_int: self.contains_int | rhs.contains_int,
+            contains_float: self.contains_float | rhs.contains_float,
             contains_null: self.contains_null | rhs.contains_null,
         }
     }
@@ -134,3 +155,7 @@ impl<'a> From<&'a str> for RawVal {
 impl From<i64> for RawVal {
     fn from(val: i64) -> RawVal { RawVal::Int(val) }
 }
+
+impl From<f64> for RawVal {
+    fn from(val: f64) -> RawVal { RawVal::Float(OrderedFloat(val)) }
+}
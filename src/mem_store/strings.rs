@@ -20,7 +20,11 @@ type HashSetSea<K> = HashSet<K, BuildHasherDefault<SeaHasher>>;
 
 const DICTIONARY_RATIO: usize = 2;
 
-pub fn fast_build_string_column<'a, T: Iterator<Item=&'a str> + Clone>(name: &str, strings: T, len: usize) -> Arc<Column> {
+/// `nulls` marks NULL values, which are stored as the empty string.
+pub fn fast_build_string_column<'a, T: Iterator<Item=&'a str> + Clone>(name: &str,
+                                                                       strings: T,
+                                                                       len: usize,
+                                                                       nulls: Option<Vec<u8>>) -> Arc<Column> {
     let mut unique_values = HashSetSea::default();
     for s in strings.clone() {
         unique_values.insert(s);
@@ -36,6 +40,7 @@ pub fn fast_build_string_column<'a, T: Iterator<Item=&'a str> + Clone>(name: &st
                 vec![DataSection::U8(packed.into_vec())],
             );
             column.lz4_encode();
+            column.set_nulls(nulls);
             return Arc::new(column);
         }
     }
@@ -82,6 +87,7 @@ pub fn fast_build_string_column<'a, T: Iterator<Item=&'a str> + Clone>(name: &st
                  DataSection::U8(dictionary_data)])
     };
     column.lz4_encode();
+    column.set_nulls(nulls);
     Arc::new(column)
 }
 
@@ -89,46 +95,57 @@ pub fn build_string_column(name: &str,
                            values: &[Option<Rc<String>>],
                            unique_values: UniqueValues<Option<Rc<String>>>)
                            -> Arc<Column> {
-    if let Some(u) = unique_values.get_values() {
+    let nulls = if values.iter().any(Option::is_none) {
+        if values.iter().all(Option::is_none) {
+            return Arc::new(Column::null(name, values.len()));
+        }
+        Some(values.iter().map(|v| v.is_none() as u8).collect::<Vec<u8>>())
+    } else {
+        None
+    };
+    let mut column = if let Some(u) = unique_values.get_values() {
 // TODO(clemens): constant column when there is only one value
         if u.len() <= From::from(u8::MAX) {
             let (indices, dictionary_indices, dictionary_data) = dictionary_compress::<u8>(values, u);
-            Arc::new(Column::new(
+            Column::new(
                 name,
                 indices.len(),
                 Some((0, dictionary_indices.len() as i64)),
                 dict_codec(EncodingType::U8),
                 vec![DataSection::U8(indices),
                      DataSection::U64(dictionary_indices),
-                     DataSection::U8(dictionary_data)]))
+                     DataSection::U8(dictionary_data)])
         } else {
             let (indices, dictionary_indices, dictionary_data) = dictionary_compress::<u16>(values, u);
-            Arc::new(Column::new(
+            Column::new(
                 name,
                 indices.len(),
                 Some((0, dictionary_indices.len() as i64)),
                 dict_codec(EncodingType::U16),
                 vec![DataSection::U16(indices),
                      DataSection::U64(dictionary_indices),
-                     DataSection::U8(dictionary_data)]))
+                     DataSection::U8(dictionary_data)])
         }
     } else {
         let packed = PackedStrings::from_nullable_strings(values);
-        Arc::new(Column::new(
+        Column::new(
             name,
             values.len(),
             None,
             string_pack_codec(),
-            vec![DataSection::U8(packed.into_vec())]))
-    }
+            vec![DataSection::U8(packed.into_vec())])
+    };
+    column.set_nulls(nulls);
+    Arc::new(column)
 }
 
+/// NULL values are encoded as the empty string, the column marks them as NULL separately.
 pub fn dictionary_compress<T: PrimInt>(strings: &[Option<Rc<String>>],
                                        unique_values: HashSet<Option<Rc<String>>>)
                                        -> (Vec<T>, Vec<u64>, Vec<u8>) {
-// TODO(clemens): handle null values
-    let mut mapping = unique_values.into_iter().map(|o| o.unwrap()).collect::<Vec<_>>();
+    let mut mapping = unique_values.into_iter().map(Option::unwrap_or_default).collect::<Vec<_>>();
     mapping.sort();
+    mapping.dedup();
     let mut packed_mapping = IndexedPackedStrings::default();
     for s in mapping {
         packed_mapping.push(&s);
@@ -140,7 +157,7 @@ pub fn dictionary_compress<T: PrimInt>(strings: &[Option<Rc<String>>],
             reverse_mapping.insert(string, index);
             index = index + T::one();
         }
-        strings.iter().map(|o| reverse_mapping[o.clone().unwrap().as_ref().as_str()]).collect()
+        strings.iter().map(|o| reverse_mapping[o.as_ref().map_or("", |s| s.as_str())]).collect()
     };
     let (dictionary_indices, dictionary_data) = packed_mapping.into_parts();
     (encoded_values, dictionary_indices, dictionary_data)
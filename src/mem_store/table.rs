@@ -1,3 +1,4 @@
+use std::cmp;
 use std::collections::HashMap;
 use std::mem;
 use std::ops::DerefMut;
@@ -33,6 +34,26 @@ impl Table {
         }
     }
 
+    /// Creates a table that is not registered with the database and holds `rows` in a single partition, e.g. the
+    /// result of a subquery.
+    pub fn temporary(name: &str, colnames: &[String], rows: Vec<Vec<RawVal>>) -> Table {
+        let table = Table::new(cmp::max(rows.len(), 1), name, LRU::default());
+        // Columns with duplicate names are only stored once
+        let unique = (0..colnames.len())
+            .filter(|&i| !colnames[..i].contains(&colnames[i]))
+            .collect::<Vec<_>>();
+        let mut columns = unique.iter()
+            .map(|&i| (colnames[i].to_string(), Vec::with_capacity(rows.len())))
+            .collect::<HashMap<_, _>>();
+        for row in rows {
+            for &i in &unique {
+                columns.get_mut(&colnames[i]).unwrap().push(row[i].clone());
+            }
+        }
+        table.ingest_heterogeneous(columns);
+        table
+    }
+
     pub fn name(&self) -> &str {
         &self.name
     }
@@ -64,7 +85,7 @@ impl Table {
     }
 
     pub fn insert_nonresident_partition(&self, md: &PartitionMetadata) {
-        let partition = Arc::new(Partition::nonresident(md.id, md.len, &md.columns, self.lru.clone()));
+        let partition = Arc::new(Partition::nonresident(md.id, md.len, &md.columns, &md.nullable_columns, self.lru.clone()));
         let mut partitions = self.partitions.write().unwrap();
         partitions.insert(md.id, partition);
     }
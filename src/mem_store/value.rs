@@ -1,6 +1,7 @@
 use std::fmt;
 use heapsize::HeapSizeOf;
 use std::convert::From;
+use ingest::ordered_float::OrderedFloat;
 use ingest::raw_val::RawVal;
 
 #[derive(Debug, PartialEq, Eq, Ord, PartialOrd, Clone, Hash)]
@@ -8,6 +9,7 @@ pub enum Val<'a> {
     Null,
     Bool(bool),
     Integer(i64),
+    Float(OrderedFloat),
     Str(&'a str),
 }
 
@@ -18,6 +20,7 @@ impl<'a> fmt::Display for Val<'a> {
             Val::Null => write!(f, "null"),
             Val::Bool(b) => write!(f, "{}", b),
             Val::Integer(i) => write!(f, "{}", i),
+            Val::Float(x) => write!(f, "{}", x),
             Val::Str(s) => write!(f, "\"{}\"", s),
         }
     }
@@ -27,7 +30,7 @@ impl<'a> HeapSizeOf for Val<'a> {
     fn heap_size_of_children(&self) -> usize {
         use self::Val::*;
         match *self {
-            Null | Bool(_) | Integer(_) => 0,
+            Null | Bool(_) | Integer(_) | Float(_) => 0,
             Str(r) => r.heap_size_of_children(),
         }
     }
@@ -72,6 +75,7 @@ impl<'a, 'b> From<&'a Val<'b>> for RawVal {
     fn from(val: &Val) -> RawVal {
         match *val {
             Val::Integer(b) => RawVal::Int(b),
+            Val::Float(f) => RawVal::Float(f),
             Val::Str(s) => RawVal::Str(s.to_string()),
             Val::Null | Val::Bool(_) => RawVal::Null,
         }
@@ -77,9 +77,19 @@ impl InnerLocustDB {
         thread::spawn(move || InnerLocustDB::enforce_mem_limit(cloned));
     }
 
+    /// Returns the partitions of `table`, or of all tables starting with `prefix` if `table` is a wildcard `prefix*`.
     pub fn snapshot(&self, table: &str) -> Option<Vec<Arc<Partition>>> {
         let tables = self.tables.read().unwrap();
-        tables.get(table).map(|t| t.snapshot())
+        if !table.ends_with('*') {
+            return tables.get(table).map(|t| t.snapshot());
+        }
+        let prefix = &table[..table.len() - 1];
+        let mut matched = tables.values().filter(|t| t.name().starts_with(prefix)).collect::<Vec<_>>();
+        if matched.is_empty() {
+            return None;
+        }
+        matched.sort_by(|a, b| a.name().cmp(b.name()));
+        Some(matched.into_iter().flat_map(|t| t.snapshot()).collect())
     }
 
     pub fn stop(&self) {
@@ -1,35 +1,80 @@
+use engine::aggregator::Aggregator;
 use ingest::raw_val::RawVal;
 use self::Expr::*;
 use std::collections::HashSet;
 
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Expr {
     ColName(String),
     Const(RawVal),
     Func1(Func1Type, Box<Expr>),
     Func2(Func2Type, Box<Expr>, Box<Expr>),
+    In(Box<Expr>, Vec<Expr>),
+    /// `if(cond, a, b)`, which is `a` in rows where `cond` is true and `b` in all other rows.
+    /// `CASE WHEN` expressions are represented as nested `If` expressions.
+    If(Box<Expr>, Box<Expr>, Box<Expr>),
+    Aggregate(Aggregator, Box<Expr>),
+    /// Query parameter (`?`, or `$name` if it has a name) with its index in the arguments of the query.
+    /// Parameters are replaced by constants before the query is executed, see `Query::bind`.
+    Parameter(usize, Option<String>),
+    /// Current time in seconds since the epoch, which is fixed when the query is executed.
+    Now,
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub enum Func2Type {
     Equals,
     NotEquals,
     LT,
+    LTE,
     GT,
+    GTE,
     And,
     Or,
     Add,
     Subtract,
     Multiply,
     Divide,
+    Modulo,
     RegexMatch,
+    Like,
+    ILike,
+    Concat,
+    /// Arguments of two-argument aggregates such as `covar(x, y)`, which can't be evaluated on their own.
+    Pair,
+    /// Value of the first argument in rows where the second argument is true, and NULL in all other rows.
+    /// Represents the `FILTER (WHERE ...)` clause of aggregates.
+    Filtered,
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq)]
 pub enum Func1Type {
     Negate,
+    Not,
     ToYear,
+    ToMonth,
+    ToDay,
+    ToHour,
+    DayOfWeek,
+    DateTrunc(TimeUnit),
+    IsNull,
+    IsNotNull,
+    Lower,
+    Upper,
+    Length,
+    /// `substr(expr, start, length)` with constant `start` and `length`.
+    Substr(i64, Option<i64>),
+}
+
+/// Unit that `date_trunc` rounds timestamps down to.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum TimeUnit {
+    Hour,
+    Day,
+    /// Weeks start on Monday.
+    Week,
+    Month,
 }
 
 impl Expr {
@@ -42,8 +87,153 @@ impl Expr {
                 expr1.add_colnames(result);
                 expr2.add_colnames(result);
             }
-            Func1(_, ref expr) => expr.add_colnames(result),
-            Const(_) => {}
+            Func1(_, ref expr) | Aggregate(_, ref expr) => expr.add_colnames(result),
+            In(ref expr, ref items) => {
+                expr.add_colnames(result);
+                for item in items {
+                    item.add_colnames(result);
+                }
+            }
+            If(ref cond, ref expr1, ref expr2) => {
+                cond.add_colnames(result);
+                expr1.add_colnames(result);
+                expr2.add_colnames(result);
+            }
+            Const(_) | Parameter(_, _) | Now => {}
+        }
+    }
+
+    /// Appends the names of all columns that cause `self` to evaluate to NULL whenever they are NULL.
+    pub fn add_nullable_colnames(&self, result: &mut HashSet<String>) {
+        match *self {
+            ColName(ref name) => {
+                result.insert(name.to_string());
+            }
+            // `NULL AND false` is false and `NULL OR true` is true
+            Func2(Func2Type::And, _, _) | Func2(Func2Type::Or, _, _) => {}
+            // Rows in which the predicate is NULL are filtered out, see `add_conditionals`
+            Func2(Func2Type::Filtered, ref expr, _) => expr.add_nullable_colnames(result),
+            Func2(_, ref expr1, ref expr2) => {
+                expr1.add_nullable_colnames(result);
+                expr2.add_nullable_colnames(result);
+            }
+            Func1(Func1Type::IsNull, _) | Func1(Func1Type::IsNotNull, _) => {}
+            Func1(_, ref expr) => expr.add_nullable_colnames(result),
+            In(ref expr, ref items) => {
+                expr.add_nullable_colnames(result);
+                for item in items {
+                    item.add_nullable_colnames(result);
+                }
+            }
+            // Whether the result is NULL depends on the branch that is taken, see `add_conditionals`
+            If(_, _, _) | Aggregate(_, _) | Const(_) | Parameter(_, _) | Now => {}
+        }
+    }
+
+    /// Appends all `Func2Type::Filtered` and `If` subexpressions that can cause `self` to evaluate to NULL depending
+    /// on the value of their condition, as well as integer divisions and modulos that are NULL if their divisor is 0.
+    pub fn add_conditionals(&self, result: &mut Vec<Expr>) {
+        match *self {
+            Func2(Func2Type::And, _, _) | Func2(Func2Type::Or, _, _) => {}
+            Func2(Func2Type::Filtered, ref expr, _) => {
+                expr.add_conditionals(result);
+                result.push(self.clone());
+            }
+            Func2(Func2Type::Divide, ref expr1, ref expr2) | Func2(Func2Type::Modulo, ref expr1, ref expr2) => {
+                expr1.add_conditionals(result);
+                expr2.add_conditionals(result);
+                let mut colnames = HashSet::new();
+                self.add_colnames(&mut colnames);
+                match **expr2 {
+                    Const(RawVal::Int(divisor)) if divisor != 0 => {}
+                    Const(RawVal::Float(_)) => {}
+                    // Constant expressions are evaluated to NULL directly
+                    _ if colnames.is_empty() => {}
+                    _ => result.push(self.clone()),
+                }
+            }
+            Func2(_, ref expr1, ref expr2) => {
+                expr1.add_conditionals(result);
+                expr2.add_conditionals(result);
+            }
+            Func1(Func1Type::IsNull, _) | Func1(Func1Type::IsNotNull, _) => {}
+            Func1(_, ref expr) => expr.add_conditionals(result),
+            In(ref expr, ref items) => {
+                expr.add_conditionals(result);
+                for item in items {
+                    item.add_conditionals(result);
+                }
+            }
+            If(_, _, _) => result.push(self.clone()),
+            ColName(_) | Aggregate(_, _) | Const(_) | Parameter(_, _) | Now => {}
+        }
+    }
+
+    /// Appends all aggregate subexpressions (e.g. `count(1)` in `count(1) > 10`) to `result`.
+    pub fn add_aggregates(&self, result: &mut Vec<(Aggregator, Expr)>) {
+        match *self {
+            Aggregate(aggregator, ref expr) => result.push((aggregator, (**expr).clone())),
+            Func2(_, ref expr1, ref expr2) => {
+                expr1.add_aggregates(result);
+                expr2.add_aggregates(result);
+            }
+            Func1(_, ref expr) => expr.add_aggregates(result),
+            In(ref expr, ref items) => {
+                expr.add_aggregates(result);
+                for item in items {
+                    item.add_aggregates(result);
+                }
+            }
+            If(ref cond, ref expr1, ref expr2) => {
+                cond.add_aggregates(result);
+                expr1.add_aggregates(result);
+                expr2.add_aggregates(result);
+            }
+            ColName(_) | Const(_) | Parameter(_, _) | Now => {}
+        }
+    }
+
+    /// Appends the index and name of all parameters to `result`.
+    pub fn add_parameters(&self, result: &mut Vec<(usize, Option<String>)>) {
+        match *self {
+            Parameter(index, ref name) => result.push((index, name.clone())),
+            Func2(_, ref expr1, ref expr2) => {
+                expr1.add_parameters(result);
+                expr2.add_parameters(result);
+            }
+            Func1(_, ref expr) | Aggregate(_, ref expr) => expr.add_parameters(result),
+            In(ref expr, ref items) => {
+                expr.add_parameters(result);
+                for item in items {
+                    item.add_parameters(result);
+                }
+            }
+            If(ref cond, ref expr1, ref expr2) => {
+                cond.add_parameters(result);
+                expr1.add_parameters(result);
+                expr2.add_parameters(result);
+            }
+            ColName(_) | Const(_) | Now => {}
+        }
+    }
+
+    /// Replaces all subexpressions for which `f` returns a replacement.
+    pub fn substitute(self, f: &Fn(&Expr) -> Option<Expr>) -> Expr {
+        if let Some(replacement) = f(&self) {
+            return replacement;
+        }
+        match self {
+            Func1(ftype, box expr) => Func1(ftype, Box::new(expr.substitute(f))),
+            Func2(ftype, box expr1, box expr2) => Func2(ftype, Box::new(expr1.substitute(f)), Box::new(expr2.substitute(f))),
+            In(box expr, items) => In(
+                Box::new(expr.substitute(f)),
+                items.into_iter().map(|item| item.substitute(f)).collect()),
+            If(box cond, box expr1, box expr2) => If(
+                Box::new(cond.substitute(f)),
+                Box::new(expr1.substitute(f)),
+                Box::new(expr2.substitute(f))),
+            Aggregate(aggregator, box expr) => Aggregate(aggregator, Box::new(expr.substitute(f))),
+            expr => expr,
         }
     }
 
@@ -1,142 +1,919 @@
 #![allow(unused_parens)]
 
 use engine::aggregator::Aggregator;
+use engine::date_function;
+use engine::hyperloglog;
 use engine::query::*;
+use errors::SyntaxError;
+use ingest::ordered_float::OrderedFloat;
 use ingest::raw_val::RawVal;
-use nom::{digit, is_alphabetic, is_alphanumeric, multispace};
+use nom::{digit, is_alphabetic, is_alphanumeric, multispace, IResult};
 use std::boxed::Box;
+use std::cell::RefCell;
+use std::cmp;
+use std::collections::HashMap;
 use std::str;
 use std::str::FromStr;
+use std::usize;
 use syntax::expression::*;
 use syntax::limit::LimitClause;
-use time;
 
 
-named!(pub parse_query<&[u8], Query>, alt_complete!(full_query | simple_query));
+/// Applies `$submac` and, if it fails, records `$expected` as a token that would have been valid at the start of the
+/// input. Syntax errors are reported at the furthest position at which parsing failed, see `parse`.
+macro_rules! expect (
+    ($i:expr, $expected:expr, $submac:ident!( $($args:tt)* )) => (
+        {
+            let result = $submac!($i, $($args)*);
+            if let IResult::Error(_) = result {
+                record_failure($i, $expected);
+            }
+            result
+        }
+    );
+    ($i:expr, $expected:expr, $f:expr) => (
+        expect!($i, $expected, call!($f));
+    );
+);
+
+named!(pub parse_query<&[u8], Query>, map!(alt_complete!(full_query | simple_query), number_parameters));
+
+/// Parses a complete query, reporting the location of syntax errors.
+pub fn parse(query: &str) -> Result<Query, SyntaxError> {
+    // Parsers return `Incomplete` when they reach the end of their input, so the query is terminated by a character
+    // that can't be part of any token
+    if let Some(offset) = query.find(END_OF_QUERY as char) {
+        return Err(SyntaxError::new(query, offset, vec![]));
+    }
+    let mut input = query.as_bytes().to_vec();
+    input.push(END_OF_QUERY);
+    FURTHEST_FAILURE.with(|failure| *failure.borrow_mut() = None);
+    if let IResult::Done(remaining, parsed) = parse_query(&input) {
+        if remaining == &[END_OF_QUERY][..] {
+            return Ok(parsed);
+        }
+        record_failure(remaining, "end of query");
+    }
+    let (remaining, expected) = FURTHEST_FAILURE.with(|failure| failure.borrow_mut().take())
+        .unwrap_or((input.len(), vec![]));
+    let offset = cmp::min(input.len() - remaining, query.len());
+    Err(SyntaxError::new(query, offset, expected.iter().map(|s| s.to_string()).collect()))
+}
+
+/// Terminates the input of the parsers, see `parse`. Queries containing this character are rejected.
+const END_OF_QUERY: u8 = b'\0';
+
+thread_local! {
+    /// The furthest position at which any parser failed, given by the length of the remaining input, and the tokens
+    /// that would have been valid at that position.
+    static FURTHEST_FAILURE: RefCell<Option<(usize, Vec<&'static str>)>> = RefCell::new(None);
+}
+
+/// Records that `expected` would have been valid at the start of `input` (ignoring whitespace), unless parsing
+/// already failed at a later position.
+fn record_failure(input: &[u8], expected: &'static str) {
+    let remaining = skip_whitespace(input).len();
+    FURTHEST_FAILURE.with(|failure| {
+        let mut failure = failure.borrow_mut();
+        let furthest = failure.as_ref().map_or(usize::MAX, |&(furthest, _)| furthest);
+        if remaining < furthest {
+            *failure = Some((remaining, vec![expected]));
+        } else if remaining == furthest {
+            let tokens = &mut failure.as_mut().unwrap().1;
+            if !tokens.contains(&expected) {
+                tokens.push(expected);
+            }
+        }
+    })
+}
+
+fn skip_whitespace(input: &[u8]) -> &[u8] {
+    match multispace(input) {
+        IResult::Done(rest, _) => rest,
+        _ => input,
+    }
+}
+
+/// Number of rows returned by queries without LIMIT clause. Subqueries return all rows by default.
+const DEFAULT_LIMIT: u64 = 100;
 
 named!(full_query<&[u8], Query>,
     do_parse!(
         opt!(multispace) >>
-        tag_no_case!("select") >>
+        query: call!(union_statement, DEFAULT_LIMIT) >>
+        opt!(multispace) >>
+        expect!("`;`", char!(';')) >>
+        (query)
+    )
+);
+
+/// Select statement followed by any number of UNION ALL select statements and ORDER BY and LIMIT clauses, which
+/// return at most `default_limit` rows if there is no LIMIT clause.
+named_args!(union_statement(default_limit: u64)<Query>,
+    do_parse!(
+        statement: select_statement >>
+        union_all: many0!(complete!(preceded!(expect!("UNION ALL", union_all_keyword), select_statement))) >>
+        opt!(multispace) >>
+        order_by: opt!(order_by_clause) >>
+        opt!(multispace) >>
+        limit_by: opt!(complete!(limit_by_clause)) >>
+        opt!(multispace) >>
+        limit: opt!(limit_clause) >>
+        (construct_union(statement, union_all, order_by, limit_by,
+                         limit.unwrap_or(LimitClause { limit: default_limit, offset: 0 })))
+    )
+);
+
+named!(union_all_keyword<&[u8], ()>,
+    do_parse!(
+        opt!(multispace) >>
+        tag_no_case!("union") >>
+        multispace >>
+        tag_no_case!("all") >>
+        multispace >>
+        (())
+    )
+);
+
+named!(select_statement<&[u8], SelectStatement>,
+    do_parse!(
+        with: opt!(with_clause) >>
+        expect!("SELECT", tag_no_case!("select")) >>
         multispace >>
+        distinct: opt!(distinct) >>
         select: select_clauses >>
         opt!(multispace) >>
-        table: from_clause >>
-        multispace >>
-        tag_no_case!("where") >>
+        from: from_clause >>
+        filter: opt!(preceded!(multispace, where_clause)) >>
+        opt!(multispace) >>
+        group_by: opt!(group_by_clause) >>
+        opt!(multispace) >>
+        having: opt!(having_clause) >>
+        (SelectStatement {
+            with,
+            select,
+            distinct: distinct.is_some(),
+            from,
+            filter: filter.unwrap_or(Expr::Const(RawVal::Int(1))),
+            group_by,
+            having,
+        })
+    )
+);
+
+named!(with_clause<&[u8], Vec<(String, Query)>>,
+    do_parse!(
+        tag_no_case!("with") >>
         multispace >>
-        filter: expr >>
+        subqueries: separated_nonempty_list!(
+            delimited!(opt!(multispace), char!(','), opt!(multispace)),
+            common_table_expression) >>
         opt!(multispace) >>
-        order_by: opt!(order_by_clause) >>
+        (subqueries)
+    )
+);
+
+named!(common_table_expression<&[u8], (String, Query)>,
+    do_parse!(
+        name: expect!("name", identifier) >>
         opt!(multispace) >>
-        limit: opt!(limit_clause) >>
+        expect!("AS", terminated!(tag_no_case!("as"), not!(ident_char))) >>
         opt!(multispace) >>
-        char!(';') >>
-        (construct_query(select, table, filter, order_by, limit))
+        query: expect!("`(`", subquery) >>
+        (name.to_string(), query)
     )
 );
 
+named!(subquery<&[u8], Query>,
+    do_parse!(
+        char!('(') >>
+        opt!(multispace) >>
+        query: call!(union_statement, u64::MAX) >>
+        opt!(multispace) >>
+        expect!("`)`", char!(')')) >>
+        (query)
+    )
+);
 
 named!(simple_query<&[u8], Query>,
     do_parse!(
         opt!(multispace) >>
-        tag_no_case!("select") >>
+        expect!("SELECT", tag_no_case!("select")) >>
         multispace >>
+        distinct: opt!(distinct) >>
         select: select_clauses >>
         opt!(multispace) >>
-        table: from_clause >>
+        from: from_clause >>
+        opt!(multispace) >>
+        group_by: opt!(group_by_clause) >>
+        opt!(multispace) >>
+        having: opt!(having_clause) >>
         opt!(multispace) >>
         order_by: opt!(order_by_clause) >>
         opt!(multispace) >>
+        limit_by: opt!(complete!(limit_by_clause)) >>
+        opt!(multispace) >>
         limit: opt!(limit_clause) >>
         opt!(multispace) >>
         opt!(char!(';')) >>
-        (construct_query(select, table, Expr::Const(RawVal::Int(1)), order_by, limit))
+        (construct_query(
+            SelectStatement {
+                with: None,
+                select,
+                distinct: distinct.is_some(),
+                from,
+                filter: Expr::Const(RawVal::Int(1)),
+                group_by,
+                having,
+            },
+            order_by,
+            limit_by,
+            limit.unwrap_or(LimitClause { limit: DEFAULT_LIMIT, offset: 0 })))
     )
 );
 
-fn construct_query(select_clauses: Vec<AggregateOrSelect>,
-                   table: &str,
-                   filter: Expr,
-                   order_by: Option<(String, bool)>,
-                   limit: Option<LimitClause>)
+/// Clauses of a select statement that precede its ORDER BY and LIMIT clauses.
+struct SelectStatement<'a> {
+    with: Option<Vec<(String, Query)>>,
+    select: Vec<(AggregateOrSelect, Option<String>)>,
+    distinct: bool,
+    from: FromClause<'a>,
+    filter: Expr,
+    group_by: Option<(Vec<Expr>, Vec<Vec<usize>>)>,
+    having: Option<Expr>,
+}
+
+/// Constructs the query for `statement` followed by the statements of its UNION ALL, which return all of their rows.
+/// ORDER BY and LIMIT clauses apply to the concatenated results of a UNION ALL, see `Query::union_all`.
+fn construct_union(statement: SelectStatement,
+                   union_all: Vec<SelectStatement>,
+                   order_by: Option<Vec<(Expr, bool)>>,
+                   limit_by: Option<LimitBy>,
+                   limit: LimitClause)
                    -> Query {
-    let (select, aggregate) = partition(select_clauses);
-    let order_desc = order_by.as_ref().map(|x| x.1).unwrap_or(false);
+    if union_all.is_empty() {
+        return construct_query(statement, order_by, limit_by, limit);
+    }
+    let unlimited = || LimitClause { limit: u64::MAX, offset: 0 };
     Query {
+        order_by: order_by.unwrap_or_default(),
+        limit,
+        limit_by,
+        union_all: union_all.into_iter()
+            .map(|statement| construct_query(statement, None, None, unlimited()))
+            .collect(),
+        ..construct_query(statement, None, None, unlimited())
+    }
+}
+
+fn construct_query(statement: SelectStatement,
+                   order_by: Option<Vec<(Expr, bool)>>,
+                   limit_by: Option<LimitBy>,
+                   limit: LimitClause)
+                   -> Query {
+    let SelectStatement { with, select, distinct, from, filter, group_by, having } = statement;
+    let (select, aggregate, aliases) = partition(select);
+
+    // Aliases can be referenced by all clauses that are evaluated after the select clause
+    let aliased = select.iter().cloned()
+        .chain(aggregate.iter().map(|&(aggregator, ref expr)| Expr::Aggregate(aggregator, Box::new(expr.clone()))))
+        .zip(aliases.iter())
+        .filter_map(|(expr, alias)| alias.clone().map(|alias| (alias, expr)))
+        .collect::<Vec<_>>();
+    let (group_by, grouping_sets) = match group_by {
+        Some((exprs, grouping_sets)) =>
+            (Some(exprs.into_iter().map(|expr| resolve_aliases(expr, &aliased)).collect()), grouping_sets),
+        None => (None, vec![]),
+    };
+    let having = having.map(|expr| resolve_aliases(expr, &aliased));
+    let order_by = order_by.unwrap_or_default().into_iter()
+        .map(|(expr, desc)| (resolve_aliases(expr, &aliased), desc))
+        .collect();
+    let limit_by = limit_by.map(|limit_by| LimitBy {
+        by: limit_by.by.into_iter().map(|expr| resolve_aliases(expr, &aliased)).collect(),
+        ..limit_by
+    });
+
+    // Without explicit GROUP BY clause, aggregation and SELECT DISTINCT queries are grouped by all selected expressions
+    let group_by = group_by.unwrap_or_else(|| if aggregate.is_empty() && !distinct { vec![] } else { select.clone() });
+    let mut with = with.unwrap_or_default();
+    if let Some(ref subquery) = from.subquery {
+        with.push((from.table.name.to_string(), subquery.clone()));
+    }
+    let query = Query {
+        with,
         select,
-        table: table.to_string(),
+        table: from.table.name.to_string(),
+        join: None,
         filter,
         aggregate,
-        order_by: order_by.map(|x| x.0),
-        order_desc,
-        limit: limit.unwrap_or(LimitClause { limit: 100, offset: 0 }),
+        group_by,
+        grouping_sets,
+        having,
+        aliases,
+        order_by,
+        limit,
+        limit_by,
         order_by_index: None,
+        union_all: vec![],
+    };
+    qualify_columns(query, &from)
+}
+
+/// Removes qualifiers that refer to the queried table from column names, and qualifies columns of the joined table by
+/// its alias.
+fn qualify_columns(query: Query, from: &FromClause) -> Query {
+    let joined = from.join.as_ref().map(|&(_, ref joined, _)| joined);
+    let qualify = |name: &str| -> String {
+        let dot = match name.find('.') {
+            Some(dot) => dot,
+            None => return name.to_string(),
+        };
+        let (qualifier, column) = (&name[..dot], &name[dot + 1..]);
+        if Some(qualifier) == from.table.alias {
+            column.to_string()
+        } else if joined.map_or(false, |joined| Some(qualifier) == joined.alias) {
+            name.to_string()
+        } else if qualifier == from.table.name {
+            column.to_string()
+        } else if let Some(joined) = joined.filter(|joined| joined.name == qualifier) {
+            format!("{}.{}", joined.qualifier(), column)
+        } else {
+            name.to_string()
+        }
+    };
+    let mut query = query.substitute(&|expr| match *expr {
+        Expr::ColName(ref name) => Some(Expr::ColName(qualify(name))),
+        _ => None,
+    });
+    if let Some((ref kind, ref joined, (ref lhs, ref rhs))) = from.join {
+        query.join = Some(Join {
+            kind: *kind,
+            table: joined.name.to_string(),
+            alias: joined.qualifier().to_string(),
+            on: (qualify(lhs), qualify(rhs)),
+        });
+    }
+    query
+}
+
+/// Numbers parameters in the order in which they appear in the query, assigning the same number to all occurrences of
+/// a named parameter. Runs once on the final query, so parsers that backtrack don't affect the numbering.
+fn number_parameters(query: Query) -> Query {
+    let mut parameters = Vec::new();
+    query.for_each_expr(&mut |expr| expr.add_parameters(&mut parameters));
+    if parameters.is_empty() {
+        return query;
+    }
+    // Parameters are positioned by the length of the input remaining after them
+    parameters.sort_by_key(|&(position, _)| cmp::Reverse(position));
+    parameters.dedup();
+    let mut indices = HashMap::new();
+    let mut names = HashMap::new();
+    let mut count = 0;
+    for (position, name) in parameters {
+        let index = match name.as_ref().and_then(|name| names.get(name).cloned()) {
+            Some(index) => index,
+            None => {
+                if let Some(name) = name {
+                    names.insert(name, count);
+                }
+                count += 1;
+                count - 1
+            }
+        };
+        indices.insert(position, index);
+    }
+    query.substitute_all(&|expr| match *expr {
+        Expr::Parameter(position, ref name) => Some(Expr::Parameter(indices[&position], name.clone())),
+        _ => None,
+    })
+}
+
+fn resolve_aliases(expr: Expr, aliases: &[(String, Expr)]) -> Expr {
+    match expr {
+        Expr::ColName(name) => match aliases.iter().find(|&&(ref alias, _)| *alias == name) {
+            Some(&(_, ref aliased)) => aliased.clone(),
+            None => Expr::ColName(name),
+        },
+        Expr::Func1(ftype, box expr) => Expr::func1(ftype, resolve_aliases(expr, aliases)),
+        Expr::Func2(ftype, box lhs, box rhs) =>
+            Expr::func(ftype, resolve_aliases(lhs, aliases), resolve_aliases(rhs, aliases)),
+        Expr::In(box lhs, items) => Expr::In(
+            Box::new(resolve_aliases(lhs, aliases)),
+            items.into_iter().map(|item| resolve_aliases(item, aliases)).collect()),
+        Expr::If(box cond, box lhs, box rhs) => Expr::If(
+            Box::new(resolve_aliases(cond, aliases)),
+            Box::new(resolve_aliases(lhs, aliases)),
+            Box::new(resolve_aliases(rhs, aliases))),
+        Expr::Aggregate(aggregator, box expr) => Expr::Aggregate(aggregator, Box::new(resolve_aliases(expr, aliases))),
+        expr => expr,
     }
 }
 
-fn partition(select_or_aggregates: Vec<AggregateOrSelect>)
-             -> (Vec<Expr>, Vec<(Aggregator, Expr)>) {
-    let (selects, aggregates): (Vec<AggregateOrSelect>, Vec<AggregateOrSelect>) =
+fn partition(select_or_aggregates: Vec<(AggregateOrSelect, Option<String>)>)
+             -> (Vec<Expr>, Vec<(Aggregator, Expr)>, Vec<Option<String>>) {
+    let (selects, aggregates): (Vec<_>, Vec<_>) =
         select_or_aggregates.into_iter()
-            .partition(|x| match *x {
+            .partition(|x| match x.0 {
                 AggregateOrSelect::Select(_) => true,
                 _ => false,
             });
 
+    let aliases = selects.iter().chain(aggregates.iter()).map(|x| x.1.clone()).collect();
     (selects.into_iter()
-         .filter_map(|x| match x {
+         .filter_map(|x| match x.0 {
              AggregateOrSelect::Select(expr) => Some(expr),
              _ => None,
          })
          .collect(),
      aggregates.into_iter()
-         .filter_map(|x| match x {
+         .filter_map(|x| match x.0 {
              AggregateOrSelect::Aggregate(agg) => Some(agg),
              _ => None,
          })
-         .collect())
+         .collect(),
+     aliases)
+}
+
+named!(distinct<&[u8], ()>,
+    do_parse!(
+        tag_no_case!("distinct") >>
+        multispace >>
+        ()
+    )
+);
+
+/// Table or subquery and optional join of the FROM clause.
+struct FromClause<'a> {
+    table: TableReference<'a>,
+    /// Query whose result is queried instead of a table, under the name of `table`.
+    subquery: Option<Query>,
+    join: Option<(JoinType, TableReference<'a>, (String, String))>,
+}
+
+/// Name of a table and the alias that qualifies its columns.
+struct TableReference<'a> {
+    name: &'a str,
+    alias: Option<&'a str>,
+}
+
+impl<'a> TableReference<'a> {
+    /// The prefix of qualified column names that refer to this table.
+    fn qualifier(&self) -> &'a str {
+        self.alias.unwrap_or(self.name)
+    }
 }
 
-named!(from_clause<&[u8], &str>,
+named!(from_clause<&[u8], FromClause>,
+    do_parse!(
+        expect!("FROM", tag_no_case!("from")) >>
+        multispace >>
+        table: alt!(
+            map!(expect!("subquery", subquery_reference), |(table, subquery)| (table, Some(subquery))) |
+            map!(expect!("table name", table_reference), |table| (table, None))
+        ) >>
+        join: opt!(complete!(preceded!(multispace, join_clause))) >>
+        (FromClause { table: table.0, subquery: table.1, join })
+    )
+);
+
+/// Name of the result of subqueries in the FROM clause that don't have an alias.
+const SUBQUERY: &str = "subquery";
+
+named!(subquery_reference<&[u8], (TableReference, Query)>,
+    do_parse!(
+        query: subquery >>
+        alias: opt!(complete!(table_alias)) >>
+        (TableReference { name: alias.unwrap_or(SUBQUERY), alias }, query)
+    )
+);
+
+named!(table_reference<&[u8], TableReference>,
+    do_parse!(
+        name: table_name >>
+        alias: opt!(complete!(table_alias)) >>
+        (TableReference { name, alias })
+    )
+);
+
+/// Name of a table, or a prefix followed by `*` that matches the names of all tables starting with the prefix.
+named!(table_name<&[u8], &str>,
+    map_res!(
+        recognize!(pair!(identifier, opt!(complete!(char!('*'))))),
+        str::from_utf8
+    )
+);
+
+named!(table_alias<&[u8], &str>,
     do_parse!(
-        tag_no_case!("from") >>
         multispace >>
-        from: identifier >>
-        (from)
+        opt!(complete!(terminated!(tag_no_case!("as"), multispace))) >>
+        alias: map_res!(identifier, non_keyword) >>
+        (alias)
+    )
+);
+
+/// Keywords that can follow a table name and are therefore not valid table aliases.
+const TABLE_KEYWORDS: [&str; 12] = ["as", "join", "inner", "left", "outer", "on", "where", "group", "having", "order", "limit", "union"];
+
+fn non_keyword(ident: &str) -> Result<&str, String> {
+    if TABLE_KEYWORDS.iter().any(|keyword| keyword.eq_ignore_ascii_case(ident)) {
+        Err(format!("{} is a keyword", ident))
+    } else {
+        Ok(ident)
+    }
+}
+
+named!(join_clause<&[u8], (JoinType, TableReference, (String, String))>,
+    do_parse!(
+        kind: join_type >>
+        expect!("JOIN", terminated!(tag_no_case!("join"), not!(ident_char))) >>
+        opt!(multispace) >>
+        table: expect!("table name", table_reference) >>
+        opt!(multispace) >>
+        expect!("ON", terminated!(tag_no_case!("on"), not!(ident_char))) >>
+        opt!(multispace) >>
+        on: expect!("`column = column`", join_condition) >>
+        (kind, table, on)
+    )
+);
+
+named!(join_condition<&[u8], (String, String)>,
+    do_parse!(
+        lhs: qualified_identifier >>
+        opt!(multispace) >>
+        char!('=') >>
+        opt!(multispace) >>
+        rhs: qualified_identifier >>
+        (lhs, rhs)
+    )
+);
+
+named!(join_type<&[u8], JoinType>,
+    alt!(
+        do_parse!(
+            tag_no_case!("left") >>
+            multispace >>
+            opt!(terminated!(tag_no_case!("outer"), multispace)) >>
+            (JoinType::Left)
+        ) |
+        do_parse!(
+            tag_no_case!("inner") >>
+            multispace >>
+            (JoinType::Inner)
+        ) |
+        value!(JoinType::Inner)
     )
 );
 
-named!(select_clauses<&[u8], Vec<AggregateOrSelect>>,
+named!(select_clauses<&[u8], Vec<(AggregateOrSelect, Option<String>)>>,
     alt!(
         do_parse!(
             opt!(multispace) >>
             tag!("*") >>
             opt!(multispace) >>
-            (vec![AggregateOrSelect::Select(Expr::ColName("*".to_string()))])
+            (vec![(AggregateOrSelect::Select(Expr::ColName("*".to_string())), None)])
         ) |
-        separated_list!(
-            tag!(","),
-            alt_complete!(aggregate_clause | select_clause)
+        map!(
+            separated_list!(
+                expect!("`,`", tag!(",")),
+                alt_complete!(
+                    quantiles_clause |
+                    do_parse!(
+                        clause: alt_complete!(aggregate_clause | select_clause) >>
+                        alias: opt!(alias) >>
+                        (vec![(clause, alias)])
+                    )
+                )
+            ),
+            |clauses: Vec<Vec<_>>| clauses.into_iter().flat_map(|clauses| clauses).collect()
         )
     )
 );
 
+named!(alias<&[u8], String>,
+    do_parse!(
+        multispace >>
+        tag_no_case!("as") >>
+        multispace >>
+        alias: identifier >>
+        (alias.to_string())
+    )
+);
+
 named!(aggregate_clause<&[u8], AggregateOrSelect>,
     do_parse!(
         opt!(multispace) >>
-        atype: aggregate_func >>
+        aggregate: filtered_aggregate >>
+        (AggregateOrSelect::Aggregate(aggregate))
+    )
+);
+
+named!(filtered_aggregate<&[u8], (Aggregator, Expr)>,
+    do_parse!(
+        aggregate: alt_complete!(parameterized_aggregate | simple_aggregate) >>
+        predicate: opt!(complete!(filter_clause)) >>
+        (apply_filter_clause(aggregate, predicate))
+    )
+);
+
+named!(filter_clause<&[u8], Expr>,
+    do_parse!(
+        multispace >>
+        tag_no_case!("filter") >>
+        opt!(multispace) >>
+        char!('(') >>
+        opt!(multispace) >>
+        tag_no_case!("where") >>
+        multispace >>
+        predicate: expr >>
+        opt!(multispace) >>
+        char!(')') >>
+        (predicate)
+    )
+);
+
+fn apply_filter_clause((aggregator, expr): (Aggregator, Expr), predicate: Option<Expr>) -> (Aggregator, Expr) {
+    match (expr, predicate) {
+        (expr, None) => (aggregator, expr),
+        // Rows are skipped if either argument is NULL
+        (Expr::Func2(Func2Type::Pair, x, y), Some(predicate)) => {
+            let x = Expr::func(Func2Type::Filtered, *x, predicate.clone());
+            let y = Expr::func(Func2Type::Filtered, *y, predicate);
+            (aggregator, Expr::func(Func2Type::Pair, x, y))
+        }
+        (expr, Some(predicate)) => (aggregator, Expr::func(Func2Type::Filtered, expr, predicate)),
+    }
+}
+
+named!(parameterized_aggregate<&[u8], (Aggregator, Expr)>,
+    alt_complete!(count_if | count_distinct | approx_count_distinct | percentile | median | two_argument_aggregate)
+);
+
+named!(count_if<&[u8], (Aggregator, Expr)>,
+    do_parse!(
+        tag_no_case!("count_if") >>
+        opt!(multispace) >>
+        char!('(') >>
+        predicate: expr >>
+        opt!(multispace) >>
+        char!(')') >>
+        ((Aggregator::Count, Expr::func(Func2Type::Filtered, Expr::Const(RawVal::Int(1)), predicate)))
+    )
+);
+
+named!(count_distinct<&[u8], (Aggregator, Expr)>,
+    do_parse!(
+        tag_no_case!("count") >>
+        opt!(multispace) >>
+        char!('(') >>
+        opt!(multispace) >>
+        distinct >>
+        e: expr >>
+        opt!(multispace) >>
+        char!(')') >>
+        ((Aggregator::CountDistinct, e))
+    )
+);
+
+named!(approx_count_distinct<&[u8], (Aggregator, Expr)>,
+    do_parse!(
+        tag_no_case!("approx_count_distinct") >>
+        opt!(multispace) >>
+        char!('(') >>
+        e: expr >>
+        precision: opt!(
+            do_parse!(
+                opt!(multispace) >>
+                char!(',') >>
+                opt!(multispace) >>
+                precision: precision >>
+                (precision)
+            )
+        ) >>
+        opt!(multispace) >>
+        char!(')') >>
+        ((Aggregator::ApproxCountDistinct(precision.unwrap_or(hyperloglog::DEFAULT_PRECISION)), e))
+    )
+);
+
+named!(precision<&[u8], u8>,
+    map_res!(
+        map_res!(
+            digit,
+            str::from_utf8
+        ),
+        FromStr::from_str
+    )
+);
+
+named!(two_argument_aggregate<&[u8], (Aggregator, Expr)>,
+    do_parse!(
+        aggregator: alt_complete!(covar | corr) >>
+        opt!(multispace) >>
+        char!('(') >>
+        x: expr >>
+        opt!(multispace) >>
+        char!(',') >>
+        y: expr >>
+        opt!(multispace) >>
+        char!(')') >>
+        ((aggregator, Expr::func(Func2Type::Pair, x, y)))
+    )
+);
+
+named!(covar<&[u8], Aggregator>,
+    map!( tag_no_case!("covar"), |_| Aggregator::Covar )
+);
+
+named!(corr<&[u8], Aggregator>,
+    map!( tag_no_case!("corr"), |_| Aggregator::Corr )
+);
+
+named!(percentile<&[u8], (Aggregator, Expr)>,
+    do_parse!(
+        tag_no_case!("percentile") >>
+        opt!(multispace) >>
+        char!('(') >>
+        e: expr >>
+        opt!(multispace) >>
+        char!(',') >>
+        quantile: quantile >>
+        opt!(multispace) >>
+        char!(')') >>
+        ((Aggregator::Percentile(quantile), e))
+    )
+);
+
+named!(median<&[u8], (Aggregator, Expr)>,
+    do_parse!(
+        tag_no_case!("median") >>
+        opt!(multispace) >>
+        char!('(') >>
+        e: expr >>
+        opt!(multispace) >>
+        char!(')') >>
+        ((Aggregator::Percentile(OrderedFloat(0.5)), e))
+    )
+);
+
+// `QUANTILES(expr, [q1, q2, ...])` yields one result column per quantile, named `alias_0`, `alias_1`, ... if the
+// clause has an alias.
+named!(quantiles_clause<&[u8], Vec<(AggregateOrSelect, Option<String>)>>,
+    do_parse!(
+        opt!(multispace) >>
+        tag_no_case!("quantiles") >>
+        opt!(multispace) >>
         char!('(') >>
         e: expr >>
         opt!(multispace) >>
+        char!(',') >>
+        opt!(multispace) >>
+        char!('[') >>
+        quantiles: separated_nonempty_list!(tag!(","), quantile) >>
+        opt!(multispace) >>
+        char!(']') >>
+        opt!(multispace) >>
         char!(')') >>
-        (AggregateOrSelect::Aggregate((atype, e)))
+        alias: opt!(alias) >>
+        (quantiles.into_iter()
+            .enumerate()
+            .map(|(i, quantile)| (
+                AggregateOrSelect::Aggregate((Aggregator::Percentile(quantile), e.clone())),
+                alias.as_ref().map(|alias| format!("{}_{}", alias, i))))
+            .collect())
+    )
+);
+
+named!(quantile<&[u8], OrderedFloat>,
+    do_parse!(
+        opt!(multispace) >>
+        quantile: map_res!(
+            map_res!(recognize!(alt_complete!(float | integer)), str::from_utf8),
+            f64::from_str
+        ) >>
+        (OrderedFloat(quantile))
     )
 );
 
 named!(select_clause<&[u8], AggregateOrSelect>, map!(expr, AggregateOrSelect::Select));
 
-named!(aggregate_func<&[u8], Aggregator>, alt!(count | sum));
+named!(where_clause<&[u8], Expr>,
+    do_parse!(
+        expect!("WHERE", tag_no_case!("where")) >>
+        not!(ident_char) >>
+        filter: expr >>
+        (filter)
+    )
+);
+
+named!(group_by_clause<&[u8], (Vec<Expr>, Vec<Vec<usize>>)>,
+    do_parse!(
+        expect!("GROUP BY", tag_no_case!("group by")) >>
+        not!(ident_char) >>
+        opt!(multispace) >>
+        group_by: alt!(
+            map!(alt!(complete!(rollup) | complete!(cube) | complete!(grouping_sets)), index_grouping_sets) |
+            map!(separated_nonempty_list!(tag!(","), expr), |exprs| (exprs, vec![]))
+        ) >>
+        (group_by)
+    )
+);
+
+named!(rollup<&[u8], Vec<Vec<Expr>>>,
+    do_parse!(
+        tag_no_case!("rollup") >>
+        exprs: parenthesized_exprs >>
+        ((0..exprs.len() + 1).rev().map(|len| exprs[..len].to_vec()).collect())
+    )
+);
+
+named!(cube<&[u8], Vec<Vec<Expr>>>,
+    do_parse!(
+        tag_no_case!("cube") >>
+        exprs: parenthesized_exprs >>
+        ((0..1usize << exprs.len()).rev()
+            .map(|subset| exprs.iter().enumerate()
+                .filter(|&(i, _)| subset & (1 << (exprs.len() - 1 - i)) != 0)
+                .map(|(_, expr)| expr.clone())
+                .collect::<Vec<_>>())
+            .collect())
+    )
+);
+
+named!(grouping_sets<&[u8], Vec<Vec<Expr>>>,
+    do_parse!(
+        tag_no_case!("grouping") >>
+        multispace >>
+        tag_no_case!("sets") >>
+        opt!(multispace) >>
+        char!('(') >>
+        sets: separated_nonempty_list!(tag!(","), grouping_set) >>
+        opt!(multispace) >>
+        char!(')') >>
+        (sets)
+    )
+);
+
+named!(grouping_set<&[u8], Vec<Expr>>,
+    alt!(
+        complete!(do_parse!(
+            opt!(multispace) >>
+            char!('(') >>
+            exprs: separated_list!(tag!(","), expr) >>
+            opt!(multispace) >>
+            char!(')') >>
+            (exprs)
+        )) |
+        map!(expr, |expr| vec![expr])
+    )
+);
+
+named!(parenthesized_exprs<&[u8], Vec<Expr>>,
+    do_parse!(
+        opt!(multispace) >>
+        char!('(') >>
+        exprs: separated_nonempty_list!(tag!(","), expr) >>
+        opt!(multispace) >>
+        char!(')') >>
+        (exprs)
+    )
+);
+
+/// Collects the distinct expressions of all grouping sets, and represents each grouping set by the (sorted) indices
+/// of its expressions.
+fn index_grouping_sets(sets: Vec<Vec<Expr>>) -> (Vec<Expr>, Vec<Vec<usize>>) {
+    let mut group_by = Vec::<Expr>::new();
+    let mut grouping_sets = Vec::with_capacity(sets.len());
+    for set in sets {
+        let mut indices = Vec::with_capacity(set.len());
+        for expr in set {
+            match group_by.iter().position(|e| *e == expr) {
+                Some(index) => indices.push(index),
+                None => {
+                    indices.push(group_by.len());
+                    group_by.push(expr);
+                }
+            }
+        }
+        indices.sort();
+        indices.dedup();
+        grouping_sets.push(indices);
+    }
+    (group_by, grouping_sets)
+}
+
+named!(having_clause<&[u8], Expr>,
+    do_parse!(
+        expect!("HAVING", tag_no_case!("having")) >>
+        not!(ident_char) >>
+        having: expr >>
+        (having)
+    )
+);
+
+named!(aggregate_func<&[u8], Aggregator>, alt!(count | sum | min | max | avg | variance | stddev));
 
 named!(count<&[u8], Aggregator>,
     map!( tag_no_case!("count"), |_| Aggregator::Count )
@@ -146,112 +923,519 @@ named!(sum<&[u8], Aggregator>,
     map!( tag_no_case!("sum"), |_| Aggregator::Sum )
 );
 
+named!(min<&[u8], Aggregator>,
+    map!( tag_no_case!("min"), |_| Aggregator::Min )
+);
+
+named!(max<&[u8], Aggregator>,
+    map!( tag_no_case!("max"), |_| Aggregator::Max )
+);
+
+named!(avg<&[u8], Aggregator>,
+    map!( tag_no_case!("avg"), |_| Aggregator::Avg )
+);
+
+named!(variance<&[u8], Aggregator>,
+    map!( tag_no_case!("variance"), |_| Aggregator::Variance )
+);
+
+named!(stddev<&[u8], Aggregator>,
+    map!( tag_no_case!("stddev"), |_| Aggregator::StdDev )
+);
+
+// Operators from lowest to highest precedence: OR, AND, NOT, IS [NOT] NULL, comparison/[NOT] IN/[NOT] BETWEEN, additive,
+// multiplicative, unary minus
 named!(expr<&[u8], Expr>,
     do_parse!(
         opt!(multispace) >>
-        result: alt!(infix_expr | expr_no_left_recur) >>
+        result: expect!("expression", or_expr) >>
         (result)
     )
 );
 
-named!(expr_no_left_recur<&[u8], Expr>,
+named!(or_expr<&[u8], Expr>,
+    do_parse!(
+        first: and_expr >>
+        rest: many0!(
+            do_parse!(
+                opt!(multispace) >>
+                ft: or >>
+                e: and_expr >>
+                (ft, e)
+            )
+        ) >>
+        (fold_infix(first, rest))
+    )
+);
+
+named!(and_expr<&[u8], Expr>,
+    do_parse!(
+        first: not_expr >>
+        rest: many0!(
+            do_parse!(
+                opt!(multispace) >>
+                ft: and >>
+                e: not_expr >>
+                (ft, e)
+            )
+        ) >>
+        (fold_infix(first, rest))
+    )
+);
+
+named!(not_expr<&[u8], Expr>,
+    alt!(
+        do_parse!(
+            opt!(multispace) >>
+            not >>
+            e: not_expr >>
+            (Expr::func1(Func1Type::Not, e))
+        ) |
+        is_null_expr
+    )
+);
+
+named!(is_null_expr<&[u8], Expr>,
+    do_parse!(
+        e: comparison_expr >>
+        is_not_null: opt!(
+            do_parse!(
+                multispace >>
+                tag_no_case!("is") >>
+                multispace >>
+                negated: opt!(terminated!(not, multispace)) >>
+                terminated!(tag_no_case!("null"), not!(ident_char)) >>
+                (negated.is_some())
+            )
+        ) >>
+        (match is_not_null {
+            Some(true) => Expr::func1(Func1Type::IsNotNull, e),
+            Some(false) => Expr::func1(Func1Type::IsNull, e),
+            None => e,
+        })
+    )
+);
+
+named!(comparison_expr<&[u8], Expr>,
+    do_parse!(
+        lhs: additive_expr >>
+        rhs: opt!(alt!(comparison_rhs | in_rhs | between_rhs | like_rhs)) >>
+        (match rhs {
+            Some(rhs) => construct_comparison(lhs, rhs),
+            None => lhs,
+        })
+    )
+);
+
+named!(comparison_rhs<&[u8], ComparisonRhs>,
+    do_parse!(
+        opt!(multispace) >>
+        ft: comparison_function_name >>
+        e: additive_expr >>
+        (ComparisonRhs::Compare(ft, e))
+    )
+);
+
+named!(in_rhs<&[u8], ComparisonRhs>,
+    do_parse!(
+        multispace >>
+        negated: opt!(terminated!(not, multispace)) >>
+        tag_no_case!("in") >>
+        opt!(multispace) >>
+        char!('(') >>
+        items: separated_nonempty_list!(tag!(","), expr) >>
+        opt!(multispace) >>
+        char!(')') >>
+        (ComparisonRhs::In(items, negated.is_some()))
+    )
+);
+
+named!(between_rhs<&[u8], ComparisonRhs>,
+    do_parse!(
+        multispace >>
+        negated: opt!(terminated!(not, multispace)) >>
+        terminated!(tag_no_case!("between"), not!(ident_char)) >>
+        low: additive_expr >>
+        opt!(multispace) >>
+        and >>
+        high: additive_expr >>
+        (ComparisonRhs::Between(low, high, negated.is_some()))
+    )
+);
+
+named!(like_rhs<&[u8], ComparisonRhs>,
+    do_parse!(
+        multispace >>
+        negated: opt!(terminated!(not, multispace)) >>
+        ft: terminated!(
+            alt!(
+                map!(tag_no_case!("like"), |_| Func2Type::Like) |
+                map!(tag_no_case!("ilike"), |_| Func2Type::ILike)
+            ),
+            not!(ident_char)
+        ) >>
+        pattern: additive_expr >>
+        (ComparisonRhs::Like(ft, pattern, negated.is_some()))
+    )
+);
+
+fn construct_comparison(lhs: Expr, rhs: ComparisonRhs) -> Expr {
+    let (expr, negated) = match rhs {
+        ComparisonRhs::Compare(ft, rhs) => (Expr::func(ft, lhs, rhs), false),
+        ComparisonRhs::In(items, negated) => (Expr::In(Box::new(lhs), items), negated),
+        ComparisonRhs::Like(ft, pattern, negated) => (Expr::func(ft, lhs, pattern), negated),
+        // `x BETWEEN a AND b` is equivalent to `x >= a AND x <= b`
+        ComparisonRhs::Between(low, high, negated) => (
+            Expr::func(Func2Type::And,
+                       Expr::func(Func2Type::GTE, lhs.clone(), low),
+                       Expr::func(Func2Type::LTE, lhs, high)),
+            negated),
+    };
+    if negated { Expr::func1(Func1Type::Not, expr) } else { expr }
+}
+
+named!(additive_expr<&[u8], Expr>,
+    do_parse!(
+        first: multiplicative_expr >>
+        rest: many0!(
+            do_parse!(
+                opt!(multispace) >>
+                ft: alt!(add | subtract) >>
+                e: multiplicative_expr >>
+                (ft, e)
+            )
+        ) >>
+        (fold_infix(first, rest))
+    )
+);
+
+named!(multiplicative_expr<&[u8], Expr>,
+    do_parse!(
+        first: unary_expr >>
+        rest: many0!(
+            do_parse!(
+                opt!(multispace) >>
+                ft: alt!(multiply | divide | modulo) >>
+                e: unary_expr >>
+                (ft, e)
+            )
+        ) >>
+        (fold_infix(first, rest))
+    )
+);
+
+named!(unary_expr<&[u8], Expr>,
     do_parse!(
         opt!(multispace) >>
-        result: alt!(parentheses | template | function | to_year | negation | colname | constant) >>
+        result: alt!(negation | parentheses | template | parameter | aggregate | conditional | now | function | date_function | string_function | constant | colname) >>
         (result)
     )
 );
 
-named!(parentheses<&[u8], Expr>,
+fn fold_infix(first: Expr, rest: Vec<(Func2Type, Expr)>) -> Expr {
+    rest.into_iter().fold(first, |lhs, (ft, rhs)| Expr::func(ft, lhs, rhs))
+}
+
+named!(parentheses<&[u8], Expr>,
+    do_parse!(
+        char!('(') >>
+        e1: expr >>
+        opt!(multispace) >>
+        expect!("`)`", char!(')')) >>
+        (e1)
+    )
+);
+
+named!(aggregate<&[u8], Expr>,
+    map!(filtered_aggregate, |(aggregator, e)| Expr::Aggregate(aggregator, Box::new(e)))
+);
+
+named!(simple_aggregate<&[u8], (Aggregator, Expr)>,
+    do_parse!(
+        atype: aggregate_func >>
+        opt!(multispace) >>
+        char!('(') >>
+        e: expr >>
+        opt!(multispace) >>
+        char!(')') >>
+        ((atype, e))
+    )
+);
+
+/// `?` or `$name`. Parameters are identified by their position until they are numbered by `number_parameters`.
+fn parameter(input: &[u8]) -> IResult<&[u8], Expr> {
+    let position = input.len();
+    alt!(input,
+        map!(char!('?'), |_| Expr::Parameter(position, None)) |
+        map!(preceded!(char!('$'), identifier), |name: &str| Expr::Parameter(position, Some(name.to_string())))
+    )
+}
+
+/// Deprecated `$LAST_HOUR` and `$LAST_DAY`, which expand to `timestamp > now() - interval '1 hour'` and
+/// `timestamp > now() - interval '1 day'`.
+named!(template<&[u8], Expr>,
+    do_parse!(
+        seconds: alt!(
+            map!(tag_no_case!("$last_hour"), |_| 3600) |
+            map!(tag_no_case!("$last_day"), |_| 86400)
+        ) >>
+        not!(ident_char) >>
+        (Expr::func(Func2Type::GT,
+                    Expr::ColName("timestamp".to_string()),
+                    Expr::func(Func2Type::Subtract, Expr::Now, Expr::Const(RawVal::Int(seconds)))))
+    )
+);
+
+named!(now<&[u8], Expr>,
+    do_parse!(
+        tag_no_case!("now") >>
+        opt!(multispace) >>
+        char!('(') >>
+        opt!(multispace) >>
+        char!(')') >>
+        (Expr::Now)
+    )
+);
+
+named!(function<&[u8], Expr>,
+    do_parse!(
+        ft: function_name >>
+        char!('(') >>
+        e1: expr >>
+        opt!(multispace) >>
+        char!(',') >>
+        e2: expr >>
+        opt!(multispace) >>
+        char!(')') >>
+        (Expr::func(ft, e1, e2))
+    )
+);
+
+named!(conditional<&[u8], Expr>,
+    alt!(case_when | if_function)
+);
+
+named!(case_when<&[u8], Expr>,
+    do_parse!(
+        terminated!(tag_no_case!("case"), not!(ident_char)) >>
+        branches: many1!(complete!(when_then)) >>
+        otherwise: opt!(complete!(
+            do_parse!(
+                multispace >>
+                terminated!(tag_no_case!("else"), not!(ident_char)) >>
+                e: expr >>
+                (e)
+            )
+        )) >>
+        multispace >>
+        terminated!(tag_no_case!("end"), not!(ident_char)) >>
+        (case_to_if(branches, otherwise))
+    )
+);
+
+named!(when_then<&[u8], (Expr, Expr)>,
+    do_parse!(
+        multispace >>
+        terminated!(tag_no_case!("when"), not!(ident_char)) >>
+        cond: expr >>
+        multispace >>
+        terminated!(tag_no_case!("then"), not!(ident_char)) >>
+        value: expr >>
+        ((cond, value))
+    )
+);
+
+/// `CASE WHEN c1 THEN a WHEN c2 THEN b ELSE c END` is `if(c1, a, if(c2, b, c))`, without `ELSE` the last branch is
+/// NULL unless its condition is true.
+fn case_to_if(branches: Vec<(Expr, Expr)>, otherwise: Option<Expr>) -> Expr {
+    let mut branches = branches.into_iter().rev();
+    let mut result = match otherwise {
+        Some(otherwise) => otherwise,
+        None => {
+            let (cond, value) = branches.next().unwrap();
+            Expr::func(Func2Type::Filtered, value, cond)
+        }
+    };
+    for (cond, value) in branches {
+        result = Expr::If(Box::new(cond), Box::new(value), Box::new(result));
+    }
+    result
+}
+
+named!(if_function<&[u8], Expr>,
     do_parse!(
+        tag_no_case!("if") >>
+        opt!(multispace) >>
         char!('(') >>
-        e1: expr >>
+        cond: expr >>
+        opt!(multispace) >>
+        char!(',') >>
+        lhs: expr >>
+        opt!(multispace) >>
+        char!(',') >>
+        rhs: expr >>
         opt!(multispace) >>
         char!(')') >>
-        (e1)
+        (Expr::If(Box::new(cond), Box::new(lhs), Box::new(rhs)))
     )
 );
 
-named!(template<&[u8], Expr>,
-    alt!( last_hour | last_day )
+named!(negation<&[u8], Expr>,
+    do_parse!(
+        char!('-') >>
+        e: unary_expr >>
+        (Expr::func1(Func1Type::Negate, e))
+    )
 );
 
-named!(last_hour<&[u8], Expr>,
-    map!(
-        tag_no_case!("$LAST_HOUR"),
-        |_| Expr::Func2(
-                Func2Type::GT,
-                Box::new(Expr::ColName("timestamp".to_string())),
-                Box::new(Expr::Const(RawVal::Int(time::now().to_timespec().sec - 3600)))
-        )
-    )
+named!(date_function<&[u8], Expr>,
+    alt!(date_function1 | date_trunc)
 );
 
-named!(last_day<&[u8], Expr>,
-    map!(
-        tag_no_case!("$LAST_DAY"),
-        |_| Expr::Func2(
-                Func2Type::GT,
-                Box::new(Expr::ColName("timestamp".to_string())),
-                Box::new(Expr::Const(RawVal::Int(time::now().to_timespec().sec - 86400)))
-        )
+named!(date_function1<&[u8], Expr>,
+    do_parse!(
+        ft: alt!(
+            map!(tag_no_case!("to_year"), |_| Func1Type::ToYear) |
+            map!(tag_no_case!("to_month"), |_| Func1Type::ToMonth) |
+            map!(tag_no_case!("to_day"), |_| Func1Type::ToDay) |
+            map!(tag_no_case!("to_hour"), |_| Func1Type::ToHour) |
+            map!(tag_no_case!("day_of_week"), |_| Func1Type::DayOfWeek)
+        ) >>
+        opt!(multispace) >>
+        char!('(') >>
+        opt!(multispace) >>
+        e: expr >>
+        opt!(multispace) >>
+        char!(')') >>
+        (Expr::func1(ft, e))
     )
 );
 
-named!(function<&[u8], Expr>,
+named!(date_trunc<&[u8], Expr>,
     do_parse!(
-        ft: function_name >>
+        tag_no_case!("date_trunc") >>
+        opt!(multispace) >>
         char!('(') >>
-        e1: expr >>
+        opt!(multispace) >>
+        quote: alt!(char!('\'') | char!('"')) >>
+        unit: alt!(
+            map!(tag_no_case!("hour"), |_| TimeUnit::Hour) |
+            map!(tag_no_case!("day"), |_| TimeUnit::Day) |
+            map!(tag_no_case!("week"), |_| TimeUnit::Week) |
+            map!(tag_no_case!("month"), |_| TimeUnit::Month)
+        ) >>
+        char!(quote) >>
         opt!(multispace) >>
         char!(',') >>
-        e2: expr >>
+        e: expr >>
         opt!(multispace) >>
         char!(')') >>
-        (Expr::func(ft, e1, e2))
+        (Expr::func1(Func1Type::DateTrunc(unit), e))
     )
 );
 
-named!(infix_expr<&[u8], Expr>,
+named!(string_function<&[u8], Expr>,
+    alt!(string_function1 | substr | concat)
+);
+
+named!(string_function1<&[u8], Expr>,
     do_parse!(
-        e1: expr_no_left_recur >>
+        ft: alt!(
+            map!(tag_no_case!("lower"), |_| Func1Type::Lower) |
+            map!(tag_no_case!("upper"), |_| Func1Type::Upper) |
+            map!(tag_no_case!("length"), |_| Func1Type::Length)
+        ) >>
         opt!(multispace) >>
-        ft: infix_function_name >>
-        e2: expr >>
-        (Expr::func(ft, e1, e2))
+        char!('(') >>
+        e: expr >>
+        opt!(multispace) >>
+        char!(')') >>
+        (Expr::func1(ft, e))
     )
 );
 
-named!(negation<&[u8], Expr>,
+named!(substr<&[u8], Expr>,
     do_parse!(
-        char!('-') >>
+        alt!(tag_no_case!("substring") | tag_no_case!("substr")) >>
         opt!(multispace) >>
+        char!('(') >>
         e: expr >>
-        (Expr::func1(Func1Type::Negate, e))
+        opt!(multispace) >>
+        char!(',') >>
+        start: signed_integer >>
+        length: opt!(
+            do_parse!(
+                opt!(multispace) >>
+                char!(',') >>
+                length: signed_integer >>
+                (length)
+            )
+        ) >>
+        opt!(multispace) >>
+        char!(')') >>
+        (Expr::func1(Func1Type::Substr(start, length), e))
     )
 );
 
-named!(to_year<&[u8], Expr>,
+named!(concat<&[u8], Expr>,
     do_parse!(
-        tag!("to_year") >>
+        tag_no_case!("concat") >>
         opt!(multispace) >>
         char!('(') >>
-        opt!(multispace) >>
-        e: expr >>
+        first: expr >>
+        rest: many0!(
+            do_parse!(
+                opt!(multispace) >>
+                char!(',') >>
+                e: expr >>
+                (Func2Type::Concat, e)
+            )
+        ) >>
         opt!(multispace) >>
         char!(')') >>
-        (Expr::func1(Func1Type::ToYear, e))
+        (fold_infix(first, rest))
+    )
+);
+
+named!(signed_integer<&[u8], i64>,
+    do_parse!(
+        opt!(multispace) >>
+        sign: opt!(char!('-')) >>
+        value: number >>
+        (if sign.is_some() { -(value as i64) } else { value as i64 })
     )
 );
 
 named!(constant<&[u8], Expr>,
     map!(
-        alt!(integer |  string),
+        alt!(float | integer | string | timestamp | interval),
         Expr::Const
     )
 );
 
 
+/// Float literal such as `1.5`, `1.`, `.5`, `1e3` or `1.5E-3`.
+named!(float<&[u8], RawVal>,
+    map!(
+        map_res!(
+            map_res!(
+                recognize!(alt!(
+                    tuple!(digit, char!('.'), opt!(complete!(digit)), opt!(complete!(exponent))) => { |_| () } |
+                    tuple!(char!('.'), digit, opt!(complete!(exponent))) => { |_| () } |
+                    tuple!(digit, exponent) => { |_| () }
+                )),
+                str::from_utf8
+            ),
+            f64::from_str
+        ),
+        |f| RawVal::Float(OrderedFloat(f))
+    )
+);
+
+named!(exponent<&[u8], &[u8]>,
+    recognize!(tuple!(one_of!("eE"), opt!(one_of!("+-")), digit))
+);
+
 named!(integer<&[u8], RawVal>,
     map!(
         map_res!(
@@ -290,10 +1474,43 @@ named!(string<&[u8], RawVal>,
     )
 );
 
+/// Date/time literal such as `'2016-03-13 00:00:00'`, represented as seconds since the epoch.
+named!(timestamp<&[u8], RawVal>,
+    do_parse!(
+        char!('\'') >>
+        s: map_res!(is_not!("'"), str::from_utf8) >>
+        char!('\'') >>
+        ts: expr_opt!(date_function::parse_timestamp(s)) >>
+        (RawVal::Int(ts))
+    )
+);
+
+/// Interval literal such as `interval '1 hour'`, represented as a number of seconds.
+named!(interval<&[u8], RawVal>,
+    do_parse!(
+        tag_no_case!("interval") >>
+        opt!(multispace) >>
+        char!('\'') >>
+        s: map_res!(is_not!("'"), str::from_utf8) >>
+        char!('\'') >>
+        seconds: expr_opt!(date_function::parse_interval(s)) >>
+        (RawVal::Int(seconds))
+    )
+);
+
 named!(colname<&[u8], Expr>,
-    map!(
-        identifier,
-        |ident: &str| Expr::ColName(ident.to_string())
+    map!(qualified_identifier, Expr::ColName)
+);
+
+/// Column name that is optionally qualified by the name or alias of its table (`table.column`).
+named!(qualified_identifier<&[u8], String>,
+    do_parse!(
+        qualifier: identifier >>
+        name: opt!(complete!(preceded!(char!('.'), identifier))) >>
+        (match name {
+            Some(name) => format!("{}.{}", qualifier, name),
+            None => qualifier.to_string(),
+        })
     )
 );
 
@@ -302,13 +1519,21 @@ named!(function_name<&[u8], Func2Type>,
 );
 
 named!(infix_function_name<&[u8], Func2Type>,
-    alt!( equals | not_equals | and | or | greater | less | add | subtract | divide | multiply )
+    alt!( equals | not_equals | and | or | greater_equals | greater | less_equals | less | add | subtract | divide | multiply | modulo )
+);
+
+named!(comparison_function_name<&[u8], Func2Type>,
+    alt!( equals | not_equals | greater_equals | greater | less_equals | less )
 );
 
 named!(divide<&[u8], Func2Type>,
     map!( tag!("/"), |_| Func2Type::Divide)
 );
 
+named!(modulo<&[u8], Func2Type>,
+    map!( tag!("%"), |_| Func2Type::Modulo)
+);
+
 named!(add<&[u8], Func2Type>,
     map!( tag!("+"), |_| Func2Type::Add)
 );
@@ -329,20 +1554,32 @@ named!(not_equals<&[u8], Func2Type>,
     map!( tag!("<>"), |_| Func2Type::NotEquals)
 );
 
+named!(greater_equals<&[u8], Func2Type>,
+    map!( tag!(">="), |_| Func2Type::GTE)
+);
+
 named!(greater<&[u8], Func2Type>,
     map!( tag!(">"), |_| Func2Type::GT)
 );
 
+named!(less_equals<&[u8], Func2Type>,
+    map!( tag!("<="), |_| Func2Type::LTE)
+);
+
 named!(less<&[u8], Func2Type>,
     map!( tag!("<"), |_| Func2Type::LT)
 );
 
 named!(and<&[u8], Func2Type>,
-    map!( tag_no_case!("and"), |_| Func2Type::And)
+    map!( terminated!(tag_no_case!("and"), not!(ident_char)), |_| Func2Type::And)
 );
 
 named!(or<&[u8], Func2Type>,
-    map!( tag_no_case!("or"), |_| Func2Type::Or)
+    map!( terminated!(tag_no_case!("or"), not!(ident_char)), |_| Func2Type::Or)
+);
+
+named!(not<&[u8], &[u8]>,
+    terminated!(tag_no_case!("not"), not!(ident_char))
 );
 
 named!(regex<&[u8], Func2Type>,
@@ -350,6 +1587,8 @@ named!(regex<&[u8], Func2Type>,
 );
 
 
+named!(ident_char<&[u8], &[u8]>, take_while1!(is_ident_char));
+
 named!(identifier<&[u8], &str>,
     map_res!(
         take_while1!(is_ident_char),
@@ -375,9 +1614,10 @@ fn is_ident_char(chr: u8) -> bool {
 
 named!(limit_clause<&[u8], LimitClause>,
     do_parse!(
-        tag_no_case!("limit") >>
-        multispace >>
-        limit_val: number >>
+        expect!("LIMIT", tag_no_case!("limit")) >>
+        not!(ident_char) >>
+        opt!(multispace) >>
+        limit_val: expect!("number", number) >>
         offset_val: opt!(
             do_parse!(
                 multispace >>
@@ -395,23 +1635,39 @@ named!(limit_clause<&[u8], LimitClause>,
     )
 );
 
-named!(order_by_clause<&[u8], (String, bool)>,
-    alt!(
-        do_parse!(
-            tag_no_case!("order by") >>
-            multispace >>
-            order_by: identifier >>
-            multispace >>
-            tag_no_case!("desc") >>
-            (order_by.to_string(), true)
-        ) |
-        do_parse!(
-            tag_no_case!("order by") >>
-            multispace >>
-            order_by: identifier >>
-            opt!(preceded!(multispace, tag_no_case!("asc"))) >>
-            (order_by.to_string(), false)
-        )
+named!(limit_by_clause<&[u8], LimitBy>,
+    do_parse!(
+        limit: limit_clause >>
+        multispace >>
+        expect!("BY", tag_no_case!("by")) >>
+        not!(ident_char) >>
+        by: separated_nonempty_list!(tag!(","), expr) >>
+        (LimitBy { limit, by })
+    )
+);
+
+named!(order_by_clause<&[u8], Vec<(Expr, bool)>>,
+    do_parse!(
+        expect!("ORDER BY", tag_no_case!("order by")) >>
+        not!(ident_char) >>
+        order_by: separated_nonempty_list!(tag!(","), order_by_key) >>
+        (order_by)
+    )
+);
+
+named!(order_by_key<&[u8], (Expr, bool)>,
+    do_parse!(
+        e: expr >>
+        desc: opt!(
+            preceded!(
+                multispace,
+                alt!(
+                    map!(tag_no_case!("desc"), |_| true) |
+                    map!(tag_no_case!("asc"), |_| false)
+                )
+            )
+        ) >>
+        (e, desc.unwrap_or(false))
     )
 );
 
@@ -420,6 +1676,13 @@ enum AggregateOrSelect {
     Select(Expr),
 }
 
+enum ComparisonRhs {
+    Compare(Func2Type, Expr),
+    In(Vec<Expr>, bool),
+    Between(Expr, Expr, bool),
+    Like(Func2Type, Expr, bool),
+}
+
 
 #[cfg(test)]
 mod tests {
@@ -429,21 +1692,301 @@ mod tests {
     fn test_select_star() {
         assert_eq!(
             format!("{:?}", parse_query("select * from default;".as_bytes())),
-            "Done([], Query { select: [ColName(\"*\")], table: \"default\", filter: Const(Int(1)), aggregate: [], order_by: None, order_desc: false, limit: LimitClause { limit: 100, offset: 0 }, order_by_index: None })");
+            "Done([], Query { with: [], select: [ColName(\"*\")], table: \"default\", join: None, filter: Const(Int(1)), aggregate: [], group_by: [], grouping_sets: [], having: None, aliases: [None], order_by: [], limit: LimitClause { limit: 100, offset: 0 }, limit_by: None, order_by_index: None, union_all: [] })");
+    }
+
+    #[test]
+    fn test_now_interval() {
+        assert_eq!(
+            format!("{:?}", parse_query("select * from default where ts > now() - interval '1 hour';".as_bytes())),
+            "Done([], Query { with: [], select: [ColName(\"*\")], table: \"default\", join: None, filter: Func2(GT, ColName(\"ts\"), Func2(Subtract, Now, Const(Int(3600)))), aggregate: [], group_by: [], grouping_sets: [], having: None, aliases: [None], order_by: [], limit: LimitClause { limit: 100, offset: 0 }, limit_by: None, order_by_index: None, union_all: [] })");
     }
 
     #[test]
     fn test_last_hour() {
-        assert!(
-            format!("{:?}", parse_query("select * from default where $LAST_HOUR;".as_bytes())).starts_with(
-                "Done([], Query { select: [ColName(\"*\")], table: \"default\", filter: Func2(GT, ColName(\"timestamp\"), Const(Int(")
-        )
+        assert_eq!(
+            format!("{:?}", parse_query("select * from default where $LAST_HOUR;".as_bytes())),
+            "Done([], Query { with: [], select: [ColName(\"*\")], table: \"default\", join: None, filter: Func2(GT, ColName(\"timestamp\"), Func2(Subtract, Now, Const(Int(3600)))), aggregate: [], group_by: [], grouping_sets: [], having: None, aliases: [None], order_by: [], limit: LimitClause { limit: 100, offset: 0 }, limit_by: None, order_by_index: None, union_all: [] })");
+        assert_eq!(
+            format!("{:?}", parse("select * from default where $last_day;").map(|query| query.filter)),
+            "Ok(Func2(GT, ColName(\"timestamp\"), Func2(Subtract, Now, Const(Int(86400)))))");
+    }
+
+    #[test]
+    fn test_parameters() {
+        assert_eq!(
+            format!("{:?}", parse_query("select count_if(x > ?), y + $a from default where z = $a or w < ?;".as_bytes())),
+            "Done([], Query { with: [], select: [Func2(Add, ColName(\"y\"), Parameter(1, Some(\"a\")))], table: \"default\", join: None, filter: Func2(Or, Func2(Equals, ColName(\"z\"), Parameter(1, Some(\"a\"))), Func2(LT, ColName(\"w\"), Parameter(2, None))), aggregate: [(Count, Func2(Filtered, Const(Int(1)), Func2(GT, ColName(\"x\"), Parameter(0, None))))], group_by: [Func2(Add, ColName(\"y\"), Parameter(1, Some(\"a\")))], grouping_sets: [], having: None, aliases: [None, None], order_by: [], limit: LimitClause { limit: 100, offset: 0 }, limit_by: None, order_by_index: None, union_all: [] })");
+        assert_eq!(
+            format!("{:?}", parse("select $b, ?, $b + ? from default").map(|query| query.select)),
+            "Ok([Parameter(0, Some(\"b\")), Parameter(1, None), Func2(Add, Parameter(0, Some(\"b\")), Parameter(2, None))])");
+    }
+
+    #[test]
+    fn test_join() {
+        assert_eq!(
+            format!("{:?}", parse_query("select trips.zone, z.name, borough from trips left join zones as z on trips.zone = z.id;".as_bytes())),
+            "Done([], Query { with: [], select: [ColName(\"zone\"), ColName(\"z.name\"), ColName(\"borough\")], table: \"trips\", join: Some(Join { kind: Left, table: \"zones\", alias: \"z\", on: (\"zone\", \"z.id\") }), filter: Const(Int(1)), aggregate: [], group_by: [], grouping_sets: [], having: None, aliases: [None, None, None], order_by: [], limit: LimitClause { limit: 100, offset: 0 }, limit_by: None, order_by_index: None, union_all: [] })");
+        assert_eq!(
+            format!("{:?}", parse_query("select count(0) from trips t join zones on zones.id = t.zone where t.fare > 10;".as_bytes())),
+            "Done([], Query { with: [], select: [], table: \"trips\", join: Some(Join { kind: Inner, table: \"zones\", alias: \"zones\", on: (\"zones.id\", \"zone\") }), filter: Func2(GT, ColName(\"fare\"), Const(Int(10))), aggregate: [(Count, Const(Int(0)))], group_by: [], grouping_sets: [], having: None, aliases: [None], order_by: [], limit: LimitClause { limit: 100, offset: 0 }, limit_by: None, order_by_index: None, union_all: [] })");
     }
 
     #[test]
     fn test_to_year() {
         assert_eq!(
             format!("{:?}", parse_query("select to_year(ts) from default;".as_bytes())),
-            "Done([], Query { select: [Func1(ToYear, ColName(\"ts\"))], table: \"default\", filter: Const(Int(1)), aggregate: [], order_by: None, order_desc: false, limit: LimitClause { limit: 100, offset: 0 }, order_by_index: None })");
+            "Done([], Query { with: [], select: [Func1(ToYear, ColName(\"ts\"))], table: \"default\", join: None, filter: Const(Int(1)), aggregate: [], group_by: [], grouping_sets: [], having: None, aliases: [None], order_by: [], limit: LimitClause { limit: 100, offset: 0 }, limit_by: None, order_by_index: None, union_all: [] })");
+    }
+
+    #[test]
+    fn test_group_by_having() {
+        assert_eq!(
+            format!("{:?}", parse_query("select tld, count(1) from default group by tld having count(1) > 10;".as_bytes())),
+            "Done([], Query { with: [], select: [ColName(\"tld\")], table: \"default\", join: None, filter: Const(Int(1)), aggregate: [(Count, Const(Int(1)))], group_by: [ColName(\"tld\")], grouping_sets: [], having: Some(Func2(GT, Aggregate(Count, Const(Int(1))), Const(Int(10)))), aliases: [None, None], order_by: [], limit: LimitClause { limit: 100, offset: 0 }, limit_by: None, order_by_index: None, union_all: [] })");
+    }
+
+    #[test]
+    fn test_limit_by() {
+        assert_eq!(
+            format!("{:?}", parse_query("select vendor, fare from trips order by fare desc limit 5 by vendor limit 20;".as_bytes())),
+            "Done([], Query { with: [], select: [ColName(\"vendor\"), ColName(\"fare\")], table: \"trips\", join: None, filter: Const(Int(1)), aggregate: [], group_by: [], grouping_sets: [], having: None, aliases: [None, None], order_by: [(ColName(\"fare\"), true)], limit: LimitClause { limit: 20, offset: 0 }, limit_by: Some(LimitBy { limit: LimitClause { limit: 5, offset: 0 }, by: [ColName(\"vendor\")] }), order_by_index: None, union_all: [] })");
+        assert_eq!(
+            format!("{:?}", parse_query("select country, page, count(0) as views from hits group by country, page order by views desc limit 10 offset 1 by country;".as_bytes()).map(|query| (query.limit, query.limit_by))),
+            "Done([], (LimitClause { limit: 100, offset: 0 }, Some(LimitBy { limit: LimitClause { limit: 10, offset: 1 }, by: [ColName(\"country\")] })))");
+    }
+
+    #[test]
+    fn test_grouping_sets() {
+        assert_eq!(
+            format!("{:?}", parse_query("select a, b, count(0) from t group by rollup(a, b);".as_bytes()).map(|query| (query.group_by, query.grouping_sets))),
+            "Done([], ([ColName(\"a\"), ColName(\"b\")], [[0, 1], [0], []]))");
+        assert_eq!(
+            format!("{:?}", parse_query("select a, b, count(0) from t group by CUBE (a, b);".as_bytes()).map(|query| (query.group_by, query.grouping_sets))),
+            "Done([], ([ColName(\"a\"), ColName(\"b\")], [[0, 1], [0], [1], []]))");
+        assert_eq!(
+            format!("{:?}", parse_query("select a, b, count(0) from t group by grouping sets ((a, b), b, ());".as_bytes()).map(|query| (query.group_by, query.grouping_sets))),
+            "Done([], ([ColName(\"a\"), ColName(\"b\")], [[0, 1], [1], []]))");
+    }
+
+    #[test]
+    fn test_min_max_avg() {
+        assert_eq!(
+            format!("{:?}", parse_query("select tld, min(num), MAX(num), avg(num) from default;".as_bytes())),
+            "Done([], Query { with: [], select: [ColName(\"tld\")], table: \"default\", join: None, filter: Const(Int(1)), aggregate: [(Min, ColName(\"num\")), (Max, ColName(\"num\")), (Avg, ColName(\"num\"))], group_by: [ColName(\"tld\")], grouping_sets: [], having: None, aliases: [None, None, None, None], order_by: [], limit: LimitClause { limit: 100, offset: 0 }, limit_by: None, order_by_index: None, union_all: [] })");
+    }
+
+    #[test]
+    fn test_distinct() {
+        assert_eq!(
+            format!("{:?}", parse_query("select distinct tld from default;".as_bytes())),
+            "Done([], Query { with: [], select: [ColName(\"tld\")], table: \"default\", join: None, filter: Const(Int(1)), aggregate: [], group_by: [ColName(\"tld\")], grouping_sets: [], having: None, aliases: [None], order_by: [], limit: LimitClause { limit: 100, offset: 0 }, limit_by: None, order_by_index: None, union_all: [] })");
+        assert_eq!(
+            format!("{:?}", parse_query("select tld, count(distinct num) from default having count( DISTINCT num) > 1;".as_bytes())),
+            "Done([], Query { with: [], select: [ColName(\"tld\")], table: \"default\", join: None, filter: Const(Int(1)), aggregate: [(CountDistinct, ColName(\"num\"))], group_by: [ColName(\"tld\")], grouping_sets: [], having: Some(Func2(GT, Aggregate(CountDistinct, ColName(\"num\")), Const(Int(1)))), aliases: [None, None], order_by: [], limit: LimitClause { limit: 100, offset: 0 }, limit_by: None, order_by_index: None, union_all: [] })");
+    }
+
+    #[test]
+    fn test_approx_count_distinct() {
+        assert_eq!(
+            format!("{:?}", parse_query("select tld, approx_count_distinct(num), APPROX_COUNT_DISTINCT( first_name , 10 ) from default;".as_bytes())),
+            "Done([], Query { with: [], select: [ColName(\"tld\")], table: \"default\", join: None, filter: Const(Int(1)), aggregate: [(ApproxCountDistinct(14), ColName(\"num\")), (ApproxCountDistinct(10), ColName(\"first_name\"))], group_by: [ColName(\"tld\")], grouping_sets: [], having: None, aliases: [None, None, None], order_by: [], limit: LimitClause { limit: 100, offset: 0 }, limit_by: None, order_by_index: None, union_all: [] })");
+    }
+
+    #[test]
+    fn test_percentile() {
+        assert_eq!(
+            format!("{:?}", parse_query("select median(latency), percentile(latency, 0.99) from default;".as_bytes())),
+            "Done([], Query { with: [], select: [], table: \"default\", join: None, filter: Const(Int(1)), aggregate: [(Percentile(0.5), ColName(\"latency\")), (Percentile(0.99), ColName(\"latency\"))], group_by: [], grouping_sets: [], having: None, aliases: [None, None], order_by: [], limit: LimitClause { limit: 100, offset: 0 }, limit_by: None, order_by_index: None, union_all: [] })");
+        assert_eq!(
+            format!("{:?}", parse_query("select host, quantiles(latency, [0.5, 1]) as q from default;".as_bytes())),
+            "Done([], Query { with: [], select: [ColName(\"host\")], table: \"default\", join: None, filter: Const(Int(1)), aggregate: [(Percentile(0.5), ColName(\"latency\")), (Percentile(1.0), ColName(\"latency\"))], group_by: [ColName(\"host\")], grouping_sets: [], having: None, aliases: [None, Some(\"q_0\"), Some(\"q_1\")], order_by: [], limit: LimitClause { limit: 100, offset: 0 }, limit_by: None, order_by_index: None, union_all: [] })");
+    }
+
+    #[test]
+    fn test_moments() {
+        assert_eq!(
+            format!("{:?}", parse_query("select variance(x), stddev(x), covar(x, y + 1), corr( x , y ) from default;".as_bytes())),
+            "Done([], Query { with: [], select: [], table: \"default\", join: None, filter: Const(Int(1)), aggregate: [(Variance, ColName(\"x\")), (StdDev, ColName(\"x\")), (Covar, Func2(Pair, ColName(\"x\"), Func2(Add, ColName(\"y\"), Const(Int(1))))), (Corr, Func2(Pair, ColName(\"x\"), ColName(\"y\")))], group_by: [], grouping_sets: [], having: None, aliases: [None, None, None, None], order_by: [], limit: LimitClause { limit: 100, offset: 0 }, limit_by: None, order_by_index: None, union_all: [] })");
+    }
+
+    #[test]
+    fn test_filter_clause() {
+        assert_eq!(
+            format!("{:?}", parse_query("select count_if(x > 1), sum(x) filter (where y), covar(x, y) FILTER(WHERE x) from default having avg(x) filter (where y) > 0;".as_bytes())),
+            "Done([], Query { with: [], select: [], table: \"default\", join: None, filter: Const(Int(1)), aggregate: [(Count, Func2(Filtered, Const(Int(1)), Func2(GT, ColName(\"x\"), Const(Int(1))))), (Sum, Func2(Filtered, ColName(\"x\"), ColName(\"y\"))), (Covar, Func2(Pair, Func2(Filtered, ColName(\"x\"), ColName(\"x\")), Func2(Filtered, ColName(\"y\"), ColName(\"x\"))))], group_by: [], grouping_sets: [], having: Some(Func2(GT, Aggregate(Avg, Func2(Filtered, ColName(\"x\"), ColName(\"y\"))), Const(Int(0)))), aliases: [None, None, None], order_by: [], limit: LimitClause { limit: 100, offset: 0 }, limit_by: None, order_by_index: None, union_all: [] })");
+    }
+
+    #[test]
+    fn test_case_when() {
+        assert_eq!(
+            format!("{:?}", parse_query("select case when x < 3 then \"small\" when x < 10 then \"medium\" else \"large\" end, CASE WHEN y THEN 1 END, if(x = 1, y, 2.5) from default;".as_bytes())),
+            "Done([], Query { with: [], select: [If(Func2(LT, ColName(\"x\"), Const(Int(3))), Const(Str(\"small\")), If(Func2(LT, ColName(\"x\"), Const(Int(10))), Const(Str(\"medium\")), Const(Str(\"large\")))), Func2(Filtered, Const(Int(1)), ColName(\"y\")), If(Func2(Equals, ColName(\"x\"), Const(Int(1))), ColName(\"y\"), Const(Float(2.5)))], table: \"default\", join: None, filter: Const(Int(1)), aggregate: [], group_by: [], grouping_sets: [], having: None, aliases: [None, None, None], order_by: [], limit: LimitClause { limit: 100, offset: 0 }, limit_by: None, order_by_index: None, union_all: [] })");
+    }
+
+    #[test]
+    fn test_subquery() {
+        assert_eq!(
+            format!("{:?}", parse_query("with t as (select num from default) select count(0) from t;".as_bytes())),
+            "Done([], Query { with: [(\"t\", Query { with: [], select: [ColName(\"num\")], table: \"default\", join: None, filter: Const(Int(1)), aggregate: [], group_by: [], grouping_sets: [], having: None, aliases: [None], order_by: [], limit: LimitClause { limit: 18446744073709551615, offset: 0 }, limit_by: None, order_by_index: None, union_all: [] })], select: [], table: \"t\", join: None, filter: Const(Int(1)), aggregate: [(Count, Const(Int(0)))], group_by: [], grouping_sets: [], having: None, aliases: [None], order_by: [], limit: LimitClause { limit: 100, offset: 0 }, limit_by: None, order_by_index: None, union_all: [] })");
+        assert_eq!(
+            format!("{:?}", parse_query("select count(0) from (select num from default) where num > 1;".as_bytes())),
+            "Done([], Query { with: [(\"subquery\", Query { with: [], select: [ColName(\"num\")], table: \"default\", join: None, filter: Const(Int(1)), aggregate: [], group_by: [], grouping_sets: [], having: None, aliases: [None], order_by: [], limit: LimitClause { limit: 18446744073709551615, offset: 0 }, limit_by: None, order_by_index: None, union_all: [] })], select: [], table: \"subquery\", join: None, filter: Func2(GT, ColName(\"num\"), Const(Int(1))), aggregate: [(Count, Const(Int(0)))], group_by: [], grouping_sets: [], having: None, aliases: [None], order_by: [], limit: LimitClause { limit: 100, offset: 0 }, limit_by: None, order_by_index: None, union_all: [] })");
+    }
+
+    #[test]
+    fn test_union_all() {
+        assert_eq!(
+            format!("{:?}", parse_query("select num from events_* union all select num from t;".as_bytes())),
+            "Done([], Query { with: [], select: [ColName(\"num\")], table: \"events_*\", join: None, filter: Const(Int(1)), aggregate: [], group_by: [], grouping_sets: [], having: None, aliases: [None], order_by: [], limit: LimitClause { limit: 100, offset: 0 }, limit_by: None, order_by_index: None, union_all: [Query { with: [], select: [ColName(\"num\")], table: \"t\", join: None, filter: Const(Int(1)), aggregate: [], group_by: [], grouping_sets: [], having: None, aliases: [None], order_by: [], limit: LimitClause { limit: 18446744073709551615, offset: 0 }, limit_by: None, order_by_index: None, union_all: [] }] })");
+        assert_eq!(
+            format!("{:?}", parse("select a as x from t union all select b from u order by x desc limit 5;")
+                .map(|query| (query.order_by, query.limit, query.union_all[0].limit.clone()))),
+            "Ok(([(ColName(\"x\"), true)], LimitClause { limit: 5, offset: 0 }, LimitClause { limit: 18446744073709551615, offset: 0 }))");
+    }
+
+    #[test]
+    fn test_alias_order_by() {
+        assert_eq!(
+            format!("{:?}", parse_query("select num / 10 as bucket, sum(num) as total from default group by bucket order by total desc, 1;".as_bytes())),
+            "Done([], Query { with: [], select: [Func2(Divide, ColName(\"num\"), Const(Int(10)))], table: \"default\", join: None, filter: Const(Int(1)), aggregate: [(Sum, ColName(\"num\"))], group_by: [Func2(Divide, ColName(\"num\"), Const(Int(10)))], grouping_sets: [], having: None, aliases: [Some(\"bucket\"), Some(\"total\")], order_by: [(Aggregate(Sum, ColName(\"num\")), true), (Const(Int(1)), false)], limit: LimitClause { limit: 100, offset: 0 }, limit_by: None, order_by_index: None, union_all: [] })");
+    }
+
+    fn parse_error(query: &str) -> String {
+        let err = parse(query).unwrap_err();
+        format!("{}\n{}", err.highlight(), err)
+    }
+
+    #[test]
+    fn test_syntax_errors() {
+        assert_eq!(
+            parse_error("select num form default;"),
+            "select num form default;\n           ^^^^\nSyntax error at line 1, column 12: expected `,` or FROM, found `form`");
+        assert_eq!(
+            parse_error("select num from default\nwhere num > 3 lmit 5;"),
+            "where num > 3 lmit 5;\n              ^^^^\nSyntax error at line 2, column 15: expected GROUP BY, HAVING, UNION ALL, ORDER BY, LIMIT or `;`, found `lmit`");
+        assert_eq!(
+            parse_error("select num from default order by;"),
+            "select num from default order by;\n                                ^\nSyntax error at line 1, column 33: expected expression, found `;`");
+        assert_eq!(
+            parse_error("select num from default where num > 3"),
+            "select num from default where num > 3\n                                     ^\nSyntax error at line 1, column 38: expected GROUP BY, HAVING, UNION ALL, ORDER BY, LIMIT or `;`, found end of query");
+        assert_eq!(
+            parse_error("select num from default left join zones z zone = z.id;"),
+            "select num from default left join zones z zone = z.id;\n                                          ^^^^\nSyntax error at line 1, column 43: expected ON, found `zone`");
+        assert_eq!(
+            parse_error("selct num from default;"),
+            "selct num from default;\n^^^^^\nSyntax error at line 1, column 1: expected SELECT, found `selct`");
+        assert_eq!(parse("select \"\0\" from default;").unwrap_err().offset, 8);
+        assert_eq!(
+            parse_error("select num from a union all selct num from b;"),
+            "select num from a union all selct num from b;\n                            ^^^^^\nSyntax error at line 1, column 29: expected SELECT, found `selct`");
+        assert_eq!(
+            parse_error("select num from (select num form default);"),
+            "select num from (select num form default);\n                            ^^^^\nSyntax error at line 1, column 29: expected `,` or FROM, found `form`");
+    }
+
+    fn parse_expr(expression: &str) -> String {
+        format!("{:?}", expr(expression.as_bytes()))
+    }
+
+    #[test]
+    fn test_left_associativity() {
+        assert_eq!(
+            parse_expr("a - b - c;"),
+            "Done([59], Func2(Subtract, Func2(Subtract, ColName(\"a\"), ColName(\"b\")), ColName(\"c\")))");
+        assert_eq!(
+            parse_expr("a / b * c;"),
+            "Done([59], Func2(Multiply, Func2(Divide, ColName(\"a\"), ColName(\"b\")), ColName(\"c\")))");
+    }
+
+    #[test]
+    fn test_arithmetic_precedence() {
+        assert_eq!(
+            parse_expr("a + b * c - -d;"),
+            "Done([59], Func2(Subtract, Func2(Add, ColName(\"a\"), Func2(Multiply, ColName(\"b\"), ColName(\"c\"))), Func1(Negate, ColName(\"d\"))))");
+        assert_eq!(
+            parse_expr("a + b % c;"),
+            "Done([59], Func2(Add, ColName(\"a\"), Func2(Modulo, ColName(\"b\"), ColName(\"c\"))))");
+        assert_eq!(
+            parse_expr("(a + b) * c < 10;"),
+            "Done([59], Func2(LT, Func2(Multiply, Func2(Add, ColName(\"a\"), ColName(\"b\")), ColName(\"c\")), Const(Int(10))))");
+    }
+
+    #[test]
+    fn test_boolean_precedence() {
+        assert_eq!(
+            parse_expr("x = 1 and y = 2 or z = 3;"),
+            "Done([59], Func2(Or, Func2(And, Func2(Equals, ColName(\"x\"), Const(Int(1))), Func2(Equals, ColName(\"y\"), Const(Int(2)))), Func2(Equals, ColName(\"z\"), Const(Int(3)))))");
+        assert_eq!(
+            parse_expr("x = 1 or y = 2 and z = 3;"),
+            "Done([59], Func2(Or, Func2(Equals, ColName(\"x\"), Const(Int(1))), Func2(And, Func2(Equals, ColName(\"y\"), Const(Int(2))), Func2(Equals, ColName(\"z\"), Const(Int(3))))))");
+        assert_eq!(
+            parse_expr("not x = 1 and note <> 2;"),
+            "Done([59], Func2(And, Func1(Not, Func2(Equals, ColName(\"x\"), Const(Int(1)))), Func2(NotEquals, ColName(\"note\"), Const(Int(2)))))");
+    }
+
+    #[test]
+    fn test_is_null() {
+        assert_eq!(
+            parse_expr("a is null or b + 1 IS NOT NULL;"),
+            "Done([59], Func2(Or, Func1(IsNull, ColName(\"a\")), Func1(IsNotNull, Func2(Add, ColName(\"b\"), Const(Int(1))))))");
+        assert_eq!(
+            parse_expr("not a is null and isnull = 1;"),
+            "Done([59], Func2(And, Func1(Not, Func1(IsNull, ColName(\"a\"))), Func2(Equals, ColName(\"isnull\"), Const(Int(1)))))");
+    }
+
+    #[test]
+    fn test_in_between() {
+        assert_eq!(
+            parse_expr("a in (\"x\", b) and c NOT IN(1);"),
+            "Done([59], Func2(And, In(ColName(\"a\"), [Const(Str(\"x\")), ColName(\"b\")]), Func1(Not, In(ColName(\"c\"), [Const(Int(1))]))))");
+        assert_eq!(
+            parse_expr("a between 1 and b + 1 and c not between 2 and 3;"),
+            "Done([59], Func2(And, Func2(And, Func2(GTE, ColName(\"a\"), Const(Int(1))), Func2(LTE, ColName(\"a\"), Func2(Add, ColName(\"b\"), Const(Int(1))))), Func1(Not, Func2(And, Func2(GTE, ColName(\"c\"), Const(Int(2))), Func2(LTE, ColName(\"c\"), Const(Int(3)))))))");
+    }
+
+    #[test]
+    fn test_string_functions() {
+        assert_eq!(
+            parse_expr("a like \"x%\" and b not ilike c;"),
+            "Done([59], Func2(And, Func2(Like, ColName(\"a\"), Const(Str(\"x%\"))), Func1(Not, Func2(ILike, ColName(\"b\"), ColName(\"c\")))))");
+        assert_eq!(
+            parse_expr("concat(lower(a), \"-\", substr(b, -1, 2)) = upper(length);"),
+            "Done([59], Func2(Equals, Func2(Concat, Func2(Concat, Func1(Lower, ColName(\"a\")), Const(Str(\"-\"))), Func1(Substr(-1, Some(2)), ColName(\"b\"))), Func1(Upper, ColName(\"length\"))))");
+    }
+
+    #[test]
+    fn test_date_functions() {
+        assert_eq!(
+            parse_expr("to_hour(ts) = day_of_week(ts) and ts > '2016-03-13 00:00:00';"),
+            "Done([59], Func2(And, Func2(Equals, Func1(ToHour, ColName(\"ts\")), Func1(DayOfWeek, ColName(\"ts\"))), Func2(GT, ColName(\"ts\"), Const(Int(1457827200)))))");
+        assert_eq!(
+            parse_expr("date_trunc('week', ts) < '2016-03-13';"),
+            "Done([59], Func2(LT, Func1(DateTrunc(Week), ColName(\"ts\")), Const(Int(1457827200))))");
+        assert!(date_trunc("date_trunc('day\", ts);".as_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_float_literals() {
+        assert_eq!(
+            parse_expr("a * 1.5 > 10 - 0.25;"),
+            "Done([59], Func2(GT, Func2(Multiply, ColName(\"a\"), Const(Float(1.5))), Func2(Subtract, Const(Int(10)), Const(Float(0.25)))))");
+        assert_eq!(
+            parse_expr("a + 1. + .5 + 2e3 + 1.5E-3 + 3;"),
+            "Done([59], Func2(Add, Func2(Add, Func2(Add, Func2(Add, Func2(Add, ColName(\"a\"), Const(Float(1.0))), Const(Float(0.5))), Const(Float(2000.0))), Const(Float(0.0015))), Const(Int(3))))");
+    }
+
+    #[test]
+    fn test_comparison_operators() {
+        assert_eq!(
+            parse_expr("a >= b;"),
+            "Done([59], Func2(GTE, ColName(\"a\"), ColName(\"b\")))");
+        assert_eq!(
+            parse_expr("a <= 1;"),
+            "Done([59], Func2(LTE, ColName(\"a\"), Const(Int(1))))");
+        assert_eq!(
+            parse_expr("a <> b;"),
+            "Done([59], Func2(NotEquals, ColName(\"a\"), ColName(\"b\")))");
     }
 }
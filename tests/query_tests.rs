@@ -32,6 +32,34 @@ fn test_query_ec(query: &str, expected_rows: &[Vec<Value>]) {
     assert_eq!(result.0.unwrap().rows, expected_rows);
 }
 
+fn test_query_floats(query: &str, expected_rows: &[Vec<Value>]) {
+    let _ = env_logger::try_init();
+    let locustdb = LocustDB::new(&Options::default());
+    let _ = block_on(locustdb.load_csv(
+        LoadOptions::new("test_data/floats.csv", "default")
+            .with_partition_size(3)));
+    let result = block_on(locustdb.run_query(query, false, vec![])).unwrap();
+    assert_eq!(result.0.unwrap().rows, expected_rows);
+}
+
+/// Like `test_query_floats`, but compares floats with a tolerance since their rounding errors depend on the order in
+/// which partitions are merged.
+fn test_query_floats_approx(query: &str, expected_rows: &[Vec<Value>]) {
+    let _ = env_logger::try_init();
+    let locustdb = LocustDB::new(&Options::default());
+    let _ = block_on(locustdb.load_csv(
+        LoadOptions::new("test_data/floats.csv", "default")
+            .with_partition_size(3)));
+    let rows = block_on(locustdb.run_query(query, false, vec![])).unwrap().0.unwrap().rows;
+    let approx_eq = |row: &[Value], expected: &[Value]| row.len() == expected.len() &&
+        row.iter().zip(expected).all(|(value, expected)| match (value, expected) {
+            (&Value::Float(value), &Value::Float(expected)) => (value.0 - expected.0).abs() < 1e-9,
+            (value, expected) => value == expected,
+        });
+    assert!(rows.len() == expected_rows.len() && rows.iter().zip(expected_rows).all(|(row, expected)| approx_eq(row, expected)),
+            "{:?} != {:?}", rows, expected_rows);
+}
+
 fn test_query_nyc(query: &str, expected_rows: &[Vec<Value>]) {
     let _ = env_logger::try_init();
     #[allow(unused_mut)]
@@ -47,6 +75,19 @@ fn test_query_nyc(query: &str, expected_rows: &[Vec<Value>]) {
     assert_eq!(&actual_rows[..min(5, actual_rows.len())], expected_rows);
 }
 
+/// Database with tables `shard_1` and `shard_2` loaded from `tiny.csv` and `tlds.csv`.
+fn sharded_db() -> LocustDB {
+    let _ = env_logger::try_init();
+    let locustdb = LocustDB::memory_only();
+    let _ = block_on(locustdb.load_csv(
+        LoadOptions::new("test_data/tiny.csv", "shard_1")
+            .with_partition_size(40)));
+    let _ = block_on(locustdb.load_csv(
+        LoadOptions::new("test_data/tlds.csv", "shard_2")
+            .with_partition_size(3)));
+    locustdb
+}
+
 #[test]
 fn test_select_string() {
     test_query(
@@ -74,8 +115,8 @@ fn test_select_integer() {
     test_query(
         "select num from default order by num limit 2;",
         &[
-            vec![0.into()],
-            vec![0.into()]
+            vec![1.into()],
+            vec![1.into()]
         ],
     )
 }
@@ -107,7 +148,6 @@ fn group_by_integer_filter_integer_lt() {
     test_query(
         "select num, count(1) from default where num < 8;",
         &[
-            vec![0.into(), 8.into()],
             vec![1.into(), 49.into()],
             vec![2.into(), 24.into()],
             vec![3.into(), 11.into()],
@@ -166,6 +206,118 @@ fn test_string_packed_column() {
     )
 }
 
+#[test]
+fn test_regex_string_packed_column() {
+    test_query_ec(
+        "select string_packed from default where regex(string_packed, \"^a.[yz]$\") order by string_packed;",
+        &[
+            vec!["axy".into()],
+            vec!["axz".into()],
+            vec!["azy".into()],
+        ],
+    )
+}
+
+#[test]
+fn test_regex_dictionary() {
+    use Value::*;
+    test_query(
+        "select tld, count(1) from default where regex(tld, \"o\") and not regex(tld, \"^(n|m|e)\");",
+        &[
+            vec![Str("com".to_string()), Int(9)],
+            vec![Str("gov".to_string()), Int(5)],
+            vec![Str("info".to_string()), Int(13)],
+            vec![Str("org".to_string()), Int(8)],
+        ],
+    )
+}
+
+#[test]
+fn test_like_upper_dictionary() {
+    use Value::*;
+    test_query(
+        "select upper(tld), count(1) from default where tld like \"%o%\" and not tld like \"n%\" group by upper(tld);",
+        &[
+            vec![Str("COM".to_string()), Int(9)],
+            vec![Str("GOV".to_string()), Int(5)],
+            vec![Str("INFO".to_string()), Int(13)],
+            vec![Str("ORG".to_string()), Int(8)],
+        ],
+    )
+}
+
+#[test]
+fn test_ilike_length() {
+    test_query(
+        "select first_name from default where first_name ilike \"A%\" and length(first_name) > 4 order by first_name;",
+        &[
+            vec!["Amanda".into()],
+            vec!["Angela".into()],
+            vec!["Anthony".into()],
+            vec!["Antonio".into()],
+        ],
+    )
+}
+
+#[test]
+fn test_concat_substr_lower() {
+    test_query_ec(
+        "select string_packed, concat(enum, \"-\", substr(string_packed, 2)) from default where lower(string_packed) like \"a%\" order by string_packed;",
+        &[
+            vec!["abc".into(), "aa-bc".into()],
+            vec!["asd".into(), "cc-sd".into()],
+            vec!["axy".into(), "bb-xy".into()],
+            vec!["axz".into(), "aa-xz".into()],
+            vec!["azy".into(), "bb-zy".into()],
+        ],
+    )
+}
+
+#[test]
+fn test_day_of_week() {
+    use Value::*;
+    test_query(
+        "select day_of_week(ts), count(1) from default;",
+        &[
+            vec![Int(0), Int(18)],
+            vec![Int(1), Int(16)],
+            vec![Int(2), Int(17)],
+            vec![Int(3), Int(11)],
+            vec![Int(4), Int(10)],
+            vec![Int(5), Int(11)],
+            vec![Int(6), Int(17)],
+        ],
+    )
+}
+
+#[test]
+fn test_timestamp_literal() {
+    use Value::*;
+    test_query(
+        "select to_month(ts), count(1) from default where ts > '2016-10-01';",
+        &[
+            vec![Int(1), Int(6)],
+            vec![Int(2), Int(5)],
+            vec![Int(10), Int(9)],
+            vec![Int(11), Int(12)],
+            vec![Int(12), Int(5)],
+        ],
+    )
+}
+
+#[test]
+fn test_date_trunc() {
+    use Value::*;
+    test_query(
+        "select date_trunc('month', ts), to_year(ts), count(1) from default where to_hour(ts) < 3 and to_day(ts) > 15;",
+        &[
+            vec![Int(1459468800), Int(2016), Int(1)],
+            vec![Int(1464739200), Int(2016), Int(3)],
+            vec![Int(1477958400), Int(2016), Int(2)],
+        ],
+    )
+}
+
 #[test]
 fn test_and_or() {
     test_query(
@@ -229,7 +381,35 @@ fn test_division() {
     test_query(
         "select num / 10, count(1) from default;",
         &[
-            vec![0.into(), 100.into()],
+            vec![Value::Int(0), Value::Int(92)],
+            vec![Value::Null, Value::Int(8)],
+        ],
+    )
+}
+
+#[test]
+fn test_division_by_zero() {
+    use Value::*;
+    test_query(
+        "select num / (num - 1), num % (num - 1), num / 0, count(1) from default;",
+        &[
+            vec![Int(1), Int(1), Null, Int(19)],
+            vec![Int(2), Int(0), Null, Int(24)],
+            vec![Null, Null, Null, Int(57)],
+        ],
+    )
+}
+
+#[test]
+fn test_division_constant_dividend() {
+    use Value::*;
+    test_query(
+        "select 10 / num, 10 % num, count(1) from default where num < 5;",
+        &[
+            vec![Int(2), Int(2), Int(5)],
+            vec![Int(3), Int(1), Int(11)],
+            vec![Int(5), Int(0), Int(24)],
+            vec![Int(10), Int(0), Int(49)],
         ],
     )
 }
@@ -250,6 +430,1046 @@ fn test_not_equals() {
     )
 }
 
+#[test]
+fn test_group_by_having() {
+    use Value::*;
+    test_query(
+        "select tld, sum(num) from default group by tld having count(1) > 11;",
+        &[
+            vec![Str("edu".to_string()), Int(24)],
+            vec![Str("info".to_string()), Int(22)],
+            vec![Str("name".to_string()), Int(26)],
+        ],
+    )
+}
+
+#[test]
+fn test_rollup() {
+    use Value::*;
+    test_query(
+        "select tld, num, count(0) from default where num > 3 group by rollup(tld, num) order by tld, num;",
+        &[
+            vec![Str("com".to_string()), Int(5), Int(1)],
+            vec![Str("com".to_string()), Null, Int(1)],
+            vec![Str("edu".to_string()), Int(4), Int(1)],
+            vec![Str("edu".to_string()), Null, Int(1)],
+            vec![Str("mil".to_string()), Int(4), Int(2)],
+            vec![Str("mil".to_string()), Int(8), Int(1)],
+            vec![Str("mil".to_string()), Null, Int(3)],
+            vec![Str("name".to_string()), Int(4), Int(1)],
+            vec![Str("name".to_string()), Null, Int(1)],
+            vec![Str("net".to_string()), Int(4), Int(1)],
+            vec![Str("net".to_string()), Null, Int(1)],
+            vec![Str("org".to_string()), Int(5), Int(1)],
+            vec![Str("org".to_string()), Null, Int(1)],
+            vec![Null, Null, Int(8)],
+        ],
+    )
+}
+
+#[test]
+fn test_cube() {
+    use Value::*;
+    test_query(
+        "select num, count(0) from default where num > 3 group by cube(num) order by num;",
+        &[
+            vec![Int(4), Int(5)],
+            vec![Int(5), Int(2)],
+            vec![Int(8), Int(1)],
+            vec![Null, Int(8)],
+        ],
+    )
+}
+
+#[test]
+fn test_grouping_sets() {
+    use Value::*;
+    test_query(
+        "select tld, num, count(0) from default where num > 3 group by grouping sets ((tld), (num)) order by tld, num;",
+        &[
+            vec![Str("com".to_string()), Null, Int(1)],
+            vec![Str("edu".to_string()), Null, Int(1)],
+            vec![Str("mil".to_string()), Null, Int(3)],
+            vec![Str("name".to_string()), Null, Int(1)],
+            vec![Str("net".to_string()), Null, Int(1)],
+            vec![Str("org".to_string()), Null, Int(1)],
+            vec![Null, Int(4), Int(5)],
+            vec![Null, Int(5), Int(2)],
+            vec![Null, Int(8), Int(1)],
+        ],
+    )
+}
+
+#[test]
+fn test_rollup_aggregates() {
+    use Value::*;
+    test_query(
+        "select tld, count(distinct num), min(num), max(num), avg(num), sum(num), percentile(num, 0.5) \
+         from default where num > 3 group by rollup(tld) order by tld;",
+        &[
+            vec![Str("com".to_string()), Int(1), Int(5), Int(5), 5.0.into(), Int(5), 5.0.into()],
+            vec![Str("edu".to_string()), Int(1), Int(4), Int(4), 4.0.into(), Int(4), 4.0.into()],
+            vec![Str("mil".to_string()), Int(2), Int(4), Int(8), (16.0 / 3.0).into(), Int(16), 4.0.into()],
+            vec![Str("name".to_string()), Int(1), Int(4), Int(4), 4.0.into(), Int(4), 4.0.into()],
+            vec![Str("net".to_string()), Int(1), Int(4), Int(4), 4.0.into(), Int(4), 4.0.into()],
+            vec![Str("org".to_string()), Int(1), Int(5), Int(5), 5.0.into(), Int(5), 5.0.into()],
+            vec![Null, Int(3), Int(4), Int(8), 4.75.into(), Int(38), 4.0.into()],
+        ],
+    )
+}
+
+#[test]
+fn test_grouping_sets_wide_key() {
+    use Value::*;
+    // The grouping key of both expressions doesn't fit into 64 bits, but the grouping key of each grouping set does
+    test_query(
+        "select num * 1000000000000000, num * 1000000000000001, count(0) from default where num > 3 \
+         group by grouping sets ((num * 1000000000000000), (num * 1000000000000001));",
+        &[
+            vec![Int(4000000000000000), Null, Int(5)],
+            vec![Int(5000000000000000), Null, Int(2)],
+            vec![Int(8000000000000000), Null, Int(1)],
+            vec![Null, Int(4000000000000004), Int(5)],
+            vec![Null, Int(5000000000000005), Int(2)],
+            vec![Null, Int(8000000000000008), Int(1)],
+        ],
+    )
+}
+
+#[test]
+fn test_limit_by() {
+    use Value::*;
+    test_query(
+        "select tld, ts from default order by ts desc limit 2 by tld limit 8;",
+        &[
+            vec![Str("net".to_string()), Int(1487173444)],
+            vec![Str("mil".to_string()), Int(1487081037)],
+            vec![Str("name".to_string()), Int(1487067684)],
+            vec![Str("edu".to_string()), Int(1486388008)],
+            vec![Str("info".to_string()), Int(1486229529)],
+            vec![Str("mil".to_string()), Int(1484445825)],
+            vec![Str("edu".to_string()), Int(1484191878)],
+            vec![Null, Int(1484063801)],
+        ],
+    );
+    test_query(
+        "select tld, num, count(0) from default group by tld, num order by count(0) desc, tld, num limit 1 by tld limit 6;",
+        &[
+            vec![Str("name".to_string()), Int(1), Int(11)],
+            vec![Null, Null, Int(8)],
+            vec![Str("edu".to_string()), Int(1), Int(7)],
+            vec![Str("info".to_string()), Int(1), Int(6)],
+            vec![Str("biz".to_string()), Int(1), Int(5)],
+            vec![Str("mil".to_string()), Int(1), Int(5)],
+        ],
+    );
+}
+
+#[test]
+fn test_group_by_unselected_column() {
+    test_query_ec(
+        "select count(0) from default group by enum having count(0) > 2;",
+        &[
+            vec![5.into()],
+            vec![3.into()],
+        ],
+    )
+}
+
+#[test]
+fn test_group_by_without_aggregate() {
+    test_query_ec(
+        "select enum from default where non_dense_ints < 2 group by enum;",
+        &[
+            vec!["aa".into()],
+            vec!["bb".into()],
+        ],
+    )
+}
+
+#[test]
+fn test_select_column_not_in_group_by() {
+    let locustdb = LocustDB::memory_only();
+    let _ = block_on(locustdb.load_csv(
+        LoadOptions::new("test_data/edge_cases.csv", "default")
+            .with_partition_size(3)));
+    let result = block_on(locustdb.run_query("select enum, count(0) from default group by non_dense_ints;", false, vec![])).unwrap();
+    assert!(result.0.is_err());
+}
+
+#[test]
+fn test_order_by_aggregate_alias() {
+    use Value::*;
+    test_query(
+        "select tld, count(1) as c from default group by tld order by c desc limit 3;",
+        &[
+            vec![Str("name".to_string()), Int(17)],
+            vec![Str("edu".to_string()), Int(13)],
+            vec![Str("info".to_string()), Int(13)],
+        ],
+    )
+}
+
+#[test]
+fn test_order_by_unselected_aggregate() {
+    test_query(
+        "select tld from default group by tld order by sum(num) desc limit 2;",
+        &[
+            vec!["mil".into()],
+            vec!["name".into()],
+        ],
+    )
+}
+
+#[test]
+fn test_order_by_ordinal() {
+    test_query(
+        "select num, count(1) from default order by 2 desc limit 2;",
+        &[
+            vec![1.into(), 49.into()],
+            vec![2.into(), 24.into()],
+        ],
+    )
+}
+
+#[test]
+fn test_order_by_unselected_column() {
+    test_query(
+        "select first_name from default order by num desc limit 1;",
+        &[vec!["Stephanie".into()]],
+    )
+}
+
+#[test]
+fn test_order_by_multiple_keys() {
+    use Value::*;
+    test_query(
+        "select num, first_name as name from default order by num desc, name limit 5;",
+        &[
+            vec![Int(8), Str("Stephanie".to_string())],
+            vec![Int(5), Str("Christina".to_string())],
+            vec![Int(5), Str("Joshua".to_string())],
+            vec![Int(4), Str("Anne".to_string())],
+            vec![Int(4), Str("Diane".to_string())],
+        ],
+    )
+}
+
+#[test]
+fn test_order_by_nulls_last() {
+    use Value::*;
+    test_query(
+        "select num, count(0) from default group by num order by num desc;",
+        &[
+            vec![Int(8), Int(1)],
+            vec![Int(5), Int(2)],
+            vec![Int(4), Int(5)],
+            vec![Int(3), Int(11)],
+            vec![Int(2), Int(24)],
+            vec![Int(1), Int(49)],
+            vec![Null, Int(8)],
+        ],
+    );
+    test_query(
+        "select first_name, num from default order by num desc, first_name limit 3 offset 91;",
+        &[
+            vec![Str("Willie".to_string()), Int(1)],
+            vec![Str("Billy".to_string()), Null],
+            vec![Str("Carl".to_string()), Null],
+        ],
+    );
+    test_query(
+        "select tld, ts from default order by tld, ts limit 2 offset 91;",
+        &[
+            vec![Str("org".to_string()), Int(1480294290)],
+            vec![Null, Int(1456591230)],
+        ],
+    );
+}
+
+#[test]
+fn test_order_by_with_filter() {
+    test_query(
+        "select first_name, num from default where (num = 8) or (num = 5) order by first_name;",
+        &[
+            vec!["Christina".into(), 5.into()],
+            vec!["Joshua".into(), 5.into()],
+            vec!["Stephanie".into(), 8.into()],
+        ],
+    )
+}
+
+#[test]
+fn test_and_or_precedence() {
+    test_query(
+        "select first_name from default where num = 8 or num = 5 and first_name = \"Joshua\" order by first_name;",
+        &[
+            vec!["Joshua".into()],
+            vec!["Stephanie".into()],
+        ],
+    )
+}
+
+#[test]
+fn test_greater_than_equals() {
+    test_query_ec(
+        "select u8_offset_encoded from default where u8_offset_encoded >= 500 order by u8_offset_encoded;",
+        &[vec![500.into()], vec![500.into()], vec![511.into()]],
+    )
+}
+
+#[test]
+fn test_constant_less_than_equals_column() {
+    test_query_ec(
+        "select non_dense_ints from default where 3 <= non_dense_ints order by non_dense_ints;",
+        &[vec![3.into()], vec![3.into()], vec![4.into()]],
+    )
+}
+
+#[test]
+fn test_compare_columns() {
+    test_query_ec(
+        "select non_dense_ints from default where non_dense_ints > constant0 order by non_dense_ints;",
+        &[
+            vec![1.into()],
+            vec![1.into()],
+            vec![2.into()],
+            vec![2.into()],
+            vec![2.into()],
+            vec![3.into()],
+            vec![3.into()],
+            vec![4.into()],
+        ],
+    )
+}
+
+#[test]
+fn test_compare_columns_same_codec() {
+    use Value::*;
+    test_query_ec(
+        "select enum, count(0) from default where enum = enum;",
+        &[
+            vec![Str("aa".to_string()), Int(5)],
+            vec![Str("bb".to_string()), Int(3)],
+            vec![Str("cc".to_string()), Int(2)]
+        ],
+    )
+}
+
+#[test]
+fn test_compare_string_columns() {
+    use Value::*;
+    test_query_ec(
+        "select enum, count(0) from default where string_packed <> enum;",
+        &[
+            vec![Str("aa".to_string()), Int(5)],
+            vec![Str("bb".to_string()), Int(3)],
+            vec![Str("cc".to_string()), Int(2)]
+        ],
+    )
+}
+
+#[test]
+fn test_subtract_constant() {
+    use Value::*;
+    test_query(
+        "select num - 1, count(1) from default where num > 3;",
+        &[
+            vec![Int(3), Int(5)],
+            vec![Int(4), Int(2)],
+            vec![Int(7), Int(1)],
+        ],
+    )
+}
+
+#[test]
+fn test_modulo() {
+    use Value::*;
+    test_query(
+        "select num % 3, count(1) from default;",
+        &[
+            vec![Int(0), Int(11)],
+            vec![Int(1), Int(54)],
+            vec![Int(2), Int(27)],
+            vec![Null, Int(8)],
+        ],
+    )
+}
+
+#[test]
+fn test_is_null() {
+    use Value::*;
+    test_query(
+        "select tld, num, count(1) from default where num is null or num is not null and num > 5;",
+        &[
+            vec![Str("mil".to_string()), Int(8), Int(1)],
+            vec![Null, Null, Int(8)],
+        ],
+    )
+}
+
+#[test]
+fn test_null_rows() {
+    use Value::*;
+    test_query(
+        "select first_name, num + 1 from default where num is null or num = 8 order by first_name limit 3;",
+        &[
+            vec![Str("Billy".to_string()), Null],
+            vec![Str("Carl".to_string()), Null],
+            vec![Str("Charles".to_string()), Null],
+        ],
+    )
+}
+
+#[test]
+fn test_comparison_with_null() {
+    use Value::*;
+    test_query(
+        "select num, count(1) from default where num <> 1 and num <> 2;",
+        &[
+            vec![Int(3), Int(11)],
+            vec![Int(4), Int(5)],
+            vec![Int(5), Int(2)],
+            vec![Int(8), Int(1)],
+        ],
+    )
+}
+
+#[test]
+fn test_aggregates_ignore_null() {
+    use Value::*;
+    test_query(
+        "select num is null, count(num), sum(num), count(1) from default;",
+        &[
+            vec![Int(0), Int(92), Int(168), Int(92)],
+            vec![Int(1), Int(0), Null, Int(8)],
+        ],
+    )
+}
+
+#[test]
+fn test_in_dictionary() {
+    use Value::*;
+    test_query(
+        "select tld, count(1) from default where tld in (\"mil\", \"gov\", \"gov\", \"does_not_exist\");",
+        &[
+            vec![Str("gov".to_string()), Int(5)],
+            vec![Str("mil".to_string()), Int(11)],
+        ],
+    )
+}
+
+#[test]
+fn test_not_in() {
+    use Value::*;
+    test_query(
+        "select tld, count(1) from default where tld not in (\"\", \"com\", \"gov\") and tld NOT IN (\"biz\", \"edu\", \"info\");",
+        &[
+            vec![Str("mil".to_string()), Int(11)],
+            vec![Str("name".to_string()), Int(17)],
+            vec![Str("net".to_string()), Int(6)],
+            vec![Str("org".to_string()), Int(8)],
+        ],
+    )
+}
+
+#[test]
+fn test_in_integers() {
+    use Value::*;
+    test_query(
+        "select num, count(1) from default where num in (4, 2, 8) or num not in (1, 2, 3, 4, 8);",
+        &[
+            vec![Int(2), Int(24)],
+            vec![Int(4), Int(5)],
+            vec![Int(5), Int(2)],
+            vec![Int(8), Int(1)],
+        ],
+    )
+}
+
+#[test]
+fn test_between() {
+    use Value::*;
+    test_query(
+        "select num, count(1) from default where num between 2 and 4;",
+        &[
+            vec![Int(2), Int(24)],
+            vec![Int(3), Int(11)],
+            vec![Int(4), Int(5)],
+        ],
+    )
+}
+
+#[test]
+fn test_not() {
+    use Value::*;
+    test_query(
+        "select num, count(1) from default where not (num < 3 or num not between 4 and 5) and not num is null;",
+        &[
+            vec![Int(4), Int(5)],
+            vec![Int(5), Int(2)],
+        ],
+    )
+}
+
+#[test]
+fn test_negative_grouping_key() {
+    use Value::*;
+    test_query(
+        "select 5 - num, count(1) from default where num > 3 order by 1;",
+        &[
+            vec![Int(-3), Int(1)],
+            vec![Int(0), Int(2)],
+            vec![Int(1), Int(5)],
+        ],
+    )
+}
+
+#[test]
+fn test_arithmetic_constant_folding() {
+    test_query(
+        "select num * (2 + 3) from default where num = 8;",
+        &[vec![40.into()]],
+    )
+}
+
+#[test]
+fn test_column_arithmetic() {
+    use Value::*;
+    test_query_ec(
+        "select enum, sum(u8_offset_encoded - non_dense_ints) from default;",
+        &[
+            vec![Str("aa".to_string()), Int(1699)],
+            vec![Str("bb".to_string()), Int(1024)],
+            vec![Str("cc".to_string()), Int(850)],
+        ],
+    )
+}
+
+#[test]
+fn test_multiply_columns() {
+    use Value::*;
+    test_query(
+        "select num * num, count(1) from default where num > 3;",
+        &[
+            vec![Int(16), Int(5)],
+            vec![Int(25), Int(2)],
+            vec![Int(64), Int(1)],
+        ],
+    )
+}
+
+#[test]
+fn test_float_comparison() {
+    test_query_floats(
+        "select float, non_dense_ints from default where non_dense_ints < float and float > 0.4 order by float;",
+        &[
+            vec![0.5.into(), 0.into()],
+            vec![1.5.into(), 1.into()],
+            vec![3.75.into(), 3.into()],
+            vec![4.0.into(), 1.into()],
+            vec![7.75.into(), 3.into()],
+            vec![100.5.into(), 0.into()],
+        ],
+    )
+}
+
+#[test]
+fn test_float_arithmetic() {
+    test_query_floats(
+        "select float * 2 - non_dense_ints, -float from default where enum = \"bb\" order by float;",
+        &[
+            vec![(-3.75).into(), (-0.125).into()],
+            vec![2.0.into(), (-1.5).into()],
+            vec![12.5.into(), (-7.75).into()],
+        ],
+    )
+}
+
+#[test]
+fn test_float_division_integral_partition() {
+    test_query_floats(
+        "select float / 4, 3 / float, 7.5 % float from default where enum = \"cc\" order by float;",
+        &[
+            vec![(-0.75).into(), (-1.0).into(), 1.5.into()],
+            vec![0.5.into(), 1.5.into(), 1.5.into()],
+        ],
+    )
+}
+
+#[test]
+fn test_float_sum() {
+    use Value::*;
+    test_query_floats(
+        "select enum, sum(float) from default;",
+        &[
+            vec![Str("aa".to_string()), 107.5.into()],
+            vec![Str("bb".to_string()), 9.375.into()],
+            vec![Str("cc".to_string()), (-1.0).into()],
+        ],
+    )
+}
+
+#[test]
+fn test_min_max_avg() {
+    use Value::*;
+    test_query_floats(
+        "select enum, min(float), max(u8_offset_encoded), avg(non_dense_ints) from default;",
+        &[
+            vec![Str("aa".to_string()), (-1.25).into(), Int(500), 1.2.into()],
+            vec![Str("bb".to_string()), 0.125.into(), Int(500), (8.0 / 3.0).into()],
+            vec![Str("cc".to_string()), (-3.0).into(), Int(511), 2.0.into()],
+        ],
+    )
+}
+
+#[test]
+fn test_count_distinct() {
+    use Value::*;
+    test_query_floats(
+        "select enum, count(distinct non_dense_ints), count(distinct string_packed), count(distinct float) from default;",
+        &[
+            vec![Str("aa".to_string()), Int(4), Int(5), Int(5)],
+            vec![Str("bb".to_string()), Int(3), Int(3), Int(3)],
+            vec![Str("cc".to_string()), Int(1), Int(2), Int(2)],
+        ],
+    )
+}
+
+#[test]
+fn test_order_by_count_distinct() {
+    use Value::*;
+    test_query(
+        "select tld, count(distinct num) from default order by count(distinct num) desc, tld limit 3;",
+        &[
+            vec![Str("mil".to_string()), Int(5)],
+            vec![Str("com".to_string()), Int(4)],
+            vec![Str("edu".to_string()), Int(4)],
+        ],
+    )
+}
+
+#[test]
+fn test_count_distinct_dictionary_nullable() {
+    use Value::*;
+    test_query(
+        "select tld, count(distinct first_name), count(distinct num) from default having count(distinct num) < 3;",
+        &[
+            vec![Str("biz".to_string()), Int(10), Int(2)],
+            vec![Str("gov".to_string()), Int(5), Int(2)],
+            vec![Str("net".to_string()), Int(6), Int(2)],
+            vec![Null, Int(8), Int(0)],
+        ],
+    )
+}
+
+#[test]
+fn test_select_distinct() {
+    use Value::*;
+    test_query_ec(
+        "select distinct enum from default;",
+        &[
+            vec![Str("aa".to_string())],
+            vec![Str("bb".to_string())],
+            vec![Str("cc".to_string())],
+        ],
+    )
+}
+
+
+#[test]
+fn test_approx_count_distinct() {
+    use Value::*;
+    test_query_floats(
+        "select enum, approx_count_distinct(float), approx_count_distinct(string_packed, 12), approx_count_distinct(u8_offset_encoded) from default;",
+        &[
+            vec![Str("aa".to_string()), Int(5), Int(5), Int(5)],
+            vec![Str("bb".to_string()), Int(3), Int(3), Int(3)],
+            vec![Str("cc".to_string()), Int(2), Int(2), Int(2)],
+        ],
+    )
+}
+
+#[test]
+fn test_approx_count_distinct_dictionary_nullable() {
+    use Value::*;
+    test_query(
+        "select tld, approx_count_distinct(first_name), approx_count_distinct(num) from default having approx_count_distinct(num) < 3;",
+        &[
+            vec![Str("biz".to_string()), Int(10), Int(2)],
+            vec![Str("gov".to_string()), Int(5), Int(2)],
+            vec![Str("net".to_string()), Int(6), Int(2)],
+            vec![Null, Int(8), Int(0)],
+        ],
+    )
+}
+
+#[test]
+fn test_approx_count_distinct_invalid_precision() {
+    let locustdb = LocustDB::memory_only();
+    let _ = block_on(locustdb.load_csv(
+        LoadOptions::new("test_data/tiny.csv", "default")
+            .with_partition_size(40)));
+    let result = block_on(locustdb.run_query("select tld, approx_count_distinct(num, 30) from default;", false, vec![])).unwrap();
+    assert!(result.0.is_err());
+}
+
+#[test]
+fn test_percentile() {
+    use Value::*;
+    test_query_floats(
+        "select enum, median(float), percentile(u8_offset_encoded, 0.99), quantiles(non_dense_ints, [0.1, 0.5]) from default;",
+        &[
+            vec![Str("aa".to_string()), 3.75.into(), 497.28.into(), 0.0.into(), 1.0.into()],
+            vec![Str("bb".to_string()), 1.5.into(), 495.5.into(), 1.4.into(), 3.0.into()],
+            vec![Str("cc".to_string()), (-0.5).into(), 509.32.into(), 2.0.into(), 2.0.into()],
+        ],
+    )
+}
+
+#[test]
+fn test_percentiles_of_same_expression() {
+    use Value::*;
+    test_query_floats(
+        "select enum, median(float), quantiles(float, [0, 1]), percentile(float, 0.5) filter (where non_dense_ints > 1) from default;",
+        &[
+            vec![Str("aa".to_string()), 3.75.into(), (-1.25).into(), 100.5.into(), 1.25.into()],
+            vec![Str("bb".to_string()), 1.5.into(), 0.125.into(), 7.75.into(), 3.9375.into()],
+            vec![Str("cc".to_string()), (-0.5).into(), (-3.0).into(), 2.0.into(), (-0.5).into()],
+        ],
+    )
+}
+
+#[test]
+fn test_variance_and_correlation() {
+    use Value::*;
+    test_query_floats_approx(
+        "select enum, variance(float), stddev(non_dense_ints), covar(float, non_dense_ints), corr(u8_offset_encoded, float) from default;",
+        &[
+            vec![Str("aa".to_string()), 1955.21875.into(), 1.3038404810405297.into(), (-29.0625).into(), 0.7804131286310131.into()],
+            vec![Str("bb".to_string()), 16.515625.into(), 1.5275252316519468.into(), 0.125.into(), 0.9721622832369222.into()],
+            vec![Str("cc".to_string()), 12.5.into(), 0.0.into(), 0.0.into(), (-1.0).into()],
+        ],
+    )
+}
+
+#[test]
+fn test_aggregates_nullable() {
+    use Value::*;
+    // Aggregates of the rows of one tld and of the rows in which `num` is NULL, which form their own group
+    let cases: Vec<(&str, &str, Vec<Value>, Vec<Value>)> = vec![
+        ("min(num), max(num), avg(num), count(0)", "gov",
+         vec![Int(1), Int(2), 1.2.into(), Int(5)], vec![Null, Null, Null, Int(8)]),
+        ("median(num), percentile(num, 0.9)", "mil",
+         vec![2.0.into(), 4.0.into()], vec![Null, Null]),
+        ("variance(num), stddev(num), covar(num, num), corr(num, num)", "mil",
+         vec![4.672727272727273.into(), 2.1616492020508953.into(), 4.672727272727273.into(), 1.0.into()], vec![Null, Null, Null, Null]),
+    ];
+    for (aggregates, tld, values, nulls) in cases {
+        let query = format!("select tld, {} from default where num IS NULL OR tld = \"{}\";", aggregates, tld);
+        let mut tld_row = vec![Str(tld.to_string())];
+        tld_row.extend(values);
+        let mut null_row = vec![Null];
+        null_row.extend(nulls);
+        test_query(&query, &[tld_row, null_row]);
+    }
+}
+
+#[test]
+fn test_count_if_and_filter() {
+    use Value::*;
+    test_query(
+        "select tld, count_if(num > 3), sum(num) filter (where num > 3), avg(num) FILTER (WHERE ts > 1474000000), sum(num > 3) from default;",
+        &[
+            vec![Str("biz".to_string()), Int(0), Null, 2.0.into(), Int(0)],
+            vec![Str("com".to_string()), Int(1), Int(5), 1.5.into(), Int(1)],
+            vec![Str("edu".to_string()), Int(1), Int(4), 1.625.into(), Int(1)],
+            vec![Str("gov".to_string()), Int(0), Null, 1.3333333333333333.into(), Int(0)],
+            vec![Str("info".to_string()), Int(0), Null, 1.5.into(), Int(0)],
+            vec![Str("mil".to_string()), Int(3), Int(16), 2.6666666666666665.into(), Int(3)],
+            vec![Str("name".to_string()), Int(1), Int(4), 1.3333333333333333.into(), Int(1)],
+            vec![Str("net".to_string()), Int(1), Int(4), 1.0.into(), Int(1)],
+            vec![Str("org".to_string()), Int(1), Int(5), 2.3333333333333335.into(), Int(1)],
+            vec![Null, Int(0), Null, Null, Null],
+        ],
+    )
+}
+
+#[test]
+fn test_filter_clause_sketches() {
+    use Value::*;
+    test_query_floats(
+        "select enum, count(distinct non_dense_ints) filter (where float > 0), median(float) filter (where non_dense_ints < 3), covar(float, non_dense_ints) filter (where u8_offset_encoded > 256) from default where enum <> \"cc\" group by enum having count_if(float > 0) > 1;",
+        &[
+            vec![Str("aa".to_string()), Int(3), 2.25.into(), (-49.25).into()],
+            vec![Str("bb".to_string()), Int(3), 1.5.into(), 0.125.into()],
+        ],
+    )
+}
+
+#[test]
+fn test_case_when_group_by() {
+    use Value::*;
+    test_query(
+        "select case when num < 2 then \"small\" when num < 4 then \"medium\" else \"large\" end, count(0) from default;",
+        &[
+            vec![Str("large".to_string()), Int(16)],
+            vec![Str("medium".to_string()), Int(35)],
+            vec![Str("small".to_string()), Int(49)],
+        ],
+    );
+    test_query(
+        "select tld, case when num > 3 then \"big\" end, count(0) from default where tld = \"mil\" or tld is null;",
+        &[
+            vec![Str("mil".to_string()), Str("big".to_string()), Int(3)],
+            vec![Str("mil".to_string()), Null, Int(8)],
+            vec![Null, Null, Int(8)],
+        ],
+    )
+}
+
+#[test]
+fn test_if_aggregates() {
+    use Value::*;
+    test_query(
+        "select tld, sum(case when num > 2 then num else 0 end), count(case when num > 2 then num end), max(if(tld = \"com\", num * 10, num)) from default;",
+        &[
+            vec![Str("biz".to_string()), Int(0), Int(0), Int(2)],
+            vec![Str("com".to_string()), Int(11), Int(3), Int(50)],
+            vec![Str("edu".to_string()), Int(13), Int(4), Int(4)],
+            vec![Str("gov".to_string()), Int(0), Int(0), Int(2)],
+            vec![Str("info".to_string()), Int(6), Int(2), Int(3)],
+            vec![Str("mil".to_string()), Int(19), Int(4), Int(8)],
+            vec![Str("name".to_string()), Int(7), Int(2), Int(4)],
+            vec![Str("net".to_string()), Int(4), Int(1), Int(4)],
+            vec![Str("org".to_string()), Int(11), Int(3), Int(5)],
+            vec![Null, Int(0), Int(0), Null],
+        ],
+    )
+}
+
+#[test]
+fn test_constant_branches_and_filtered_constants() {
+    use Value::*;
+    test_query(
+        "select tld, count(distinct if(num > 3, \"big\", first_name)), sum(2) filter (where num > 3), sum(0.5) filter (where num > 2) from default where tld = \"mil\" or tld = \"gov\";",
+        &[
+            vec![Str("gov".to_string()), Int(5), Null, Null],
+            vec![Str("mil".to_string()), Int(9), Int(6), 2.0.into()],
+        ],
+    )
+}
+
+#[test]
+fn test_if_mixed_types() {
+    use Value::*;
+    test_query_floats(
+        "select enum, sum(if(float > 1, float, non_dense_ints)), max(case when string_packed like \"a%\" then u8_offset_encoded else float end) from default;",
+        &[
+            vec![Str("aa".to_string()), 110.25.into(), 259.0.into()],
+            vec![Str("bb".to_string()), 13.25.into(), 275.0.into()],
+            vec![Str("cc".to_string()), 4.0.into(), 343.0.into()],
+        ],
+    )
+}
+
+#[test]
+fn test_query_errors() {
+    let _ = env_logger::try_init();
+    let locustdb = LocustDB::memory_only();
+    let _ = block_on(locustdb.load_csv(
+        LoadOptions::new("test_data/tiny.csv", "default")
+            .with_partition_size(40)));
+    let error = |query: &str| match block_on(locustdb.run_query(query, false, vec![])).unwrap().0 {
+        Ok(_) => panic!("Expected query to fail: {}", query),
+        Err(err) => format!("{}", err),
+    };
+    assert_eq!(
+        error("select tld, count(0) from default where num > 1 grop by tld;"),
+        "Syntax error at line 1, column 49: expected GROUP BY, HAVING, UNION ALL, ORDER BY, LIMIT or `;`, found `grop`");
+    assert_eq!(
+        error("select tdl, count(0) from default;"),
+        "Invalid query: Referencing missing column tdl. Did you mean `tld`?");
+    assert_eq!(
+        error("select first from default where nmu > 1;"),
+        "Invalid query: Referencing missing column first. Did you mean `first_name`?");
+}
+
+#[test]
+fn test_prepared_query() {
+    use Value::*;
+    let _ = env_logger::try_init();
+    let locustdb = LocustDB::memory_only();
+    let _ = block_on(locustdb.load_csv(
+        LoadOptions::new("test_data/tiny.csv", "default")
+            .with_partition_size(40)));
+    let query = locustdb.prepare(
+        "select tld, count(0) from default where num > ? and tld = $tld and ts < now() - interval '1 day';").unwrap();
+    assert_eq!(query.parameters(), vec![None, Some("tld".to_string())]);
+    let execute = |args: &[Value]| block_on(locustdb.execute(&query, args, false, vec![])).unwrap().0;
+    assert_eq!(execute(&[Int(2), Str("mil".to_string())]).unwrap().rows, vec![vec![Str("mil".to_string()), Int(4)]]);
+    assert_eq!(execute(&[Int(3), Str("com".to_string())]).unwrap().rows, vec![vec![Str("com".to_string()), Int(1)]]);
+    match execute(&[Int(3)]) {
+        Ok(_) => panic!("Expected query with missing argument to fail"),
+        Err(err) => assert_eq!(format!("{}", err), "Invalid query: Query has 2 parameters, but 1 arguments were supplied"),
+    }
+}
+
+#[test]
+fn test_join() {
+    use Value::*;
+    let _ = env_logger::try_init();
+    let locustdb = LocustDB::memory_only();
+    let _ = block_on(locustdb.load_csv(
+        LoadOptions::new("test_data/tiny.csv", "default")
+            .with_partition_size(40)));
+    let _ = block_on(locustdb.load_csv(
+        LoadOptions::new("test_data/tlds.csv", "tlds")
+            .with_partition_size(3)));
+    let query = |query: &str| block_on(locustdb.run_query(query, false, vec![])).unwrap().0;
+    let str = |s: &str| Str(s.to_string());
+    assert_eq!(
+        query("select t.category, count(0) from default join tlds as t on default.tld = t.tld group by t.category order by count(0) desc limit 3;").unwrap().rows,
+        vec![vec![str("education"), Int(13)], vec![str("generic"), Int(13)], vec![str("military"), Int(11)]]);
+    assert_eq!(
+        query("select tld, category, count(0) from default left join tlds on tld = tlds.tld where num > 2 group by tld, category;").unwrap().rows,
+        vec![
+            vec![str("com"), str("commercial"), Int(3)],
+            vec![str("edu"), str("education"), Int(4)],
+            vec![str("info"), str("generic"), Int(2)],
+            vec![str("mil"), str("military"), Int(4)],
+            vec![str("net"), str("network"), Int(1)],
+            vec![str("org"), str("organization"), Int(3)],
+            vec![str("name"), Null, Int(2)],
+        ]);
+    assert_eq!(
+        query("select num, t.tld from default d inner join tlds t on d.num = t.rank order by num desc limit 2;").unwrap().rows,
+        vec![vec![Int(5), str("gov")], vec![Int(5), str("gov")]]);
+    match query("select tld from tlds t join default on t.tld = default.tld;") {
+        Ok(_) => panic!("Expected join on duplicate keys to fail"),
+        // Which duplicate is found first depends on the order in which partitions are read
+        Err(err) => assert!(format!("{}", err).starts_with("Invalid query: JOIN on column tld of default, which contains duplicate value")),
+    }
+}
+
+#[test]
+fn test_subquery() {
+    use Value::*;
+    let _ = env_logger::try_init();
+    let locustdb = LocustDB::memory_only();
+    let _ = block_on(locustdb.load_csv(
+        LoadOptions::new("test_data/tiny.csv", "default")
+            .with_partition_size(40)));
+    let query = |query: &str| block_on(locustdb.run_query(query, false, vec![])).unwrap().0;
+    assert_eq!(
+        query("select max(c), sum(c) from (select tld, count(0) as c from default group by tld);").unwrap().rows,
+        vec![vec![Int(17), Int(100)]]);
+    assert_eq!(
+        query("select count(0) from (select tld, count(0) as c from default) as t where t.c > 10;").unwrap().rows,
+        vec![vec![Int(4)]]);
+    assert_eq!(
+        query("with per_num as (select num, count(0) as c from default where num > 0), \
+               large as (select num from per_num where c >= 5) \
+               select sum(num) from large;").unwrap().rows,
+        vec![vec![Int(10)]]);
+    assert_eq!(
+        query("select count(0) from (select num from default where num > 100);").unwrap().rows,
+        Vec::<Vec<Value>>::new());
+}
+
+#[test]
+fn test_subquery_default_limit() {
+    use Value::*;
+    let _ = env_logger::try_init();
+    let locustdb = LocustDB::memory_only();
+    // Loads the 100 rows of `tiny.csv` twice, so that subqueries return more rows than the default limit
+    for _ in 0..2 {
+        let _ = block_on(locustdb.load_csv(
+            LoadOptions::new("test_data/tiny.csv", "default")
+                .with_partition_size(40)));
+    }
+    let query = |query: &str| block_on(locustdb.run_query(query, false, vec![])).unwrap().0;
+    assert_eq!(
+        query("select count(0) from (select num from default);").unwrap().rows,
+        vec![vec![Int(200)]]);
+    assert_eq!(
+        query("with t as (select num from default) select count(0) from t;").unwrap().rows,
+        vec![vec![Int(200)]]);
+    assert_eq!(
+        query("select count(0) from (select num from default limit 150);").unwrap().rows,
+        vec![vec![Int(150)]]);
+    assert_eq!(query("select * from (select num from default);").unwrap().rows.len(), 100);
+}
+
+#[test]
+fn test_wildcard_table_name() {
+    use Value::*;
+    let locustdb = sharded_db();
+    let query = |query: &str| block_on(locustdb.run_query(query, false, vec![])).unwrap().0;
+    assert_eq!(query("select count(0) from shard_*;").unwrap().rows, vec![vec![Int(107)]]);
+    assert_eq!(
+        query("select tld, count(0) from shard_* where tld = \"mil\";").unwrap().rows,
+        vec![vec![Str("mil".to_string()), Int(12)]]);
+    assert_eq!(
+        query("select count(0), sum(rank) from shard_* where num is null;").unwrap().rows,
+        vec![vec![Int(15), Int(28)]]);
+    assert_eq!(
+        query("with shard_3 as (select tld from shard_2) select count(0) from shard_*;").unwrap().rows,
+        vec![vec![Int(107)]]);
+}
+
+#[test]
+fn test_union_all() {
+    use Value::*;
+    let locustdb = sharded_db();
+    let query = |query: &str| block_on(locustdb.run_query(query, false, vec![])).unwrap().0;
+    let str = |s: &str| Str(s.to_string());
+    assert_eq!(
+        query("select tld from shard_2 where rank < 3 union all select tld from shard_2 where rank > 6 order by tld;").unwrap().rows,
+        vec![vec![str("com")], vec![str("info")], vec![str("org")]]);
+    assert_eq!(
+        query("select tld from shard_2 where rank < 3 union all select tld from shard_2 where rank > 6 order by 1 desc limit 2;").unwrap().rows,
+        vec![vec![str("org")], vec![str("info")]]);
+    assert_eq!(query("select tld from shard_1 union all select tld from shard_2;").unwrap().rows.len(), 100);
+    assert_eq!(
+        query("select count(0) from (select tld from shard_2 union all select tld from shard_2 limit 10);").unwrap().rows,
+        vec![vec![Int(10)]]);
+    assert_eq!(
+        query("select count(0) from shard_1 union all select count(0) from shard_2;").unwrap().rows,
+        vec![vec![Int(100)], vec![Int(7)]]);
+    assert_eq!(
+        query("select count(0) from (select tld from shard_1 union all select tld from shard_2) where tld = \"com\";").unwrap().rows,
+        vec![vec![Int(10)]]);
+    let result = query("select tld from shard_2 where rank = 1 union all select category from shard_2 where rank = 2;").unwrap();
+    assert_eq!(result.colnames, vec!["tld".to_string()]);
+    assert_eq!(result.rows, vec![vec![str("com")], vec![str("organization")]]);
+}
+
+#[test]
+fn test_union_all_int_float_columns() {
+    use Value::*;
+    let locustdb = sharded_db();
+    let query = |query: &str| block_on(locustdb.run_query(query, false, vec![])).unwrap().0;
+    assert_eq!(
+        query("select count(0) from shard_2 union all select avg(rank) from shard_2;").unwrap().rows,
+        vec![vec![Float(OrderedFloat(7.0))], vec![Float(OrderedFloat(4.0))]]);
+}
+
+#[test]
+fn test_union_all_incompatible_columns() {
+    let locustdb = sharded_db();
+    let error = |query: &str| match block_on(locustdb.run_query(query, false, vec![])).unwrap().0 {
+        Ok(_) => panic!("Expected query to fail: {}", query),
+        Err(err) => format!("{}", err),
+    };
+    assert_eq!(
+        error("select tld, rank from shard_2 union all select tld from shard_2;"),
+        "Invalid query: Queries of UNION ALL have 2 and 1 columns");
+    assert_eq!(
+        error("select tld from shard_2 union all select rank from shard_2;"),
+        "Invalid query: Column `tld` of UNION ALL has types String and Integer");
+}
+
 
 // Tests are run in alphabetical order (why ;_;) and these take a few seconds to run, so prepend z to run last
 #[test]